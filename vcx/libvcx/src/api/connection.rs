@@ -7,8 +7,11 @@ use utils::error::error_string;
 use std::ptr;
 use std::thread;
 use error::ToErrorCode;
+use error::ToErrorDetails;
 use error::connection::ConnectionError;
-use connection::{get_source_id, build_connection, build_connection_with_invite, connect, to_string, get_state, release, is_valid_handle, update_state, from_string, get_invite_details, delete_connection};
+use connection::{get_source_id, build_connection, build_connection_with_invite, connect, to_string, get_state, release, is_valid_handle, update_state, from_string, get_invite_details, delete_connection, get_state_history};
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /**
  * connection object
@@ -32,9 +35,10 @@ pub extern fn vcx_connection_delete_connection(command_handle: u32,
                                                cb: Option<extern fn(
                                                    xcommand_handle: u32,
                                                    err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     if !is_valid_handle(connection_handle) {
-        return ConnectionError::InvalidHandle().to_error_code()
+        return ConnectionError::InvalidHandle().record_error()
     }
     info!("vcx_connection_delete_connection(command_handle: {}, connection_handle: {})", command_handle, connection_handle);
     thread::spawn(move|| {
@@ -45,7 +49,7 @@ pub extern fn vcx_connection_delete_connection(command_handle: u32,
             },
             Err(e) => {
                 info!("vcx_connection_delete_connection_cb(command_handle: {}, rc: {})", command_handle, e);
-                cb(command_handle, e.to_error_code())
+                cb(command_handle, e.record_error())
             },
         }
     });
@@ -68,6 +72,7 @@ pub extern fn vcx_connection_delete_connection(command_handle: u32,
 pub extern fn vcx_connection_create(command_handle: u32,
                                     source_id: *const c_char,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, connection_handle: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     info!("vcx_connection_create(command_handle: {}, source_id: {})", command_handle, source_id);
@@ -81,7 +86,7 @@ pub extern fn vcx_connection_create(command_handle: u32,
             Err(x) => {
                 warn!("vcx_connection_create_cb(command_handle: {}, rc: {}, handle: {})",
                       command_handle, x.to_string(), 0);
-                cb(command_handle, x.to_error_code(), 0)
+                cb(command_handle, x.record_error(), 0)
             },
         };
     });
@@ -107,7 +112,7 @@ pub extern fn vcx_connection_create_with_invite(command_handle: u32,
                                                 source_id: *const c_char,
                                                 invite_details: *const c_char,
                                                 cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(invite_details, error::INVALID_OPTION.code_num);
@@ -122,7 +127,7 @@ pub extern fn vcx_connection_create_with_invite(command_handle: u32,
             Err(x) => {
                 warn!("vcx_connection_create_with_invite_cb(command_handle: {}, rc: {}, handle: {})",
                       command_handle, x.to_string(), 0);
-                cb(command_handle, x.to_error_code(), 0)
+                cb(command_handle, x.record_error(), 0)
             },
         };
     });
@@ -150,12 +155,12 @@ pub extern fn vcx_connection_connect(command_handle:u32,
                                      connection_handle: u32,
                                      connection_options: *const c_char,
                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, invite_details: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !is_valid_handle(connection_handle) {
         error!("vcx_connection_get_state - invalid handle");
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     let options = if !connection_options.is_null() {
@@ -190,7 +195,7 @@ pub extern fn vcx_connection_connect(command_handle:u32,
             Err(x) => {
                 warn!("vcx_connection_connect_cb(command_handle: {}, connection_handle: {}, rc: {}, details: {}, source_id: {})",
                       command_handle, connection_handle, x.to_string(), "null", source_id);
-                cb(command_handle,x.to_error_code(), ptr::null_mut())
+                cb(command_handle, x.record_error(), ptr::null_mut())
             },
         };
     });
@@ -213,7 +218,7 @@ pub extern fn vcx_connection_connect(command_handle:u32,
 pub extern fn vcx_connection_serialize(command_handle: u32,
                                        connection_handle: u32,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, serialized_data: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = get_source_id(connection_handle).unwrap_or_default();
@@ -222,7 +227,7 @@ pub extern fn vcx_connection_serialize(command_handle: u32,
 
     if !is_valid_handle(connection_handle) {
         error!("vcx_connection_get_state - invalid handle");
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -236,7 +241,7 @@ pub extern fn vcx_connection_serialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_connection_serialize_cb(command_handle: {}, connection_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, connection_handle, error_string(x), "null", source_id);
-                cb(command_handle, x, ptr::null_mut());
+                cb(command_handle, record_error_code(x), ptr::null_mut());
             },
         };
     });
@@ -259,7 +264,7 @@ pub extern fn vcx_connection_serialize(command_handle: u32,
 pub extern fn vcx_connection_deserialize(command_handle: u32,
                                       connection_data: *const c_char,
                                       cb: Option<extern fn(xcommand_handle: u32, err: u32, connection_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(connection_data, error::INVALID_OPTION.code_num);
 
@@ -276,7 +281,7 @@ pub extern fn vcx_connection_deserialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_connection_deserialize_cb(command_handle: {}, rc: {}, handle: {} )",
                       command_handle, error_string(x.to_error_code()), 0);
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
 
@@ -302,7 +307,7 @@ pub extern fn vcx_connection_deserialize(command_handle: u32,
 pub extern fn vcx_connection_update_state(command_handle: u32,
                                           connection_handle: u32,
                                           cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = get_source_id(connection_handle).unwrap_or_default();
@@ -311,7 +316,7 @@ pub extern fn vcx_connection_update_state(command_handle: u32,
 
     if !is_valid_handle(connection_handle) {
         error!("vcx_connection_get_state - invalid handle");
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -325,7 +330,7 @@ pub extern fn vcx_connection_update_state(command_handle: u32,
                 warn!("vcx_connection_update_state_cb(command_handle: {}, rc: {}, connection_handle: {}, state: {}), source_id: {:?}",
                       // TODO: Refactor Error
                       command_handle, error_string(x.to_error_code()), connection_handle, get_state(connection_handle), source_id);
-                x.to_error_code()
+                x.record_error()
             },
         };
         let state = get_state(connection_handle);
@@ -339,7 +344,7 @@ pub extern fn vcx_connection_update_state(command_handle: u32,
 pub extern fn vcx_connection_get_state(command_handle: u32,
                                        connection_handle: u32,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = get_source_id(connection_handle).unwrap_or_default();
@@ -348,7 +353,7 @@ pub extern fn vcx_connection_get_state(command_handle: u32,
 
     if !is_valid_handle(connection_handle) {
         error!("vcx_connection_get_state - invalid handle");
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -360,6 +365,52 @@ pub extern fn vcx_connection_get_state(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Get the transitions the connection went through, for debugging
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// connection_handle: Connection handle that was provided during creation. Used to identify connection object
+///
+/// cb: Callback that provides the json array of transitions and provides error status
+/// -> [{"event":"SendOffer","from":1,"to":2,"timestamp":1537000000}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_connection_get_state_history(command_handle: u32,
+                                               connection_handle: u32,
+                                               cb: Option<extern fn(xcommand_handle: u32, err: u32, history: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !is_valid_handle(connection_handle) {
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
+    }
+
+    let source_id = get_source_id(connection_handle).unwrap_or_default();
+    info!("vcx_connection_get_state_history(command_handle: {}, connection_handle: {}), source_id: {:?}",
+          command_handle, connection_handle, source_id);
+
+    thread::spawn(move|| {
+        match get_state_history(connection_handle) {
+            Ok(x) => {
+                info!("vcx_connection_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_connection_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(x.to_error_code()), "null", source_id);
+                cb(command_handle, x.record_error(), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Gets the current connection details
 ///
 /// #Params
@@ -378,7 +429,7 @@ pub extern fn vcx_connection_invite_details(command_handle: u32,
                                             connection_handle: u32,
                                             abbreviated: bool,
                                             cb: Option<extern fn(xcommand_handle: u32, err: u32, details: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = get_source_id(connection_handle).unwrap_or_default();
@@ -387,7 +438,7 @@ pub extern fn vcx_connection_invite_details(command_handle: u32,
 
     if !is_valid_handle(connection_handle) {
         error!("vcx_connection_get_state - invalid handle");
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -401,7 +452,7 @@ pub extern fn vcx_connection_invite_details(command_handle: u32,
             Err(x) => {
                 warn!("vcx_connection_invite_details_cb(command_handle: {}, connection_handle: {}, rc: {}, details: {}, source_id: {:?})",
                       command_handle, connection_handle, error_string(x.to_error_code()), "null", source_id);
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             }
         }
     });
@@ -418,6 +469,7 @@ pub extern fn vcx_connection_invite_details(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_connection_release(connection_handle: u32) -> u32 {
+    reset_current_error();
     let source_id = get_source_id(connection_handle).unwrap_or_default();
     match release(connection_handle) {
         Ok(_) => info!("vcx_connection_release(connection_handle: {}, rc: {}), source_id: {:?}",
@@ -11,6 +11,9 @@ use std::thread;
 use std::ptr;
 use error::credential::CredentialError;
 use error::ToErrorCode;
+use error::ToErrorDetails;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /// Retrieves Payment Info from a Credential
 ///
@@ -28,6 +31,7 @@ use error::ToErrorCode;
 pub extern fn vcx_credential_get_payment_info(command_handle: u32,
                                                credential_handle: u32,
                                                cb: Option<extern fn(xcommand_handle: u32, err: u32, *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     thread::spawn(move|| {
         match credential::get_payment_information(credential_handle) {
@@ -51,7 +55,7 @@ pub extern fn vcx_credential_get_payment_info(command_handle: u32,
                       command_handle,
                       e.to_error_code(),
                       "{}".to_string());
-                cb(command_handle, e.to_error_code(), ptr::null_mut())
+                cb(command_handle, e.record_error(), ptr::null_mut())
             }
         }
     });
@@ -64,10 +68,13 @@ pub extern fn vcx_credential_get_payment_info(command_handle: u32,
 ///
 /// source_id: Institution's personal identification for the credential, should be unique.
 ///
-/// offer: credential offer received via "vcx_credential_get_offers"
+/// offer: credential offer received via "vcx_credential_get_offers", or an out of band offer
+/// generated by "vcx_issuer_get_out_of_band_credential_offer"
 ///
 /// # Example offer -> "[{"msg_type": "CREDENTIAL_OFFER","version": "0.1","to_did": "...","from_did":"...","credential": {"account_num": ["...."],"name_on_account": ["Alice"]},"schema_seq_no": 48,"issuer_did": "...","credential_name": "Account Certificate","credential_id": "3675417066","msg_ref_id": "ymy5nth"}]
 ///
+/// # Example out of band offer -> {"@type":"CRED_OFFER","@msg":"[...]","service":{"endpoint":"...","routing_key":"...","recipient_did":"...","recipient_key":"...","agent_did":"...","agent_vk":"..."},"signer_did":"...","signer_vk":"...","signature":"..."}
+///
 /// cb: Callback that provides credential handle or error status
 ///
 /// #Returns
@@ -79,7 +86,7 @@ pub extern fn vcx_credential_create_with_offer(command_handle: u32,
                                                source_id: *const c_char,
                                                offer: *const c_char,
                                                cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(offer, error::INVALID_OPTION.code_num);
@@ -97,7 +104,7 @@ pub extern fn vcx_credential_create_with_offer(command_handle: u32,
             Err(x) => {
                 warn!("vcx_credential_create_with_offer_cb(command_handle: {}, source_id: {}, rc: {}, handle: {})",
                       command_handle, source_id, x.to_error_code(), 0);
-                cb(command_handle, x.to_error_code(), 0);
+                cb(command_handle, x.record_error(), 0);
             },
         };
     });
@@ -122,9 +129,10 @@ pub extern fn vcx_credential_create_with_offer(command_handle: u32,
 pub extern fn vcx_get_credential(command_handle: u32,
                                  credential_handle: u32,
                                  cb: Option<extern fn(xcommand_handle:u32, err: u32, credential: *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     if !credential::is_valid_handle(credential_handle) {
-        return CredentialError::InvalidHandle().to_error_code();
+        return CredentialError::InvalidHandle().record_error();
     }
 
     thread::spawn(move|| {
@@ -138,7 +146,7 @@ pub extern fn vcx_get_credential(command_handle: u32,
             Err(e) => {
                 error!("vcx_get_credential_cb(commmand_handle: {}, rc: {}, msg: {})",
                     command_handle, e.to_error_code(), "".to_string());
-                cb(command_handle, e.to_error_code(), ptr::null_mut());
+                cb(command_handle, e.record_error(), ptr::null_mut());
 
             },
         };
@@ -169,7 +177,7 @@ pub extern fn vcx_credential_create_with_msgid(command_handle: u32,
                                                connection_handle: u32,
                                                msg_id: *const c_char,
                                                cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_handle: u32, offer: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(msg_id, error::INVALID_OPTION.code_num);
@@ -194,11 +202,11 @@ pub extern fn vcx_credential_create_with_msgid(command_handle: u32,
                     Err(e) => {
                         warn!("vcx_credential_create_with_offer_cb(command_handle: {}, source_id: {}, rc: {}, handle: {})",
                               command_handle, source_id, e.to_error_code(), 0);
-                        cb(command_handle, e.to_error_code(), 0, ptr::null_mut());
+                        cb(command_handle, e.record_error(), 0, ptr::null_mut());
                     },
                 };
             },
-            Err(e) => cb(command_handle, e.to_error_code(), 0, ptr::null_mut()),
+            Err(e) => cb(command_handle, e.record_error(), 0, ptr::null_mut()),
         };
     });
 
@@ -225,15 +233,15 @@ pub extern fn vcx_credential_send_request(command_handle: u32,
                                           connection_handle: u32,
                                           payment_handle: u32,
                                           cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !credential::is_valid_handle(credential_handle) {
-        return error::INVALID_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
     }
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     let source_id = credential::get_source_id(credential_handle).unwrap_or_default();
@@ -250,7 +258,86 @@ pub extern fn vcx_credential_send_request(command_handle: u32,
             Err(e) => {
                 warn!("vcx_credential_send_request_cb(command_handle: {}, rc: {}), source_id: {:?}",
                       command_handle, e.to_string(), source_id);
-                cb(command_handle,e.to_error_code());
+                cb(command_handle, e.record_error());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Choose the link secret the credential is requested with, instead of the selected one. The
+/// credential is bound to this link secret once stored.
+///
+/// #params
+/// credential_handle: credential handle that was provided during creation. Used to identify credential object
+///
+/// alias: alias of a link secret created with vcx_link_secret_create
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credential_set_link_secret(credential_handle: u32, alias: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(alias, error::INVALID_OPTION.code_num);
+
+    let source_id = credential::get_source_id(credential_handle).unwrap_or_default();
+    info!("vcx_credential_set_link_secret(credential_handle: {}, alias: {}), source_id: {:?}",
+          credential_handle, alias, source_id);
+
+    if !credential::is_valid_handle(credential_handle) {
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
+    }
+
+    match credential::set_link_secret(credential_handle, &alias) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(e) => {
+            warn!("vcx_credential_set_link_secret(credential_handle: {}, rc: {}), source_id: {:?}",
+                  credential_handle, e.to_string(), source_id);
+            e.record_error()
+        },
+    }
+}
+
+/// Send a credential request to the service of an out of band credential offer. The credential
+/// is delivered without a connection and is received with "vcx_credential_update_state". The
+/// pairwise agent created to receive it is deleted once the credential is stored.
+///
+/// #params
+/// command_handle: command handle to map callback to user context
+///
+/// credential_handle: credential handle created from an out of band offer
+///
+/// cb: Callback that provides error status of credential request
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credential_send_request_to_service(command_handle: u32,
+                                                     credential_handle: u32,
+                                                     cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !credential::is_valid_handle(credential_handle) {
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
+    }
+
+    let source_id = credential::get_source_id(credential_handle).unwrap_or_default();
+    info!("vcx_credential_send_request_to_service(command_handle: {}, credential_handle: {}), source_id: {:?}",
+          command_handle, credential_handle, source_id);
+
+    thread::spawn(move|| {
+        match credential::send_credential_request_to_service(credential_handle) {
+            Ok(x) => {
+                info!("vcx_credential_send_request_to_service_cb(command_handle: {}, rc: {}, source_id: {:?})",
+                      command_handle, x.to_string(), source_id);
+                cb(command_handle,x);
+            },
+            Err(e) => {
+                warn!("vcx_credential_send_request_to_service_cb(command_handle: {}, rc: {}), source_id: {:?}",
+                      command_handle, e.to_string(), source_id);
+                cb(command_handle, e.record_error());
             },
         };
     });
@@ -274,11 +361,11 @@ pub extern fn vcx_credential_send_request(command_handle: u32,
 pub extern fn vcx_credential_get_offers(command_handle: u32,
                                    connection_handle: u32,
                                    cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_offers: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     info!("vcx_credential_get_offers(command_handle: {}, connection_handle: {})",
@@ -295,7 +382,7 @@ pub extern fn vcx_credential_get_offers(command_handle: u32,
             Err(x) => {
                 error!("vcx_credential_get_offers_cb(command_handle: {}, rc: {}, msg: null)",
                       command_handle, x.to_string());
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
     });
@@ -320,11 +407,11 @@ pub extern fn vcx_credential_get_offers(command_handle: u32,
 pub extern fn vcx_credential_update_state(command_handle: u32,
                                           credential_handle: u32,
                                           cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !credential::is_valid_handle(credential_handle) {
-        return error::INVALID_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
     }
 
     let source_id = credential::get_source_id(credential_handle).unwrap_or_default();
@@ -337,7 +424,7 @@ pub extern fn vcx_credential_update_state(command_handle: u32,
             Err(e) => {
                 error!("vcx_credential_update_state_cb(command_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, error_string(e), 0, source_id);
-                cb(command_handle, e, 0)
+                cb(command_handle, record_error_code(e), 0)
             }
         }
 
@@ -350,7 +437,7 @@ pub extern fn vcx_credential_update_state(command_handle: u32,
             Err(e) => {
                 error!("vcx_credential_update_state_cb(command_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, error_string(e.to_error_code()), 0, source_id);
-                cb(command_handle, e.to_error_code(), 0)
+                cb(command_handle, e.record_error(), 0)
             }
         };
     });
@@ -362,10 +449,11 @@ pub extern fn vcx_credential_update_state(command_handle: u32,
 pub extern fn vcx_credential_get_state(command_handle: u32,
                                        handle: u32,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !credential::is_valid_handle(handle) {
-        return error::INVALID_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
     }
 
     let source_id = credential::get_source_id(handle).unwrap_or_default();
@@ -382,7 +470,7 @@ pub extern fn vcx_credential_get_state(command_handle: u32,
             Err(e) => {
                 error!("vcx_credential_get_state_cb(command_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, error_string(e.to_error_code()), 0, source_id);
-                cb(command_handle, e.to_error_code(), 0)
+                cb(command_handle, e.record_error(), 0)
             }
         };
     });
@@ -390,6 +478,52 @@ pub extern fn vcx_credential_get_state(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Get the transitions the credential went through, for debugging
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// handle: Credential handle that was provided during creation. Used to identify credential object
+///
+/// cb: Callback that provides the json array of transitions and provides error status
+/// -> [{"event":"SendOffer","from":1,"to":2,"timestamp":1537000000}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credential_get_state_history(command_handle: u32,
+                                               handle: u32,
+                                               cb: Option<extern fn(xcommand_handle: u32, err: u32, history: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !credential::is_valid_handle(handle) {
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
+    }
+
+    let source_id = credential::get_source_id(handle).unwrap_or_default();
+    info!("vcx_credential_get_state_history(command_handle: {}, handle: {}), source_id: {:?}",
+          command_handle, handle, source_id);
+
+    thread::spawn(move|| {
+        match credential::get_state_history(handle) {
+            Ok(x) => {
+                info!("vcx_credential_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_credential_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(x.to_error_code()), "null", source_id);
+                cb(command_handle, x.record_error(), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 
 /// Takes the credential object and returns a json string of all its attributes
 ///
@@ -406,11 +540,11 @@ pub extern fn vcx_credential_get_state(command_handle: u32,
 pub extern fn vcx_credential_serialize(command_handle: u32,
                                        handle: u32,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, data: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !credential::is_valid_handle(handle) {
-        return error::INVALID_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_CREDENTIAL_HANDLE.code_num);
     }
 
     let source_id = credential::get_source_id(handle).unwrap_or_default();
@@ -452,7 +586,7 @@ pub extern fn vcx_credential_serialize(command_handle: u32,
 pub extern fn vcx_credential_deserialize(command_handle: u32,
                                          credential_data: *const c_char,
                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(credential_data, error::INVALID_OPTION.code_num);
 
@@ -470,7 +604,7 @@ pub extern fn vcx_credential_deserialize(command_handle: u32,
             Err(x) => {
                 error!("vcx_credential_deserialize_cb(command_handle: {}, rc: {}, credential_handle: {}), source_id: {:?}",
                       command_handle, error_string(x), 0, "");
-                cb(command_handle, x, 0);
+                cb(command_handle, record_error_code(x), 0);
             },
         };
     });
@@ -487,6 +621,7 @@ pub extern fn vcx_credential_deserialize(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_credential_release(handle: u32) -> u32 {
+    reset_current_error();
     let source_id = credential::get_source_id(handle).unwrap_or_default();
     match credential::release(handle) {
         Ok(_) => info!("vcx_credential_release(handle: {}, rc: {}), source_id: {:?}",
@@ -519,7 +654,7 @@ pub extern fn vcx_credential_release(handle: u32) -> u32 {
 pub extern fn vcx_credential_get_payment_txn(command_handle: u32,
                                              handle: u32,
                                              cb: Option<extern fn(xcommand_handle: u32, err: u32, txn: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_credential_get_payment_txn(command_handle: {})", command_handle);
@@ -545,7 +680,7 @@ pub extern fn vcx_credential_get_payment_txn(command_handle: u32,
             Err(x) => {
                 error!("vcx_credential_get_payment_txn_cb(command_handle: {}, rc: {}, txn: {}), source_id: {:?}",
                        command_handle, x.to_string(), "null", credential::get_source_id(handle).unwrap_or_default());
-                cb(command_handle, x.to_error_code(), ptr::null());
+                cb(command_handle, x.record_error(), ptr::null());
             },
         };
     });
@@ -625,6 +760,25 @@ mod tests {
         cb.receive(Some(Duration::from_secs(10))).unwrap();
     }
 
+    #[test]
+    fn test_vcx_credential_send_request_to_service() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let issuer_h = ::issuer_credential::issuer_credential_create(::utils::constants::CRED_DEF_ID.to_string(),
+                                                                     "1".to_string(),
+                                                                     settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap(),
+                                                                     "credential_name".to_string(),
+                                                                     "{\"attr\":\"value\"}".to_owned(),
+                                                                     0).unwrap();
+        let offer = ::issuer_credential::generate_out_of_band_offer(issuer_h).unwrap();
+        let handle = credential::credential_create_with_offer("test_send_request_to_service", &offer).unwrap();
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_credential_send_request_to_service(cb.command_handle, handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(credential::get_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+    }
+
     #[test]
     fn test_vcx_credential_get_new_offers(){
         settings::set_defaults();
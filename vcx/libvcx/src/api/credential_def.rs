@@ -8,8 +8,13 @@ use utils::error::error_string;
 use std::thread;
 use std::ptr;
 use credential_def;
+use error::cred_def::CredDefError;
+use utils::libindy::pool;
 use settings;
 use error::ToErrorCode;
+use error::ToErrorDetails;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /// Create a new CredentialDef object that can create credential definitions on the ledger
 ///
@@ -45,12 +50,55 @@ pub extern fn vcx_credentialdef_create(command_handle: u32,
                                        config: *const c_char,
                                        payment_handle: u32,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_handle: u32)>) -> u32 {
+    vcx_credentialdef_create_with_pool(command_handle, source_id, credentialdef_name, schema_id, issuer_did,
+                                       tag, config, payment_handle, ptr::null(), cb)
+}
+
+/// Create a new CredentialDef object and write the credential definition to the given pool ledger
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// source_id: Enterprise's personal identification for the user.
+///
+/// credentialdef_name: Name of credential definition
+///
+/// schema_id: The schema id given during the creation of the schema
+///
+/// issuer_did: did corresponding to entity issuing a credential. Needs to have Trust Anchor permissions on ledger
+///
+/// tag: way to create a unique credential def with the same schema and issuer did.
+///
+/// config: revocation info
+///
+/// payment_handle: future use (currently uses any address in wallet)
+///
+/// pool_name: named pool (see vcx_open_pool) the credential definition is written to, NULL uses the
+/// default write pool. The credential definition object keeps using this pool.
+///
+/// cb: Callback that provides CredentialDef handle and error status of request.
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credentialdef_create_with_pool(command_handle: u32,
+                                                 source_id: *const c_char,
+                                                 credentialdef_name: *const c_char,
+                                                 schema_id: *const c_char,
+                                                 issuer_did: *const c_char,
+                                                 tag: *const c_char,
+                                                 config: *const c_char,
+                                                 payment_handle: u32,
+                                                 pool_name: *const c_char,
+                                                 cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_handle: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(credentialdef_name, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(schema_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(tag, error::INVALID_OPTION.code_num);
     check_useful_c_str!(config, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(pool_name, error::INVALID_OPTION.code_num);
 
     let issuer_did: String = if !issuer_did.is_null() {
         check_useful_c_str!(issuer_did, error::INVALID_OPTION.code_num);
@@ -58,25 +106,31 @@ pub extern fn vcx_credentialdef_create(command_handle: u32,
     } else {
         match settings::get_config_value(settings::CONFIG_INSTITUTION_DID) {
             Ok(x) => x,
-            Err(x) => return x
+            Err(x) => return record_error_code(x)
         }
     };
-    info!("vcx_credential_def_create(command_handle: {}, source_id: {}, credentialdef_name: {} schema_id: {}, issuer_did: {}, tag: {}, config: {})",
+    info!("vcx_credential_def_create(command_handle: {}, source_id: {}, credentialdef_name: {} schema_id: {}, issuer_did: {}, tag: {}, config: {}, pool_name: {:?})",
           command_handle,
           source_id,
           credentialdef_name,
           schema_id,
           issuer_did,
           tag,
-          config);
+          config,
+          pool_name);
 
     thread::spawn( move|| {
-        let ( rc, handle) = match credential_def::create_new_credentialdef(source_id,
-                                                                 credentialdef_name,
-                                                                 issuer_did,
-                                                                 schema_id,
-                                                                 tag,
-                                                                 config) {
+        let rc = pool::with_pool(pool_name.as_ref().map(String::as_str),
+                                 || credential_def::create_new_credentialdef(source_id,
+                                                                            credentialdef_name,
+                                                                            issuer_did,
+                                                                            schema_id,
+                                                                            tag,
+                                                                            config))
+            .map_err(CredDefError::CommonError)
+            .and_then(|rc| rc);
+
+        let ( rc, handle) = match rc {
             Ok(x) => {
                 info!("vcx_credential_def_create_cb(command_handle: {}, rc: {}, credentialdef_handle: {}), source_id: {:?}",
                       command_handle, error_string(0), x, credential_def::get_source_id(x).unwrap_or_default());
@@ -85,7 +139,7 @@ pub extern fn vcx_credentialdef_create(command_handle: u32,
             Err(x) => {
                 warn!("vcx_credential_def_create_cb(command_handle: {}, rc: {}, credentialdef_handle: {}), source_id: {:?}",
                       command_handle, error_string(x.to_error_code()), 0, "");
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
         cb(command_handle, rc, handle);
@@ -93,6 +147,169 @@ pub extern fn vcx_credentialdef_create(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Create a new CredentialDef object as a transaction author. The credential definition is stored in
+/// the wallet but not written to the ledger, the returned transaction has to be endorsed and submitted
+/// with vcx_endorse_transaction by an endorser.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// source_id: Enterprise's personal identification for the user.
+///
+/// credentialdef_name: Name of credential definition
+///
+/// schema_id: The schema id given during the creation of the schema
+///
+/// issuer_did: did of the author, NULL uses the institution did
+///
+/// tag: way to create a unique credential def with the same schema and issuer did.
+///
+/// config: revocation info
+///
+/// cb: Callback that provides CredentialDef handle, the transaction to endorse and error status of request.
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credentialdef_prepare_for_endorser(command_handle: u32,
+                                                     source_id: *const c_char,
+                                                     credentialdef_name: *const c_char,
+                                                     schema_id: *const c_char,
+                                                     issuer_did: *const c_char,
+                                                     tag: *const c_char,
+                                                     config: *const c_char,
+                                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_handle: u32, credentialdef_txn: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(credentialdef_name, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(schema_id, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(tag, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(config, error::INVALID_OPTION.code_num);
+
+    let issuer_did: String = if !issuer_did.is_null() {
+        check_useful_c_str!(issuer_did, error::INVALID_OPTION.code_num);
+        issuer_did.to_owned()
+    } else {
+        match settings::get_config_value(settings::CONFIG_INSTITUTION_DID) {
+            Ok(x) => x,
+            Err(x) => return record_error_code(x)
+        }
+    };
+    info!("vcx_credentialdef_prepare_for_endorser(command_handle: {}, source_id: {}, credentialdef_name: {} schema_id: {}, issuer_did: {}, tag: {}, config: {})",
+          command_handle,
+          source_id,
+          credentialdef_name,
+          schema_id,
+          issuer_did,
+          tag,
+          config);
+
+    thread::spawn( move|| {
+        match credential_def::prepare_credentialdef_for_endorser(source_id,
+                                                                 credentialdef_name,
+                                                                 issuer_did,
+                                                                 schema_id,
+                                                                 tag,
+                                                                 config) {
+            Ok((handle, txn)) => {
+                info!("vcx_credentialdef_prepare_for_endorser_cb(command_handle: {}, rc: {}, credentialdef_handle: {}, credentialdef_txn: {}), source_id: {:?}",
+                      command_handle, error_string(0), handle, txn, credential_def::get_source_id(handle).unwrap_or_default());
+                let txn = CStringUtils::string_to_cstring(txn);
+                cb(command_handle, error::SUCCESS.code_num, handle, txn.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_credentialdef_prepare_for_endorser_cb(command_handle: {}, rc: {}, credentialdef_handle: {}, credentialdef_txn: {}), source_id: {:?}",
+                      command_handle, error_string(x.to_error_code()), 0, "null", "");
+                cb(command_handle, x.record_error(), 0, ptr::null());
+            },
+        };
+    });
+    error::SUCCESS.code_num
+}
+
+/// Checks the ledger for a credential definition that is pending endorsement
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// credentialdef_handle: Credentialdef handle that was provided during creation. Used to access credentialdef object
+///
+/// cb: Callback that provides the most current state (0 - pending endorsement, 1 - published) and error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credentialdef_update_state(command_handle: u32,
+                                             credentialdef_handle: u32,
+                                             cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_credentialdef_update_state(command_handle: {}, credentialdef_handle: {})", command_handle, credentialdef_handle);
+    if !credential_def::is_valid_handle(credentialdef_handle) {
+        return record_error_code(error::INVALID_CREDENTIAL_DEF_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match credential_def::update_state(credentialdef_handle) {
+            Ok(state) => {
+                info!("vcx_credentialdef_update_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, error_string(0), state);
+                cb(command_handle, error::SUCCESS.code_num, state);
+            },
+            Err(x) => {
+                warn!("vcx_credentialdef_update_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, x.to_string(), 0);
+                cb(command_handle, x.record_error(), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Get the current state of the credential definition object
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// credentialdef_handle: Credentialdef handle that was provided during creation. Used to access credentialdef object
+///
+/// cb: Callback that provides the state (0 - pending endorsement, 1 - published) and error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_credentialdef_get_state(command_handle: u32,
+                                          credentialdef_handle: u32,
+                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_credentialdef_get_state(command_handle: {}, credentialdef_handle: {})", command_handle, credentialdef_handle);
+    if !credential_def::is_valid_handle(credentialdef_handle) {
+        return record_error_code(error::INVALID_CREDENTIAL_DEF_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match credential_def::get_state(credentialdef_handle) {
+            Ok(state) => {
+                info!("vcx_credentialdef_get_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, error_string(0), state);
+                cb(command_handle, error::SUCCESS.code_num, state);
+            },
+            Err(x) => {
+                warn!("vcx_credentialdef_get_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, x.to_string(), 0);
+                cb(command_handle, x.record_error(), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Takes the credentialdef object and returns a json string of all its attributes
 ///
 /// #Params
@@ -108,7 +325,7 @@ pub extern fn vcx_credentialdef_create(command_handle: u32,
 pub extern fn vcx_credentialdef_serialize(command_handle: u32,
                                      credentialdef_handle: u32,
                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_state: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = credential_def::get_source_id(credentialdef_handle).unwrap_or_default();
@@ -116,7 +333,7 @@ pub extern fn vcx_credentialdef_serialize(command_handle: u32,
           command_handle, credentialdef_handle, source_id);
 
     if !credential_def::is_valid_handle(credentialdef_handle) {
-        return error::INVALID_CREDENTIAL_DEF_HANDLE.code_num;
+        return record_error_code(error::INVALID_CREDENTIAL_DEF_HANDLE.code_num);
     };
 
     thread::spawn( move|| {
@@ -130,7 +347,7 @@ pub extern fn vcx_credentialdef_serialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_credentialdef_serialize_cb(command_handle: {}, credentialdef_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, credentialdef_handle, error_string(x), "null", source_id);
-                cb(command_handle, x, ptr::null_mut());
+                cb(command_handle, record_error_code(x), ptr::null_mut());
             },
         };
 
@@ -154,7 +371,7 @@ pub extern fn vcx_credentialdef_serialize(command_handle: u32,
 pub extern fn vcx_credentialdef_deserialize(command_handle: u32,
                                        credentialdef_data: *const c_char,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(credentialdef_data, error::INVALID_OPTION.code_num);
 
@@ -168,7 +385,7 @@ pub extern fn vcx_credentialdef_deserialize(command_handle: u32,
                 (error::SUCCESS.code_num, x)
             },
             Err(e) => {
-                let error_code = e.to_error_code();
+                let error_code = e.record_error();
                 warn!("vcx_credentialdef_deserialize_cb(command_handle: {}, rc: {}, handle: {}), source_id: {:?}",
                       command_handle, error_code, 0, "");
                 (error_code, 0)
@@ -191,11 +408,12 @@ pub extern fn vcx_credentialdef_deserialize(command_handle: u32,
 /// Error code as a u32
 #[no_mangle]
 pub extern fn vcx_credentialdef_get_cred_def_id(command_handle: u32, cred_def_handle: u32, cb: Option<extern fn(xcommand_handle: u32, err: u32, cred_def_id: *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_credentialdef_get_cred_def_id(command_handle: {}, cred_def_handle: {})", command_handle, cred_def_handle);
     if !credential_def::is_valid_handle(cred_def_handle) {
-        return error::INVALID_CREDENTIAL_DEF_HANDLE.code_num;
+        return record_error_code(error::INVALID_CREDENTIAL_DEF_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -209,7 +427,7 @@ pub extern fn vcx_credentialdef_get_cred_def_id(command_handle: u32, cred_def_ha
             Err(x) => {
                 warn!("vcx_credentialdef_get_cred_def_id(command_handle: {}, cred_def_handle: {}, rc: {}, cred_def_id: {})",
                       command_handle, cred_def_handle, x.to_string(), "");
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
     });
@@ -239,7 +457,7 @@ pub extern fn vcx_credentialdef_get_cred_def_id(command_handle: u32, cred_def_ha
 pub extern fn vcx_credentialdef_get_payment_txn(command_handle: u32,
                                          handle: u32,
                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, txn: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_credentialdef_get_payment_txn(command_handle: {})", command_handle);
@@ -265,7 +483,7 @@ pub extern fn vcx_credentialdef_get_payment_txn(command_handle: u32,
             Err(x) => {
                 error!("vcx_credentialdef_get_payment_txn_cb(command_handle: {}, rc: {}, txn: {}), source_id: {:?}",
                        command_handle, x.to_string(), "null", credential_def::get_source_id(handle).unwrap_or_default());
-                cb(command_handle, x.to_error_code(), ptr::null());
+                cb(command_handle, x.record_error(), ptr::null());
             },
         };
     });
@@ -282,6 +500,7 @@ pub extern fn vcx_credentialdef_get_payment_txn(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_credentialdef_release(credentialdef_handle: u32) -> u32 {
+    reset_current_error();
     let source_id = credential_def::get_source_id(credentialdef_handle).unwrap_or_default();
     match credential_def::release(credentialdef_handle) {
         Ok(_) => info!("vcx_credentialdef_release(credentialdef_handle: {}, rc: {}), source_id: {:?}",
@@ -324,6 +543,29 @@ mod tests {
         cb.receive(Some(Duration::from_secs(10))).unwrap();
     }
 
+    #[test]
+    fn test_vcx_create_credentialdef_on_named_pool() {
+        set_default_and_enable_test_mode();
+        let pool_name = "test_vcx_create_credentialdef_on_named_pool";
+        ::utils::libindy::pool::open_named_pool(pool_name, "", vec![]).unwrap();
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_credentialdef_create_with_pool(cb.command_handle,
+                                                      CString::new("Test Source ID").unwrap().into_raw(),
+                                                      CString::new("Test Credential Def").unwrap().into_raw(),
+                                                      CString::new(SCHEMA_ID).unwrap().into_raw(),
+                                                      CString::new("6vkhW3L28AophhA68SSzRS").unwrap().into_raw(),
+                                                      CString::new("tag").unwrap().into_raw(),
+                                                      CString::new("{}").unwrap().into_raw(),
+                                                      0,
+                                                      CString::new(pool_name).unwrap().into_raw(),
+                                                      Some(cb.get_callback())), error::SUCCESS.code_num);
+        let handle = cb.receive(Some(Duration::from_secs(10))).unwrap();
+        assert!(credential_def::to_string(handle).unwrap().contains(pool_name));
+
+        ::utils::libindy::pool::close_named_pool(pool_name).unwrap();
+    }
+
     #[test]
     fn test_vcx_create_credentialdef_fails() {
         settings::set_defaults();
@@ -375,6 +617,31 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_vcx_credentialdef_prepare_for_endorser() {
+        set_default_and_enable_test_mode();
+        let cb = return_types_u32::Return_U32_U32_STR::new().unwrap();
+        assert_eq!(vcx_credentialdef_prepare_for_endorser(cb.command_handle,
+                                                          CString::new("Test Source ID").unwrap().into_raw(),
+                                                          CString::new("Test Credential Def").unwrap().into_raw(),
+                                                          CString::new(SCHEMA_ID).unwrap().into_raw(),
+                                                          ptr::null(),
+                                                          CString::new("tag").unwrap().into_raw(),
+                                                          CString::new("{}").unwrap().into_raw(),
+                                                          Some(cb.get_callback())), error::SUCCESS.code_num);
+        let (handle, txn) = cb.receive(Some(Duration::from_secs(10))).unwrap();
+        assert!(handle > 0);
+        assert!(txn.is_some());
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_credentialdef_get_state(cb.command_handle, handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).unwrap(), ::api::PublicEntityStateType::PendingEndorsement as u32);
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_credentialdef_update_state(cb.command_handle, handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).unwrap(), ::api::PublicEntityStateType::Published as u32);
+    }
+
     #[test]
     fn test_vcx_creddef_get_id(){
         set_default_and_enable_test_mode();
@@ -9,6 +9,9 @@ use disclosed_proof;
 use std::thread;
 use std::ptr;
 use error::ToErrorCode;
+use error::ToErrorDetails;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /// Create a proof for fulfilling a corresponding proof request
 ///
@@ -30,7 +33,7 @@ pub extern fn vcx_disclosed_proof_create_with_request(command_handle: u32,
                                                       source_id: *const c_char,
                                                       proof_req: *const c_char,
                                                       cb: Option<extern fn(xcommand_handle: u32, err: u32, handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(proof_req, error::INVALID_OPTION.code_num);
@@ -48,7 +51,7 @@ pub extern fn vcx_disclosed_proof_create_with_request(command_handle: u32,
             Err(x) => {
                 error!("vcx_disclosed_proof_create_with_request_cb(command_handle: {}, rc: {}, handle: {})",
                       command_handle, error_string(x.to_error_code()), 0);
-                cb(command_handle, x.to_error_code(), 0);
+                cb(command_handle, x.record_error(), 0);
             },
         };
     });
@@ -80,7 +83,7 @@ pub extern fn vcx_disclosed_proof_create_with_msgid(command_handle: u32,
                                                     connection_handle: u32,
                                                     msg_id: *const c_char,
                                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, proof_handle: u32, proof_req: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(msg_id, error::INVALID_OPTION.code_num);
@@ -103,11 +106,11 @@ pub extern fn vcx_disclosed_proof_create_with_msgid(command_handle: u32,
                         warn!("vcx_disclosed_proof_create_with_msgid_cb(command_handle: {}, rc: {}, handle: {}, proof_req: {})",
                               command_handle, e.to_string(), 0, request);
                         let msg = CStringUtils::string_to_cstring(request);
-                        cb(command_handle, e.to_error_code(), 0, msg.as_ptr());
+                        cb(command_handle, e.record_error(), 0, msg.as_ptr());
                     },
                 };
             },
-            Err(e) => cb(command_handle, e.to_error_code(), 0, ptr::null()),
+            Err(e) => cb(command_handle, e.record_error(), 0, ptr::null()),
         };
     });
 
@@ -133,15 +136,15 @@ pub extern fn vcx_disclosed_proof_send_proof(command_handle: u32,
                                              proof_handle: u32,
                                              connection_handle: u32,
                                              cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !disclosed_proof::is_valid_handle(proof_handle) {
-        return error::INVALID_DISCLOSED_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
     }
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
@@ -158,7 +161,95 @@ pub extern fn vcx_disclosed_proof_send_proof(command_handle: u32,
             Err(x) => {
                 error!("vcx_disclosed_proof_send_proof_cb(command_handle: {}, rc: {}), source_id: {:?}",
                       command_handle, error_string(x.to_error_code()), source_id);
-                cb(command_handle,x.to_error_code());
+                cb(command_handle, x.record_error());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Create a proof for a signed proof request received without a connection, e.g. from a QR code
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// source_id: Institution's identification for the proof, should be unique.
+///
+/// request: out of band proof request generated by the verifier with "vcx_proof_get_out_of_band_request"
+///
+/// cb: Callback that provides proof handle or error status
+///
+/// #Returns
+/// Error code as u32
+#[no_mangle]
+pub extern fn vcx_disclosed_proof_create_with_out_of_band_request(command_handle: u32,
+                                                                  source_id: *const c_char,
+                                                                  request: *const c_char,
+                                                                  cb: Option<extern fn(xcommand_handle: u32, err: u32, handle: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(request, error::INVALID_OPTION.code_num);
+
+    info!("vcx_disclosed_proof_create_with_out_of_band_request(command_handle: {}, source_id: {}, request: {})",
+          command_handle, source_id, request);
+
+    thread::spawn(move|| {
+        match disclosed_proof::create_proof_with_out_of_band_request(source_id, &request) {
+            Ok(x) => {
+                info!("vcx_disclosed_proof_create_with_out_of_band_request_cb(command_handle: {}, rc: {}, handle: {})",
+                      command_handle, error_string(0), x);
+                cb(command_handle, 0, x);
+            },
+            Err(x) => {
+                error!("vcx_disclosed_proof_create_with_out_of_band_request_cb(command_handle: {}, rc: {}, handle: {})",
+                      command_handle, error_string(x.to_error_code()), 0);
+                cb(command_handle, x.record_error(), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Send a proof to the service endpoint of an out of band proof request, no connection is needed
+///
+/// #params
+/// command_handle: command handle to map callback to API user context.
+///
+/// proof_handle: proof handle created with "vcx_disclosed_proof_create_with_out_of_band_request"
+///
+/// cb: Callback that provides error status of proof send request
+///
+/// #Returns
+/// Error code as u32
+#[no_mangle]
+pub extern fn vcx_disclosed_proof_send_proof_to_service(command_handle: u32,
+                                                        proof_handle: u32,
+                                                        cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !disclosed_proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
+    }
+
+    let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
+    info!("vcx_disclosed_proof_send_proof_to_service(command_handle: {}, proof_handle: {}), source_id: {:?}",
+          command_handle, proof_handle, source_id);
+
+    thread::spawn(move|| {
+        match disclosed_proof::send_proof_to_service(proof_handle) {
+            Ok(x) => {
+                info!("vcx_disclosed_proof_send_proof_to_service_cb(command_handle: {}, rc: {}), source_id: {:?}",
+                      command_handle, error_string(0), source_id);
+                cb(command_handle, record_error_code(x));
+            },
+            Err(x) => {
+                error!("vcx_disclosed_proof_send_proof_to_service_cb(command_handle: {}, rc: {}), source_id: {:?}",
+                      command_handle, error_string(x.to_error_code()), source_id);
+                cb(command_handle, x.record_error());
             },
         };
     });
@@ -182,11 +273,11 @@ pub extern fn vcx_disclosed_proof_send_proof(command_handle: u32,
 pub extern fn vcx_disclosed_proof_get_requests(command_handle: u32,
                                                connection_handle: u32,
                                                cb: Option<extern fn(xcommand_handle: u32, err: u32, requests: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     info!("vcx_disclosed_proof_get_requests(command_handle: {}, connection_handle: {})",
@@ -203,7 +294,7 @@ pub extern fn vcx_disclosed_proof_get_requests(command_handle: u32,
             Err(x) => {
                 error!("vcx_disclosed_proof_get_requests_cb(command_handle: {}, rc: {}, msg: {})",
                       command_handle, error_string(0), x);
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
     });
@@ -215,11 +306,11 @@ pub extern fn vcx_disclosed_proof_get_requests(command_handle: u32,
 pub extern fn vcx_disclosed_proof_get_state(command_handle: u32,
                                             proof_handle: u32,
                                             cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !disclosed_proof::is_valid_handle(proof_handle) {
-        return error::INVALID_DISCLOSED_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
     }
 
     let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
@@ -236,7 +327,7 @@ pub extern fn vcx_disclosed_proof_get_state(command_handle: u32,
             Err(e) => {
                 error!("vcx_disclosed_proof_get_state_cb(command_handle: {}, rc: {}, state: {}), source_id: {:?}",
                        command_handle, error_string(e), 0, source_id);
-                cb(command_handle, e, 0)
+                cb(command_handle, record_error_code(e), 0)
             }
         };
     });
@@ -244,15 +335,61 @@ pub extern fn vcx_disclosed_proof_get_state(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Get the transitions the disclosed proof went through, for debugging
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// proof_handle: Proof handle that was provided during creation. Used to identify the disclosed proof object
+///
+/// cb: Callback that provides the json array of transitions and provides error status
+/// -> [{"event":"SendOffer","from":1,"to":2,"timestamp":1537000000}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_disclosed_proof_get_state_history(command_handle: u32,
+                                                    proof_handle: u32,
+                                                    cb: Option<extern fn(xcommand_handle: u32, err: u32, history: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !disclosed_proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
+    }
+
+    let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
+    info!("vcx_disclosed_proof_get_state_history(command_handle: {}, proof_handle: {}), source_id: {:?}",
+          command_handle, proof_handle, source_id);
+
+    thread::spawn(move|| {
+        match disclosed_proof::get_state_history(proof_handle) {
+            Ok(x) => {
+                info!("vcx_disclosed_proof_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_disclosed_proof_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(x), "null", source_id);
+                cb(command_handle, record_error_code(x), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 #[no_mangle]
 pub extern fn vcx_disclosed_proof_update_state(command_handle: u32,
                                                proof_handle: u32,
                                                cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !disclosed_proof::is_valid_handle(proof_handle) {
-        return error::INVALID_DISCLOSED_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
     }
 
     let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
@@ -269,7 +406,7 @@ pub extern fn vcx_disclosed_proof_update_state(command_handle: u32,
             Err(e) => {
                 error!("vcx_disclosed_proof_update_state_cb(command_handle: {}, rc: {}, state: {}), source_id: {:?}",
                        command_handle, error_string(e), 0, source_id);
-                cb(command_handle, e, 0)
+                cb(command_handle, record_error_code(e), 0)
             }
         };
     });
@@ -292,11 +429,11 @@ pub extern fn vcx_disclosed_proof_update_state(command_handle: u32,
 pub extern fn vcx_disclosed_proof_serialize(command_handle: u32,
                                             proof_handle: u32,
                                             cb: Option<extern fn(xcommand_handle: u32, err: u32, data: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !disclosed_proof::is_valid_handle(proof_handle) {
-        return error::INVALID_DISCLOSED_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
     }
 
     let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
@@ -338,7 +475,7 @@ pub extern fn vcx_disclosed_proof_serialize(command_handle: u32,
 pub extern fn vcx_disclosed_proof_deserialize(command_handle: u32,
                                               proof_data: *const c_char,
                                               cb: Option<extern fn(xcommand_handle: u32, err: u32, handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(proof_data, error::INVALID_OPTION.code_num);
 
@@ -356,7 +493,7 @@ pub extern fn vcx_disclosed_proof_deserialize(command_handle: u32,
             Err(x) => {
                 error!("vcx_disclosed_proof_deserialize_cb(command_handle: {}, rc: {}, proof_handle: {}), source_id: {:?}",
                        command_handle, error_string(x.to_error_code()), 0, "");
-                cb(command_handle, x.to_error_code(), 0);
+                cb(command_handle, x.record_error(), 0);
             },
         };
     });
@@ -379,11 +516,11 @@ pub extern fn vcx_disclosed_proof_deserialize(command_handle: u32,
 pub extern fn vcx_disclosed_proof_retrieve_credentials(command_handle: u32,
                                                        proof_handle: u32,
                                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, data: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !disclosed_proof::is_valid_handle(proof_handle) {
-        return error::INVALID_DISCLOSED_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
     }
 
     let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
@@ -401,7 +538,65 @@ pub extern fn vcx_disclosed_proof_retrieve_credentials(command_handle: u32,
             Err(x) => {
                 error!("vcx_disclosed_proof_retrieve_credentials(command_handle: {}, rc: {}, data: {}), source_id: {:?}",
                        command_handle, error_string(x.to_error_code()), 0, source_id);
-                cb(command_handle,x.to_error_code(),ptr::null_mut());
+                cb(command_handle, x.record_error(),ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Selects the credentials answering the proof request without user interaction
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// handle: Proof handle that was provided during creation. Used to identify the disclosed proof object
+///
+/// policy: json string with the rules the selection follows, every field is optional.
+/// Credentials from preferred issuers come first, then those holding the fewest attributes
+/// (unless minimize_revealed is false), then the most recently stored.
+/// hidden_attributes are proven without disclosing their value, self_attested values are used
+/// for attributes without restrictions no credential was found for.
+/// # Example policy -> "{"preferred_issuers":["V4SGRU86Z58d6TV7PBUe6f"],"minimize_revealed":true,
+///                       "hidden_attributes":["age"],"self_attested":{"phone":"8-800-300"}}"
+///
+/// cb: Callback that provides the selection. selected_credentials and self_attested_attrs can be passed
+/// to vcx_disclosed_proof_generate_proof, unsatisfied lists the referents nothing was found for.
+/// # Example selection -> "{"selected_credentials":{"attrs":{"attr1_referent":{"cred_info":cred_info,"interval":null,"revealed":true}},
+///                          "predicates":{}},"self_attested_attrs":{"attr3_referent":"8-800-300"},"unsatisfied":["predicate1_referent"]}"
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_disclosed_proof_select_credentials(command_handle: u32,
+                                                     proof_handle: u32,
+                                                     policy: *const c_char,
+                                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, selection: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(policy, error::INVALID_OPTION.code_num);
+
+    if !disclosed_proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
+    }
+
+    let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
+    info!("vcx_disclosed_proof_select_credentials(command_handle: {}, proof_handle: {}, policy: {}), source_id: {:?}",
+          command_handle, proof_handle, policy, source_id);
+
+    thread::spawn(move|| {
+        match disclosed_proof::select_credentials(proof_handle, &policy) {
+            Ok(x) => {
+                info!("vcx_disclosed_proof_select_credentials_cb(command_handle: {}, rc: {}, selection: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                error!("vcx_disclosed_proof_select_credentials_cb(command_handle: {}, rc: {}, selection: {}), source_id: {:?}",
+                       command_handle, error_string(x.to_error_code()), "null", source_id);
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
     });
@@ -417,9 +612,12 @@ pub extern fn vcx_disclosed_proof_retrieve_credentials(command_handle: u32,
 ///
 /// handle: Proof handle that was provided during creation. Used to identify the disclosed proof object
 ///
-/// selected_credentials: a json string with a credential for each proof request attribute.
-/// List of possible credentials for each attribute is returned from vcx_disclosed_proof_retrieve_credentials
-/// # Examples selected_credential -> "{"req_attr_0":cred_info}" Where cred_info is returned from retrieve credentials
+/// selected_credentials: a json string with a credential for each proof request attribute and predicate.
+/// List of possible credentials for each attribute and predicate is returned from vcx_disclosed_proof_retrieve_credentials.
+/// An attribute is disclosed unless "revealed" is false, a predicate never discloses the attribute.
+/// # Examples selected_credential -> "{"attrs":{"req_attr_0":{"cred_info":cred_info},"req_attr_1":{"cred_info":cred_info,"revealed":false}},
+///                                     "predicates":{"req_predicate_0":{"cred_info":cred_info}}}"
+/// Where cred_info is returned from retrieve credentials
 ///
 /// self_attested_attrs: a json string with attributes self attested by user
 /// # Examples self_attested_attrs -> "{"self_attested_attr_0":"attested_val"}"
@@ -434,13 +632,13 @@ pub extern fn vcx_disclosed_proof_generate_proof(command_handle: u32,
                                                  selected_credentials: *const c_char,
                                                  self_attested_attrs: *const c_char,
                                                  cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_str!(selected_credentials, error::INVALID_OPTION.code_num);
     check_useful_c_str!(self_attested_attrs, error::INVALID_OPTION.code_num);
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !disclosed_proof::is_valid_handle(proof_handle) {
-        return error::INVALID_DISCLOSED_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
     }
 
     let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
@@ -457,7 +655,7 @@ pub extern fn vcx_disclosed_proof_generate_proof(command_handle: u32,
             Err(x) => {
                 error!("vcx_disclosed_proof_generate_proof(command_handle: {}, rc: {}), source_id: {:?}",
                        command_handle, error_string(x.to_error_code()), source_id);
-                cb(command_handle,x.to_error_code());
+                cb(command_handle, x.record_error());
             },
         };
     });
@@ -466,6 +664,40 @@ pub extern fn vcx_disclosed_proof_generate_proof(command_handle: u32,
 }
 
 
+/// Choose the link secret the proof is created with. By default the link secret the selected
+/// credentials are bound to is used, with this set credentials bound to another one are rejected.
+///
+/// #Params
+/// proof_handle: Proof handle that was provided during creation. Used to identify the disclosed proof object
+///
+/// alias: alias of a link secret created with vcx_link_secret_create
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_disclosed_proof_set_link_secret(proof_handle: u32, alias: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(alias, error::INVALID_OPTION.code_num);
+
+    let source_id = disclosed_proof::get_source_id(proof_handle).unwrap_or_default();
+    info!("vcx_disclosed_proof_set_link_secret(proof_handle: {}, alias: {}), source_id: {:?}",
+          proof_handle, alias, source_id);
+
+    if !disclosed_proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_DISCLOSED_PROOF_HANDLE.code_num);
+    }
+
+    match disclosed_proof::set_link_secret(proof_handle, &alias) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => {
+            warn!("vcx_disclosed_proof_set_link_secret(proof_handle: {}, rc: {}), source_id: {:?}",
+                  proof_handle, error_string(x.to_error_code()), source_id);
+            x.record_error()
+        },
+    }
+}
+
+
 /// Releases the disclosed proof object by de-allocating memory
 ///
 /// #Params
@@ -475,6 +707,7 @@ pub extern fn vcx_disclosed_proof_generate_proof(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_disclosed_proof_release(handle: u32) -> u32 {
+    reset_current_error();
     let source_id = disclosed_proof::get_source_id(handle).unwrap_or_default();
     match disclosed_proof::release(handle) {
         Ok(_) => info!("vcx_disclosed_proof_release(handle: {}, rc: {}), source_id: {:?}",
@@ -579,6 +812,29 @@ mod tests {
         cb.receive(Some(Duration::from_secs(10))).unwrap();
     }
 
+    #[test]
+    fn test_vcx_send_proof_to_service() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+
+        let verifier_handle = ::proof::create_proof("1".to_string(), ::utils::constants::REQUESTED_ATTRS.to_owned(),
+                                                    ::utils::constants::REQUESTED_PREDICATES.to_owned(), "Optional".to_owned()).unwrap();
+        let request = ::proof::generate_out_of_band_request(verifier_handle).unwrap();
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_disclosed_proof_create_with_out_of_band_request(cb.command_handle,
+                                                                       CString::new("test_send_proof_to_service").unwrap().into_raw(),
+                                                                       CString::new(request).unwrap().into_raw(),
+                                                                       Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let handle = cb.receive(Some(Duration::from_secs(10))).unwrap();
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_disclosed_proof_send_proof_to_service(cb.command_handle, handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(disclosed_proof::get_state(handle).unwrap(), VcxStateType::VcxStateAccepted as u32);
+    }
+
     #[test]
     fn test_vcx_proof_get_requests(){
         settings::set_defaults();
@@ -620,6 +876,27 @@ mod tests {
         let credentials = cb.receive(None).unwrap().unwrap();
     }
 
+    #[test]
+    fn test_vcx_disclosed_proof_select_credentials() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_disclosed_proof_create_with_request(cb.command_handle,
+                                                           CString::new("test_select").unwrap().into_raw(),
+                                                           CString::new(::utils::constants::PROOF_REQUEST_JSON).unwrap().into_raw(),
+                                                           Some(cb.get_callback())), error::SUCCESS.code_num);
+        let handle = cb.receive(Some(Duration::from_secs(2))).unwrap();
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_disclosed_proof_select_credentials(cb.command_handle,
+                                                          handle,
+                                                          CString::new(r#"{"self_attested":{"phone":"8-800-300"}}"#).unwrap().into_raw(),
+                                                          Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let selection: Value = serde_json::from_str(&cb.receive(None).unwrap().unwrap()).unwrap();
+        assert_eq!(selection["self_attested_attrs"]["attr3_referent"], json!("8-800-300"));
+        assert_eq!(selection["unsatisfied"], json!(["attr1_referent", "attr2_referent", "predicate1_referent"]));
+    }
+
     #[test]
     fn test_vcx_disclosed_proof_generate_proof() {
         settings::set_defaults();
@@ -0,0 +1,191 @@
+extern crate libc;
+extern crate serde_json;
+
+use self::libc::c_char;
+use utils::cstring::CStringUtils;
+use utils::error;
+use utils::error::error_string;
+use dispatcher;
+use std::thread;
+use std::ptr;
+use error::context::reset_current_error;
+use error::record_error_code;
+
+/// Register a callback fired by the message dispatcher each time it changes the state of a
+/// Connection, IssuerCredential, Credential or Proof
+///
+/// #Params
+/// command_handle: command handle passed back to the callback, also used to unregister it.
+///
+/// cb: Callback that provides the object type (0: Connection, 1: IssuerCredential, 2: Credential, 3: Proof),
+/// the handle of the object and its new state
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_dispatcher_register_callback(command_handle: u32,
+                                               cb: Option<extern fn(xcommand_handle: u32, object_type: u32, handle: u32, state: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_dispatcher_register_callback(command_handle: {})", command_handle);
+
+    match dispatcher::register_callback(command_handle, cb) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Remove a callback registered with vcx_dispatcher_register_callback
+///
+/// #Params
+/// command_handle: command handle the callback was registered with
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_dispatcher_unregister_callback(command_handle: u32) -> u32 {
+    reset_current_error();
+    info!("vcx_dispatcher_unregister_callback(command_handle: {})", command_handle);
+
+    match dispatcher::unregister_callback(command_handle) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Download new messages for every object waiting on an answer with a single agency call
+/// and update the objects they answer
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// cb: Callback that provides the number of objects whose state changed
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_dispatcher_poll(command_handle: u32,
+                                  cb: Option<extern fn(xcommand_handle: u32, err: u32, updated: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_dispatcher_poll(command_handle: {})", command_handle);
+
+    thread::spawn(move|| {
+        match dispatcher::poll() {
+            Ok(x) => {
+                info!("vcx_dispatcher_poll_cb(command_handle: {}, rc: {}, updated: {})",
+                      command_handle, error_string(0), x);
+                cb(command_handle, error::SUCCESS.code_num, x);
+            },
+            Err(x) => {
+                error!("vcx_dispatcher_poll_cb(command_handle: {}, rc: {}, updated: {})",
+                       command_handle, error_string(x), 0);
+                cb(command_handle, record_error_code(x), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Update the objects waiting on the message a push notification announces, fetching only that
+/// message from the agency
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// payload: data of the push notification as received by the app (FCM data, APNs payload or webhook body)
+/// -> {"data":{"uid":"ntc2ytb","forDID":"8XFh8yBzrpJQmNyZzgoTqB","type":"credReq"}}
+///
+/// cb: Callback that provides the objects whose state changed
+/// -> [{"object_type":1,"handle":2371854417,"state":3}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_dispatcher_handle_push_notification(command_handle: u32,
+                                                      payload: *const c_char,
+                                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, changes: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(payload, error::INVALID_OPTION.code_num);
+
+    info!("vcx_dispatcher_handle_push_notification(command_handle: {}, payload: {})", command_handle, payload);
+
+    thread::spawn(move|| {
+        let result = dispatcher::handle_push_notification(&payload)
+            .and_then(|changes| serde_json::to_string(&changes).or(Err(error::INVALID_JSON.code_num)));
+
+        match result {
+            Ok(x) => {
+                info!("vcx_dispatcher_handle_push_notification_cb(command_handle: {}, rc: {}, changes: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                error!("vcx_dispatcher_handle_push_notification_cb(command_handle: {}, rc: {}, changes: {})",
+                       command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Start a thread that polls the agency for new messages every interval_ms milliseconds
+///
+/// #Params
+/// interval_ms: time between two polls
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_dispatcher_start(interval_ms: u32) -> u32 {
+    reset_current_error();
+    info!("vcx_dispatcher_start(interval_ms: {})", interval_ms);
+
+    match dispatcher::start(interval_ms) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Stop the thread started with vcx_dispatcher_start
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_dispatcher_stop() -> u32 {
+    reset_current_error();
+    info!("vcx_dispatcher_stop()");
+
+    dispatcher::stop();
+    error::SUCCESS.code_num
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::time::Duration;
+    use utils::libindy::return_types_u32;
+
+    #[test]
+    fn test_vcx_dispatcher_register_callback_requires_callback() {
+        assert_eq!(vcx_dispatcher_register_callback(0, None), error::INVALID_OPTION.code_num);
+    }
+
+    #[test]
+    fn test_vcx_dispatcher_handle_invalid_push_notification() {
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_dispatcher_handle_push_notification(cb.command_handle,
+                                                           CString::new(r#"{"aps":{"alert":"hi"}}"#).unwrap().into_raw(),
+                                                           Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).err(), Some(error::INVALID_PUSH_NOTIFICATION.code_num));
+    }
+}
@@ -11,6 +11,9 @@ use issuer_credential;
 use std::thread;
 use std::ptr;
 use error::ToErrorCode;
+use error::ToErrorDetails;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /**
  * credential object
@@ -50,7 +53,7 @@ pub extern fn vcx_issuer_create_credential(command_handle: u32,
                                       credential_name: *const c_char,
                                       price: u64,
                                       cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(credential_data, error::INVALID_OPTION.code_num);
     check_useful_c_str!(credential_name, error::INVALID_OPTION.code_num);
@@ -63,7 +66,7 @@ pub extern fn vcx_issuer_create_credential(command_handle: u32,
     } else {
         match settings::get_config_value(settings::CONFIG_INSTITUTION_DID) {
             Ok(x) => x,
-            Err(x) => return x
+            Err(x) => return record_error_code(x)
         }
     };
 
@@ -85,7 +88,7 @@ pub extern fn vcx_issuer_create_credential(command_handle: u32,
             Err(x) => {
                 warn!("vcx_issuer_create_credential_cb(command_handle: {}, rc: {}, handle: {}, source_id: {:?}",
                       command_handle, error_string(x.to_error_code()), 0, "");
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
 
@@ -113,7 +116,7 @@ pub extern fn vcx_issuer_send_credential_offer(command_handle: u32,
                                           credential_handle: u32,
                                           connection_handle: u32,
                                           cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
@@ -121,11 +124,11 @@ pub extern fn vcx_issuer_send_credential_offer(command_handle: u32,
           command_handle, credential_handle, connection_handle, source_id);
 
     if !issuer_credential::is_valid_handle(credential_handle) {
-        return error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
     }
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -138,7 +141,7 @@ pub extern fn vcx_issuer_send_credential_offer(command_handle: u32,
             Err(x) => {
                 warn!("vcx_issuer_send_credential_cb(command_handle: {}, credential_handle: {}, rc: {}, source_id: {:?})",
                       command_handle, credential_handle, error_string(x.to_error_code()), source_id);
-                x.to_error_code()
+                x.record_error()
             },
         };
 
@@ -148,6 +151,58 @@ pub extern fn vcx_issuer_send_credential_offer(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Generate a signed credential offer that does not need a connection, e.g. to show as a QR code.
+/// The holder sends its credential request back to the service endpoint embedded in the offer,
+/// check for it with vcx_issuer_credential_update_state.
+///
+/// Anyone who reads the offer can answer it. The first credential request that arrives is the
+/// one accepted, and the credential is sent to the reply-to service it carries. Neither the
+/// request nor its service are authenticated, so only show the offer to the intended holder.
+/// The pairwise agent created for the offer is deleted once the request arrives.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// credential_handle: Credential handle that was provided during creation. Used to identify credential object
+///
+/// cb: Callback that provides the out of band credential offer and error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_issuer_get_out_of_band_credential_offer(command_handle: u32,
+                                                          credential_handle: u32,
+                                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, offer: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
+    info!("vcx_issuer_get_out_of_band_credential_offer(command_handle: {}, credential_handle: {}), source_id: {:?}",
+          command_handle, credential_handle, source_id);
+
+    if !issuer_credential::is_valid_handle(credential_handle) {
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match issuer_credential::generate_out_of_band_offer(credential_handle) {
+            Ok(x) => {
+                info!("vcx_issuer_get_out_of_band_credential_offer_cb(command_handle: {}, credential_handle: {}, rc: {}, offer: {}), source_id: {:?}",
+                      command_handle, credential_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_issuer_get_out_of_band_credential_offer_cb(command_handle: {}, credential_handle: {}, rc: {}, offer: {}), source_id: {:?}",
+                      command_handle, credential_handle, error_string(x.to_error_code()), "null", source_id);
+                cb(command_handle, x.record_error(), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Checks for any state change in the credential and updates the the state attribute
 ///
 /// #Params
@@ -163,7 +218,7 @@ pub extern fn vcx_issuer_send_credential_offer(command_handle: u32,
 pub extern fn vcx_issuer_credential_update_state(command_handle: u32,
                                             credential_handle: u32,
                                             cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
@@ -171,7 +226,7 @@ pub extern fn vcx_issuer_credential_update_state(command_handle: u32,
           command_handle, credential_handle);
 
     if !issuer_credential::is_valid_handle(credential_handle) {
-        return error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -184,7 +239,7 @@ pub extern fn vcx_issuer_credential_update_state(command_handle: u32,
             Err(x) => {
                 warn!("vcx_issuer_credential_update_state_cb(command_handle: {}, credential_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, credential_handle, x.to_string(), 0, source_id);
-                cb(command_handle, x.to_error_code(), 0);
+                cb(command_handle, x.record_error(), 0);
             }
         }
     });
@@ -196,7 +251,7 @@ pub extern fn vcx_issuer_credential_update_state(command_handle: u32,
 pub extern fn vcx_issuer_credential_get_state(command_handle: u32,
                                          credential_handle: u32,
                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
@@ -204,7 +259,7 @@ pub extern fn vcx_issuer_credential_get_state(command_handle: u32,
           command_handle, credential_handle, source_id);
 
     if !issuer_credential::is_valid_handle(credential_handle) {
-        return error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -217,7 +272,53 @@ pub extern fn vcx_issuer_credential_get_state(command_handle: u32,
             Err(x) => {
                 warn!("vcx_issuer_credential_get_state_cb(command_handle: {}, credential_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, credential_handle, error_string(x), 0, source_id);
-                cb(command_handle, x, 0);
+                cb(command_handle, record_error_code(x), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Get the transitions the issuer credential went through, for debugging
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// credential_handle: Credential handle that was provided during creation. Used to identify credential object
+///
+/// cb: Callback that provides the json array of transitions and provides error status
+/// -> [{"event":"SendOffer","from":1,"to":2,"timestamp":1537000000}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_issuer_credential_get_state_history(command_handle: u32,
+                                                      credential_handle: u32,
+                                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, history: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !issuer_credential::is_valid_handle(credential_handle) {
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
+    }
+
+    let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
+    info!("vcx_issuer_credential_get_state_history(command_handle: {}, credential_handle: {}), source_id: {:?}",
+          command_handle, credential_handle, source_id);
+
+    thread::spawn(move|| {
+        match issuer_credential::get_state_history(credential_handle) {
+            Ok(x) => {
+                info!("vcx_issuer_credential_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_issuer_credential_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(x.to_error_code()), "null", source_id);
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
     });
@@ -248,15 +349,15 @@ pub extern fn vcx_issuer_send_credential(command_handle: u32,
                                     credential_handle: u32,
                                     connection_handle: u32,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !issuer_credential::is_valid_handle(credential_handle) {
-        return error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
     }
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
@@ -272,7 +373,52 @@ pub extern fn vcx_issuer_send_credential(command_handle: u32,
             Err(x) => {
                 warn!("vcx_issuer_send_credential_cb(command_handle: {}, credential_handle: {}, rc: {})",
                       command_handle, credential_handle, error_string(x.to_error_code()));
-                x.to_error_code()
+                x.record_error()
+            },
+        };
+
+        cb(command_handle,err);
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Send Credential to the service a holder included in the request to an out of band credential offer
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// credential_handle: Credential handle that was provided during creation. Used to identify credential object
+///
+/// cb: Callback that provides error status of sending the credential
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_issuer_send_credential_to_service(command_handle: u32,
+                                                    credential_handle: u32,
+                                                    cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !issuer_credential::is_valid_handle(credential_handle) {
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
+    }
+
+    let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
+    info!("vcx_issuer_send_credential_to_service(command_handle: {}, credential_handle: {}), source_id: {:?}",
+          command_handle, credential_handle, source_id);
+    thread::spawn(move|| {
+        let err = match issuer_credential::send_credential_to_service(credential_handle) {
+            Ok(x) => {
+                info!("vcx_issuer_send_credential_to_service_cb(command_handle: {}, credential_handle: {}, rc: {})",
+                      command_handle, credential_handle, error_string(x));
+                x
+            },
+            Err(x) => {
+                warn!("vcx_issuer_send_credential_to_service_cb(command_handle: {}, credential_handle: {}, rc: {})",
+                      command_handle, credential_handle, error_string(x.to_error_code()));
+                x.record_error()
             },
         };
 
@@ -300,11 +446,11 @@ pub extern fn vcx_issuer_terminate_credential(credential_handle: u32, terminatio
 pub extern fn vcx_issuer_credential_serialize(command_handle: u32,
                                          credential_handle: u32,
                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_state: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !issuer_credential::is_valid_handle(credential_handle) {
-        return error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num;
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
     }
 
     let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
@@ -321,7 +467,7 @@ pub extern fn vcx_issuer_credential_serialize(command_handle: u32,
             Err(x) => {
                 info!("vcx_issuer_credential_serialize_cb(command_handle: {}, credential_handle: {}, rc: {}, state: {}, source_id: {:?})",
                       command_handle, credential_handle, error_string(x.to_error_code()), "null", source_id);
-                cb(command_handle,x.to_error_code(),ptr::null_mut());
+                cb(command_handle, x.record_error(),ptr::null_mut());
             },
         };
     });
@@ -344,7 +490,7 @@ pub extern fn vcx_issuer_credential_serialize(command_handle: u32,
 pub extern fn vcx_issuer_credential_deserialize(command_handle: u32,
                                       credential_data: *const c_char,
                                       cb: Option<extern fn(xcommand_handle: u32, err: u32, credential_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(credential_data, error::INVALID_OPTION.code_num);
 
@@ -360,7 +506,7 @@ pub extern fn vcx_issuer_credential_deserialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_issuer_credential_deserialize_cb(command_handle: {}, rc: {}, handle: {})",
                       command_handle, x.to_string(), 0);
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
 
@@ -379,6 +525,7 @@ pub extern fn vcx_issuer_credential_deserialize(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_issuer_credential_release(credential_handle: u32) -> u32 {
+    reset_current_error();
     let source_id = issuer_credential::get_source_id(credential_handle).unwrap_or_default();
     match issuer_credential::release(credential_handle) {
         Ok(_) => info!("(vcx_issuer_credential_release credential_handle: {}, rc: {}), source_id: {:?}",
@@ -411,7 +558,7 @@ pub extern fn vcx_issuer_credential_release(credential_handle: u32) -> u32 {
 pub extern fn vcx_issuer_credential_get_payment_txn(command_handle: u32,
                                                 handle: u32,
                                                 cb: Option<extern fn(xcommand_handle: u32, err: u32, txn: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_issuer_credential_get_payment_txn(command_handle: {})", command_handle);
@@ -437,7 +584,7 @@ pub extern fn vcx_issuer_credential_get_payment_txn(command_handle: u32,
             Err(x) => {
                 error!("vcx_issuer_credential_get_payment_txn_cb(command_handle: {}, rc: {}, txn: {}), source_id: {:?}",
                        command_handle, x.to_string(), "null", issuer_credential::get_source_id(handle).unwrap_or_default());
-                cb(command_handle, x.to_error_code(), ptr::null());
+                cb(command_handle, x.record_error(), ptr::null());
             },
         };
     });
@@ -445,6 +592,58 @@ pub extern fn vcx_issuer_credential_get_payment_txn(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Declare how attributes of the credential are encoded. Attributes that are not declared keep the
+/// legacy encoding, which hashes any value that is not an unsigned 32 bit integer and so cannot be
+/// used in predicates. Must be called before the credential is sent.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// credential_handle: Credential handle that was provided during creation. Used to identify credential object
+///
+/// encodings: encoding per attribute name, one of legacy, int32, int64, date (YYYY-MM-DD) or bool
+/// -> {"age":"int32","date_of_birth":"date","veteran":"bool"}
+///
+/// cb: Callback that provides error status of the declaration
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_issuer_credential_set_attribute_encodings(command_handle: u32,
+                                                            credential_handle: u32,
+                                                            encodings: *const c_char,
+                                                            cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(encodings, error::INVALID_OPTION.code_num);
+
+    if !issuer_credential::is_valid_handle(credential_handle) {
+        return record_error_code(error::INVALID_ISSUER_CREDENTIAL_HANDLE.code_num);
+    }
+
+    info!("vcx_issuer_credential_set_attribute_encodings(command_handle: {}, credential_handle: {}, encodings: {}), source_id: {:?}",
+          command_handle, credential_handle, encodings, issuer_credential::get_source_id(credential_handle).unwrap_or_default());
+
+    thread::spawn(move|| {
+        let err = match issuer_credential::set_attribute_encodings(credential_handle, &encodings) {
+            Ok(x) => {
+                info!("vcx_issuer_credential_set_attribute_encodings_cb(command_handle: {}, credential_handle: {}, rc: {}), source_id: {:?}",
+                      command_handle, credential_handle, error_string(0), issuer_credential::get_source_id(credential_handle).unwrap_or_default());
+                x
+            },
+            Err(x) => {
+                warn!("vcx_issuer_credential_set_attribute_encodings_cb(command_handle: {}, credential_handle: {}, rc: {}), source_id: {:?}",
+                      command_handle, credential_handle, error_string(x.to_error_code()), issuer_credential::get_source_id(credential_handle).unwrap_or_default());
+                x.record_error()
+            },
+        };
+
+        cb(command_handle, err);
+    });
+
+    error::SUCCESS.code_num
+}
+
 #[cfg(test)]
 mod tests {
     extern crate serde_json;
@@ -480,6 +679,36 @@ mod tests {
         cb.receive(Some(Duration::from_secs(10))).unwrap();
     }
 
+    #[test]
+    fn test_vcx_issuer_credential_set_attribute_encodings() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let handle = issuer_credential::issuer_credential_create(CRED_DEF_ID.to_string(),
+                                                                 "1".to_string(),
+                                                                 DEFAULT_DID.to_string(),
+                                                                 DEFAULT_CREDENTIAL_NAME.to_string(),
+                                                                 r#"{"age":["-5"],"name":["Bob"]}"#.to_string(),
+                                                                 0).unwrap();
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_issuer_credential_set_attribute_encodings(cb.command_handle,
+                                                                 handle,
+                                                                 CString::new(r#"{"age":"int32"}"#).unwrap().into_raw(),
+                                                                 Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+        let encoded = issuer_credential::get_encoded_attributes(handle).unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&encoded).unwrap()["age"], json!(["-5", "-5"]));
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_issuer_credential_set_attribute_encodings(cb.command_handle,
+                                                                 handle,
+                                                                 CString::new(r#"{"name":"int32"}"#).unwrap().into_raw(),
+                                                                 Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).err(), Some(error::INVALID_ATTRIBUTE_ENCODING.code_num));
+    }
+
     #[test]
     fn test_vcx_issuer_create_credential_fails() {
         settings::set_defaults();
@@ -579,6 +808,30 @@ mod tests {
         cb.receive(Some(Duration::from_secs(10))).unwrap();
     }
 
+    #[test]
+    fn test_vcx_issuer_out_of_band_credential() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let handle = issuer_credential::issuer_credential_create(CRED_DEF_ID.to_string(),
+                                                                 "1".to_string(),
+                                                                 DEFAULT_DID.to_owned(),
+                                                                 DEFAULT_CREDENTIAL_NAME.to_string(),
+                                                                 DEFAULT_ATTR.to_owned(),
+                                                                 0).unwrap();
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_issuer_get_out_of_band_credential_offer(cb.command_handle, handle, Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let offer = cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap();
+        assert!(offer.contains("CRED_OFFER"));
+
+        // no credential request was received from the holder yet
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_issuer_send_credential_to_service(cb.command_handle, handle, Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).err(), Some(error::INVALID_STATE.code_num));
+    }
+
     #[test]
     fn test_create_credential_arguments_correct(){
         settings::set_defaults();
@@ -0,0 +1,138 @@
+extern crate libc;
+extern crate serde_json;
+
+use self::libc::c_char;
+use utils::cstring::CStringUtils;
+use utils::error;
+use utils::error::error_string;
+use link_secret;
+use std::thread;
+use std::ptr;
+use error::context::reset_current_error;
+use error::record_error_code;
+
+/// Create a new link secret in the wallet. Credentials bound to different link secrets cannot be
+/// linked to each other, e.g. to keep a work and a personal persona apart.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// alias: name of the link secret, used to choose it for credentials and proofs
+///
+/// cb: Callback that provides error status of creating the link secret
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_link_secret_create(command_handle: u32,
+                                     alias: *const c_char,
+                                     cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(alias, error::INVALID_OPTION.code_num);
+
+    info!("vcx_link_secret_create(command_handle: {}, alias: {})", command_handle, alias);
+
+    thread::spawn(move|| {
+        let err = match link_secret::create(&alias) {
+            Ok(_) => {
+                info!("vcx_link_secret_create_cb(command_handle: {}, rc: {})", command_handle, error_string(0));
+                error::SUCCESS.code_num
+            },
+            Err(x) => {
+                warn!("vcx_link_secret_create_cb(command_handle: {}, rc: {})", command_handle, error_string(x));
+                record_error_code(x)
+            },
+        };
+
+        cb(command_handle, err);
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// List the link secrets in the wallet
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// cb: Callback that provides a json array of link secret aliases
+/// # Example aliases -> ["main","work"]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_link_secret_list(command_handle: u32,
+                                   cb: Option<extern fn(xcommand_handle: u32, err: u32, aliases: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_link_secret_list(command_handle: {})", command_handle);
+
+    thread::spawn(move|| {
+        match link_secret::list().and_then(|x| serde_json::to_string(&x).or(Err(error::INVALID_JSON.code_num))) {
+            Ok(x) => {
+                info!("vcx_link_secret_list_cb(command_handle: {}, rc: {}, aliases: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_link_secret_list_cb(command_handle: {}, rc: {}, aliases: {})",
+                      command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Select the link secret new credentials and proofs use unless another one is chosen for them
+///
+/// #Params
+/// alias: alias of a link secret in the wallet
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_link_secret_select(alias: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(alias, error::INVALID_OPTION.code_num);
+
+    info!("vcx_link_secret_select(alias: {})", alias);
+
+    match link_secret::select(&alias) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::time::Duration;
+    use settings;
+    use utils::libindy::return_types_u32;
+
+    #[test]
+    fn test_vcx_link_secret_create_and_list() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_link_secret_create(cb.command_handle,
+                                          CString::new("test_vcx_link_secret").unwrap().into_raw(),
+                                          Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_link_secret_list(cb.command_handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        let aliases: Vec<String> = serde_json::from_str(&cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap()).unwrap();
+        assert!(aliases.contains(&"test_vcx_link_secret".to_string()));
+
+        assert_eq!(vcx_link_secret_select(CString::new("unknown").unwrap().into_raw()), error::LINK_SECRET_NOT_FOUND.code_num);
+    }
+}
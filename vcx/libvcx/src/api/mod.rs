@@ -10,6 +10,12 @@ pub mod schema;
 pub mod credential;
 pub mod disclosed_proof;
 pub mod wallet;
+pub mod nym;
+pub mod pool;
+pub mod dispatcher;
+pub mod outbox;
+pub mod proof_template;
+pub mod link_secret;
 
 use std::fmt;
 
@@ -83,6 +89,8 @@ enum_number!(VcxStateType
     VcxStateUnfulfilled = 5,
     VcxStateExpired = 6,
     VcxStateRevoked = 7,
+    // the message moving the object to its next state waits in the outbox for the agency
+    VcxStateQueued = 8,
 });
 
 // undefined is correlated with VcxStateNon -> Haven't received Proof
@@ -95,6 +103,28 @@ enum_number!(ProofStateType
     ProofInvalid = 2,
 });
 
+// Schemas and credential definitions prepared by an author stay PendingEndorsement
+// until the endorser has submitted them and they can be read back from the ledger
+enum_number!(PublicEntityStateType
+{
+    PendingEndorsement = 0,
+    Published = 1,
+});
+
+// objects serialized before endorsement was supported were always written to the ledger
+impl Default for PublicEntityStateType {
+    fn default() -> Self { PublicEntityStateType::Published }
+}
+
+// Objects the message dispatcher reports state changes for
+enum_number!(VcxObjectType
+{
+    Connection = 0,
+    IssuerCredential = 1,
+    Credential = 2,
+    Proof = 3,
+});
+
 #[repr(C)]
 pub struct VcxStatus {
     pub handle: ::std::os::raw::c_int,
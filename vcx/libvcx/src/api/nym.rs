@@ -0,0 +1,293 @@
+extern crate libc;
+extern crate serde_json;
+
+use self::libc::c_char;
+use utils::cstring::CStringUtils;
+use utils::error;
+use utils::error::error_string;
+use utils::libindy::payments::PaymentTxn;
+use std::thread;
+use std::ptr;
+use nym;
+use utils::libindy::pool;
+use error::context::reset_current_error;
+use error::record_error_code;
+
+fn payment_to_string(payment: Option<PaymentTxn>) -> Option<String> {
+    match payment {
+        Some(x) => serde_json::to_string(&x).ok(),
+        None => None,
+    }
+}
+
+/// Write a NYM transaction to the ledger: registers a new DID or updates the verkey/role of an existing one.
+/// The institution DID is the submitter and pays the fees if the ledger charges for NYM transactions.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// target_did: DID to register or update
+///
+/// verkey: verkey of the target DID, NULL keeps the current verkey
+///
+/// role: TRUSTEE, STEWARD, TRUST_ANCHOR, NETWORK_MONITOR, empty string to remove the role, NULL keeps the current role
+///
+/// pool_name: named pool (see vcx_open_pool) the transaction is written to, NULL uses the default write pool
+///
+/// cb: Callback that provides the payment information (NULL when no fees were paid) or error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_ledger_write_nym(command_handle: u32,
+                                   target_did: *const c_char,
+                                   verkey: *const c_char,
+                                   role: *const c_char,
+                                   pool_name: *const c_char,
+                                   cb: Option<extern fn(xcommand_handle: u32, err: u32, payment_txn: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(target_did, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(verkey, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(role, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(pool_name, error::INVALID_OPTION.code_num);
+
+    info!("vcx_ledger_write_nym(command_handle: {}, target_did: {}, verkey: {:?}, role: {:?}, pool_name: {:?})",
+          command_handle, target_did, verkey, role, pool_name);
+
+    thread::spawn(move|| {
+        match pool::with_pool(pool_name.as_ref().map(String::as_str),
+                              || nym::write_nym(&target_did, verkey.as_ref().map(String::as_str), role.as_ref().map(String::as_str)))
+            .and_then(|rc| rc) {
+            Ok(payment) => {
+                let payment = payment_to_string(payment);
+                info!("vcx_ledger_write_nym_cb(command_handle: {}, rc: {}, payment_txn: {:?})",
+                      command_handle, error_string(0), payment);
+                match payment {
+                    Some(x) => {
+                        let msg = CStringUtils::string_to_cstring(x);
+                        cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+                    },
+                    None => cb(command_handle, error::SUCCESS.code_num, ptr::null()),
+                }
+            },
+            Err(x) => {
+                error!("vcx_ledger_write_nym_cb(command_handle: {}, rc: {}, payment_txn: {})",
+                       command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Read the NYM data of a DID from the ledger
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// did: DID to look up
+///
+/// cb: Callback that provides the nym data or error status
+/// -> {"dest":"","identifier":"","role":"","seqNo":0,"txnTime":0,"verkey":""}
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_ledger_get_nym(command_handle: u32,
+                                 did: *const c_char,
+                                 cb: Option<extern fn(xcommand_handle: u32, err: u32, nym: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(did, error::INVALID_OPTION.code_num);
+
+    info!("vcx_ledger_get_nym(command_handle: {}, did: {})", command_handle, did);
+
+    thread::spawn(move|| {
+        match nym::get_nym(&did) {
+            Ok(x) => {
+                info!("vcx_ledger_get_nym_cb(command_handle: {}, rc: {}, nym: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                error!("vcx_ledger_get_nym_cb(command_handle: {}, rc: {}, nym: {})",
+                       command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Write a raw ATTRIB transaction (ie, service endpoint) for a DID owned by the institution.
+/// Fees are paid by the institution if the ledger charges for ATTRIB transactions.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// target_did: DID the attribute belongs to
+///
+/// attrib_json: raw attribute -> {"endpoint":{"ha":"127.0.0.1:5555"}}
+///
+/// pool_name: named pool (see vcx_open_pool) the transaction is written to, NULL uses the default write pool
+///
+/// cb: Callback that provides the payment information (NULL when no fees were paid) or error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_ledger_write_attrib(command_handle: u32,
+                                      target_did: *const c_char,
+                                      attrib_json: *const c_char,
+                                      pool_name: *const c_char,
+                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, payment_txn: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(target_did, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(attrib_json, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(pool_name, error::INVALID_OPTION.code_num);
+
+    info!("vcx_ledger_write_attrib(command_handle: {}, target_did: {}, attrib_json: {}, pool_name: {:?})",
+          command_handle, target_did, attrib_json, pool_name);
+
+    thread::spawn(move|| {
+        match pool::with_pool(pool_name.as_ref().map(String::as_str), || nym::write_attrib(&target_did, &attrib_json))
+            .and_then(|rc| rc) {
+            Ok(payment) => {
+                let payment = payment_to_string(payment);
+                info!("vcx_ledger_write_attrib_cb(command_handle: {}, rc: {}, payment_txn: {:?})",
+                      command_handle, error_string(0), payment);
+                match payment {
+                    Some(x) => {
+                        let msg = CStringUtils::string_to_cstring(x);
+                        cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+                    },
+                    None => cb(command_handle, error::SUCCESS.code_num, ptr::null()),
+                }
+            },
+            Err(x) => {
+                error!("vcx_ledger_write_attrib_cb(command_handle: {}, rc: {}, payment_txn: {})",
+                       command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Read a raw ATTRIB of a DID from the ledger
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// did: DID the attribute belongs to
+///
+/// attrib_name: name of the raw attribute (ie, endpoint)
+///
+/// cb: Callback that provides the attribute json or error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_ledger_get_attrib(command_handle: u32,
+                                    did: *const c_char,
+                                    attrib_name: *const c_char,
+                                    cb: Option<extern fn(xcommand_handle: u32, err: u32, attrib_json: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(did, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(attrib_name, error::INVALID_OPTION.code_num);
+
+    info!("vcx_ledger_get_attrib(command_handle: {}, did: {}, attrib_name: {})",
+          command_handle, did, attrib_name);
+
+    thread::spawn(move|| {
+        match nym::get_attrib(&did, &attrib_name) {
+            Ok(x) => {
+                info!("vcx_ledger_get_attrib_cb(command_handle: {}, rc: {}, attrib_json: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                error!("vcx_ledger_get_attrib_cb(command_handle: {}, rc: {}, attrib_json: {})",
+                       command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use settings;
+    use std::ffi::CString;
+    use std::time::Duration;
+    use utils::libindy::return_types_u32;
+
+    static TARGET_DID: &str = "V4SGRU86Z58d6TV7PBUe6f";
+
+    #[test]
+    fn test_vcx_ledger_write_and_get_nym() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_ledger_write_nym(cb.command_handle,
+                                        CString::new(TARGET_DID).unwrap().into_raw(),
+                                        ptr::null(),
+                                        CString::new("TRUST_ANCHOR").unwrap().into_raw(),
+                                        ptr::null(),
+                                        Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert!(cb.receive(Some(Duration::from_secs(10))).unwrap().is_some());
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_ledger_write_nym(cb.command_handle,
+                                        CString::new(TARGET_DID).unwrap().into_raw(),
+                                        ptr::null(),
+                                        ptr::null(),
+                                        CString::new("unknown_pool").unwrap().into_raw(),
+                                        Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).err(), Some(error::NO_POOL_OPEN.code_num));
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_ledger_get_nym(cb.command_handle,
+                                      CString::new(TARGET_DID).unwrap().into_raw(),
+                                      Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert!(cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap().contains(TARGET_DID));
+    }
+
+    #[test]
+    fn test_vcx_ledger_write_and_get_attrib() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_ledger_write_attrib(cb.command_handle,
+                                           CString::new(TARGET_DID).unwrap().into_raw(),
+                                           CString::new(r#"{"endpoint":{"ha":"127.0.0.1:5555"}}"#).unwrap().into_raw(),
+                                           ptr::null(),
+                                           Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_ledger_get_attrib(cb.command_handle,
+                                         CString::new(TARGET_DID).unwrap().into_raw(),
+                                         CString::new("endpoint").unwrap().into_raw(),
+                                         Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert!(cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap().contains("127.0.0.1:5555"));
+    }
+}
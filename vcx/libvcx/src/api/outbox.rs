@@ -0,0 +1,127 @@
+extern crate libc;
+extern crate serde_json;
+
+use self::libc::c_char;
+use utils::cstring::CStringUtils;
+use utils::error;
+use utils::error::error_string;
+use messages::outbox;
+use std::thread;
+use std::ptr;
+use error::context::reset_current_error;
+use error::record_error_code;
+
+/// List the messages kept in the outbox because the agency could not be reached.
+/// The outbox is enabled with "outbox_enabled":"true" in the config, entries expire after "outbox_ttl" seconds.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// cb: Callback that provides the outbox entries, status is one of Pending, Delivered, Failed, Expired or Cancelled
+/// -> [{"id":"1539958200-1803346562","kind":"SendMessage","status":"Pending","created_at":1539958200,
+///      "expires_at":1540044600,"attempts":2,"next_attempt_at":1539958220,"last_error":1010}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_outbox_list(command_handle: u32,
+                              cb: Option<extern fn(xcommand_handle: u32, err: u32, entries: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_outbox_list(command_handle: {})", command_handle);
+
+    thread::spawn(move|| {
+        match serde_json::to_string(&outbox::list()) {
+            Ok(x) => {
+                info!("vcx_outbox_list_cb(command_handle: {}, rc: {}, entries: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(_) => {
+                let x = error::INVALID_JSON.code_num;
+                error!("vcx_outbox_list_cb(command_handle: {}, rc: {}, entries: {})",
+                       command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Stop retrying a pending outbox message. The object that sent it goes back to the state it
+/// was in before sending on its next update_state.
+///
+/// #Params
+/// id: id of the outbox entry as returned by vcx_outbox_list
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_outbox_cancel(id: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(id, error::INVALID_OPTION.code_num);
+
+    info!("vcx_outbox_cancel(id: {})", id);
+
+    match outbox::cancel(&id) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Try to deliver every pending outbox message now instead of waiting for its next retry
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// cb: Callback that provides the number of messages still pending
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_outbox_retry(command_handle: u32,
+                               cb: Option<extern fn(xcommand_handle: u32, err: u32, pending: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_outbox_retry(command_handle: {})", command_handle);
+
+    thread::spawn(move|| {
+        let pending = outbox::retry_now() as u32;
+        info!("vcx_outbox_retry_cb(command_handle: {}, rc: {}, pending: {})",
+              command_handle, error_string(0), pending);
+        cb(command_handle, error::SUCCESS.code_num, pending);
+    });
+
+    error::SUCCESS.code_num
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::time::Duration;
+    use settings;
+    use utils::libindy::return_types_u32;
+
+    #[test]
+    fn test_vcx_outbox_cancel() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let id = outbox::tests::queue_test_message(outbox::MessageKind::SendInvite);
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_outbox_list(cb.command_handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        let entries = cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap();
+        assert!(entries.contains(&id));
+
+        assert_eq!(vcx_outbox_cancel(CString::new(id.clone()).unwrap().into_raw()), error::SUCCESS.code_num);
+        assert_eq!(vcx_outbox_cancel(CString::new(id.clone()).unwrap().into_raw()), error::OUTBOX_ENTRY_NOT_PENDING.code_num);
+        assert_eq!(vcx_outbox_cancel(CString::new("unknown").unwrap().into_raw()), error::OUTBOX_ENTRY_NOT_FOUND.code_num);
+        outbox::remove(&id);
+    }
+}
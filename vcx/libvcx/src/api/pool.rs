@@ -0,0 +1,239 @@
+extern crate libc;
+extern crate serde_json;
+
+use self::libc::c_char;
+use utils::cstring::CStringUtils;
+use utils::error;
+use utils::error::error_string;
+use utils::libindy::pool;
+use std::thread;
+use std::ptr;
+use error::context::reset_current_error;
+use error::record_error_code;
+
+/// Open an additional pool ledger next to the pool configured at init (ie, a staging network)
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// pool_name: name of the pool
+///
+/// genesis_path: path to the genesis transactions of the pool
+///
+/// namespaces: json array of DIDs or namespaces whose ledger reads are routed to this pool, may be NULL
+/// -> ["V4SGRU86Z58d6TV7PBUe6f", "did:sov:staging:"]
+///
+/// cb: Callback that provides error status of the request
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_open_pool(command_handle: u32,
+                            pool_name: *const c_char,
+                            genesis_path: *const c_char,
+                            namespaces: *const c_char,
+                            cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(pool_name, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(genesis_path, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(namespaces, error::INVALID_OPTION.code_num);
+
+    info!("vcx_open_pool(command_handle: {}, pool_name: {}, genesis_path: {}, namespaces: {:?})",
+          command_handle, pool_name, genesis_path, namespaces);
+
+    let namespaces: Vec<String> = match namespaces {
+        Some(x) => match serde_json::from_str(&x) {
+            Ok(x) => x,
+            Err(_) => return error::INVALID_JSON.code_num,
+        },
+        None => Vec::new(),
+    };
+
+    thread::spawn(move|| {
+        match pool::open_named_pool(&pool_name, &genesis_path, namespaces) {
+            Ok(_) => {
+                info!("vcx_open_pool_cb(command_handle: {}, rc: {})",
+                      command_handle, error_string(0));
+                cb(command_handle, error::SUCCESS.code_num);
+            },
+            Err(x) => {
+                error!("vcx_open_pool_cb(command_handle: {}, rc: {})",
+                       command_handle, error_string(x));
+                cb(command_handle, record_error_code(x));
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Close a pool opened with vcx_open_pool
+///
+/// #Params
+/// pool_name: name of the pool
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_close_pool(pool_name: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(pool_name, error::INVALID_OPTION.code_num);
+
+    info!("vcx_close_pool(pool_name: {})", pool_name);
+
+    match pool::close_named_pool(&pool_name) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Set the pool ledger writes go to (schemas, credential definitions, payments, ...).
+/// Objects keep reading from the pool they were written to.
+///
+/// #Params
+/// pool_name: name of a pool opened with vcx_open_pool, NULL restores the pool configured at init
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_pool_set_default_write_pool(pool_name: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_opt_c_str!(pool_name, error::INVALID_OPTION.code_num);
+
+    info!("vcx_pool_set_default_write_pool(pool_name: {:?})", pool_name);
+
+    match pool::set_default_write_pool(pool_name.as_ref().map(String::as_str)) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Report the health of the pool configured at init
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// cb: Callback that provides the pool status or error status
+/// -> {"open":true,"pool_name":"pool1","genesis_path":"/tmp/genesis.txn","last_successful_read":1537000000,
+///     "last_refresh":null,"last_error":null,"named_pools":[]}
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_pool_get_status(command_handle: u32,
+                                  cb: Option<extern fn(xcommand_handle: u32, err: u32, status: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_pool_get_status(command_handle: {})", command_handle);
+
+    thread::spawn(move|| {
+        match serde_json::to_string(&pool::get_pool_status()) {
+            Ok(x) => {
+                info!("vcx_pool_get_status_cb(command_handle: {}, rc: {}, status: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(_) => {
+                error!("vcx_pool_get_status_cb(command_handle: {}, rc: {}, status: {})",
+                       command_handle, error_string(error::INVALID_JSON.code_num), "null");
+                cb(command_handle, error::INVALID_JSON.code_num, ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Close and reopen the pool configured at init, ie after the connection to the nodes was lost
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// genesis_path: path to an updated genesis file, NULL reopens the pool with the stored genesis path
+///
+/// cb: Callback that provides error status of the refresh
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_pool_refresh(command_handle: u32,
+                               genesis_path: *const c_char,
+                               cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(genesis_path, error::INVALID_OPTION.code_num);
+
+    info!("vcx_pool_refresh(command_handle: {}, genesis_path: {:?})", command_handle, genesis_path);
+
+    thread::spawn(move|| {
+        match pool::refresh(genesis_path.as_ref().map(String::as_str)) {
+            Ok(_) => {
+                info!("vcx_pool_refresh_cb(command_handle: {}, rc: {})",
+                      command_handle, error_string(0));
+                cb(command_handle, error::SUCCESS.code_num);
+            },
+            Err(x) => {
+                error!("vcx_pool_refresh_cb(command_handle: {}, rc: {})",
+                       command_handle, error_string(x));
+                cb(command_handle, record_error_code(x));
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use settings;
+    use std::ffi::CString;
+    use std::time::Duration;
+    use utils::libindy::return_types_u32;
+    use utils::constants::GENESIS_PATH;
+
+    #[test]
+    fn test_vcx_open_and_close_pool() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_open_pool(cb.command_handle,
+                                 CString::new("test_vcx_open_and_close_pool").unwrap().into_raw(),
+                                 CString::new(GENESIS_PATH).unwrap().into_raw(),
+                                 CString::new(r#"["did:sov:staging:"]"#).unwrap().into_raw(),
+                                 Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+
+        assert_eq!(vcx_pool_set_default_write_pool(CString::new("unknown_pool").unwrap().into_raw()),
+                   error::NO_POOL_OPEN.code_num);
+        assert_eq!(vcx_pool_set_default_write_pool(CString::new("test_vcx_open_and_close_pool").unwrap().into_raw()),
+                   error::SUCCESS.code_num);
+        assert_eq!(vcx_pool_set_default_write_pool(ptr::null()), error::SUCCESS.code_num);
+
+        assert_eq!(vcx_close_pool(CString::new("test_vcx_open_and_close_pool").unwrap().into_raw()),
+                   error::SUCCESS.code_num);
+        assert_eq!(vcx_close_pool(CString::new("test_vcx_open_and_close_pool").unwrap().into_raw()),
+                   error::NO_POOL_OPEN.code_num);
+    }
+
+    #[test]
+    fn test_vcx_pool_refresh_and_status() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_pool_refresh(cb.command_handle, ptr::null(), Some(cb.get_callback())), error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_pool_get_status(cb.command_handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        let status: pool::PoolStatus = serde_json::from_str(&cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap()).unwrap();
+        assert!(status.open);
+        assert!(status.last_refresh.is_some());
+    }
+}
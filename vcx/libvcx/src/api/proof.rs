@@ -9,6 +9,9 @@ use connection;
 use std::thread;
 use std::ptr;
 use error::ToErrorCode;
+use error::ToErrorDetails;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /// Create a new Proof object that requests a proof for an enterprise
 ///
@@ -20,6 +23,8 @@ use error::ToErrorCode;
 /// requested_attrs: attributes/claims prover must provide in proof
 ///
 /// # Example requested_attrs -> "[{"name":"attrName","restrictions":["issuer_did":"did","schema_id":"id","schema_issuer_did":"did","schema_name":"name","schema_version":"1.1.1","cred_def_id":"id"}]]"
+/// An attribute the issuer declared an encoding for names it, e.g. {"name":"dob","encoding":"date"},
+/// so its revealed value is checked against it. Other attributes are checked as legacy values.
 ///
 /// requested_predicates: predicate specifications prover must provide claim for
 ///
@@ -37,7 +42,7 @@ pub extern fn vcx_proof_create(command_handle: u32,
                                requested_predicates: *const c_char,
                                name: *const c_char,
                                cb: Option<extern fn(xcommand_handle: u32, err: u32, proof_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(requested_attrs, error::INVALID_OPTION.code_num);
     check_useful_c_str!(requested_predicates, error::INVALID_OPTION.code_num);
@@ -57,7 +62,7 @@ pub extern fn vcx_proof_create(command_handle: u32,
             Err(x) => {
                 warn!("vcx_proof_create_cb(command_handle: {}, rc: {}, handle: {}), source_id: {:?}",
                       command_handle, error_string(x.to_error_code()), 0, proof::get_source_id(x.to_error_code()).unwrap_or_default());
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
         cb(command_handle, rc, handle);
@@ -81,6 +86,7 @@ pub extern fn vcx_proof_create(command_handle: u32,
 pub extern fn vcx_proof_update_state(command_handle: u32,
                                      proof_handle: u32,
                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = proof::get_source_id(proof_handle).unwrap_or_default();
@@ -88,7 +94,7 @@ pub extern fn vcx_proof_update_state(command_handle: u32,
           command_handle, proof_handle, source_id);
 
     if !proof::is_valid_handle(proof_handle) {
-        return error::INVALID_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -101,7 +107,7 @@ pub extern fn vcx_proof_update_state(command_handle: u32,
             Err(x) => {
                 warn!("vcx_proof_update_state_cb(command_handle: {}, rc: {}, proof_handle: {}, state: {}), source_id: {:?}",
                       command_handle, x.to_string(), proof_handle, 0, source_id);
-                cb(command_handle, x.to_error_code(), 0);
+                cb(command_handle, x.record_error(), 0);
             }
         }
     });
@@ -113,6 +119,7 @@ pub extern fn vcx_proof_update_state(command_handle: u32,
 pub extern fn vcx_proof_get_state(command_handle: u32,
                                   proof_handle: u32,
                                   cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = proof::get_source_id(proof_handle).unwrap_or_default();
@@ -120,7 +127,7 @@ pub extern fn vcx_proof_get_state(command_handle: u32,
           command_handle, proof_handle, source_id);
 
     if !proof::is_valid_handle(proof_handle) {
-        return error::INVALID_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -133,7 +140,7 @@ pub extern fn vcx_proof_get_state(command_handle: u32,
             Err(x) => {
                 warn!("vcx_proof_get_state_cb(command_handle: {}, rc: {}, proof_handle: {}, state: {}), source_id: {:?}",
                       command_handle, x.to_string(), proof_handle, 0, source_id);
-                cb(command_handle, x.to_error_code(), 0);
+                cb(command_handle, x.record_error(), 0);
             }
         }
     });
@@ -141,6 +148,52 @@ pub extern fn vcx_proof_get_state(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Get the transitions the proof went through, for debugging
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// proof_handle: Proof handle that was provided during creation. Used to identify proof object
+///
+/// cb: Callback that provides the json array of transitions and provides error status
+/// -> [{"event":"SendOffer","from":1,"to":2,"timestamp":1537000000}]
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_get_state_history(command_handle: u32,
+                                          proof_handle: u32,
+                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, history: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    if !proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
+    }
+
+    let source_id = proof::get_source_id(proof_handle).unwrap_or_default();
+    info!("vcx_proof_get_state_history(command_handle: {}, proof_handle: {}), source_id: {:?}",
+          command_handle, proof_handle, source_id);
+
+    thread::spawn(move|| {
+        match proof::get_state_history(proof_handle) {
+            Ok(x) => {
+                info!("vcx_proof_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, source_id);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_proof_get_state_history_cb(command_handle: {}, rc: {}, history: {}), source_id: {:?}",
+                      command_handle, error_string(x.to_error_code()), "null", source_id);
+                cb(command_handle, x.record_error(), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Takes the proof object and returns a json string of all its attributes
 ///
 /// #Params
@@ -156,14 +209,14 @@ pub extern fn vcx_proof_get_state(command_handle: u32,
 pub extern fn vcx_proof_serialize(command_handle: u32,
                                   proof_handle: u32,
                                   cb: Option<extern fn(xcommand_handle: u32, err: u32, proof_state: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = proof::get_source_id(proof_handle).unwrap_or_default();
     info!("vcx_proof_serialize(command_handle: {}, proof_handle: {}), source_id: {:?}", command_handle, proof_handle, source_id);
 
     if !proof::is_valid_handle(proof_handle) {
-        return error::INVALID_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
     };
 
     thread::spawn( move|| {
@@ -177,7 +230,7 @@ pub extern fn vcx_proof_serialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_proof_serialize_cb(command_handle: {}, proof_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, proof_handle, error_string(x.to_error_code()), "null", source_id);
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
 
@@ -201,7 +254,7 @@ pub extern fn vcx_proof_serialize(command_handle: u32,
 pub extern fn vcx_proof_deserialize(command_handle: u32,
                                     proof_data: *const c_char,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, proof_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(proof_data, error::INVALID_OPTION.code_num);
 
@@ -218,7 +271,7 @@ pub extern fn vcx_proof_deserialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_proof_deserialize_cb(command_handle: {}, rc: {}, handle: {}), source_id: {:?}",
                       command_handle, error_string(x.to_error_code()), 0, "");
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
         cb(command_handle, rc, handle);
@@ -236,6 +289,7 @@ pub extern fn vcx_proof_deserialize(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_proof_release(proof_handle: u32) -> u32 {
+    reset_current_error();
     let source_id = proof::get_source_id(proof_handle).unwrap_or_default();
     match proof::release(proof_handle) {
         Ok(x) => info!("vcx_proof_release(proof_handle: {}, rc: {}), source_id: {:?}",
@@ -264,16 +318,16 @@ pub extern fn vcx_proof_send_request(command_handle: u32,
                                      proof_handle: u32,
                                      connection_handle: u32,
                                      cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_proof_send_request(command_handle: {}, proof_handle: {}, connection_handle: {})", command_handle, proof_handle, connection_handle);
     if !proof::is_valid_handle(proof_handle) {
-        return error::INVALID_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
     }
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -284,7 +338,7 @@ pub extern fn vcx_proof_send_request(command_handle: u32,
             },
             Err(x) => {
                 warn!("vcx_proof_send_request_cb(command_handle: {}, rc: {}, proof_handle: {})", command_handle, x.to_error_code(), proof_handle);
-                x.to_error_code()
+                x.record_error()
             },
         };
 
@@ -294,6 +348,52 @@ pub extern fn vcx_proof_send_request(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Generate a signed proof request that does not need a connection, e.g. to show as a QR code.
+/// The prover sends the proof back to the service endpoint embedded in the request, check for it
+/// with vcx_proof_update_state. The agency must accept the anonymous message the proof arrives
+/// in. The pairwise agent created for the request is deleted once the proof arrives or the
+/// request expires.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// proof_handle: Proof handle that was provided during creation. Used to access proof object
+///
+/// cb: Callback that provides the out of band proof request and error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_get_out_of_band_request(command_handle: u32,
+                                                proof_handle: u32,
+                                                cb: Option<extern fn(xcommand_handle: u32, err: u32, request: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_get_out_of_band_request(command_handle: {}, proof_handle: {})", command_handle, proof_handle);
+    if !proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match proof::generate_out_of_band_request(proof_handle) {
+            Ok(x) => {
+                info!("vcx_proof_get_out_of_band_request_cb(command_handle: {}, rc: {}, proof_handle: {}, request: {})",
+                      command_handle, 0, proof_handle, x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_proof_get_out_of_band_request_cb(command_handle: {}, rc: {}, proof_handle: {}, request: {})",
+                      command_handle, x.to_error_code(), proof_handle, "null");
+                cb(command_handle, x.record_error(), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Get Proof
 ///
 /// #Params
@@ -312,15 +412,16 @@ pub extern fn vcx_get_proof(command_handle: u32,
                                         proof_handle: u32,
                                         connection_handle: u32,
                                         cb: Option<extern fn(xcommand_handle: u32, err: u32, proof_state:u32, response_data: *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_get_proof(command_handle: {}, proof_handle: {}, connection_handle: {})", command_handle, proof_handle, connection_handle);
     if !proof::is_valid_handle(proof_handle) {
-        return error::INVALID_PROOF_HANDLE.code_num;
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
     }
 
     if !connection::is_valid_handle(connection_handle) {
-        return error::INVALID_CONNECTION_HANDLE.code_num;
+        return record_error_code(error::INVALID_CONNECTION_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -338,7 +439,7 @@ pub extern fn vcx_get_proof(command_handle: u32,
             },
             Err(x) => {
                 warn!("vcx_get_proof_cb(command_handle: {}, proof_handle: {}, rc: {}, proof: {})", command_handle, proof_handle, x.to_error_code(), "null");
-                cb(command_handle, x.to_error_code(), proof::get_proof_state(proof_handle).unwrap(), ptr::null_mut());
+                cb(command_handle, x.record_error(), proof::get_proof_state(proof_handle).unwrap(), ptr::null_mut());
             },
         };
     });
@@ -347,12 +448,103 @@ pub extern fn vcx_get_proof(command_handle: u32,
 }
 
 
+/// Get the results of a received proof per requested referent, instead of the raw libindy proof
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// proof_handle: Proof handle that was provided during creation. Used to identify proof object
+///
+/// cb: Callback that provides the proof state and the results. status is one of Revealed, Unrevealed,
+/// SelfAttested or Predicate, value is set for revealed and self attested attributes
+/// # Example results -> {"proof_state":1,"referents":[{"referent":"attr1_referent","status":"Revealed","value":"Alex",
+///     "schema_id":"NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0","cred_def_id":"NcYxiDXkpYi6ov5FcYDi1e:3:CL:NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0",
+///     "issuer_did":"NcYxiDXkpYi6ov5FcYDi1e"}]}
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_get_results(command_handle: u32,
+                                    proof_handle: u32,
+                                    cb: Option<extern fn(xcommand_handle: u32, err: u32, results: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_get_results(command_handle: {}, proof_handle: {})", command_handle, proof_handle);
+    if !proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match proof::get_proof_results(proof_handle) {
+            Ok(x) => {
+                info!("vcx_proof_get_results_cb(command_handle: {}, proof_handle: {}, rc: {}, results: {})", command_handle, proof_handle, 0, x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_proof_get_results_cb(command_handle: {}, proof_handle: {}, rc: {}, results: {})", command_handle, proof_handle, x.to_error_code(), "null");
+                cb(command_handle, x.record_error(), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Set a deadline for the prover to answer the proof request. A request that is not answered in
+/// time moves to VcxStateExpired on its next update_state and proofs received later are rejected.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// proof_handle: Proof handle that was provided during creation. Used to identify proof object
+///
+/// deadline: unix time in seconds after which the request expires, 0 removes the deadline
+///
+/// cb: Callback that provides error status of setting the deadline
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_set_response_deadline(command_handle: u32,
+                                              proof_handle: u32,
+                                              deadline: u64,
+                                              cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_set_response_deadline(command_handle: {}, proof_handle: {}, deadline: {})", command_handle, proof_handle, deadline);
+    if !proof::is_valid_handle(proof_handle) {
+        return record_error_code(error::INVALID_PROOF_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        let deadline = if deadline == 0 { None } else { Some(deadline) };
+        let err = match proof::set_response_deadline(proof_handle, deadline) {
+            Ok(x) => {
+                info!("vcx_proof_set_response_deadline_cb(command_handle: {}, proof_handle: {}, rc: {})", command_handle, proof_handle, 0);
+                x
+            },
+            Err(x) => {
+                warn!("vcx_proof_set_response_deadline_cb(command_handle: {}, proof_handle: {}, rc: {})", command_handle, proof_handle, x.to_error_code());
+                x.record_error()
+            },
+        };
+
+        cb(command_handle, err);
+    });
+
+    error::SUCCESS.code_num
+}
+
 #[allow(unused_variables)]
 pub extern fn vcx_proof_accepted(proof_handle: u32, response_data: *const c_char) -> u32 { error::SUCCESS.code_num }
 
 
 #[cfg(test)]
 mod tests {
+    reset_current_error();
     use super::*;
     use std::ffi::CString;
     use std::ptr;
@@ -509,6 +701,56 @@ mod tests {
         vcx_proof_release(proof_handle);
     }
 
+    #[test]
+    fn test_vcx_proof_get_results() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let proof_handle = proof::from_string(PROOF_WITH_INVALID_STATE).unwrap();
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_proof_get_results(cb.command_handle,
+                                         proof_handle,
+                                         Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let results: ::serde_json::Value = ::serde_json::from_str(&cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap()).unwrap();
+        assert_eq!(results["proof_state"], json!(ProofStateType::ProofInvalid as u32));
+        assert_eq!(results["referents"][1]["value"], json!("87121"));
+        assert_eq!(results["referents"][1]["cred_def_id"], json!("2hoqvcwupRTUNkXn6ArYzs:3:CL:2200"));
+        vcx_proof_release(proof_handle);
+    }
+
+    #[test]
+    fn test_vcx_proof_set_response_deadline() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let proof_handle = proof::from_string(PROOF_OFFER_SENT).unwrap();
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_proof_set_response_deadline(cb.command_handle,
+                                                   proof_handle,
+                                                   1,
+                                                   Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+        assert_eq!(proof::update_state(proof_handle).unwrap(), VcxStateType::VcxStateExpired as u32);
+        vcx_proof_release(proof_handle);
+    }
+
+    #[test]
+    fn test_vcx_proof_get_out_of_band_request() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let proof_handle = proof::create_proof("1".to_string(),
+                                               REQUESTED_ATTRS.to_owned(),
+                                               REQUESTED_PREDICATES.to_owned(),
+                                               "Optional".to_owned()).unwrap();
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_proof_get_out_of_band_request(cb.command_handle, proof_handle, Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let request = cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap();
+        assert!(request.contains("PROOF_REQUEST"));
+        assert_eq!(proof::get_state(proof_handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        vcx_proof_release(proof_handle);
+    }
+
     #[test]
     fn test_vcx_connection_get_state() {
         settings::set_defaults();
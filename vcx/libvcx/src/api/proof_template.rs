@@ -0,0 +1,212 @@
+extern crate libc;
+extern crate serde_json;
+
+use self::libc::c_char;
+use utils::cstring::CStringUtils;
+use utils::error;
+use utils::error::error_string;
+use proof;
+use proof_template;
+use error::ToErrorCode;
+use error::ToErrorDetails;
+use std::thread;
+use std::ptr;
+use error::context::reset_current_error;
+use error::record_error_code;
+
+/// Store a proof request template in the wallet. Storing a new version of a template keeps the
+/// previous versions, the highest version is the latest.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// template: template with {{parameter}} placeholders, defaults gives values for parameters that are not provided
+/// # Example template -> {"id":"age_check","version":"1.0","name":"Age check",
+///     "requested_attrs":[{"name":"name","restrictions":[{"issuer_did":"{{issuer_did}}"}]}],
+///     "requested_predicates":[{"name":"age","p_type":"GE","p_value":"{{threshold}}"}],"defaults":{"threshold":18}}
+///
+/// cb: Callback that provides error status of storing the template
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_template_store(command_handle: u32,
+                                       template: *const c_char,
+                                       cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(template, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_template_store(command_handle: {}, template: {})", command_handle, template);
+
+    thread::spawn(move|| {
+        let err = match proof_template::store(&template) {
+            Ok(_) => {
+                info!("vcx_proof_template_store_cb(command_handle: {}, rc: {})", command_handle, error_string(0));
+                error::SUCCESS.code_num
+            },
+            Err(x) => {
+                warn!("vcx_proof_template_store_cb(command_handle: {}, rc: {})", command_handle, error_string(x));
+                record_error_code(x)
+            },
+        };
+
+        cb(command_handle, err);
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Get a proof request template stored in the wallet
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// template_id: id of the template for its latest version, or id:version for a specific version
+///
+/// cb: Callback that provides the template
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_template_get(command_handle: u32,
+                                     template_id: *const c_char,
+                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, template: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(template_id, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_template_get(command_handle: {}, template_id: {})", command_handle, template_id);
+
+    thread::spawn(move|| {
+        match proof_template::get(&template_id).and_then(|x| serde_json::to_string(&x).or(Err(error::INVALID_JSON.code_num))) {
+            Ok(x) => {
+                info!("vcx_proof_template_get_cb(command_handle: {}, rc: {}, template: {})",
+                      command_handle, error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_proof_template_get_cb(command_handle: {}, rc: {}, template: {})",
+                      command_handle, error_string(x), "null");
+                cb(command_handle, record_error_code(x), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Delete a proof request template from the wallet
+///
+/// #Params
+/// template_id: id:version of the template version to delete, id alone deletes its latest version.
+/// The highest remaining version becomes the latest.
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_template_delete(template_id: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(template_id, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_template_delete(template_id: {})", template_id);
+
+    match proof_template::delete(&template_id) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(x) => record_error_code(x),
+    }
+}
+
+/// Create a new Proof object from a proof request template stored in the wallet
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// source_id: Enterprise's personal identification for the user.
+///
+/// template_id: id of the template for its latest version, or id:version for a specific version
+///
+/// params: values of the template parameters
+/// # Example params -> {"issuer_did":"V4SGRU86Z58d6TV7PBUe6f","threshold":21}
+///
+/// cb: Callback that provides proof handle and error status of request.
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_proof_create_from_template(command_handle: u32,
+                                             source_id: *const c_char,
+                                             template_id: *const c_char,
+                                             params: *const c_char,
+                                             cb: Option<extern fn(xcommand_handle: u32, err: u32, proof_handle: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(template_id, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(params, error::INVALID_OPTION.code_num);
+
+    info!("vcx_proof_create_from_template(command_handle: {}, source_id: {}, template_id: {}, params: {})",
+          command_handle, source_id, template_id, params);
+
+    thread::spawn(move|| {
+        let (rc, handle) = match proof_template::create_proof_from_template(source_id, &template_id, &params) {
+            Ok(x) => {
+                info!("vcx_proof_create_from_template_cb(command_handle: {}, rc: {}, handle: {}), source_id: {:?}",
+                      command_handle, error_string(0), x, proof::get_source_id(x).unwrap_or_default());
+                (error::SUCCESS.code_num, x)
+            },
+            Err(x) => {
+                warn!("vcx_proof_create_from_template_cb(command_handle: {}, rc: {}, handle: {})",
+                      command_handle, error_string(x.to_error_code()), 0);
+                (x.record_error(), 0)
+            },
+        };
+        cb(command_handle, rc, handle);
+    });
+
+    error::SUCCESS.code_num
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::time::Duration;
+    use settings;
+    use proof_template::tests::AGE_TEMPLATE;
+    use utils::libindy::return_types_u32;
+
+    #[test]
+    fn test_vcx_proof_create_from_template() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_proof_template_store(cb.command_handle,
+                                            CString::new(AGE_TEMPLATE).unwrap().into_raw(),
+                                            Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(10))).unwrap();
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_proof_create_from_template(cb.command_handle,
+                                                  CString::new("1").unwrap().into_raw(),
+                                                  CString::new("age_check").unwrap().into_raw(),
+                                                  CString::new(r#"{"venue":"Bar"}"#).unwrap().into_raw(),
+                                                  Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(10))).err(), Some(error::MISSING_TEMPLATE_PARAMETER.code_num));
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_proof_create_from_template(cb.command_handle,
+                                                  CString::new("1").unwrap().into_raw(),
+                                                  CString::new("age_check").unwrap().into_raw(),
+                                                  CString::new(r#"{"venue":"Bar","issuer_did":"V4SGRU86Z58d6TV7PBUe6f"}"#).unwrap().into_raw(),
+                                                  Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let handle = cb.receive(Some(Duration::from_secs(10))).unwrap();
+        assert!(handle > 0);
+        proof::release(handle).unwrap();
+    }
+}
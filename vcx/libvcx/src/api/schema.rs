@@ -8,8 +8,13 @@ use utils::error::error_string;
 use std::thread;
 use std::ptr;
 use schema;
+use error::schema::SchemaError;
+use utils::libindy::pool;
 use settings;
 use error::ToErrorCode;
+use error::ToErrorDetails;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 /// Create a new Schema object that can create or look up schemas on the ledger
 ///
@@ -40,25 +45,62 @@ pub extern fn vcx_schema_create(command_handle: u32,
                                 schema_data: *const c_char,
                                 payment_handle: u32,
                                 cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_handle: u32)>) -> u32 {
+    vcx_schema_create_with_pool(command_handle, source_id, schema_name, version, schema_data, payment_handle, ptr::null(), cb)
+}
+
+/// Create a new Schema object and write the schema to the given pool ledger
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// source_id: Enterprise's personal identification for the user.
+///
+/// schema_name: Name of schema
+///
+/// version: version of schema
+///
+/// schema_data: list of attributes that will make up the schema
+///
+/// payment_handle: future use (currently uses any address in the wallet)
+///
+/// pool_name: named pool (see vcx_open_pool) the schema is written to, NULL uses the default write pool.
+/// The schema object keeps using this pool.
+///
+/// cb: Callback that provides Schema handle and error status of request.
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_schema_create_with_pool(command_handle: u32,
+                                          source_id: *const c_char,
+                                          schema_name: *const c_char,
+                                          version: *const c_char,
+                                          schema_data: *const c_char,
+                                          payment_handle: u32,
+                                          pool_name: *const c_char,
+                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, credentialdef_handle: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(schema_name, error::INVALID_OPTION.code_num);
     check_useful_c_str!(version, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(schema_data, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(pool_name, error::INVALID_OPTION.code_num);
 
     let issuer_did = match settings::get_config_value(settings::CONFIG_INSTITUTION_DID) {
         Ok(x) => x,
-        Err(x) => return x
+        Err(x) => return record_error_code(x)
     };
-    info!(target:"vcx","vcx_schema_create(command_handle: {}, source_id: {}, schema_name: {},  schema_data: {})",
-          command_handle, source_id, schema_name, schema_data);
+    info!(target:"vcx","vcx_schema_create(command_handle: {}, source_id: {}, schema_name: {},  schema_data: {}, pool_name: {:?})",
+          command_handle, source_id, schema_name, schema_data, pool_name);
 
     thread::spawn( move|| {
-        let ( rc, handle) = match schema::create_new_schema(&source_id,
-                                                            issuer_did,
-                                                            schema_name,
-                                                            version,
-                                                            schema_data) {
+        let rc = pool::with_pool(pool_name.as_ref().map(String::as_str),
+                                 || schema::create_new_schema(&source_id, issuer_did, schema_name, version, schema_data))
+            .map_err(SchemaError::CommonError)
+            .and_then(|rc| rc);
+
+        let ( rc, handle) = match rc {
             Ok(x) => {
                 info!(target:"vcx", "vcx_schema_create_cb(command_handle: {}, rc: {}, handle: {}), source_id: {:?}",
                       command_handle, error_string(0), x, &source_id);
@@ -67,7 +109,7 @@ pub extern fn vcx_schema_create(command_handle: u32,
             Err(x) => {
                 warn!("vcx_schema_create_cb(command_handle: {}, rc: {}, handle: {}, source_id: {:?})",
                       command_handle, error_string(x.to_error_code()), 0, source_id);
-                (x.to_error_code(), 0) },
+                (x.record_error(), 0) },
         };
 
         cb(command_handle, rc, handle);
@@ -76,6 +118,151 @@ pub extern fn vcx_schema_create(command_handle: u32,
 }
 
 
+/// Create a new Schema object as a transaction author. The schema is not written to the ledger,
+/// the returned transaction has to be endorsed and submitted with vcx_endorse_transaction by an endorser.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// source_id: Enterprise's personal identification for the user.
+///
+/// schema_name: Name of schema
+///
+/// version: version of schema
+///
+/// schema_data: list of attributes that will make up the schema
+///
+/// # Example schema_data -> "["attr1", "attr2", "attr3"]"
+///
+/// cb: Callback that provides Schema handle, the transaction to endorse and error status of request.
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_schema_prepare_for_endorser(command_handle: u32,
+                                              source_id: *const c_char,
+                                              schema_name: *const c_char,
+                                              version: *const c_char,
+                                              schema_data: *const c_char,
+                                              cb: Option<extern fn(xcommand_handle: u32, err: u32, schema_handle: u32, schema_txn: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(schema_name, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(version, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(schema_data, error::INVALID_OPTION.code_num);
+
+    let author_did = match settings::get_config_value(settings::CONFIG_INSTITUTION_DID) {
+        Ok(x) => x,
+        Err(x) => return record_error_code(x)
+    };
+    info!(target:"vcx","vcx_schema_prepare_for_endorser(command_handle: {}, source_id: {}, schema_name: {},  schema_data: {})",
+          command_handle, source_id, schema_name, schema_data);
+
+    thread::spawn( move|| {
+        match schema::prepare_schema_for_endorser(&source_id,
+                                                  author_did,
+                                                  schema_name,
+                                                  version,
+                                                  schema_data) {
+            Ok((handle, txn)) => {
+                info!(target:"vcx", "vcx_schema_prepare_for_endorser_cb(command_handle: {}, rc: {}, handle: {}, schema_txn: {}), source_id: {:?}",
+                      command_handle, error_string(0), handle, txn, &source_id);
+                let txn = CStringUtils::string_to_cstring(txn);
+                cb(command_handle, error::SUCCESS.code_num, handle, txn.as_ptr());
+            },
+            Err(x) => {
+                warn!("vcx_schema_prepare_for_endorser_cb(command_handle: {}, rc: {}, handle: {}, schema_txn: {}, source_id: {:?})",
+                      command_handle, error_string(x.to_error_code()), 0, "null", source_id);
+                cb(command_handle, x.record_error(), 0, ptr::null());
+            },
+        };
+    });
+    error::SUCCESS.code_num
+}
+
+/// Checks the ledger for a schema that is pending endorsement
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// schema_handle: Schema handle that was provided during creation. Used to access schema object
+///
+/// cb: Callback that provides the most current state (0 - pending endorsement, 1 - published) and error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_schema_update_state(command_handle: u32,
+                                      schema_handle: u32,
+                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_schema_update_state(command_handle: {}, schema_handle: {})", command_handle, schema_handle);
+    if !schema::is_valid_handle(schema_handle) {
+        return record_error_code(error::INVALID_SCHEMA_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match schema::update_state(schema_handle) {
+            Ok(state) => {
+                info!("vcx_schema_update_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, error_string(0), state);
+                cb(command_handle, error::SUCCESS.code_num, state);
+            },
+            Err(x) => {
+                warn!("vcx_schema_update_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, x.to_string(), 0);
+                cb(command_handle, x.record_error(), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Get the current state of the schema object
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// schema_handle: Schema handle that was provided during creation. Used to access schema object
+///
+/// cb: Callback that provides the state (0 - pending endorsement, 1 - published) and error status
+///
+/// #Returns
+/// Error code as a u32
+#[no_mangle]
+pub extern fn vcx_schema_get_state(command_handle: u32,
+                                   schema_handle: u32,
+                                   cb: Option<extern fn(xcommand_handle: u32, err: u32, state: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_schema_get_state(command_handle: {}, schema_handle: {})", command_handle, schema_handle);
+    if !schema::is_valid_handle(schema_handle) {
+        return record_error_code(error::INVALID_SCHEMA_HANDLE.code_num);
+    }
+
+    thread::spawn(move|| {
+        match schema::get_state(schema_handle) {
+            Ok(state) => {
+                info!("vcx_schema_get_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, error_string(0), state);
+                cb(command_handle, error::SUCCESS.code_num, state);
+            },
+            Err(x) => {
+                warn!("vcx_schema_get_state_cb(command_handle: {}, rc: {}, state: {})",
+                      command_handle, x.to_string(), 0);
+                cb(command_handle, x.record_error(), 0);
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Takes the schema object and returns a json string of all its attributes
 ///
 /// #Params
@@ -91,7 +278,7 @@ pub extern fn vcx_schema_create(command_handle: u32,
 pub extern fn vcx_schema_serialize(command_handle: u32,
                                    schema_handle: u32,
                                    cb: Option<extern fn(xcommand_handle: u32, err: u32, schema_state: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let source_id = schema::get_source_id(schema_handle).unwrap_or_default();
@@ -99,7 +286,7 @@ pub extern fn vcx_schema_serialize(command_handle: u32,
           command_handle, schema_handle, source_id);
 
     if !schema::is_valid_handle(schema_handle) {
-        return error::INVALID_SCHEMA_HANDLE.code_num;
+        return record_error_code(error::INVALID_SCHEMA_HANDLE.code_num);
     };
 
     thread::spawn( move|| {
@@ -113,7 +300,7 @@ pub extern fn vcx_schema_serialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_schema_serialize_cb(command_handle: {}, schema_handle: {}, rc: {}, state: {}), source_id: {:?}",
                       command_handle, schema_handle, error_string(x.to_error_code()), "null", source_id);
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
 
@@ -137,7 +324,7 @@ pub extern fn vcx_schema_serialize(command_handle: u32,
 pub extern fn vcx_schema_deserialize(command_handle: u32,
                                      schema_data: *const c_char,
                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, schema_handle: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(schema_data, error::INVALID_OPTION.code_num);
 
@@ -152,7 +339,7 @@ pub extern fn vcx_schema_deserialize(command_handle: u32,
             Err(x) => {
                 warn!("vcx_schema_deserialize_cb(command_handle: {}, rc: {}, handle: {}), source_id: {:?}",
                       command_handle, error_string(x.to_error_code()), 0, "");
-                (x.to_error_code(), 0)
+                (x.record_error(), 0)
             },
         };
         cb(command_handle, rc, handle);
@@ -170,6 +357,7 @@ pub extern fn vcx_schema_deserialize(command_handle: u32,
 /// Success
 #[no_mangle]
 pub extern fn vcx_schema_release(schema_handle: u32) -> u32 {
+    reset_current_error();
     let source_id = schema::get_source_id(schema_handle).unwrap_or_default();
     match schema::release(schema_handle) {
         Ok(x) => info!("vcx_schema_release(schema_handle: {}, rc: {}), source_id: {:?}",
@@ -193,11 +381,12 @@ pub extern fn vcx_schema_release(schema_handle: u32) -> u32 {
 pub extern fn vcx_schema_get_schema_id(command_handle: u32,
                                        schema_handle: u32,
                                        cb: Option<extern fn(xcommand_handle: u32, err: u32, schema_id: *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_schema_get_schema_id(command_handle: {}, schema_handle: {})", command_handle, schema_handle);
     if !schema::is_valid_handle(schema_handle) {
-        return error::INVALID_SCHEMA_HANDLE.code_num;
+        return record_error_code(error::INVALID_SCHEMA_HANDLE.code_num);
     }
 
     thread::spawn(move|| {
@@ -211,7 +400,7 @@ pub extern fn vcx_schema_get_schema_id(command_handle: u32,
             Err(x) => {
                 warn!("vcx_schema_get_schema_id(command_handle: {}, schema_handle: {}, rc: {}, schema_seq_no: {})",
                       command_handle, schema_handle, x.to_string(), "");
-                cb(command_handle, x.to_error_code(), ptr::null_mut());
+                cb(command_handle, x.record_error(), ptr::null_mut());
             },
         };
     });
@@ -237,6 +426,7 @@ pub extern fn vcx_schema_get_attributes(command_handle: u32,
                                         source_id: *const c_char,
                                         schema_id: *const c_char,
                                         cb: Option<extern fn(xcommand_handle: u32, err: u32, s_handle: u32, schema_attrs: *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(source_id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(schema_id, error::INVALID_OPTION.code_num);
@@ -256,7 +446,7 @@ pub extern fn vcx_schema_get_attributes(command_handle: u32,
             Err(x) => {
                 warn!("vcx_schema_get_attributes_cb(command_handle: {}, rc: {}, handle: {}, attrs: {})",
                       command_handle, error_string(x.to_error_code()), 0, "");
-                cb(command_handle, x.to_error_code(), 0, ptr::null_mut());
+                cb(command_handle, x.record_error(), 0, ptr::null_mut());
             },
         };
 
@@ -287,7 +477,7 @@ pub extern fn vcx_schema_get_attributes(command_handle: u32,
 pub extern fn vcx_schema_get_payment_txn(command_handle: u32,
                                              handle: u32,
                                              cb: Option<extern fn(xcommand_handle: u32, err: u32, txn: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     info!("vcx_schema_get_payment_txn(command_handle: {})", command_handle);
@@ -313,7 +503,7 @@ pub extern fn vcx_schema_get_payment_txn(command_handle: u32,
             Err(x) => {
                 error!("vcx_schema_get_payment_txn_cb(command_handle: {}, rc: {}, txn: {}), source_id: {:?}",
                        command_handle, x.to_string(), "null", schema::get_source_id(handle).unwrap_or_default());
-                cb(command_handle, x.to_error_code(), ptr::null());
+                cb(command_handle, x.record_error(), ptr::null());
             },
         };
     });
@@ -334,8 +524,9 @@ mod tests {
     use std::time::Duration;
     use settings;
     #[allow(unused_imports)]
-    use utils::constants::{ SCHEMA_ID, SCHEMA_WITH_VERSION, DEFAULT_SCHEMA_ATTRS, DEFAULT_SCHEMA_ID, DEFAULT_SCHEMA_NAME };
+    use utils::constants::{ SCHEMA_ID, SCHEMA_WITH_VERSION, DEFAULT_SCHEMA_ATTRS, DEFAULT_SCHEMA_ID, DEFAULT_SCHEMA_NAME, DEFAULT_SERIALIZE_VERSION };
     use utils::libindy::return_types_u32;
+    use api::PublicEntityStateType;
 
     fn set_default_and_enable_test_mode() {
         settings::set_defaults();
@@ -357,6 +548,38 @@ mod tests {
         assert!(handle>0)
     }
 
+    #[test]
+    fn test_vcx_schema_create_on_named_pool() {
+        set_default_and_enable_test_mode();
+        let pool_name = "test_vcx_schema_create_on_named_pool";
+        ::utils::libindy::pool::open_named_pool(pool_name, "", vec![]).unwrap();
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_schema_create_with_pool(cb.command_handle,
+                                               CString::new("Test Source ID").unwrap().into_raw(),
+                                               CString::new("Test Schema").unwrap().into_raw(),
+                                               CString::new("0.0").unwrap().into_raw(),
+                                               CString::new("[att1, att2]").unwrap().into_raw(),
+                                               0,
+                                               CString::new(pool_name).unwrap().into_raw(),
+                                               Some(cb.get_callback())), error::SUCCESS.code_num);
+        let handle = cb.receive(Some(Duration::from_secs(2))).unwrap();
+        assert!(schema::to_string(handle).unwrap().contains(pool_name));
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_schema_create_with_pool(cb.command_handle,
+                                               CString::new("Test Source ID").unwrap().into_raw(),
+                                               CString::new("Test Schema").unwrap().into_raw(),
+                                               CString::new("0.0").unwrap().into_raw(),
+                                               CString::new("[att1, att2]").unwrap().into_raw(),
+                                               0,
+                                               CString::new("unknown_pool").unwrap().into_raw(),
+                                               Some(cb.get_callback())), error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(2))).err(), Some(error::NO_POOL_OPEN.code_num));
+
+        ::utils::libindy::pool::close_named_pool(pool_name).unwrap();
+    }
+
     #[cfg(feature="pool_tests")]
     #[test]
     fn test_vcx_create_schema_with_pool() {
@@ -401,6 +624,29 @@ mod tests {
         ::utils::devsetup::tests::cleanup_dev_env(wallet_name);
     }
 
+    #[test]
+    fn test_vcx_schema_prepare_for_endorser() {
+        set_default_and_enable_test_mode();
+        let cb = return_types_u32::Return_U32_U32_STR::new().unwrap();
+        assert_eq!(vcx_schema_prepare_for_endorser(cb.command_handle,
+                                                   CString::new("Test Source ID").unwrap().into_raw(),
+                                                   CString::new("Test Schema").unwrap().into_raw(),
+                                                   CString::new("0.0").unwrap().into_raw(),
+                                                   CString::new("[att1, att2]").unwrap().into_raw(),
+                                                   Some(cb.get_callback())), error::SUCCESS.code_num);
+        let (handle, txn) = cb.receive(Some(Duration::from_secs(2))).unwrap();
+        assert!(handle > 0);
+        assert!(txn.is_some());
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_schema_get_state(cb.command_handle, handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(2))).unwrap(), PublicEntityStateType::PendingEndorsement as u32);
+
+        let cb = return_types_u32::Return_U32_U32::new().unwrap();
+        assert_eq!(vcx_schema_update_state(cb.command_handle, handle, Some(cb.get_callback())), error::SUCCESS.code_num);
+        assert_eq!(cb.receive(Some(Duration::from_secs(2))).unwrap(), PublicEntityStateType::Published as u32);
+    }
+
     #[test]
     fn test_vcx_schema_serialize() {
         set_default_and_enable_test_mode();
@@ -503,7 +749,7 @@ mod tests {
         println!("{}", &data);
         let j:serde_json::Value = serde_json::from_str(&data.clone()).unwrap();
         let schema:CreateSchema = serde_json::from_value(j["data"].clone()).unwrap();
-        assert_eq!(j["version"], "1.0");
+        assert_eq!(j["version"], DEFAULT_SERIALIZE_VERSION);
         assert_eq!(schema.get_source_id(), source_id);
 	    ::utils::devsetup::tests::cleanup_dev_env(wallet_name);
     }
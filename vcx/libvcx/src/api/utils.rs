@@ -3,6 +3,8 @@ extern crate serde_json;
 
 use self::libc::c_char;
 use messages;
+use messages::agent_utils::ProvisionToken;
+use messages::get_message::{DownloadOptions, download_messages_with_options};
 use std::ptr;
 use std::thread;
 use utils::httpclient;
@@ -10,6 +12,8 @@ use utils::constants::*;
 use utils::cstring::CStringUtils;
 use utils::error;
 use utils::error::error_string;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
@@ -20,6 +24,15 @@ pub struct Config {
     wallet_key: String,
     agent_seed: Option<String>,
     enterprise_seed: Option<String>,
+    provision_token: Option<ProvisionToken>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AgencyConfig {
+    agency_url: String,
+    agency_did: String,
+    agency_verkey: String,
+    provision_token: Option<ProvisionToken>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -32,17 +45,26 @@ pub struct UpdateAgentInfo {
 /// NOTE: for asynchronous call use vcx_agent_provision_async
 ///
 /// #Params
-/// json: configuration
+/// json: configuration. Agencies that restrict sign-ups also require a "provision_token"
+/// -> {"sponsorId":"","sponseeId":"","nonce":"","timestamp":"","sig":"","sponsorVerKey":""}
 ///
 /// #Returns
 /// Configuration (wallet also populated), on error returns NULL
 
 #[no_mangle]
 pub extern fn vcx_provision_agent(json: *const c_char) -> *mut c_char {
-    check_useful_c_str!(json, ptr::null_mut());
+    reset_current_error();
+    let json = match CStringUtils::c_str_to_string(json) {
+        Ok(Some(json)) => json,
+        _ => {
+            record_error_code(error::INVALID_OPTION.code_num);
+            return ptr::null_mut()
+        },
+    };
     let my_config: Config = match serde_json::from_str(&json) {
         Ok(x) => x,
         Err(x) => {
+            record_error_code(error::INVALID_JSON.code_num);
             return ptr::null_mut()
         },
     };
@@ -53,9 +75,10 @@ pub extern fn vcx_provision_agent(json: *const c_char) -> *mut c_char {
                                                          my_config.wallet_name,
                                                          my_config.agent_seed,
                                                          my_config.enterprise_seed,
-                                                         &my_config.wallet_key, None, None, None) {
+                                                         &my_config.wallet_key, None, None, None, my_config.provision_token) {
         Err(e) => {
             error!("Provision Agent Error {}.", e);
+            record_error_code(e);
             return ptr::null_mut();
         },
         Ok(s) => {
@@ -84,6 +107,7 @@ pub extern fn vcx_provision_agent(json: *const c_char) -> *mut c_char {
 pub extern fn vcx_agent_provision_async(command_handle : u32,
                                json: *const c_char,
                                cb: Option<extern fn(xcommand_handle: u32, err: u32, config: *const c_char)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(json, error::INVALID_OPTION.code_num);
 
@@ -104,10 +128,10 @@ pub extern fn vcx_agent_provision_async(command_handle : u32,
                                                                 my_config.wallet_name,
                                                                 my_config.agent_seed,
                                                                 my_config.enterprise_seed,
-                                                                &my_config.wallet_key, None, None, None) {
+                                                                &my_config.wallet_key, None, None, None, my_config.provision_token) {
             Err(e) => {
                 error!("vcx_agent_provision_async_cb(command_handle: {}, rc: {}, config: NULL", command_handle, error_string(e));
-                cb(command_handle, e, ptr::null_mut());
+                cb(command_handle, record_error_code(e), ptr::null_mut());
             },
             Ok(s) => {
                 info!("vcx_agent_provision_async_cb(command_handle: {}, rc: {}, config: {})",
@@ -121,6 +145,86 @@ pub extern fn vcx_agent_provision_async(command_handle : u32,
     error::SUCCESS.code_num
 }
 
+/// Re-provision an already provisioned wallet with a (new) agency. The wallet, institution did
+/// and sdk keys are reused, the wallet opened by vcx_init is used when there is one. The given
+/// connections are moved to agents on the new agency and returned serialized again, use them in
+/// place of the previous ones. The other side of an accepted connection is not notified, it
+/// keeps sending to the previous agent. Call vcx_init_with_config with the returned config afterwards.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// config: configuration returned from the original provisioning
+///
+/// agency_config: new agency information -> {"agency_url":"","agency_did":"","agency_verkey":""}
+/// and optionally a "provision_token" if the new agency restricts sign-ups
+///
+/// connections: JSON array of serialized connections to move, NULL to move none
+///
+/// cb: Callback that provides updated configuration and the connections or error status
+/// # Example connections -> {"moved":["<serialized connection>"],
+///     "not_moved":[{"source_id":"alice","connection":"<serialized connection>","error":1010}]}
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_agent_reprovision_async(command_handle: u32,
+                                          config: *const c_char,
+                                          agency_config: *const c_char,
+                                          connections: *const c_char,
+                                          cb: Option<extern fn(xcommand_handle: u32, err: u32, config: *const c_char, connections: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(config, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(agency_config, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(connections, error::INVALID_OPTION.code_num);
+
+    let agency_config: AgencyConfig = match serde_json::from_str(&agency_config) {
+        Ok(x) => x,
+        Err(x) => {
+            return record_error_code(error::INVALID_JSON.code_num)
+        },
+    };
+
+    let connections: Vec<String> = match connections.map(|x| serde_json::from_str(&x)) {
+        None => Vec::new(),
+        Some(Ok(x)) => x,
+        Some(Err(_)) => return record_error_code(error::INVALID_JSON.code_num),
+    };
+
+    info!("vcx_agent_reprovision_async(command_handle: {}, agency_config: {:?}, connections: {})",
+          command_handle, agency_config, connections.len());
+
+    thread::spawn(move|| {
+        let result = messages::agent_utils::connect_register_reprovision(&config,
+                                                                         &agency_config.agency_url,
+                                                                         &agency_config.agency_did,
+                                                                         &agency_config.agency_verkey,
+                                                                         agency_config.provision_token,
+                                                                         &connections)
+            .and_then(|(config, connections)| {
+                serde_json::to_string(&connections).map(|x| (config, x)).or(Err(error::INVALID_JSON.code_num))
+            });
+
+        match result {
+            Err(e) => {
+                error!("vcx_agent_reprovision_async_cb(command_handle: {}, rc: {}, config: NULL, connections: NULL", command_handle, error_string(e));
+                cb(command_handle, record_error_code(e), ptr::null_mut(), ptr::null_mut());
+            },
+            Ok((config, connections)) => {
+                info!("vcx_agent_reprovision_async_cb(command_handle: {}, rc: {}, connections: {})",
+                      command_handle, error_string(0), connections);
+                let config = CStringUtils::string_to_cstring(config);
+                let connections = CStringUtils::string_to_cstring(connections);
+                cb(command_handle, 0, config.as_ptr(), connections.as_ptr());
+            },
+        }
+    });
+
+    error::SUCCESS.code_num
+}
+
 /// Update information on the agent (ie, comm method and type)
 ///
 /// #Params
@@ -137,7 +241,7 @@ pub extern fn vcx_agent_provision_async(command_handle : u32,
 pub extern fn vcx_agent_update_info(command_handle: u32,
                                     json: *const c_char,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(json, error::INVALID_OPTION.code_num);
 
@@ -161,7 +265,7 @@ pub extern fn vcx_agent_update_info(command_handle: u32,
             Err(e) => {
                 error!("vcx_agent_update_info_cb(command_handle: {}, rc: {})",
                       command_handle, error::error_string(e));
-                cb(command_handle, e);
+                cb(command_handle, record_error_code(e));
             },
         };
     });
@@ -182,7 +286,7 @@ pub extern fn vcx_agent_update_info(command_handle: u32,
 #[no_mangle]
 pub extern fn vcx_ledger_get_fees(command_handle: u32,
                                   cb: Option<extern fn(xcommand_handle: u32, err: u32, fees: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     info!("vcx_ledger_get_fees(command_handle: {})",
           command_handle);
@@ -200,7 +304,110 @@ pub extern fn vcx_ledger_get_fees(command_handle: u32,
                 warn!("vcx_ledget_get_fees_cb(command_handle: {}, rc: {}, fees: {})",
                       command_handle, error_string(e), "null");
 
-                cb(command_handle, e, ptr::null_mut());
+                cb(command_handle, record_error_code(e), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Endorse a transaction prepared by an author (ie, vcx_schema_prepare_for_endorser) and write it to the ledger.
+/// The institution did of this wallet is used as the endorser.
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// transaction: transaction json returned to the author
+///
+/// pool_name: named pool (see vcx_open_pool) the transaction is submitted to, NULL uses the default write pool
+///
+/// cb: Callback that provides error status of the endorsement
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_endorse_transaction(command_handle: u32,
+                                      transaction: *const c_char,
+                                      pool_name: *const c_char,
+                                      cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(transaction, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(pool_name, error::INVALID_OPTION.code_num);
+
+    info!("vcx_endorse_transaction(command_handle: {}, transaction: {}, pool_name: {:?})",
+          command_handle, transaction, pool_name);
+
+    thread::spawn(move|| {
+        match ::utils::libindy::pool::with_pool(pool_name.as_ref().map(String::as_str),
+                                                || ::utils::libindy::ledger::endorse_transaction(&transaction))
+            .and_then(|rc| rc) {
+            Ok(_) => {
+                info!("vcx_endorse_transaction_cb(command_handle: {}, rc: {})",
+                      command_handle, error::error_string(0));
+                cb(command_handle, error::SUCCESS.code_num);
+            },
+            Err(e) => {
+                error!("vcx_endorse_transaction_cb(command_handle: {}, rc: {})",
+                       command_handle, error_string(e));
+                cb(command_handle, record_error_code(e));
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Fetch schemas and credential definitions from the ledger and store them in the ledger cache
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// schema_ids: json array of schema ids to cache -> ["id1", "id2"]
+///
+/// cred_def_ids: json array of credential definition ids to cache -> ["id1", "id2"]
+///
+/// cb: Callback that provides error status of the warm up
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_ledger_cache_warm_up(command_handle: u32,
+                                       schema_ids: *const c_char,
+                                       cred_def_ids: *const c_char,
+                                       cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(schema_ids, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(cred_def_ids, error::INVALID_OPTION.code_num);
+
+    let schema_ids: Vec<String> = match serde_json::from_str(&schema_ids) {
+        Ok(x) => x,
+        Err(_) => return error::INVALID_JSON.code_num,
+    };
+
+    let cred_def_ids: Vec<String> = match serde_json::from_str(&cred_def_ids) {
+        Ok(x) => x,
+        Err(_) => return error::INVALID_JSON.code_num,
+    };
+
+    info!("vcx_ledger_cache_warm_up(command_handle: {}, schema_ids: {:?}, cred_def_ids: {:?})",
+          command_handle, schema_ids, cred_def_ids);
+
+    thread::spawn(move|| {
+        match ::utils::ledger_cache::warm_up(&schema_ids, &cred_def_ids) {
+            Ok(_) => {
+                info!("vcx_ledger_cache_warm_up_cb(command_handle: {}, rc: {})",
+                      command_handle, error::error_string(0));
+                cb(command_handle, error::SUCCESS.code_num);
+            },
+            Err(e) => {
+                warn!("vcx_ledger_cache_warm_up_cb(command_handle: {}, rc: {})",
+                      command_handle, error_string(e));
+                cb(command_handle, record_error_code(e));
             },
         };
     });
@@ -208,8 +415,117 @@ pub extern fn vcx_ledger_get_fees(command_handle: u32,
     error::SUCCESS.code_num
 }
 
+/// Remove a schema or credential definition from the ledger cache so the next read goes to the ledger
+///
+/// #Params
+/// id: schema id or credential definition id
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_ledger_cache_invalidate(id: *const c_char) -> u32 {
+    reset_current_error();
+    check_useful_c_str!(id, error::INVALID_OPTION.code_num);
+    info!("vcx_ledger_cache_invalidate(id: {})", id);
+
+    ::utils::ledger_cache::invalidate_id(&id);
+    error::SUCCESS.code_num
+}
+
+/// Drop all schemas and credential definitions held in memory by the ledger cache
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_ledger_cache_clear() -> u32 {
+    reset_current_error();
+    info!("vcx_ledger_cache_clear()");
+
+    ::utils::ledger_cache::clear();
+    error::SUCCESS.code_num
+}
+
+/// Retrieve the Transaction Author Agreement and the list of acceptance mechanisms set on the ledger
+///
+/// #Params
+/// command_handle: command handle to map callback to user context.
+///
+/// cb: Callback that provides the agreement or error status
+/// -> {"text":"","version":"","aml":{"<mechanism>":"<description>"},"amlContext":""}
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_get_ledger_author_agreement(command_handle: u32,
+                                              cb: Option<extern fn(xcommand_handle: u32, err: u32, author_agreement: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+
+    info!("vcx_get_ledger_author_agreement(command_handle: {})", command_handle);
+
+    thread::spawn(move|| {
+        match ::utils::libindy::ledger::get_txn_author_agreement() {
+            Ok(x) => {
+                info!("vcx_get_ledger_author_agreement_cb(command_handle: {}, rc: {}, author_agreement: {})",
+                      command_handle, error::error_string(0), x);
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(e) => {
+                error!("vcx_get_ledger_author_agreement_cb(command_handle: {}, rc: {}, author_agreement: {})",
+                       command_handle, error_string(e), "null");
+                cb(command_handle, record_error_code(e), ptr::null());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Record the acceptance of the Transaction Author Agreement. Every following ledger write
+/// (schema, credential definition, payment, ...) carries the acceptance.
+///
+/// #Params
+/// text: text of the accepted agreement, may be NULL if hash is provided
+///
+/// version: version of the accepted agreement, may be NULL if hash is provided
+///
+/// hash: sha256 hash of the version and text of the agreement, may be NULL if text and version are provided
+///
+/// acc_mech_type: mechanism used to accept the agreement (one of the aml returned by vcx_get_ledger_author_agreement)
+///
+/// time_of_acceptance: UTC timestamp in seconds of the acceptance
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_set_active_txn_author_agreement_meta(text: *const c_char,
+                                                       version: *const c_char,
+                                                       hash: *const c_char,
+                                                       acc_mech_type: *const c_char,
+                                                       time_of_acceptance: u64) -> u32 {
+    reset_current_error();
+    check_useful_opt_c_str!(text, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(version, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(hash, error::INVALID_OPTION.code_num);
+    check_useful_c_str!(acc_mech_type, error::INVALID_OPTION.code_num);
+
+    info!("vcx_set_active_txn_author_agreement_meta(text: {:?}, version: {:?}, hash: {:?}, acc_mech_type: {}, time_of_acceptance: {})",
+          text, version, hash, acc_mech_type, time_of_acceptance);
+
+    match ::utils::author_agreement::set_txn_author_agreement(text, version, hash, acc_mech_type, time_of_acceptance) {
+        Ok(_) => error::SUCCESS.code_num,
+        Err(e) => record_error_code(e),
+    }
+}
+
 #[no_mangle]
 pub extern fn vcx_set_next_agency_response(message_index: u32) {
+    reset_current_error();
     let message = match message_index {
         1 => CREATE_KEYS_RESPONSE.to_vec(),
         2 => UPDATE_PROFILE_RESPONSE.to_vec(),
@@ -246,7 +562,7 @@ pub extern fn vcx_messages_download(command_handle: u32,
                                     uids: *const c_char,
                                     pw_dids: *const c_char,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, messages: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     let message_status = if !message_status.is_null() {
@@ -302,7 +618,84 @@ pub extern fn vcx_messages_download(command_handle: u32,
                 warn!("vcx_messages_download_cb(command_handle: {}, rc: {}, messages: {})",
                       command_handle, error_string(e), "null");
 
-                cb(command_handle, e, ptr::null_mut());
+                cb(command_handle, record_error_code(e), ptr::null_mut());
+            },
+        };
+    });
+
+    error::SUCCESS.code_num
+}
+
+/// Retrieve one page of messages, for agents with too many messages to download at once
+///
+/// #params
+///
+/// command_handle: command handle to map callback to user context.
+///
+/// message_status: optional, comma separated - query for messages with the specified status
+///
+/// uids: optional, comma separated - query for messages with the specified uids
+///
+/// pw_dids: optional, comma separated - query for messages of the specified connections
+///
+/// options: optional - paging and additional filters, since and until are unix timestamps
+/// -> {"limit":100,"cursor":"mgrmngq","msg_types":["credReq","proof"],"since":1537000000,"until":1538000000}
+/// limit, cursor, since and until fail with MESSAGE_PAGING_NOT_SUPPORTED when the agency does not
+/// support paging, msg_types is always applied
+///
+/// cb: Callback that provides array of matching messages retrieved, each connection carries the
+/// total number of matching messages and the nextCursor to pass to get the next page
+///
+/// #Returns
+/// Error code as a u32
+
+#[no_mangle]
+pub extern fn vcx_messages_download_with_options(command_handle: u32,
+                                                 message_status: *const c_char,
+                                                 uids: *const c_char,
+                                                 pw_dids: *const c_char,
+                                                 options: *const c_char,
+                                                 cb: Option<extern fn(xcommand_handle: u32, err: u32, messages: *const c_char)>) -> u32 {
+    reset_current_error();
+    check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(message_status, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(uids, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(pw_dids, error::INVALID_OPTION.code_num);
+    check_useful_opt_c_str!(options, error::INVALID_OPTION.code_num);
+
+    info!("vcx_messages_download_with_options(command_handle: {}, message_status: {:?}, uids: {:?}, pw_dids: {:?}, options: {:?})",
+          command_handle, message_status, uids, pw_dids, options);
+
+    let split = |x: Option<String>| x.map(|x| x.split(',').map(String::from).collect::<Vec<String>>());
+    let message_status = split(message_status);
+    let uids = split(uids);
+    let pw_dids = split(pw_dids);
+
+    let options: DownloadOptions = match options {
+        Some(x) => match serde_json::from_str(&x) {
+            Ok(x) => x,
+            Err(_) => return error::INVALID_JSON.code_num,
+        },
+        None => DownloadOptions::default(),
+    };
+
+    thread::spawn(move|| {
+        let result = download_messages_with_options(pw_dids, message_status, uids, &options)
+            .and_then(|x| serde_json::to_string(&x).or(Err(error::INVALID_JSON.code_num)));
+
+        match result {
+            Ok(x) => {
+                info!("vcx_messages_download_with_options_cb(command_handle: {}, rc: {}, messages: {})",
+                      command_handle, error::error_string(0), x);
+
+                let msg = CStringUtils::string_to_cstring(x);
+                cb(command_handle, error::SUCCESS.code_num, msg.as_ptr());
+            },
+            Err(e) => {
+                warn!("vcx_messages_download_with_options_cb(command_handle: {}, rc: {}, messages: {})",
+                      command_handle, error_string(e), "null");
+
+                cb(command_handle, record_error_code(e), ptr::null_mut());
             },
         };
     });
@@ -330,7 +723,7 @@ pub extern fn vcx_messages_update_status(command_handle: u32,
                                          message_status: *const c_char,
                                          msg_json: *const c_char,
                                          cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(message_status, error::INVALID_OPTION.code_num);
     check_useful_c_str!(msg_json, error::INVALID_OPTION.code_num);
@@ -350,7 +743,7 @@ pub extern fn vcx_messages_update_status(command_handle: u32,
                 warn!("vcx_messages_set_status_cb(command_handle: {}, rc: {})",
                       command_handle, error_string(e));
 
-                cb(command_handle, e);
+                cb(command_handle, record_error_code(e));
             },
         };
     });
@@ -377,7 +770,7 @@ mod tests {
 
         let result = vcx_provision_agent(c_json);
 
-        check_useful_c_str!(result,());
+        let result = CStringUtils::c_str_to_string(result).unwrap().unwrap();
         assert!(result.len() > 0);
     }
 
@@ -410,6 +803,45 @@ mod tests {
                    error::INVALID_JSON.code_num);
     }
 
+    #[test]
+    fn test_reprovision_agent() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let config = json!({
+            "wallet_name": "test_reprovision_agent",
+            "wallet_key": "key",
+            "sdk_to_remote_did": settings::DEFAULT_DID,
+            "sdk_to_remote_verkey": settings::DEFAULT_VERKEY,
+            "institution_did": settings::DEFAULT_DID,
+        }).to_string();
+        let agency_config = r#"{"agency_url":"https://new-agency.example.org","agency_did":"Ab8TvZa3Q19VNkQVzAWVL7","agency_verkey":"5LXaR43B1aQyeh94VBP8LG1Sgvjk7aNfqiksBCSjwqbf"}"#;
+        let handle = ::connection::build_connection("test_reprovision_agent").unwrap();
+        let connections = json!([::connection::to_string(handle).unwrap(), "garbage"]).to_string();
+        ::connection::release(handle).unwrap();
+
+        let cb = return_types_u32::Return_U32_STR_STR::new().unwrap();
+        let result = vcx_agent_reprovision_async(cb.command_handle,
+                                                 CString::new(config).unwrap().into_raw(),
+                                                 CString::new(agency_config).unwrap().into_raw(),
+                                                 CString::new(connections).unwrap().into_raw(),
+                                                 Some(cb.get_callback()));
+        assert_eq!(0, result);
+        let (config, connections) = cb.receive(Some(Duration::from_secs(2))).unwrap();
+        assert!(config.unwrap().contains("https://new-agency.example.org"));
+        let connections: serde_json::Value = serde_json::from_str(&connections.unwrap()).unwrap();
+        assert_eq!(connections["moved"].as_array().unwrap().len(), 1);
+        assert_eq!(connections["not_moved"][0]["connection"], json!("garbage"));
+
+        let cb = return_types_u32::Return_U32_STR_STR::new().unwrap();
+        assert_eq!(vcx_agent_reprovision_async(cb.command_handle,
+                                               CString::new("{}").unwrap().into_raw(),
+                                               CString::new("{}").unwrap().into_raw(),
+                                               ptr::null(),
+                                               Some(cb.get_callback())),
+                   error::INVALID_JSON.code_num);
+    }
+
     #[test]
     fn test_update_agent_info() {
         settings::set_defaults();
@@ -450,6 +882,78 @@ mod tests {
                    error::SUCCESS.code_num);
     }
 
+    #[test]
+    fn test_get_ledger_author_agreement() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_get_ledger_author_agreement(cb.command_handle,
+                                                   Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        let agreement = cb.receive(Some(Duration::from_secs(2))).unwrap();
+        assert_eq!(agreement, Some(DEFAULT_AUTHOR_AGREEMENT.to_string()));
+    }
+
+    #[test]
+    fn test_set_active_txn_author_agreement_meta() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        assert_eq!(vcx_set_active_txn_author_agreement_meta(CString::new("indy agreement").unwrap().into_raw(),
+                                                            CString::new("1.0.0").unwrap().into_raw(),
+                                                            ptr::null(),
+                                                            CString::new("on_file").unwrap().into_raw(),
+                                                            123456789),
+                   error::SUCCESS.code_num);
+        assert!(settings::get_config_value(settings::CONFIG_TXN_AUTHOR_AGREEMENT).is_ok());
+
+        assert_eq!(vcx_set_active_txn_author_agreement_meta(ptr::null(),
+                                                            ptr::null(),
+                                                            ptr::null(),
+                                                            CString::new("on_file").unwrap().into_raw(),
+                                                            123456789),
+                   error::INVALID_AUTHOR_AGREEMENT.code_num);
+    }
+
+    #[test]
+    fn test_endorse_transaction() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_endorse_transaction(cb.command_handle,
+                                           CString::new(SCHEMA_REQUEST_FOR_ENDORSER).unwrap().into_raw(),
+                                           ptr::null(),
+                                           Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(2))).unwrap();
+    }
+
+    #[test]
+    fn test_ledger_cache_warm_up() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_ledger_cache_warm_up(cb.command_handle,
+                                            CString::new(format!(r#"["{}"]"#, SCHEMA_ID)).unwrap().into_raw(),
+                                            CString::new(format!(r#"["{}"]"#, CRED_DEF_ID)).unwrap().into_raw(),
+                                            Some(cb.get_callback())),
+                   error::SUCCESS.code_num);
+        cb.receive(Some(Duration::from_secs(2))).unwrap();
+
+        let cb = return_types_u32::Return_U32::new().unwrap();
+        assert_eq!(vcx_ledger_cache_warm_up(cb.command_handle,
+                                            CString::new("not json").unwrap().into_raw(),
+                                            CString::new("[]").unwrap().into_raw(),
+                                            Some(cb.get_callback())),
+                   error::INVALID_JSON.code_num);
+
+        assert_eq!(vcx_ledger_cache_invalidate(CString::new(SCHEMA_ID).unwrap().into_raw()), error::SUCCESS.code_num);
+        assert_eq!(vcx_ledger_cache_clear(), error::SUCCESS.code_num);
+    }
+
     #[test]
     fn test_messages_download() {
         settings::set_defaults();
@@ -460,6 +964,22 @@ mod tests {
         cb.receive(Some(Duration::from_secs(10))).unwrap();
     }
 
+    #[test]
+    fn test_messages_download_with_options() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+
+        let options = CString::new(r#"{"msg_types":["unknownType"]}"#).unwrap().into_raw();
+        let cb = return_types_u32::Return_U32_STR::new().unwrap();
+        assert_eq!(vcx_messages_download_with_options(cb.command_handle, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), options, Some(cb.get_callback())), error::SUCCESS.code_num);
+        let messages: serde_json::Value = serde_json::from_str(&cb.receive(Some(Duration::from_secs(10))).unwrap().unwrap()).unwrap();
+        assert_eq!(messages[0]["msgs"], json!([]));
+        assert_eq!(messages[0]["total"], json!(0));
+
+        let options = CString::new("{limit:10}").unwrap().into_raw();
+        assert_eq!(vcx_messages_download_with_options(0, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), options, Some(cb.get_callback())), error::INVALID_JSON.code_num);
+    }
+
     #[test]
     fn test_messages_update_status() {
         settings::set_defaults();
@@ -8,7 +8,11 @@ use utils::libindy::{wallet, pool};
 use utils::error;
 use settings;
 use std::thread;
+use std::ptr;
+use std::cell::RefCell;
 use std::ffi::CString;
+use error::context;
+use error::record_error_code;
 
 
 /// Initializes VCX with config settings
@@ -28,6 +32,7 @@ use std::ffi::CString;
 pub extern fn vcx_init_with_config(command_handle: u32,
                                    config: *const c_char,
                                    cb: Option<extern fn(xcommand_handle: u32, err:u32)>) -> u32 {
+    context::reset_current_error();
     check_useful_c_str!(config,error::INVALID_OPTION.code_num);
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
@@ -64,7 +69,7 @@ pub extern fn vcx_init_with_config(command_handle: u32,
 pub extern fn vcx_init (command_handle: u32,
                         config_path:*const c_char,
                         cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
-
+    context::reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
 
     if !config_path.is_null() {
@@ -95,7 +100,7 @@ fn _finish_init(command_handle: u32, cb: extern fn(xcommand_handle: u32, err: u3
 
     match ::utils::libindy::payments::init_payments() {
         Ok(_) => (),
-        Err(x) => return x,
+        Err(x) => return record_error_code(x),
     };
 
     settings::log_settings();
@@ -122,7 +127,7 @@ fn _finish_init(command_handle: u32, cb: extern fn(xcommand_handle: u32, err: u3
                 Ok(_) => (),
                 Err(e) => {
                     error!("Init Pool Error {}.", e);
-                    return cb(command_handle, e)
+                    return cb(command_handle, record_error_code(e))
                 },
             }
         }
@@ -130,11 +135,15 @@ fn _finish_init(command_handle: u32, cb: extern fn(xcommand_handle: u32, err: u3
         match wallet::open_wallet(&wallet_name) {
             Ok(_) => {
                 debug!("Init Wallet Successful");
+                ::messages::outbox::load();
+                if let Err(e) = ::utils::nonce::prune() {
+                    warn!("could not prune proof nonces: {}", error::error_string(e));
+                }
                 cb(command_handle, error::SUCCESS.code_num)
             },
             Err(e) => {
                 error!("Init Wallet Error {}.", e);
-                cb(command_handle, e);
+                cb(command_handle, record_error_code(e));
             }
         }
     });
@@ -162,6 +171,8 @@ pub extern fn vcx_version() -> *const c_char {
 /// Success
 #[no_mangle]
 pub extern fn vcx_shutdown(delete: bool) -> u32 {
+    context::reset_current_error();
+    ::dispatcher::stop();
 
     match wallet::close_wallet() {
         Ok(_) => {},
@@ -173,6 +184,12 @@ pub extern fn vcx_shutdown(delete: bool) -> u32 {
         Err(_) => {},
     };
 
+    pool::close_all_named_pools();
+    match pool::set_default_write_pool(None) {
+        Ok(_) => {},
+        Err(_) => {},
+    };
+
     ::schema::release_all();
     ::connection::release_all();
     ::issuer_credential::release_all();
@@ -180,6 +197,8 @@ pub extern fn vcx_shutdown(delete: bool) -> u32 {
     ::proof::release_all();
     ::disclosed_proof::release_all();
     ::credential::release_all();
+    ::utils::ledger_cache::clear();
+    ::messages::outbox::clear();
 
     if delete {
         let pool_name = settings::get_config_value(settings::CONFIG_POOL_NAME)
@@ -210,8 +229,39 @@ pub extern fn vcx_error_c_message(error_code: u32) -> *const c_char {
     error::error_c_message(&error_code).as_ptr()
 }
 
+thread_local! {
+    // keeps the json returned by vcx_get_current_error alive until the next call on the thread
+    static CURRENT_ERROR_C_JSON: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Get details for the last error that occurred on the calling thread. Callbacks run on the
+/// thread that failed, so this should be called from the callback receiving the error code.
+///
+/// #Params
+/// error_json_p: reference that will contain the error details or NULL if no error occurred
+/// -> {"error":1087,"message":"Ledger rejected the transaction","causes":["..."],
+///     "context":{"handle":"..","ledger_response":"..","libindy_error":"..","agency_status":".."}}
+///
+/// The string stays valid until the next call of vcx_get_current_error on the same thread.
+#[no_mangle]
+pub extern fn vcx_get_current_error(error_json_p: *mut *const c_char) {
+    if error_json_p.is_null() { return; }
+
+    let error_json = context::get_current_error()
+        .and_then(|details| serde_json::to_string(&details).ok())
+        .and_then(|json| CString::new(json).ok());
+
+    CURRENT_ERROR_C_JSON.with(|current| {
+        let mut current = current.borrow_mut();
+        *current = error_json;
+        let ptr = current.as_ref().map(|json| json.as_ptr()).unwrap_or(ptr::null());
+        unsafe { *error_json_p = ptr; }
+    });
+}
+
 #[no_mangle]
 pub extern fn vcx_update_institution_info(name: *const c_char, logo_url: *const c_char) -> u32 {
+    context::reset_current_error();
     check_useful_c_str!(name, error::INVALID_CONFIGURATION.code_num);
     check_useful_c_str!(logo_url, error::INVALID_CONFIGURATION.code_num);
     info!("vcx_update_institution_info(name: {}, logo_url: {})", name, logo_url);
@@ -224,6 +274,7 @@ pub extern fn vcx_update_institution_info(name: *const c_char, logo_url: *const
 
 #[no_mangle]
 pub extern fn vcx_mint_tokens(number_of_addresses: u32, tokens_per_address: u32) {
+    context::reset_current_error();
     let ledger_fees = r#"{"101":2, "102":3}"#;
     info!("vcx_mint_tokens(number_of_addresses: {}, tokens_per_address: {})", number_of_addresses, tokens_per_address);
     ::utils::libindy::payments::mint_tokens_and_set_fees(Some(number_of_addresses), Some(tokens_per_address), Some(ledger_fees), false).unwrap_or_default();
@@ -688,6 +739,52 @@ mod tests {
         assert_eq!(c_message,error::INVALID_ATTRIBUTES_STRUCTURE.message);
     }
 
+    #[test]
+    fn test_get_current_error() {
+        use error::ToErrorDetails;
+        use error::schema::SchemaError;
+
+        context::reset_current_error();
+        let mut error_json_p: *const c_char = ptr::null();
+        vcx_get_current_error(&mut error_json_p);
+        assert!(error_json_p.is_null());
+
+        context::add_error_context("handle", "1234");
+        let code = SchemaError::InvalidHandle().record_error();
+
+        vcx_get_current_error(&mut error_json_p);
+        let details = CStringUtils::c_str_to_string(error_json_p).unwrap().unwrap();
+        let details: context::ErrorDetails = serde_json::from_str(&details).unwrap();
+        assert_eq!(details.error, code);
+        assert_eq!(details.context.get("handle").unwrap(), "1234");
+        assert_eq!(details.causes.len(), 1);
+        context::reset_current_error();
+    }
+
+    #[test]
+    fn test_api_call_resets_current_error() {
+        use error::ToErrorDetails;
+        use error::schema::SchemaError;
+
+        settings::set_defaults();
+        SchemaError::InvalidHandle().record_error();
+        let name = CString::new("name").unwrap();
+        let logo_url = CString::new("http://www.evernym.com").unwrap();
+        assert_eq!(vcx_update_institution_info(name.as_ptr(), logo_url.as_ptr()), error::SUCCESS.code_num);
+
+        let mut error_json_p: *const c_char = ptr::null();
+        vcx_get_current_error(&mut error_json_p);
+        assert!(error_json_p.is_null());
+
+        assert_eq!(vcx_update_institution_info(ptr::null(), logo_url.as_ptr()), error::INVALID_CONFIGURATION.code_num);
+        vcx_get_current_error(&mut error_json_p);
+        let details = CStringUtils::c_str_to_string(error_json_p).unwrap().unwrap();
+        let details: context::ErrorDetails = serde_json::from_str(&details).unwrap();
+        assert_eq!(details.error, error::INVALID_CONFIGURATION.code_num);
+        context::reset_current_error();
+        settings::set_defaults();
+    }
+
     #[test]
     fn test_vcx_version() {
         let return_version = CStringUtils::c_str_to_string(vcx_version()).unwrap().unwrap();
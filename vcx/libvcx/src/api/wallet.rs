@@ -6,10 +6,13 @@ use utils::cstring::CStringUtils;
 use utils::error;
 use utils::error::error_string;
 use error::ToErrorCode;
+use error::ToErrorDetails;
 use utils::libindy::payments::{pay_a_payee, get_wallet_token_info, create_address};
 use utils::libindy::wallet::{export, import, get_wallet_handle};
 use utils::libindy::wallet;
 use std::path::Path;
+use error::context::reset_current_error;
+use error::record_error_code;
 
 extern {
     pub fn indy_add_wallet_record(command_handle: i32,
@@ -58,7 +61,7 @@ extern {
 pub extern fn vcx_wallet_get_token_info(command_handle: u32,
                                      payment_handle: u32,
                                      cb: Option<extern fn(xcommand_handle: u32, err:u32, *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     info!("vcx_wallet_get_token_info(command_handle: {}, payment_handle: {})",
           command_handle, payment_handle);
@@ -77,7 +80,7 @@ pub extern fn vcx_wallet_get_token_info(command_handle: u32,
                     command_handle, error_string(x), "null");
 
 		let msg = CStringUtils::string_to_cstring("".to_string());
-                cb(command_handle, x, msg.as_ptr());
+                cb(command_handle, record_error_code(x), msg.as_ptr());
             },
         }
     });
@@ -98,7 +101,7 @@ pub extern fn vcx_wallet_get_token_info(command_handle: u32,
 pub extern fn vcx_wallet_create_payment_address(command_handle: u32,
                                                 seed: *const c_char,
                                                 cb: Option<extern fn(xcommand_handle: u32, err:u32, address: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     let seed = if !seed.is_null() {
         check_useful_opt_c_str!(seed, error::INVALID_OPTION.code_num);
@@ -124,7 +127,7 @@ pub extern fn vcx_wallet_create_payment_address(command_handle: u32,
                     command_handle, error_string(x), "null");
 
 		let msg = CStringUtils::string_to_cstring("".to_string());
-                cb(command_handle, x, msg.as_ptr());
+                cb(command_handle, record_error_code(x), msg.as_ptr());
             },
         }
     });
@@ -168,6 +171,7 @@ pub extern fn vcx_wallet_add_record(command_handle: u32,
                                     value: *const c_char,
                                     tags_json: *const c_char,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(value, error::INVALID_OPTION.code_num);
@@ -190,7 +194,7 @@ pub extern fn vcx_wallet_add_record(command_handle: u32,
                 info!("vcx_wallet_add_record(command_handle: {}, rc: {})",
                       command_handle, error_string(x));
 
-                cb(command_handle, x);
+                cb(command_handle, record_error_code(x));
             },
         }
     });
@@ -222,6 +226,7 @@ pub extern fn vcx_wallet_update_record_value(command_handle: u32,
                                              id: *const c_char,
                                              value: *const c_char,
                                              cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(value, error::INVALID_OPTION.code_num);
@@ -242,7 +247,7 @@ pub extern fn vcx_wallet_update_record_value(command_handle: u32,
                 info!("vcx_wallet_update_record_value(command_handle: {}, rc: {})",
                       command_handle, error_string(x));
 
-                cb(command_handle, x);
+                cb(command_handle, record_error_code(x));
             },
         }
     });
@@ -274,6 +279,7 @@ pub extern fn vcx_wallet_update_record_tags(command_handle: u32,
                                              id: *const c_char,
                                              tags: *const c_char,
                                              cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(tags, error::INVALID_OPTION.code_num);
@@ -308,6 +314,7 @@ pub extern fn vcx_wallet_add_record_tags(command_handle: u32,
                                             id: *const c_char,
                                             tags: *const c_char,
                                             cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(tags, error::INVALID_OPTION.code_num);
@@ -342,6 +349,7 @@ pub extern fn vcx_wallet_delete_record_tags(command_handle: u32,
                                          id: *const c_char,
                                          tags: *const c_char,
                                          cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(tags, error::INVALID_OPTION.code_num);
@@ -375,7 +383,7 @@ pub extern fn vcx_wallet_get_record(command_handle: u32,
                                     id: *const c_char,
                                     options_json: *const c_char,
                                     cb: Option<extern fn(xcommand_handle: u32, err: u32, record_json: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_str!(options_json, error::INVALID_OPTION.code_num);
@@ -399,7 +407,7 @@ pub extern fn vcx_wallet_get_record(command_handle: u32,
                       command_handle, error_string(x), "null");
 
                 let msg = CStringUtils::string_to_cstring("".to_string());
-                cb(command_handle, x, msg.as_ptr());
+                cb(command_handle, record_error_code(x), msg.as_ptr());
             },
         }
     });
@@ -429,6 +437,7 @@ pub extern fn vcx_wallet_delete_record(command_handle: u32,
                                             type_: *const c_char,
                                             id: *const c_char,
                                             cb: Option<extern fn(xcommand_handle: u32, err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_str!(type_, error::INVALID_OPTION.code_num);
     check_useful_c_str!(id, error::INVALID_OPTION.code_num);
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
@@ -448,7 +457,7 @@ pub extern fn vcx_wallet_delete_record(command_handle: u32,
                 info!("vcx_wallet_delete_record(command_handle: {}, rc: {})",
                       command_handle, error_string(x));
 
-                cb(command_handle, x);
+                cb(command_handle, record_error_code(x));
             },
         }
     });
@@ -480,7 +489,7 @@ pub extern fn vcx_wallet_send_tokens(command_handle: u32,
                                      tokens: *const c_char,
                                      recipient: *const c_char,
                                      cb: Option<extern fn(xcommand_handle: u32, err: u32, receipt: *const c_char)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(recipient, error::INVALID_OPTION.code_num);
     check_useful_c_str!(tokens, error::INVALID_OPTION.code_num);
@@ -504,7 +513,7 @@ pub extern fn vcx_wallet_send_tokens(command_handle: u32,
                 let msg = "Failed to send tokens".to_string();
                 info!("vcx_wallet_send_tokens_cb(command_handle: {}, rc: {}, reciept: {})", command_handle, e.to_error_code(), msg);
                 let msg = CStringUtils::string_to_cstring("".to_string());
-                cb(command_handle, e.to_error_code(), msg.as_ptr());
+                cb(command_handle, e.record_error(), msg.as_ptr());
             },
         }
     });
@@ -609,7 +618,7 @@ pub  extern fn vcx_wallet_search_next_records(command_handle: i32,
 pub extern fn vcx_wallet_close_search(command_handle: u32,
                                         search_handle: u32,
                                         cb: Option<extern fn(xcommand_handle: u32, err:u32)>) -> u32 {
-
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     info!("vcx_wallet_close_search(command_handle: {}, search_handle: {})",
           command_handle, search_handle);
@@ -642,6 +651,7 @@ pub extern fn vcx_wallet_export(command_handle: u32,
                                 backup_key: *const c_char,
                                 cb: Option<extern fn(xcommand_handle: u32,
                                                      err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(path,  error::INVALID_OPTION.code_num);
     check_useful_c_str!(backup_key, error::INVALID_OPTION.code_num);
@@ -655,7 +665,7 @@ pub extern fn vcx_wallet_export(command_handle: u32,
                 cb(command_handle, return_code);
             }
             Err(e) => {
-                let return_code = e.to_error_code();
+                let return_code = e.record_error();
                 warn!("vcx_wallet_export(command_handle: {}, rc: {})", command_handle, return_code);
                 cb(command_handle, return_code);
             }
@@ -683,6 +693,7 @@ pub extern fn vcx_wallet_import(command_handle: u32,
                                 config: *const c_char,
                                 cb: Option<extern fn(xcommand_handle: u32,
                                                      err: u32)>) -> u32 {
+    reset_current_error();
     check_useful_c_callback!(cb, error::INVALID_OPTION.code_num);
     check_useful_c_str!(config,  error::INVALID_OPTION.code_num);
 
@@ -695,7 +706,7 @@ pub extern fn vcx_wallet_import(command_handle: u32,
                 cb(command_handle, return_code);
             }
             Err(e) => {
-                let return_code = e.to_error_code();
+                let return_code = e.record_error();
                 warn!("vcx_wallet_import(command_handle: {}, rc: {})", command_handle, return_code);
                 cb(command_handle, return_code);
             }
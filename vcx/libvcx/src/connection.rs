@@ -6,6 +6,7 @@ extern crate libc;
 
 use utils::libindy::wallet;
 use utils::error;
+use utils::error::error_string;
 use utils::libindy::signus::create_and_store_my_did;
 use utils::libindy::crypto;
 use utils::json::mapped_key_rewrite;
@@ -15,6 +16,7 @@ use messages::GeneralMessage;
 use messages;
 use messages::invite::{InviteDetail, SenderDetail};
 use messages::get_message::Message;
+use messages::outbox::Delivery;
 use serde::Deserialize;
 use self::rmp_serde::{encode, Deserializer};
 use messages::MessageResponseCode::{ MessageAccepted };
@@ -23,12 +25,22 @@ use utils::json::KeyMatch;
 use error::connection::ConnectionError;
 use error::ToErrorCode;
 use object_cache::ObjectCache;
-use utils::constants::DEFAULT_SERIALIZE_VERSION;
+use utils::serialization::{self, Versioned, Migration};
+use state_machine::{self, ProtocolEvent, Transition, StateTransition, QueuedEvent, ALL_STATES};
 
 lazy_static! {
     static ref CONNECTION_MAP: ObjectCache<Connection> = Default::default();
 }
 
+static CONNECTION_TRANSITIONS: &[Transition] = &[
+    Transition { event: ProtocolEvent::Initialize, from: &[VcxStateType::VcxStateNone], to: VcxStateType::VcxStateInitialized },
+    Transition { event: ProtocolEvent::ReceiveInvite, from: &[VcxStateType::VcxStateInitialized], to: VcxStateType::VcxStateRequestReceived },
+    Transition { event: ProtocolEvent::SendInvite, from: &[VcxStateType::VcxStateInitialized, VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateOfferSent },
+    Transition { event: ProtocolEvent::AcceptInvite, from: &[VcxStateType::VcxStateRequestReceived], to: VcxStateType::VcxStateAccepted },
+    Transition { event: ProtocolEvent::ReceiveInviteAcceptance, from: &[VcxStateType::VcxStateInitialized, VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateAccepted },
+    Transition { event: ProtocolEvent::Delete, from: ALL_STATES, to: VcxStateType::VcxStateNone },
+];
+
 #[derive(Serialize, Deserialize)]
 struct ConnectionOptions {
     #[serde(default)]
@@ -37,12 +49,32 @@ struct ConnectionOptions {
     phone: Option<String>,
 }
 
+/// Connections moved to agents on another agency by recreate_agents
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct MovedConnections {
+    /// the moved connections serialized again, connections without an agent are returned unchanged
+    pub moved: Vec<String>,
+    pub not_moved: Vec<NotMovedConnection>,
+}
+
+/// A connection recreate_agents could not move, returned unchanged with the reason
+#[derive(Serialize, Debug, PartialEq)]
+pub struct NotMovedConnection {
+    pub source_id: String,
+    pub connection: String,
+    pub error: u32,
+}
+
 #[derive(Serialize, Deserialize)]
 struct Connection {
     source_id: String,
     pw_did: String,
     pw_verkey: String,
     state: VcxStateType,
+    state_history: Vec<StateTransition>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queued_event: Option<QueuedEvent>,
     uuid: String,
     endpoint: String,
     // For QR code invitation
@@ -56,6 +88,7 @@ struct Connection {
 impl Connection {
     fn _connect_send_invite(&mut self, options: Option<String>) -> Result<u32, ConnectionError> {
         debug!("\"_connect_send_invite\" for connection {}", self.source_id);
+        self.check_event(ProtocolEvent::SendInvite)?;
 
         let options_obj: ConnectionOptions = match options {
             Some(opt) => {
@@ -86,27 +119,38 @@ impl Connection {
             .phone_number(&options_obj.phone)
             .agent_did(&self.agent_did)
             .agent_vk(&self.agent_vk)
-            .send_secure() {
+            .send_secure_or_queue() {
             Err(ec) => {
                 // TODO: Refactor Error
                 // TODO: Implement Correct Error
                 return Err(ConnectionError::CommonError(ec))
             },
-            Ok(response) => {
-                self.state = VcxStateType::VcxStateOfferSent;
-                self.invite_detail = match parse_invite_detail(&response[0]) {
-                    Ok(x) => Some(x),
-                    Err(x) => {
-                        error!("error when sending invite: {}", x);
-                        // TODO: Refactor Error
-                        // TODO: Implement Correct Error
-                        return Err(ConnectionError::GeneralConnectionError())
-                    },
-                };
+            Ok(Delivery::Sent(response)) => {
+                self.apply_event(ProtocolEvent::SendInvite)?;
+                self.set_invite_detail_from_response(&response)?;
+                Ok(error::SUCCESS.code_num)
+            },
+            Ok(Delivery::Queued(id)) => {
+                self.queue_event(ProtocolEvent::SendInvite, id)?;
+                debug!("queued invite for connection {}", self.source_id);
                 Ok(error::SUCCESS.code_num)
             }
         }
     }
+
+    fn set_invite_detail_from_response(&mut self, response: &str) -> Result<(), ConnectionError> {
+        self.invite_detail = match parse_invite_detail(response) {
+            Ok(x) => Some(x),
+            Err(x) => {
+                error!("error when sending invite: {}", x);
+                // TODO: Refactor Error
+                // TODO: Implement Correct Error
+                return Err(ConnectionError::GeneralConnectionError())
+            },
+        };
+        Ok(())
+    }
+
     pub fn delete_connection(&mut self) -> Result<u32, ConnectionError> {
         match messages::delete_connection()
             .to(&self.pw_did)
@@ -118,7 +162,7 @@ impl Connection {
                 return Err(ConnectionError::CannotDeleteConnection())
             },
             Ok(response) => {
-                self.state = VcxStateType::VcxStateNone;
+                self.apply_event(ProtocolEvent::Delete)?;
                 Ok(error::SUCCESS.code_num)
             }
         }
@@ -126,55 +170,85 @@ impl Connection {
     }
 
     fn _connect_accept_invite(&mut self, options: Option<String>) -> Result<u32,ConnectionError> {
-        debug!("\"_connect_send_invite\" for connection {}", self.source_id);
+        debug!("\"_connect_accept_invite\" for connection {}", self.source_id);
+        self.check_event(ProtocolEvent::AcceptInvite)?;
 
-        if let Some(ref details) = self.invite_detail {
-            match messages::accept_invite()
-                .to(&self.pw_did)
-                .to_vk(&self.pw_verkey)
-                .agent_did(&self.agent_did)
-                .agent_vk(&self.agent_vk)
-                .sender_details(&details.sender_detail)
-                .sender_agency_details(&details.sender_agency_detail)
-                .answer_status_code("MS-104")
-                .reply_to(&details.conn_req_id)
-                .send_secure() {
-                Err(_) => {
-                    // TODO: Refactor Error
-                    // TODO: Implement Correct Error
-                    Err(ConnectionError::GeneralConnectionError())
-                },
-                Ok(response) => {
-                    self.state = VcxStateType::VcxStateAccepted;
-                    Ok(error::SUCCESS.code_num)
-                }
+        let details = match self.invite_detail {
+            Some(ref details) => details.clone(),
+            None => {
+                warn!("Can not connect without Invite Details");
+                // TODO: Refactor Error
+                // TODO: Implement Correct Error
+                return Err(ConnectionError::GeneralConnectionError())
+            },
+        };
+
+        match messages::accept_invite()
+            .to(&self.pw_did)
+            .to_vk(&self.pw_verkey)
+            .agent_did(&self.agent_did)
+            .agent_vk(&self.agent_vk)
+            .sender_details(&details.sender_detail)
+            .sender_agency_details(&details.sender_agency_detail)
+            .answer_status_code("MS-104")
+            .reply_to(&details.conn_req_id)
+            .send_secure_or_queue() {
+            Err(_) => {
+                // TODO: Refactor Error
+                // TODO: Implement Correct Error
+                Err(ConnectionError::GeneralConnectionError())
+            },
+            Ok(Delivery::Sent(_)) => {
+                self.apply_event(ProtocolEvent::AcceptInvite)?;
+                Ok(error::SUCCESS.code_num)
+            },
+            Ok(Delivery::Queued(id)) => {
+                self.queue_event(ProtocolEvent::AcceptInvite, id)?;
+                debug!("queued invite acceptance for connection {}", self.source_id);
+                Ok(error::SUCCESS.code_num)
             }
         }
-        else{
-            warn!("Can not connect without Invite Details");
-            // TODO: Refactor Error
-            // TODO: Implement Correct Error
-            Err(ConnectionError::GeneralConnectionError())
-        }
     }
 
 
     fn connect(&mut self, options: Option<String>) -> Result<u32,ConnectionError> {
         match self.state {
-            VcxStateType::VcxStateInitialized
-                | VcxStateType::VcxStateOfferSent => self._connect_send_invite(options),
             VcxStateType::VcxStateRequestReceived => self._connect_accept_invite(options),
-            _ => {
-                warn!("connection {} in state {} not ready to connect",self.source_id, self.state as u32);
-                // TODO: Refactor Error
-                // TODO: Implement Correct Error
-                Err(ConnectionError::GeneralConnectionError())
-            }
+            _ => self._connect_send_invite(options),
         }
     }
 
     fn get_state(&self) -> u32 { self.state as u32 }
-    fn set_state(&mut self, state: VcxStateType) { self.state = state; }
+
+    fn check_event(&self, event: ProtocolEvent) -> Result<(), ConnectionError> {
+        state_machine::next_state(CONNECTION_TRANSITIONS, self.state, event)
+            .map(|_| ())
+            .map_err(|ec| ConnectionError::CommonError(ec))
+    }
+
+    fn apply_event(&mut self, event: ProtocolEvent) -> Result<(), ConnectionError> {
+        state_machine::apply_event(CONNECTION_TRANSITIONS, &mut self.state, &mut self.state_history, event)
+            .map_err(|ec| ConnectionError::CommonError(ec))
+    }
+
+    fn queue_event(&mut self, event: ProtocolEvent, outbox_id: String) -> Result<(), ConnectionError> {
+        state_machine::queue_event(CONNECTION_TRANSITIONS, &mut self.state, &mut self.state_history,
+                                   &mut self.queued_event, event, outbox_id)
+            .map_err(|ec| ConnectionError::CommonError(ec))
+    }
+
+    fn complete_queued_event(&mut self) -> Result<(), ConnectionError> {
+        let event = self.queued_event.as_ref().map(|queued| queued.event);
+
+        let delivered = state_machine::poll_queued_event(CONNECTION_TRANSITIONS, &mut self.state,
+                                                         &mut self.state_history, &mut self.queued_event)
+            .map_err(|ec| ConnectionError::CommonError(ec))?;
+
+        match (event, delivered) {
+            (Some(ProtocolEvent::SendInvite), Some(response)) => self.set_invite_detail_from_response(&response),
+            _ => Ok(()),
+        }
+    }
 
     fn get_pw_did(&self) -> &String { &self.pw_did }
     fn set_pw_did(&mut self, did: &str) { self.pw_did = did.to_string(); }
@@ -214,18 +288,20 @@ impl Connection {
     }
 
     fn from_str(s: &str) -> Result<Self, ConnectionError> {
-        let s:Value = serde_json::from_str(&s)
-            .or(Err(ConnectionError::InvalidJson()))?;
-        let connection: Connection = serde_json::from_value(s["data"].clone())
-            .or(Err(ConnectionError::InvalidJson()))?;
-        Ok(connection)
+        serialization::from_versioned_str(s).map_err(|err| {
+            if err == error::INVALID_JSON.code_num { ConnectionError::InvalidJson() } else { ConnectionError::CommonError(err) }
+        })
     }
 
     fn to_string(&self) -> String {
-        json!({
-            "version": DEFAULT_SERIALIZE_VERSION,
-            "data": json!(self),
-        }).to_string()
+        serialization::to_versioned_string(self)
+    }
+}
+
+impl Versioned for Connection {
+    fn migrations() -> Vec<Migration> {
+        vec![serialization::unchanged("1.0", "2.0"),
+             Migration { from: "2.0", to: "3.0", migrate: state_machine::add_empty_state_history }]
     }
 }
 
@@ -348,11 +424,16 @@ pub fn get_state(handle: u32) -> u32 {
     }
 }
 
-pub fn set_state(handle: u32, state: VcxStateType) -> Result<(), ConnectionError> {
+pub fn apply_event(handle: u32, event: ProtocolEvent) -> Result<(), ConnectionError> {
     CONNECTION_MAP.get_mut(handle, |cxn| {
-        cxn.set_state(state);
-        Ok(())
-    }).or(Err(ConnectionError::InvalidHandle()))
+        cxn.apply_event(event).map_err(|e| e.to_error_code())
+    }).map_err(|ec| if ec == error::INVALID_OBJ_HANDLE.code_num { ConnectionError::InvalidHandle() } else { ConnectionError::CommonError(ec) })
+}
+
+pub fn get_state_history(handle: u32) -> Result<String, ConnectionError> {
+    CONNECTION_MAP.get(handle, |cxn| {
+        state_machine::history_to_string(&cxn.state_history)
+    }).map_err(|ec| if ec == error::INVALID_OBJ_HANDLE.code_num { ConnectionError::InvalidHandle() } else { ConnectionError::CommonError(ec) })
 }
 
 pub fn get_source_id(handle: u32) -> Result<String, ConnectionError> {
@@ -392,6 +473,48 @@ pub fn update_agent_profile(handle: u32) -> Result<u32, ConnectionError> {
     } else { Ok(error::SUCCESS.code_num) }
 }
 
+// Used after re-provisioning with a new agency: the pairwise keys stay the same but the
+// agent on the new agency has to be created and configured again
+fn recreate_agent_pairwise(handle: u32) -> Result<u32, ConnectionError> {
+    debug!("recreating pairwise agent for connection handle {}", handle);
+    create_agent_pairwise(handle)?;
+    update_agent_profile(handle)
+}
+
+fn recreate_agent(connection: &str) -> Result<String, (String, u32)> {
+    let handle = from_string(connection).map_err(|err| (String::new(), err.to_error_code()))?;
+    let source_id = get_source_id(handle).unwrap_or_default();
+
+    // connections that never got an agent have nothing to move
+    let result = if get_agent_did(handle).map(|did| did.is_empty()).unwrap_or(true) {
+        Ok(connection.to_string())
+    } else {
+        recreate_agent_pairwise(handle)
+            .map_err(|err| err.to_error_code())
+            .and_then(|_| to_string(handle))
+    };
+
+    release(handle).ok();
+    result.map_err(|ec| (source_id, ec))
+}
+
+/// Moves the serialized connections to agents on the currently configured agency. The
+/// counterparty of an accepted connection is not told about the new agent, it keeps sending to
+/// the agent on the previous agency until the connection is established again.
+pub fn recreate_agents(connections: &[String]) -> MovedConnections {
+    let mut rtn = MovedConnections::default();
+    for connection in connections {
+        match recreate_agent(connection) {
+            Ok(moved) => rtn.moved.push(moved),
+            Err((source_id, error)) => {
+                warn!("could not move connection {} to the new agency: {}", source_id, error_string(error));
+                rtn.not_moved.push(NotMovedConnection { source_id, connection: connection.to_string(), error });
+            },
+        }
+    }
+    rtn
+}
+
 //
 // NOTE: build_connection and create_connection are broken up to make it easier to create connections in tests
 //       you can call create_connection without test_mode and you don't have to build a wallet or
@@ -405,6 +528,8 @@ fn create_connection(source_id: &str) -> Result<u32, ConnectionError> {
         pw_did: String::new(),
         pw_verkey: String::new(),
         state: VcxStateType::VcxStateNone,
+        state_history: Vec::new(),
+        queued_event: None,
         uuid: String::new(),
         endpoint: String::new(),
         invite_detail: None,
@@ -451,7 +576,7 @@ fn init_connection(handle: u32) -> Result<u32, ConnectionError> {
         Ok(_) => debug!("updated profile on agent"),
     };
 
-    set_state(handle, VcxStateType::VcxStateInitialized).err();
+    apply_event(handle, ProtocolEvent::Initialize)?;
 
     Ok(error::SUCCESS.code_num)
 }
@@ -502,7 +627,7 @@ pub fn build_connection_with_invite(source_id: &str, details: &str) -> Result<u3
 
     set_invite_details(new_handle, invite_details).err();
 
-    set_state(new_handle, VcxStateType::VcxStateRequestReceived).err();
+    apply_event(new_handle, ProtocolEvent::ReceiveInvite)?;
 
     Ok(new_handle)
 }
@@ -538,6 +663,13 @@ pub fn parse_acceptance_details(handle: u32, message: &Message) -> Result<Sender
 
 pub fn update_state(handle: u32) -> Result<u32, ConnectionError> {
     debug!("updating state for connection handle {}", handle);
+    CONNECTION_MAP.get_mut(handle, |cxn| {
+        cxn.complete_queued_event().map_err(|e| e.to_error_code())
+    }).map_err(|ec| if ec == error::INVALID_OBJ_HANDLE.code_num { ConnectionError::InvalidHandle() } else { ConnectionError::CommonError(ec) })?;
+    if get_state(handle) == VcxStateType::VcxStateQueued as u32 {
+        return Ok(error::SUCCESS.code_num);
+    }
+
     // TODO: Refactor Error
     let pw_did = get_pw_did(handle)?;
     let pw_vk = get_pw_verkey(handle)?;
@@ -566,7 +698,8 @@ pub fn update_state(handle: u32) -> Result<u32, ConnectionError> {
                           let details = parse_acceptance_details(handle, &i)?;
                           set_their_pw_did(handle, &details.did).ok();
                           set_their_pw_verkey(handle, &details.verkey).ok();
-                          set_state(handle, VcxStateType::VcxStateAccepted).ok();
+                          apply_event(handle, ProtocolEvent::ReceiveInviteAcceptance)?;
+                          break;
                      }
                  }
             };
@@ -576,6 +709,30 @@ pub fn update_state(handle: u32) -> Result<u32, ConnectionError> {
         },
     }
 }
+/// Handles of connections waiting for their invite to be accepted, with their pairwise did.
+/// The answer is matched by type, there is no message uid it answers.
+pub fn get_waiting_handles() -> Vec<(u32, String, Option<String>)> {
+    CONNECTION_MAP.handles().into_iter().filter_map(|handle| {
+        CONNECTION_MAP.get(handle, |cxn| {
+            Ok(if cxn.get_state() == VcxStateType::VcxStateOfferSent as u32 { Some(cxn.get_pw_did().clone()) } else { None })
+        }).ok().and_then(|did| did.map(|did| (handle, did, None)))
+    }).collect()
+}
+
+/// The invite answer is encrypted for the sdk verkey rather than the pairwise one, so
+/// download_messages cannot decrypt it. When one has arrived it is fetched with update_state.
+pub fn update_state_with_messages(handle: u32, messages: &[Message]) -> Result<u32, ConnectionError> {
+    if get_state(handle) != VcxStateType::VcxStateOfferSent as u32 {
+        return Ok(get_state(handle));
+    }
+
+    if messages.iter().any(|msg| msg.status_code == MessageAccepted.as_string() && msg.msg_type == "connReqAnswer") {
+        update_state(handle)?;
+    }
+
+    Ok(get_state(handle))
+}
+
 pub fn delete_connection(handle:u32) -> Result<u32, ConnectionError> {
     CONNECTION_MAP.get_mut(handle, |t| {
         match t.delete_connection() {
@@ -587,6 +744,18 @@ pub fn delete_connection(handle:u32) -> Result<u32, ConnectionError> {
     }).or(Err(ConnectionError::CannotDeleteConnection())).and(release(handle))
 }
 
+/// Deletes the agent of a pairwise key that is not kept as a connection, like the key an
+/// out-of-band message is answered to.
+pub fn delete_pairwise_agent(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str) -> Result<(), u32> {
+    messages::delete_connection()
+        .to(pw_did)
+        .to_vk(pw_vk)
+        .agent_did(agent_did)
+        .agent_vk(agent_vk)
+        .send_secure()
+        .map(|_| ())
+}
+
 pub fn connect(handle: u32, options: Option<String>) -> Result<u32, ConnectionError> {
     CONNECTION_MAP.get_mut(handle, |t| {
         t.connect(options.clone()).map_err(|ec| ec.to_error_code())
@@ -815,12 +984,40 @@ pub mod tests {
         assert!(!get_pw_verkey(handle).unwrap().is_empty());
         assert_eq!(get_state(handle), VcxStateType::VcxStateInitialized as u32);
         connect(handle, Some("{}".to_string())).unwrap();
+        let history: Vec<StateTransition> = serde_json::from_str(&get_state_history(handle).unwrap()).unwrap();
+        assert_eq!(history.iter().map(|t| t.event).collect::<Vec<ProtocolEvent>>(),
+                   vec![ProtocolEvent::Initialize, ProtocolEvent::SendInvite]);
         assert_eq!(delete_connection(handle).unwrap(), 0);
         // This errors b/c we release handle in delete connection
         assert!(release(handle).is_err());
 
     }
 
+    #[test]
+    fn test_recreate_agents() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+        let handle = build_connection("test_recreate_agents").unwrap();
+        let connection = to_string(handle).unwrap();
+        release(handle).unwrap();
+        let no_agent = create_connection("test_recreate_agents_no_agent").unwrap();
+        let no_agent_connection = to_string(no_agent).unwrap();
+        release(no_agent).unwrap();
+
+        let result = recreate_agents(&[connection, no_agent_connection.clone(), "garbage".to_string()]);
+        assert_eq!(result.moved.len(), 2);
+        let moved = from_string(&result.moved[0]).unwrap();
+        assert_eq!(get_source_id(moved).unwrap(), "test_recreate_agents");
+        assert!(!get_agent_did(moved).unwrap().is_empty());
+        assert_eq!(result.moved[1], no_agent_connection);
+        assert_eq!(result.not_moved, vec![NotMovedConnection {
+            source_id: String::new(),
+            connection: "garbage".to_string(),
+            error: error::INVALID_JSON.code_num,
+        }]);
+        release(moved).unwrap();
+    }
+
     #[test]
     fn test_create_drop_create() {
         settings::set_defaults();
@@ -873,6 +1070,8 @@ pub mod tests {
             pw_did: "8XFh8yBzrpJQmNyZzgoTqB".to_string(),
             pw_verkey: "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A".to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             uuid: String::new(),
             endpoint: String::new(),
             invite_detail: Some(InviteDetail::new()),
@@ -965,6 +1164,8 @@ pub mod tests {
             pw_did: "8XFh8yBzrpJQmNyZzgoTqB".to_string(),
             pw_verkey: "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A".to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             uuid: String::new(),
             endpoint: String::new(),
             invite_detail: None,
@@ -1121,6 +1322,8 @@ pub mod tests {
             pw_did: "8XFh8yBzrpJQmNyZzgoTqB".to_string(),
             pw_verkey: "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A".to_string(),
             state: VcxStateType::VcxStateRequestReceived,
+            state_history: Vec::new(),
+            queued_event: None,
             uuid: String::new(),
             endpoint: String::new(),
             invite_detail: None,
@@ -1146,7 +1349,7 @@ pub mod tests {
     #[test]
     fn test_void_functions_actually_have_results(){
         assert_eq!(set_their_pw_verkey(1, "blah").err(), Some(ConnectionError::InvalidHandle()));
-        assert_eq!(set_state(1, VcxStateType::VcxStateNone).err(), Some(ConnectionError::InvalidHandle()));
+        assert_eq!(apply_event(1, ProtocolEvent::Delete).err(), Some(ConnectionError::InvalidHandle()));
         assert_eq!(set_pw_did(1, "blah").err(), Some(ConnectionError::InvalidHandle()));
         assert_eq!(set_their_pw_did(1,"blah").err(), Some(ConnectionError::InvalidHandle()));
         assert_eq!(set_uuid(1,"blah").err(), Some(ConnectionError::InvalidHandle()));
@@ -1156,4 +1359,12 @@ pub mod tests {
         assert_eq!(set_invite_details(1, details).err(), Some(ConnectionError::InvalidHandle()));
         assert_eq!(set_pw_verkey(1, "blah").err(), Some(ConnectionError::InvalidHandle()));
     }
+
+    #[test]
+    fn test_deserialize_historical_version() {
+        let obj = Connection::from_str(DEFAULT_CONNECTION).unwrap();
+        let serialized = obj.to_string();
+        assert_eq!(serialization::parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(Connection::from_str(&serialized).unwrap().to_string(), serialized);
+    }
 }
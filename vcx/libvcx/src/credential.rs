@@ -9,13 +9,16 @@ use api::VcxStateType;
 use utils::error;
 use issuer_credential::{ CredentialOffer, CredentialMessage, PaymentInfo };
 
-use credential_request::{ CredentialRequest };
+use credential_request::{ CredentialRequest, OutOfBandCredentialRequest };
 
 use messages;
 use messages::to_u8;
 use messages::GeneralMessage;
 use messages::send_message::parse_msg_uid;
 use messages::extract_json_payload;
+use messages::get_message::Message;
+use messages::outbox::Delivery;
+use messages::out_of_band::{self, OutOfBandMessage, Service};
 
 use utils::libindy::anoncreds::{libindy_prover_create_credential_req, libindy_prover_store_credential};
 use utils::libindy::crypto;
@@ -23,9 +26,12 @@ use utils::libindy::payments::{pay_a_payee, PaymentTxn};
 
 use credential_def::retrieve_credential_def;
 use connection;
+use link_secret;
+use credential_selection;
 
 use settings;
-use utils::constants::DEFAULT_SERIALIZE_VERSION;
+use utils::serialization::{self, Versioned, Migration};
+use state_machine::{self, ProtocolEvent, Transition, StateTransition, QueuedEvent};
 
 use error::{ToErrorCode, credential::CredentialError};
 use serde_json::Value;
@@ -35,12 +41,21 @@ lazy_static! {
     static ref HANDLE_MAP: ObjectCache<Credential>  = Default::default();
 }
 
+static CREDENTIAL_TRANSITIONS: &[Transition] = &[
+    Transition { event: ProtocolEvent::Initialize, from: &[VcxStateType::VcxStateNone], to: VcxStateType::VcxStateInitialized },
+    Transition { event: ProtocolEvent::ReceiveOffer, from: &[VcxStateType::VcxStateInitialized], to: VcxStateType::VcxStateRequestReceived },
+    Transition { event: ProtocolEvent::SendRequest, from: &[VcxStateType::VcxStateRequestReceived], to: VcxStateType::VcxStateOfferSent },
+    Transition { event: ProtocolEvent::ReceiveCredential, from: &[VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateAccepted },
+];
+
 impl Default for Credential {
     fn default() -> Credential
     {
         Credential {
             source_id: String::new(),
             state: VcxStateType::VcxStateNone,
+            state_history: Vec::new(),
+            queued_event: None,
             credential_name: None,
             credential_request: None,
             agent_did: None,
@@ -55,6 +70,8 @@ impl Default for Credential {
             credential: None,
             payment_info: None,
             payment_txn: None,
+            service: None,
+            link_secret_alias: settings::DEFAULT_LINK_SECRET_ALIAS.to_string(),
         }
     }
 }
@@ -64,6 +81,10 @@ impl Default for Credential {
 pub struct Credential {
     source_id: String,
     state: VcxStateType,
+    state_history: Vec<StateTransition>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queued_event: Option<QueuedEvent>,
     credential_name: Option<String>,
     credential_request: Option<CredentialRequest>,
     credential_offer: Option<CredentialOffer>,
@@ -79,6 +100,13 @@ pub struct Credential {
     cred_id: Option<String>,
     payment_info: Option<PaymentInfo>,
     payment_txn: Option<PaymentTxn>,
+    // reply-to of an offer received out of band, the request is sent there instead of to a connection
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service: Option<Service>,
+    // link secret the credential request is created with, the stored credential is bound to it
+    #[serde(default = "link_secret::default_alias")]
+    link_secret_alias: String,
 }
 
 impl Credential {
@@ -99,7 +127,8 @@ impl Credential {
 
         let (req, req_meta) = libindy_prover_create_credential_req(&prover_did,
                                                                    &credential_offer.libindy_offer,
-                                                                   &cred_def_json)
+                                                                   &cred_def_json,
+                                                                   &self.link_secret_alias)
             .map_err(|ec| CredentialError::CommonError(ec))?;
 
         Ok(CredentialRequest {
@@ -116,6 +145,7 @@ impl Credential {
 
     fn send_request(&mut self, connection_handle: u32) -> Result<u32, CredentialError> {
         debug!("sending credential request via connection: {}", connection_handle);
+        self.check_event(ProtocolEvent::SendRequest)?;
         self.my_did = Some(connection::get_pw_did(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
         self.my_vk = Some(connection::get_pw_verkey(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
         self.agent_did = Some(connection::get_agent_did(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
@@ -156,10 +186,15 @@ impl Credential {
             .agent_vk(local_agent_vk)
             .edge_agent_payload(&data)
             .ref_msg_id(offer_msg_id)
-            .send_secure() {
-            Ok(response) => {
-                self.msg_uid = Some(parse_msg_uid(&response[0]).map_err(|ec| CredentialError::CommonError(ec))?);
-                self.state = VcxStateType::VcxStateOfferSent;
+            .send_secure_or_queue() {
+            Ok(Delivery::Sent(response)) => {
+                self.msg_uid = Some(parse_msg_uid(&response).map_err(|ec| CredentialError::CommonError(ec))?);
+                self.apply_event(ProtocolEvent::SendRequest)?;
+                return Ok(error::SUCCESS.code_num)
+            },
+            Ok(Delivery::Queued(id)) => {
+                self.queue_event(ProtocolEvent::SendRequest, id)?;
+                debug!("queued credential request for: {}", self.source_id);
                 return Ok(error::SUCCESS.code_num)
             },
             Err(x) => {
@@ -169,19 +204,105 @@ impl Credential {
         }
     }
 
+    // The credential comes back to a pairwise key on our agency that only the issuer of this
+    // offer learns about, no connection is kept once the credential is stored.
+    fn send_request_to_service(&mut self) -> Result<u32, CredentialError> {
+        debug!("sending credential request {} to the issuer service", self.source_id);
+        self.check_event(ProtocolEvent::SendRequest)?;
+        let service = self.service.clone().ok_or(CredentialError::CommonError(error::INVALID_CONNECTION_HANDLE.code_num))?;
+
+        let connection_handle = connection::build_connection(&self.source_id).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?;
+        self.my_did = Some(connection::get_pw_did(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
+        self.my_vk = Some(connection::get_pw_verkey(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
+        self.agent_did = Some(connection::get_agent_did(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
+        self.agent_vk = Some(connection::get_agent_verkey(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
+        connection::release(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?;
+        self.their_did = Some(service.recipient_did.clone());
+        self.their_vk = Some(service.recipient_key.clone());
+
+        if let Err(err) = self.reply_with_request(&service) {
+            self.delete_out_of_band_agent();
+            return Err(err);
+        }
+        self.apply_event(ProtocolEvent::SendRequest)?;
+        Ok(error::SUCCESS.code_num)
+    }
+
+    fn reply_with_request(&mut self, service: &Service) -> Result<(), CredentialError> {
+        let local_my_did = self.my_did.clone().ok_or(CredentialError::InvalidHandle())?;
+        let local_my_vk = self.my_vk.clone().ok_or(CredentialError::InvalidHandle())?;
+
+        let req: CredentialRequest = self.build_request(&local_my_did, &service.recipient_did)?;
+        self.credential_request = Some(req.clone());
+        let local_agent_did = self.agent_did.clone().ok_or(CredentialError::InvalidHandle())?;
+        let local_agent_vk = self.agent_vk.clone().ok_or(CredentialError::InvalidHandle())?;
+        let reply_to = Service::for_pairwise(&local_my_did, &local_my_vk, &local_agent_did, &local_agent_vk).map_err(|ec| CredentialError::CommonError(ec))?;
+        let req = serde_json::to_string(&OutOfBandCredentialRequest { credential_request: req, service: reply_to })
+            .or(Err(CredentialError::InvalidCredentialJson()))?;
+
+        if self.payment_info.is_some() {
+            let (payment_txn, _) = self.submit_payment()?;
+            self.payment_txn = Some(payment_txn);
+        }
+
+        out_of_band::reply(service, "CRED_REQ", &req).map_err(|ec| CredentialError::CommonError(ec))
+    }
+
+    // The agent of a request sent to a service only ever receives the one credential
+    fn delete_out_of_band_agent(&self) {
+        let (my_did, my_vk, agent_did, agent_vk) = match (&self.my_did, &self.my_vk, &self.agent_did, &self.agent_vk) {
+            (&Some(ref my_did), &Some(ref my_vk), &Some(ref agent_did), &Some(ref agent_vk)) => (my_did, my_vk, agent_did, agent_vk),
+            _ => return,
+        };
+        if let Err(ec) = connection::delete_pairwise_agent(my_did, my_vk, agent_did, agent_vk) {
+            warn!("could not delete the agent of credential {}: {}", self.source_id, error::error_string(ec));
+        }
+    }
+
+    fn _check_out_of_band_msg(&mut self) -> Result<(), u32> {
+        let e_code: u32 = error::INVALID_CONNECTION_HANDLE.code_num;
+
+        let agent_did = self.agent_did.clone().ok_or(e_code)?;
+        let agent_vk = self.agent_vk.clone().ok_or(e_code)?;
+        let my_did = self.my_did.clone().ok_or(e_code)?;
+        let my_vk = self.my_vk.clone().ok_or(e_code)?;
+
+        let payload = messages::get_messages()
+            .to(&my_did)
+            .to_vk(&my_vk)
+            .agent_did(&agent_did)
+            .agent_vk(&agent_vk)
+            .send_secure()?;
+
+        for msg in payload {
+            if let Some(ref data) = msg.payload {
+                match out_of_band::parse_reply(&my_vk, &to_u8(data)) {
+                    Ok((ref msg_type, ref credential)) if msg_type == "CRED" => {
+                        self.msg_uid = Some(msg.uid.clone());
+                        self.store_credential(credential.clone())?;
+                        self.delete_out_of_band_agent();
+                        return Ok(());
+                    },
+                    _ => debug!("message {} is not a credential", msg.uid),
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn _check_msg(&mut self) -> Result<(), u32> {
         let e_code: u32 = error::INVALID_CONNECTION_HANDLE.code_num;
 
-        let agent_did = self.agent_did.as_ref().ok_or(e_code)?;
-        let agent_vk = self.agent_vk.as_ref().ok_or(e_code)?;
-        let my_did = self.my_did.as_ref().ok_or(e_code)?;
-        let my_vk = self.my_vk.as_ref().ok_or(e_code)?;
-        let msg_uid = self.msg_uid.as_ref().ok_or(e_code)?;
+        let agent_did = self.agent_did.clone().ok_or(e_code)?;
+        let agent_vk = self.agent_vk.clone().ok_or(e_code)?;
+        let my_did = self.my_did.clone().ok_or(e_code)?;
+        let my_vk = self.my_vk.clone().ok_or(e_code)?;
+        self.msg_uid.as_ref().ok_or(e_code)?;
 
-        let payload = messages::get_message::get_connection_messages(my_did,
-                                                                     my_vk,
-                                                                     agent_did,
-                                                                     agent_vk,
+        let payload = messages::get_message::get_connection_messages(&my_did,
+                                                                     &my_vk,
+                                                                     &agent_did,
+                                                                     &agent_vk,
                                                                      None)?;
 
         for msg in payload {
@@ -192,23 +313,7 @@ impl Credential {
                         let (_, data) = crypto::parse_msg(&my_vk, data.as_slice())?;
 
                         let credential = extract_json_payload(&data)?;
-
-                        let credential_msg: CredentialMessage = serde_json::from_str(&credential)
-                            .or(Err(error::INVALID_CREDENTIAL_JSON.code_num)).unwrap();
-
-                        let cred_req: &CredentialRequest = self.credential_request.as_ref()
-                            .ok_or(CredentialError::InvalidCredentialJson().to_error_code())?;
-
-                        let (_, cred_def_json) = ::credential_def::retrieve_credential_def(&cred_req.cred_def_id)
-                            .map_err(|err| CredentialError::CommonError(err.to_error_code()).to_error_code())?;
-
-                        self.credential = Some(credential);
-                        self.cred_id = Some(libindy_prover_store_credential(None,
-                                                                      &cred_req.libindy_cred_req_meta,
-                                                                      &credential_msg.libindy_cred,
-                                                                      &cred_def_json,
-                                                                      None)?);
-                        self.state = VcxStateType::VcxStateAccepted;
+                        self.store_credential(credential)?;
                     },
                     None => return Err(error::INVALID_HTTP_RESPONSE.code_num)
                 };
@@ -217,8 +322,83 @@ impl Credential {
         Ok(())
     }
 
+    fn update_state_with_messages(&mut self, messages: &[Message]) -> Result<(), u32> {
+        if self.state != VcxStateType::VcxStateOfferSent {
+            return Ok(());
+        }
+
+        match messages.iter().filter(|msg| msg.msg_type.eq("cred")).filter_map(messages::get_message::decrypted_msg).next() {
+            Some(credential) => self.store_credential(credential),
+            None => Ok(()),
+        }
+    }
+
+    fn store_credential(&mut self, credential: String) -> Result<(), u32> {
+        let credential_msg: CredentialMessage = serde_json::from_str(&credential)
+            .or(Err(error::INVALID_CREDENTIAL_JSON.code_num))?;
+
+        let cred_req: &CredentialRequest = self.credential_request.as_ref()
+            .ok_or(CredentialError::InvalidCredentialJson().to_error_code())?;
+
+        let (_, cred_def_json) = ::credential_def::retrieve_credential_def(&cred_req.cred_def_id)
+            .map_err(|err| CredentialError::CommonError(err.to_error_code()).to_error_code())?;
+
+        let cred_id = libindy_prover_store_credential(None,
+                                                      &cred_req.libindy_cred_req_meta,
+                                                      &credential_msg.libindy_cred,
+                                                      &cred_def_json,
+                                                      None)?;
+        link_secret::bind_credential(&cred_id, &self.link_secret_alias)?;
+        credential_selection::record_stored(&cred_id)?;
+
+        self.credential = Some(credential);
+        self.cred_id = Some(cred_id);
+        self.apply_event(ProtocolEvent::ReceiveCredential).map_err(|e| e.to_error_code())
+    }
+
+    fn set_link_secret(&mut self, alias: &str) -> Result<(), CredentialError> {
+        // the request binds the credential to the link secret, it cannot change afterwards
+        self.check_event(ProtocolEvent::SendRequest)?;
+        if !link_secret::exists(alias).map_err(|ec| CredentialError::CommonError(ec))? {
+            return Err(CredentialError::CommonError(error::LINK_SECRET_NOT_FOUND.code_num));
+        }
+
+        self.link_secret_alias = alias.to_string();
+        Ok(())
+    }
+
+    fn check_event(&self, event: ProtocolEvent) -> Result<(), CredentialError> {
+        state_machine::next_state(CREDENTIAL_TRANSITIONS, self.state, event)
+            .map(|_| ())
+            .map_err(|ec| CredentialError::CommonError(ec))
+    }
+
+    fn apply_event(&mut self, event: ProtocolEvent) -> Result<(), CredentialError> {
+        state_machine::apply_event(CREDENTIAL_TRANSITIONS, &mut self.state, &mut self.state_history, event)
+            .map_err(|ec| CredentialError::CommonError(ec))
+    }
+
+    fn queue_event(&mut self, event: ProtocolEvent, outbox_id: String) -> Result<(), CredentialError> {
+        state_machine::queue_event(CREDENTIAL_TRANSITIONS, &mut self.state, &mut self.state_history,
+                                   &mut self.queued_event, event, outbox_id)
+            .map_err(|ec| CredentialError::CommonError(ec))
+    }
+
     fn update_state(&mut self) {
+        match state_machine::poll_queued_event(CREDENTIAL_TRANSITIONS, &mut self.state,
+                                               &mut self.state_history, &mut self.queued_event) {
+            Ok(Some(response)) => {
+                self.msg_uid = parse_msg_uid(&response).ok();
+                debug!("queued credential request {} was delivered", self.source_id);
+            },
+            Ok(None) => (),
+            Err(x) => warn!("could not complete queued message of credential {}: {}", self.source_id, x),
+        }
+
         match self.state {
+            VcxStateType::VcxStateOfferSent if self.service.is_some() => {
+                let _ = self._check_out_of_band_msg();
+            },
             VcxStateType::VcxStateOfferSent => {
                 //Check for messages
                 let _ = self._check_msg();
@@ -323,18 +503,20 @@ impl Credential {
     }
 
     fn to_string(&self) -> String {
-        json!({
-            "version": DEFAULT_SERIALIZE_VERSION,
-            "data": json!(self),
-        }).to_string()
+        serialization::to_versioned_string(self)
     }
 
     fn from_str(s: &str) -> Result<Credential, CredentialError> {
-        let s:Value = serde_json::from_str(&s)
-            .or(Err(CredentialError::InvalidCredentialJson()))?;
-        let obj: Credential = serde_json::from_value(s["data"].clone())
-            .or(Err(CredentialError::InvalidCredentialJson()))?;
-        Ok(obj)
+        serialization::from_versioned_str(s).map_err(|err| {
+            if err == error::INVALID_JSON.code_num { CredentialError::InvalidCredentialJson() } else { CredentialError::CommonError(err) }
+        })
+    }
+}
+
+impl Versioned for Credential {
+    fn migrations() -> Vec<Migration> {
+        vec![serialization::unchanged("1.0", "2.0"),
+             Migration { from: "2.0", to: "3.0", migrate: state_machine::add_empty_state_history }]
     }
 }
 
@@ -351,26 +533,37 @@ fn handle_err(code_num: u32) -> CredentialError {
 }
 
 pub fn credential_create_with_offer(source_id: &str, offer: &str) -> Result<u32, CredentialError> {
-    let mut new_credential = _credential_create(source_id);
+    let mut new_credential = _credential_create(source_id)?;
+
+    // an out of band offer is a signed object, an offer received over a connection is an array
+    let offer = match serde_json::from_str::<Value>(offer) {
+        Ok(ref value) if value.is_object() => {
+            let message = OutOfBandMessage::from_str(offer, "CRED_OFFER").map_err(|ec| CredentialError::CommonError(ec))?;
+            new_credential.service = Some(message.service);
+            message.msg
+        },
+        _ => offer.to_string(),
+    };
 
-    let (offer, payment_info) = parse_json_offer(offer)?;
+    let (offer, payment_info) = parse_json_offer(&offer)?;
     new_credential.set_credential_offer(offer);
     new_credential.payment_info = payment_info;
 
-    new_credential.state = VcxStateType::VcxStateRequestReceived;
+    new_credential.apply_event(ProtocolEvent::ReceiveOffer)?;
 
     debug!("inserting credential into handle map");
     Ok(HANDLE_MAP.add(new_credential).map_err(|ec|CredentialError::CommonError(ec))?)
 }
 
-fn _credential_create(source_id: &str) -> Credential {
+fn _credential_create(source_id: &str) -> Result<Credential, CredentialError> {
 
     let mut new_credential: Credential = Default::default();
 
-    new_credential.state = VcxStateType::VcxStateInitialized;
+    new_credential.apply_event(ProtocolEvent::Initialize)?;
     new_credential.set_source_id(source_id);
+    new_credential.link_secret_alias = link_secret::selected();
 
-    new_credential
+    Ok(new_credential)
 }
 
 pub fn update_state(handle: u32) -> Result<u32, u32> {
@@ -381,6 +574,23 @@ pub fn update_state(handle: u32) -> Result<u32, u32> {
 
 }
 
+/// Handles of credentials waiting for the issuer to send the credential, with their pairwise did.
+/// The credential is matched by type, there is no message uid it answers.
+pub fn get_waiting_handles() -> Vec<(u32, String, Option<String>)> {
+    HANDLE_MAP.handles().into_iter().filter_map(|handle| {
+        HANDLE_MAP.get(handle, |obj| {
+            Ok(if obj.state == VcxStateType::VcxStateOfferSent { obj.my_did.clone() } else { None })
+        }).ok().and_then(|did| did.map(|did| (handle, did, None)))
+    }).collect()
+}
+
+pub fn update_state_with_messages(handle: u32, messages: &[Message]) -> Result<u32, CredentialError> {
+    HANDLE_MAP.get_mut(handle, |obj| {
+        obj.update_state_with_messages(messages)?;
+        Ok(obj.get_state())
+    }).map_err(handle_err)
+}
+
 pub fn get_credential(handle: u32) -> Result<String, CredentialError> {
     HANDLE_MAP.get(handle, |obj| {
         obj.get_credential().map_err(|e| e.to_error_code())
@@ -411,12 +621,30 @@ pub fn get_state(handle: u32) -> Result<u32, CredentialError> {
     }).map_err(handle_err)
 }
 
+pub fn get_state_history(handle: u32) -> Result<String, CredentialError> {
+    HANDLE_MAP.get(handle, |obj| {
+        state_machine::history_to_string(&obj.state_history)
+    }).map_err(handle_err)
+}
+
 pub fn send_credential_request(handle: u32, connection_handle: u32) -> Result<u32, CredentialError> {
     HANDLE_MAP.get_mut(handle, |obj| {
         obj.send_request(connection_handle).map_err(|e| e.to_error_code())
     }).map_err(handle_err)
 }
 
+pub fn set_link_secret(handle: u32, alias: &str) -> Result<(), CredentialError> {
+    HANDLE_MAP.get_mut(handle, |obj| {
+        obj.set_link_secret(alias).map_err(|e| e.to_error_code())
+    }).map_err(handle_err)
+}
+
+pub fn send_credential_request_to_service(handle: u32) -> Result<u32, CredentialError> {
+    HANDLE_MAP.get_mut(handle, |obj| {
+        obj.send_request_to_service().map_err(|e| e.to_error_code())
+    }).map_err(handle_err)
+}
+
 pub fn get_credential_offer_msg(connection_handle: u32, msg_id: &str) -> Result<String, CredentialError> {
     let my_did = connection::get_pw_did(connection_handle).map_err(|e| CredentialError::CommonError(e.to_error_code()))?;
     let my_vk = connection::get_pw_verkey(connection_handle).map_err(|e| CredentialError::CommonError(e.to_error_code()))?;
@@ -758,4 +986,62 @@ pub mod tests {
         let new_balance = get_wallet_token_info().unwrap().get_balance();
         assert_eq!(new_balance, balance);
     }
+
+    #[test]
+    fn test_out_of_band_credential_cycle() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let issuer_h = ::issuer_credential::issuer_credential_create(::utils::constants::CRED_DEF_ID.to_string(),
+                                                                     "1".to_string(),
+                                                                     settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap(),
+                                                                     "credential_name".to_string(),
+                                                                     "{\"attr\":\"value\"}".to_owned(),
+                                                                     0).unwrap();
+        let offer = ::issuer_credential::generate_out_of_band_offer(issuer_h).unwrap();
+
+        let forged = offer.replace("credential_name", "forged_name");
+        assert_ne!(forged, offer);
+        assert_eq!(credential_create_with_offer("test_out_of_band_credential_cycle", &forged).err(),
+                   Some(CredentialError::CommonError(error::INVALID_OUT_OF_BAND_MESSAGE.code_num)));
+
+        let handle = credential_create_with_offer("test_out_of_band_credential_cycle", &offer).unwrap();
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateRequestReceived as u32);
+        send_credential_request_to_service(handle).unwrap();
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(update_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+
+        let handle = credential_create_with_offer("test_out_of_band_credential_cycle", ::utils::constants::CREDENTIAL_OFFER_JSON).unwrap();
+        // an offer received over a connection has no service to reply to
+        assert_eq!(send_credential_request_to_service(handle).err(),
+                   Some(CredentialError::CommonError(error::INVALID_CONNECTION_HANDLE.code_num)));
+    }
+
+    #[test]
+    fn test_credential_link_secret() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        ::link_secret::create("test_credential_link_secret").unwrap_or(());
+
+        let handle = credential_create_with_offer("test_credential_link_secret", ::utils::constants::CREDENTIAL_OFFER_JSON).unwrap();
+        assert_eq!(set_link_secret(handle, "unknown").err(), Some(CredentialError::CommonError(error::LINK_SECRET_NOT_FOUND.code_num)));
+        set_link_secret(handle, "test_credential_link_secret").unwrap();
+
+        let mut credential = Credential::from_str(&to_string(handle).unwrap()).unwrap();
+        credential.my_did = Some(settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap());
+        credential.credential_request = Some(credential.build_request("test1", "test2").unwrap());
+        credential.state = VcxStateType::VcxStateOfferSent;
+        credential.store_credential(::utils::constants::CRED_MSG.to_string()).unwrap();
+        assert_eq!(::link_secret::credential_link_secret("cred_id"), Some("test_credential_link_secret".to_string()));
+        assert_eq!(credential.set_link_secret("main").err(), Some(CredentialError::CommonError(error::INVALID_STATE.code_num)));
+    }
+
+    #[test]
+    fn test_deserialize_historical_version() {
+        use utils::constants::DEFAULT_SERIALIZE_VERSION;
+        let obj = Credential::from_str(DEFAULT_SERIALIZED_CREDENTIAL).unwrap();
+        let serialized = obj.to_string();
+        assert_eq!(serialization::parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(Credential::from_str(&serialized).unwrap().to_string(), serialized);
+    }
 }
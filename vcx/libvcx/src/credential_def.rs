@@ -3,18 +3,24 @@ extern crate rand;
 extern crate libc;
 
 use utils::error;
+use utils::ledger_cache;
+use utils::author_agreement::append_txn_author_agreement_to_request;
 use settings;
 use schema::LedgerSchema;
-use utils::constants::{ CRED_DEF_ID, CRED_DEF_JSON, CRED_DEF_TXN_TYPE };
+use utils::constants::{ CRED_DEF_ID, CRED_DEF_JSON, CRED_DEF_TXN_TYPE, CRED_DEF_REQUEST_FOR_ENDORSER };
 use utils::libindy::payments::{pay_for_txn, PaymentTxn};
 use utils::libindy::anoncreds::{libindy_create_and_store_credential_def};
-use utils::libindy::ledger::{libindy_submit_request,
+use utils::libindy::pool;
+use utils::libindy::ledger::{libindy_submit_read_request,
                              libindy_build_get_credential_def_txn,
                              libindy_build_create_credential_def_txn,
-                             libindy_parse_get_cred_def_response};
+                             libindy_parse_get_cred_def_response,
+                             multisign_request};
 use error::ToErrorCode;
 use error::cred_def::CredDefError;
 use object_cache::ObjectCache;
+use api::PublicEntityStateType;
+use utils::serialization::{self, Versioned, Migration};
 
 lazy_static! {
     static ref CREDENTIALDEF_MAP: ObjectCache<CredentialDef> = Default::default();
@@ -27,6 +33,10 @@ pub struct CredentialDef {
     name: String,
     source_id: String,
     payment_txn: Option<PaymentTxn>,
+    state: PublicEntityStateType,
+    // named pool the object was written to, None is the pool opened at init
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool_name: Option<String>,
 }
 
 impl Default for CredentialDef {
@@ -37,6 +47,8 @@ impl Default for CredentialDef {
             name: String::new(),
             source_id: String::new(),
             payment_txn: None,
+            state: PublicEntityStateType::Published,
+            pool_name: None,
         }
     }
 }
@@ -44,7 +56,7 @@ impl Default for CredentialDef {
 impl CredentialDef {
 
     pub fn from_str(input: &str) -> Result<CredentialDef, CredDefError> {
-        CredentialDef::from_string_with_version(&input).or(Err(CredDefError::CreateCredDefError()))
+        CredentialDef::from_string_with_version(&input)
     }
 
     pub fn to_string(&self) -> String {
@@ -67,18 +79,45 @@ impl CredentialDef {
         }
     }
 
+    fn get_state(&self) -> u32 { self.state as u32 }
+
+    fn update_state(&mut self) -> Result<u32, CredDefError> {
+        if self.state == PublicEntityStateType::PendingEndorsement {
+            let cred_def = pool::with_pool(self.pool_name.as_ref().map(String::as_str),
+                                           || fetch_credential_def(&self.id, true))
+                .map_err(|err| CredDefError::CommonError(err))?;
+
+            match cred_def {
+                Ok(_) => self.state = PublicEntityStateType::Published,
+                Err(_) => debug!("cred def {} has not been written to the ledger yet", self.id),
+            }
+        }
+        Ok(self.get_state())
+    }
+
     fn to_string_with_version(&self) -> String {
-        json!({
-            "version": "1.0",
-            "data": json!(self),
-        }).to_string()
+        serialization::to_versioned_string(self)
     }
 
     fn from_string_with_version(data: &str) -> Result<CredentialDef, CredDefError> {
-        let values:serde_json::Value = serde_json::from_str(data).unwrap();
-        let version = values["version"].to_string();
-        let data = values["data"].to_string();
-        serde_json::from_str(&data).or(Err(CredDefError::CreateCredDefError()))
+        serialization::from_versioned_str(data).map_err(|err| {
+            if err == error::INVALID_JSON.code_num { CredDefError::CreateCredDefError() } else { CredDefError::CommonError(err) }
+        })
+    }
+}
+
+// credential definitions serialized before 2.0 have no state, they were always written to the ledger
+fn add_published_state(mut data: serde_json::Value) -> Result<serde_json::Value, u32> {
+    if data.get("state").is_none() {
+        data["state"] = json!(PublicEntityStateType::Published);
+    }
+    Ok(data)
+}
+
+impl Versioned for CredentialDef {
+    fn migrations() -> Vec<Migration> {
+        vec![Migration { from: "1.0", to: "2.0", migrate: add_published_state },
+             serialization::unchanged("2.0", "3.0")]
     }
 }
 
@@ -106,6 +145,8 @@ pub fn create_new_credentialdef(source_id: String,
         tag,
         id,
         payment_txn,
+        state: PublicEntityStateType::Published,
+        pool_name: pool::get_write_pool_name(),
     };
 
     let new_handle = CREDENTIALDEF_MAP.add(new_cred_def).map_err(|key|CredDefError::CreateCredDefError())?;
@@ -113,6 +154,40 @@ pub fn create_new_credentialdef(source_id: String,
     Ok(new_handle)
 }
 
+/// Prepares a credential definition as a transaction author. The returned transaction must be endorsed
+/// and submitted by an endorser (see ledger::endorse_transaction), the cred def stays PendingEndorsement
+/// until update_state finds it on the ledger.
+pub fn prepare_credentialdef_for_endorser(source_id: String,
+                                          name: String,
+                                          author_did: String,
+                                          schema_id: String,
+                                          tag: String,
+                                          config_json: String) -> Result<(u32, String), CredDefError> {
+    let schema_json = LedgerSchema::new_from_ledger(&schema_id)
+        .map_err(|x| CredDefError::CommonError(x.to_error_code()))?.schema_json;
+
+    debug!("preparing credentialdef for endorser with source_id: {}, name: {}, author_did: {}, schema_id: {}", source_id, name, author_did, schema_id);
+    let (id, request) = _prepare_credential_def_for_endorser(&author_did,
+                                                             &schema_json,
+                                                             &tag,
+                                                             None,
+                                                             &config_json)?;
+
+    let new_cred_def = CredentialDef {
+        source_id,
+        name,
+        tag,
+        id,
+        payment_txn: None,
+        state: PublicEntityStateType::PendingEndorsement,
+        pool_name: pool::get_write_pool_name(),
+    };
+
+    let new_handle = CREDENTIALDEF_MAP.add(new_cred_def).map_err(|key|CredDefError::CreateCredDefError())?;
+
+    Ok((new_handle, request))
+}
+
 //Todo: possibly move _create_and_store_credential_def and retrieve_cred_def to a common trait
 fn _create_and_store_credential_def(issuer_did: &str,
                                    schema_json: &str,
@@ -123,11 +198,51 @@ fn _create_and_store_credential_def(issuer_did: &str,
         return Ok((CRED_DEF_ID.to_string(), Some(PaymentTxn::from_parts(r#"["pay:null:9UFgyjuJxi1i1HD"]"#,r#"[{"amount":4,"extra":null,"recipient":"pay:null:xkIsxem0YNtHrRO"}]"#,1).unwrap())));
     }
 
-    let (id, cred_def_json) = libindy_create_and_store_credential_def(issuer_did,
-                                                                      schema_json,
-                                                                      tag,
-                                                                      sig_type,
-                                                                      config_json)
+    let (id, cred_def_json) = _store_credential_def(issuer_did, schema_json, tag, sig_type, config_json)?;
+
+    let cred_def_req = libindy_build_create_credential_def_txn(issuer_did, &cred_def_json)
+        .or(Err(CredDefError::CreateCredDefError()))?;
+
+    let (payment, response) = pay_for_txn(&cred_def_req, CRED_DEF_TXN_TYPE)
+        .map_err(|err| CredDefError::CommonError(err))?;
+
+    Ok((id, payment))
+}
+
+// Builds the cred def request signed by the author, it still has to be endorsed before it can be written
+fn _prepare_credential_def_for_endorser(author_did: &str,
+                                        schema_json: &str,
+                                        tag: &str,
+                                        sig_type: Option<&str>,
+                                        config_json: &str) -> Result<(String, String), CredDefError> {
+    if settings::test_indy_mode_enabled() {
+        return Ok((CRED_DEF_ID.to_string(), CRED_DEF_REQUEST_FOR_ENDORSER.to_string()));
+    }
+
+    let (id, cred_def_json) = _store_credential_def(author_did, schema_json, tag, sig_type, config_json)?;
+
+    let cred_def_req = libindy_build_create_credential_def_txn(author_did, &cred_def_json)
+        .or(Err(CredDefError::CreateCredDefError()))?;
+
+    let cred_def_req = append_txn_author_agreement_to_request(&cred_def_req)
+        .map_err(|err| CredDefError::CommonError(err))?;
+
+    let cred_def_req = multisign_request(author_did, &cred_def_req)
+        .map_err(|err| CredDefError::CommonError(err))?;
+
+    Ok((id, cred_def_req))
+}
+
+fn _store_credential_def(issuer_did: &str,
+                         schema_json: &str,
+                         tag: &str,
+                         sig_type: Option<&str>,
+                         config_json: &str) -> Result<(String, String), CredDefError> {
+    libindy_create_and_store_credential_def(issuer_did,
+                                            schema_json,
+                                            tag,
+                                            sig_type,
+                                            config_json)
         .map_err(|err| {
             match err {
                 //Todo: Find out how to match on Cred...code_num
@@ -140,35 +255,53 @@ fn _create_and_store_credential_def(issuer_did: &str,
                     CredDefError::CreateCredDefError()
                 }
             }
-        })?;
-
-
-    let cred_def_req = libindy_build_create_credential_def_txn(issuer_did, &cred_def_json)
-        .or(Err(CredDefError::CreateCredDefError()))?;
-
-    let (payment, response) = pay_for_txn(&cred_def_req, CRED_DEF_TXN_TYPE)
-        .map_err(|err| CredDefError::CommonError(err))?;
-
-    Ok((id, payment))
+        })
 }
 
 pub fn retrieve_credential_def(cred_def_id: &str) -> Result<(String, String), CredDefError> {
+    fetch_credential_def(cred_def_id, false)
+}
+
+/// Reads the credential definition through the ledger cache, bypass_cache forces a fresh read from the pool
+pub fn fetch_credential_def(cred_def_id: &str, bypass_cache: bool) -> Result<(String, String), CredDefError> {
     if settings::test_indy_mode_enabled() { return Ok((CRED_DEF_ID.to_string(), CRED_DEF_JSON.to_string())); }
 
+    if !bypass_cache {
+        if let Some(cached) = ledger_cache::get(ledger_cache::CRED_DEF_CACHE, cred_def_id) {
+            return Ok(cached);
+        }
+    }
+
     let get_cred_def_req = libindy_build_get_credential_def_txn(cred_def_id)
         .or(Err(CredDefError::BuildCredDefRequestError()))?;
 
-    let get_cred_def_response = libindy_submit_request(&get_cred_def_req)
+    let get_cred_def_response = libindy_submit_read_request(cred_def_id, &get_cred_def_req)
         .map_err(|err| CredDefError::CommonError(err))?;
 
-    libindy_parse_get_cred_def_response(&get_cred_def_response)
-        .or(Err(CredDefError::RetrieveCredDefError()))
+    let (id, cred_def_json) = libindy_parse_get_cred_def_response(&get_cred_def_response)
+        .or(Err(CredDefError::RetrieveCredDefError()))?;
+
+    ledger_cache::put(ledger_cache::CRED_DEF_CACHE, cred_def_id, &id, &cred_def_json);
+
+    Ok((id, cred_def_json))
 }
 
 pub fn is_valid_handle(handle: u32) -> bool {
     CREDENTIALDEF_MAP.has_handle(handle)
 }
 
+pub fn get_state(handle: u32) -> Result<u32, CredDefError> {
+    CREDENTIALDEF_MAP.get(handle, |cd| {
+        Ok(cd.get_state())
+    }).map_err(|ec|CredDefError::CommonError(ec))
+}
+
+pub fn update_state(handle: u32) -> Result<u32, CredDefError> {
+    CREDENTIALDEF_MAP.get_mut(handle, |cd| {
+        cd.update_state().map_err(|e| e.to_error_code())
+    }).map_err(|ec|CredDefError::CommonError(ec))
+}
+
 pub fn to_string(handle: u32) -> Result<String, u32> {
     CREDENTIALDEF_MAP.get(handle, |cd| {
         Ok(CredentialDef::to_string_with_version(&cd))
@@ -375,7 +508,7 @@ pub mod tests {
                                               "{}".to_string()).unwrap();
         let credential_string = to_string(handle).unwrap();
         let credential_values: serde_json::Value = serde_json::from_str(&credential_string).unwrap();
-        assert_eq!(credential_values["version"].clone(), "1.0");
+        assert_eq!(credential_values["version"].clone(), ::utils::constants::DEFAULT_SERIALIZE_VERSION);
     }
 
     #[test]
@@ -398,6 +531,24 @@ pub mod tests {
         assert_eq!(CredentialDef::from_str("{}").err(), Some(CredDefError::CreateCredDefError()));
     }
 
+    #[test]
+    fn test_prepare_credentialdef_for_endorser() {
+        set_default_and_enable_test_mode();
+        let (handle, request) = prepare_credentialdef_for_endorser("SourceId".to_string(),
+                                                                   CREDENTIAL_DEF_NAME.to_string(),
+                                                                   ISSUER_DID.to_string(),
+                                                                   SCHEMA_ID.to_string(),
+                                                                   "tag".to_string(),
+                                                                   "{}".to_string()).unwrap();
+        assert!(request.len() > 0);
+        assert_eq!(get_state(handle).unwrap(), PublicEntityStateType::PendingEndorsement as u32);
+
+        let new_handle = from_string(&to_string(handle).unwrap()).unwrap();
+        assert_eq!(get_state(new_handle).unwrap(), PublicEntityStateType::PendingEndorsement as u32);
+
+        assert_eq!(update_state(handle).unwrap(), PublicEntityStateType::Published as u32);
+    }
+
     #[test]
     fn test_release_all() {
         settings::set_defaults();
@@ -414,4 +565,14 @@ pub mod tests {
         assert_eq!(release(h4),Err(CredDefError::InvalidHandle()));
         assert_eq!(release(h5),Err(CredDefError::InvalidHandle()));
     }
+
+    #[test]
+    fn test_deserialize_historical_version() {
+        use utils::constants::{DEFAULT_SERIALIZED_CREDENTIAL_DEF, DEFAULT_SERIALIZE_VERSION};
+        let obj = CredentialDef::from_str(DEFAULT_SERIALIZED_CREDENTIAL_DEF).unwrap();
+        assert_eq!(obj.get_state(), PublicEntityStateType::Published as u32);
+        let serialized = obj.to_string();
+        assert_eq!(serialization::parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(CredentialDef::from_str(&serialized).unwrap().to_string(), serialized);
+    }
 }
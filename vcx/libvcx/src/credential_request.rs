@@ -1,6 +1,7 @@
 extern crate serde_json;
 
 use error::issuer_cred::IssuerCredError;
+use messages::out_of_band::Service;
 
 static ISSUER_DID: &'static str = "issuer_did";
 static SEQUENCE_NUMBER: &'static str = "schema_seq_no";
@@ -44,6 +45,12 @@ impl CredentialRequest {
     }
 }
 
+/// Request answering an out of band credential offer, the credential is sent to service
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct OutOfBandCredentialRequest {
+    pub credential_request: CredentialRequest,
+    pub service: Service,
+}
 
 #[cfg(test)]
 mod tests {
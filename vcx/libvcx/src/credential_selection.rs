@@ -0,0 +1,373 @@
+extern crate serde_json;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use messages::proofs::proof_request::ProofRequestData;
+use serde_json::{Map, Value};
+use settings;
+use link_secret;
+use utils::error;
+use utils::libindy::wallet;
+
+static STORED_AT_RECORD: &str = "vcx_credential_stored_at";
+static WALLET_GET_OPTIONS: &str = r#"{"retrieveType":false,"retrieveValue":true,"retrieveTags":false}"#;
+
+lazy_static! {
+    // stands in for the wallet in test mode, where wallet records are mocked
+    static ref TEST_STORED_AT: Mutex<HashMap<String, u64>> = Default::default();
+}
+
+/// Rules used to answer a proof request without user interaction
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SelectionPolicy {
+    // issuer dids in order of preference, credentials from other issuers come last
+    #[serde(default)]
+    pub preferred_issuers: Vec<String>,
+    // prefer credentials holding fewer attributes, so less is disclosed with the proof
+    #[serde(default = "default_minimize_revealed")]
+    pub minimize_revealed: bool,
+    // attribute referents or names proven without disclosing their value
+    #[serde(default)]
+    pub hidden_attributes: Vec<String>,
+    // values by attribute referent or name, used for attributes no credential is required for
+    #[serde(default)]
+    pub self_attested: HashMap<String, String>,
+}
+
+fn default_minimize_revealed() -> bool { true }
+
+impl Default for SelectionPolicy {
+    fn default() -> SelectionPolicy {
+        SelectionPolicy {
+            preferred_issuers: Vec::new(),
+            minimize_revealed: default_minimize_revealed(),
+            hidden_attributes: Vec::new(),
+            self_attested: HashMap::new(),
+        }
+    }
+}
+
+impl SelectionPolicy {
+    pub fn from_str(policy: &str) -> Result<SelectionPolicy, u32> {
+        if policy.trim().is_empty() { return Ok(SelectionPolicy::default()) }
+        serde_json::from_str(policy).or(Err(error::INVALID_JSON.code_num))
+    }
+}
+
+/// Selection made by select_credentials, selected_credentials and self_attested_attrs are the
+/// arguments expected by generate_proof
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CredentialSelection {
+    pub selected_credentials: Value,
+    pub self_attested_attrs: HashMap<String, String>,
+    // referents of the proof request no credential or self attested value was found for
+    pub unsatisfied: Vec<String>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Records when a credential was stored in the wallet, the selection prefers the newest credentials
+pub fn record_stored(cred_id: &str) -> Result<(), u32> {
+    let stored_at = now();
+    if settings::test_indy_mode_enabled() {
+        TEST_STORED_AT.lock().or(Err(error::UNKNOWN_ERROR.code_num))?.insert(cred_id.to_string(), stored_at);
+        return Ok(());
+    }
+
+    let stored_at = stored_at.to_string();
+    match wallet::add_record(STORED_AT_RECORD, cred_id, &stored_at, "{}") {
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => wallet::update_record_value(STORED_AT_RECORD, cred_id, &stored_at),
+        rc => rc,
+    }
+}
+
+/// When a credential was stored, None for credentials stored before it was recorded
+pub fn stored_at(cred_id: &str) -> Option<u64> {
+    if settings::test_indy_mode_enabled() {
+        return TEST_STORED_AT.lock().ok()?.get(cred_id).cloned();
+    }
+
+    let record = wallet::get_record(STORED_AT_RECORD, cred_id, WALLET_GET_OPTIONS).ok()?;
+    let record: Value = serde_json::from_str(&record).ok()?;
+    record["value"].as_str()?.parse().ok()
+}
+
+fn issuer_did(cred_info: &Value) -> &str {
+    cred_info["cred_def_id"].as_str()
+        .and_then(|id| id.split(':').next())
+        .unwrap_or("")
+}
+
+fn cred_id(candidate: &Value) -> &str {
+    candidate["cred_info"]["referent"].as_str().unwrap_or("")
+}
+
+fn candidate_link_secret(candidate: &Value) -> String {
+    link_secret::credential_link_secret(cred_id(candidate)).unwrap_or(link_secret::default_alias())
+}
+
+/// Picks the candidate bound to link_secret preferred by the policy: issuer first, then the amount
+/// of data held by the credential, then the most recently stored one. Credentials stored before
+/// the time was recorded come after the others, in the order libindy returns them, which is the
+/// order they were stored in the wallet.
+fn best_candidate<'a>(candidates: &'a [Value], policy: &SelectionPolicy, link_secret: &str) -> Option<&'a Value> {
+    candidates.iter()
+        .enumerate()
+        .filter(|&(_, candidate)| candidate["cred_info"].is_object())
+        .filter(|&(_, candidate)| candidate_link_secret(candidate) == link_secret)
+        .min_by_key(|&(position, candidate)| {
+            let cred_info = &candidate["cred_info"];
+            let issuer = issuer_did(cred_info);
+            let issuer_rank = policy.preferred_issuers.iter()
+                .position(|did| did == issuer)
+                .unwrap_or(policy.preferred_issuers.len());
+            let size = if policy.minimize_revealed {
+                cred_info["attrs"].as_object().map(|attrs| attrs.len()).unwrap_or(0)
+            } else { 0 };
+            (issuer_rank, size, Reverse(stored_at(cred_id(candidate))), Reverse(position))
+        })
+        .map(|(_, candidate)| candidate)
+}
+
+fn candidates<'a>(credentials: &'a Value, section: &str, referent: &str) -> &'a [Value] {
+    credentials[section][referent].as_array().map(|x| x.as_slice()).unwrap_or(&[])
+}
+
+/// Fills every attribute and predicate of proof_request from the credentials returned by
+/// retrieve_credentials according to policy. A proof is created with a single link secret, so
+/// only credentials bound to link_secret are used. Without one, the link secret whose credentials
+/// satisfy the most of the request is used, the selected link secret first on a tie.
+pub fn select_credentials(proof_request: &ProofRequestData,
+                          credentials: &str,
+                          policy: &SelectionPolicy,
+                          link_secret: Option<&str>) -> Result<CredentialSelection, u32> {
+    let credentials: Value = serde_json::from_str(credentials).or(Err(error::INVALID_JSON.code_num))?;
+
+    let aliases = match link_secret {
+        Some(alias) => vec![alias.to_string()],
+        None => candidate_link_secrets(&credentials),
+    };
+
+    let mut rtn: Option<CredentialSelection> = None;
+    for alias in aliases {
+        let selection = select_for_link_secret(proof_request, &credentials, policy, &alias);
+        match rtn {
+            Some(ref best) if best.unsatisfied.len() <= selection.unsatisfied.len() => (),
+            _ => rtn = Some(selection),
+        }
+    }
+    let selection = rtn.ok_or(error::INVALID_JSON.code_num)?;
+
+    if !selection.unsatisfied.is_empty() {
+        warn!("no credential satisfies {:?}", selection.unsatisfied);
+    }
+
+    Ok(selection)
+}
+
+// the selected link secret, then the link secrets of the candidates in the order they are found
+fn candidate_link_secrets(credentials: &Value) -> Vec<String> {
+    let mut aliases = vec![link_secret::selected()];
+    for section in &["attrs", "predicates"] {
+        let referents = match credentials[section].as_object() {
+            Some(x) => x,
+            None => continue,
+        };
+        for candidate in referents.values().filter_map(Value::as_array).flat_map(|x| x.iter()) {
+            let alias = candidate_link_secret(candidate);
+            if !aliases.contains(&alias) { aliases.push(alias); }
+        }
+    }
+    aliases
+}
+
+fn select_for_link_secret(proof_request: &ProofRequestData,
+                          credentials: &Value,
+                          policy: &SelectionPolicy,
+                          link_secret: &str) -> CredentialSelection {
+    let mut attrs = Map::new();
+    let mut predicates = Map::new();
+    let mut self_attested_attrs = HashMap::new();
+    let mut unsatisfied = Vec::new();
+
+    let mut attr_referents: Vec<&String> = proof_request.requested_attributes.keys().collect();
+    attr_referents.sort();
+    for referent in attr_referents {
+        let attr = &proof_request.requested_attributes[referent];
+
+        if let Some(candidate) = best_candidate(candidates(credentials, "attrs", referent), policy, link_secret) {
+            let hidden = policy.hidden_attributes.iter().any(|x| x == referent || x == &attr.name);
+            attrs.insert(referent.to_string(), json!({
+                "cred_info": candidate["cred_info"],
+                "interval": candidate["interval"],
+                "revealed": !hidden,
+            }));
+            continue;
+        }
+
+        let restricted = attr.restrictions.as_ref().map(|x| !x.is_empty()).unwrap_or(false);
+        match policy.self_attested.get(referent).or(policy.self_attested.get(&attr.name)) {
+            Some(value) if !restricted => { self_attested_attrs.insert(referent.to_string(), value.to_string()); },
+            _ => unsatisfied.push(referent.to_string()),
+        }
+    }
+
+    let mut predicate_referents: Vec<&String> = proof_request.requested_predicates.keys().collect();
+    predicate_referents.sort();
+    for referent in predicate_referents {
+        match best_candidate(candidates(credentials, "predicates", referent), policy, link_secret) {
+            Some(candidate) => {
+                predicates.insert(referent.to_string(), json!({
+                    "cred_info": candidate["cred_info"],
+                    "interval": candidate["interval"],
+                }));
+            },
+            None => unsatisfied.push(referent.to_string()),
+        }
+    }
+
+    CredentialSelection {
+        selected_credentials: json!({ "attrs": attrs, "predicates": predicates }),
+        self_attested_attrs,
+        unsatisfied,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_test_mode() {
+        ::settings::set_defaults();
+        ::settings::set_config_value(::settings::CONFIG_ENABLE_TEST_MODE, "true");
+    }
+
+    fn proof_request() -> ProofRequestData {
+        serde_json::from_value(json!({
+            "nonce": "123432421212",
+            "name": "proof_req_1",
+            "version": "0.1",
+            "requested_attributes": {
+                "name_1": { "name": "name", "restrictions": [{ "issuer_did": "2hoqvcwupRTUNkXn6ArYzs" }] },
+                "phone_2": { "name": "phone" },
+                "degree_3": { "name": "degree", "restrictions": [{ "issuer_did": "V4SGRU86Z58d6TV7PBUe6f" }] },
+            },
+            "requested_predicates": {
+                "age_1": { "name": "age", "p_type": ">=", "p_value": 18 },
+            },
+        })).unwrap()
+    }
+
+    fn cred(referent: &str, cred_def_id: &str, attrs: Value) -> Value {
+        json!({
+            "cred_info": {
+                "referent": referent,
+                "attrs": attrs,
+                "schema_id": "2hoqvcwupRTUNkXn6ArYzs:2:test-licence:4.4.4",
+                "cred_def_id": cred_def_id,
+                "rev_reg_id": null,
+                "cred_rev_id": null
+            },
+            "interval": null
+        })
+    }
+
+    fn credentials() -> String {
+        let licence = cred("licence", "2hoqvcwupRTUNkXn6ArYzs:3:CL:2471", json!({"name": "Bob", "age": "111", "height": "4'11"}));
+        let old_id = cred("old_id", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111"}));
+        let new_id = cred("new_id", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111"}));
+        json!({
+            "attrs": { "name_1": [licence.clone(), old_id.clone(), new_id.clone()], "phone_2": [], "degree_3": [] },
+            "predicates": { "age_1": [licence, old_id, new_id] },
+        }).to_string()
+    }
+
+    fn selected_referent(selection: &CredentialSelection, section: &str, referent: &str) -> String {
+        selection.selected_credentials[section][referent]["cred_info"]["referent"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_select_minimal_and_most_recent_credential() {
+        set_test_mode();
+        let selection = select_credentials(&proof_request(), &credentials(), &SelectionPolicy::default(), None).unwrap();
+
+        assert_eq!(selected_referent(&selection, "attrs", "name_1"), "new_id");
+        assert_eq!(selected_referent(&selection, "predicates", "age_1"), "new_id");
+        assert_eq!(selection.selected_credentials["attrs"]["name_1"]["revealed"], json!(true));
+        assert_eq!(selection.unsatisfied, vec!["degree_3".to_string(), "phone_2".to_string()]);
+    }
+
+    #[test]
+    fn test_select_with_policy() {
+        set_test_mode();
+        let policy = SelectionPolicy::from_str(r#"{
+            "preferred_issuers": ["2hoqvcwupRTUNkXn6ArYzs"],
+            "hidden_attributes": ["name"],
+            "self_attested": {"phone": "8-800-300", "degree": "PhD"}
+        }"#).unwrap();
+        let selection = select_credentials(&proof_request(), &credentials(), &policy, None).unwrap();
+
+        assert_eq!(selected_referent(&selection, "attrs", "name_1"), "licence");
+        assert_eq!(selection.selected_credentials["attrs"]["name_1"]["revealed"], json!(false));
+        assert_eq!(selection.self_attested_attrs.get("phone_2").unwrap(), "8-800-300");
+        // a restricted attribute cannot be self attested
+        assert_eq!(selection.unsatisfied, vec!["degree_3".to_string()]);
+    }
+
+    #[test]
+    fn test_select_most_recently_stored_credential() {
+        set_test_mode();
+        TEST_STORED_AT.lock().unwrap().insert("stored_later".to_string(), 300);
+        TEST_STORED_AT.lock().unwrap().insert("stored_earlier".to_string(), 200);
+        let later = cred("stored_later", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111"}));
+        let earlier = cred("stored_earlier", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111"}));
+        let unrecorded = cred("stored_unrecorded", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111"}));
+        let credentials = json!({
+            "attrs": { "name_1": [later.clone(), earlier.clone(), unrecorded.clone()] },
+            "predicates": { "age_1": [later, earlier, unrecorded] },
+        }).to_string();
+
+        let selection = select_credentials(&proof_request(), &credentials, &SelectionPolicy::default(), None).unwrap();
+
+        assert_eq!(selected_referent(&selection, "attrs", "name_1"), "stored_later");
+        assert_eq!(selected_referent(&selection, "predicates", "age_1"), "stored_later");
+    }
+
+    #[test]
+    fn test_select_credentials_of_one_link_secret() {
+        set_test_mode();
+        ::link_secret::bind_credential("bound_work", "work").unwrap();
+        ::link_secret::bind_credential("bound_work_age", "work").unwrap();
+        ::link_secret::bind_credential("bound_home", "home").unwrap();
+        let work = cred("bound_work", "2hoqvcwupRTUNkXn6ArYzs:3:CL:2471", json!({"name": "Bob", "age": "111", "height": "4'11"}));
+        let work_age = cred("bound_work_age", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111", "height": "4'11"}));
+        let home = cred("bound_home", "V4SGRU86Z58d6TV7PBUe6f:3:CL:12", json!({"name": "Bob", "age": "111"}));
+        let credentials = json!({
+            "attrs": { "name_1": [work.clone(), home.clone()] },
+            "predicates": { "age_1": [work_age, home] },
+        }).to_string();
+
+        let selection = select_credentials(&proof_request(), &credentials, &SelectionPolicy::default(), Some("work")).unwrap();
+        assert_eq!(selected_referent(&selection, "attrs", "name_1"), "bound_work");
+        assert_eq!(selected_referent(&selection, "predicates", "age_1"), "bound_work_age");
+
+        let selection = select_credentials(&proof_request(), &credentials, &SelectionPolicy::default(), Some("main")).unwrap();
+        assert!(selection.selected_credentials["attrs"]["name_1"].is_null());
+        assert!(selection.unsatisfied.contains(&"name_1".to_string()));
+
+        // both link secrets satisfy the same referents, the first one found is kept
+        let selection = select_credentials(&proof_request(), &credentials, &SelectionPolicy::default(), None).unwrap();
+        assert_eq!(selected_referent(&selection, "attrs", "name_1"), "bound_work");
+        assert_eq!(selected_referent(&selection, "predicates", "age_1"), "bound_work_age");
+    }
+
+    #[test]
+    fn test_invalid_policy() {
+        assert_eq!(SelectionPolicy::from_str("").unwrap(), SelectionPolicy::default());
+        assert_eq!(SelectionPolicy::from_str("{\"preferred_issuers\":1}").err(), Some(error::INVALID_JSON.code_num));
+    }
+}
@@ -0,0 +1,349 @@
+use api::{VcxStateType, VcxObjectType};
+use messages::get_message::{self, Message};
+use messages::push_notification;
+use messages::update_message;
+use messages::MessageResponseCode::{MessageAccepted, MessagePending, MessageAnswered};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+use connection;
+use issuer_credential;
+use credential;
+use proof;
+use error::ToErrorCode;
+use utils::error;
+
+/// Called with the handle it was registered with, the type and handle of the object and its new state
+pub type StateCallback = extern fn(command_handle: u32, object_type: u32, handle: u32, state: u32);
+
+/// State an object was moved to by messages routed from the agency
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StateChange {
+    pub object_type: VcxObjectType,
+    pub handle: u32,
+    pub state: u32,
+}
+
+struct WaitingObject {
+    object_type: VcxObjectType,
+    handle: u32,
+    pairwise_did: String,
+    // uid of the offer or request the object expects an answer to
+    msg_uid: Option<String>,
+}
+
+lazy_static! {
+    static ref CALLBACKS: Mutex<HashMap<u32, StateCallback>> = Default::default();
+    static ref POLLER: Mutex<Option<Arc<AtomicBool>>> = Default::default();
+}
+
+pub fn register_callback(command_handle: u32, cb: StateCallback) -> Result<(), u32> {
+    let mut callbacks = CALLBACKS.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    callbacks.insert(command_handle, cb);
+    Ok(())
+}
+
+pub fn unregister_callback(command_handle: u32) -> Result<(), u32> {
+    let mut callbacks = CALLBACKS.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    callbacks.remove(&command_handle);
+    Ok(())
+}
+
+fn notify(object_type: VcxObjectType, handle: u32, state: u32) {
+    // callbacks may register or unregister callbacks, so they are not run under the lock
+    let callbacks: Vec<(u32, StateCallback)> = match CALLBACKS.lock() {
+        Ok(callbacks) => callbacks.iter().map(|(&command_handle, &cb)| (command_handle, cb)).collect(),
+        Err(_) => return,
+    };
+
+    for (command_handle, cb) in callbacks {
+        debug!("dispatcher callback(command_handle: {}, object_type: {:?}, handle: {}, state: {})",
+               command_handle, object_type, handle, state);
+        cb(command_handle, object_type as u32, handle, state);
+    }
+}
+
+fn waiting_objects() -> Vec<WaitingObject> {
+    let mut waiting = Vec::new();
+
+    for (object_type, handles) in vec![(VcxObjectType::Connection, connection::get_waiting_handles()),
+                                       (VcxObjectType::IssuerCredential, issuer_credential::get_waiting_handles()),
+                                       (VcxObjectType::Credential, credential::get_waiting_handles()),
+                                       (VcxObjectType::Proof, proof::get_waiting_handles())] {
+        waiting.extend(handles.into_iter().map(|(handle, pairwise_did, msg_uid)| {
+            WaitingObject { object_type, handle, pairwise_did, msg_uid }
+        }));
+    }
+
+    waiting
+}
+
+fn update_object(object_type: VcxObjectType, handle: u32, messages: &[Message]) -> Result<u32, u32> {
+    match object_type {
+        VcxObjectType::Connection => connection::update_state_with_messages(handle, messages).map_err(|err| err.to_error_code()),
+        VcxObjectType::IssuerCredential => issuer_credential::update_state_with_messages(handle, messages).map_err(|err| err.to_error_code()),
+        VcxObjectType::Credential => credential::update_state_with_messages(handle, messages).map_err(|err| err.to_error_code()),
+        VcxObjectType::Proof => proof::update_state_with_messages(handle, messages).map_err(|err| err.to_error_code()),
+    }
+}
+
+// Uid of the answer an object consumed when it changed state: the message its offer or request
+// refers to, or for a credential the first credential received
+fn answered_uid(object: &WaitingObject, messages: &[Message]) -> Option<String> {
+    match object.msg_uid {
+        Some(ref msg_uid) => messages.iter()
+            .find(|msg| &msg.uid == msg_uid && msg.status_code == MessageAccepted.as_string())
+            .and_then(|msg| msg.ref_msg_id.clone()),
+        None if object.object_type == VcxObjectType::Credential => messages.iter()
+            .find(|msg| msg.msg_type == "cred" && get_message::decrypted_msg(msg).is_some())
+            .map(|msg| msg.uid.clone()),
+        None => None,
+    }
+}
+
+// Marks the answers routed to objects as reviewed so they are not downloaded again
+fn mark_reviewed(answered: HashMap<String, Vec<String>>) {
+    if answered.is_empty() { return }
+
+    let uids_by_conns: Vec<_> = answered.into_iter()
+        .map(|(pairwise_did, uids)| json!({ "pairwiseDID": pairwise_did, "uids": uids }))
+        .collect();
+    if let Err(err) = update_message::update_agency_messages(&MessageAnswered.as_string(), &json!(uids_by_conns).to_string()) {
+        warn!("dispatcher could not mark routed messages as reviewed: {}", err);
+    }
+}
+
+fn route(waiting: Vec<WaitingObject>, messages: &HashMap<String, Vec<Message>>) -> Vec<StateChange> {
+    let mut changes = Vec::new();
+    let mut answered: HashMap<String, Vec<String>> = HashMap::new();
+
+    for object in waiting {
+        let msgs = match messages.get(&object.pairwise_did) {
+            Some(msgs) => msgs,
+            None => continue,
+        };
+
+        match update_object(object.object_type, object.handle, msgs) {
+            Ok(state) => if state != VcxStateType::VcxStateOfferSent as u32 {
+                if let Some(uid) = answered_uid(&object, msgs) {
+                    answered.entry(object.pairwise_did.clone()).or_insert_with(Vec::new).push(uid);
+                }
+                notify(object.object_type, object.handle, state);
+                changes.push(StateChange { object_type: object.object_type, handle: object.handle, state });
+            },
+            Err(err) => warn!("dispatcher could not update {:?} {}: {}", object.object_type, object.handle, err),
+        }
+    }
+
+    mark_reviewed(answered);
+    changes
+}
+
+fn download(pairwise_dids: Vec<String>,
+            status_codes: Option<Vec<String>>,
+            uids: Option<Vec<String>>) -> Result<HashMap<String, Vec<Message>>, u32> {
+    Ok(get_message::download_messages(Some(pairwise_dids), status_codes, uids)?
+        .into_iter()
+        .map(|connection| (connection.pairwise_did, connection.msgs))
+        .collect())
+}
+
+fn merge(messages: &mut HashMap<String, Vec<Message>>, more: HashMap<String, Vec<Message>>) {
+    for (pairwise_did, msgs) in more {
+        let known = messages.entry(pairwise_did).or_insert_with(Vec::new);
+        for msg in msgs {
+            if !known.iter().any(|x| x.uid == msg.uid) { known.push(msg); }
+        }
+    }
+}
+
+fn sorted_dids<'a, I: Iterator<Item=&'a WaitingObject>>(objects: I) -> Vec<String> {
+    let mut pairwise_dids: Vec<String> = objects.map(|object| object.pairwise_did.clone()).collect();
+    pairwise_dids.sort();
+    pairwise_dids.dedup();
+    pairwise_dids
+}
+
+/// Downloads the answers received for the objects waiting on one with a single agency call and
+/// routes them by pairwise did to those objects. Only messages not reviewed yet are downloaded,
+/// the offers and requests they answer are then fetched by uid. Registered callbacks are fired
+/// for each object whose state changed and the answers are marked reviewed. Returns the number
+/// of objects updated.
+pub fn poll() -> Result<u32, u32> {
+    let waiting = waiting_objects();
+    if waiting.is_empty() {
+        return Ok(0);
+    }
+
+    let pairwise_dids = sorted_dids(waiting.iter());
+    debug!("dispatcher downloading messages for {} objects on {} pairwise dids", waiting.len(), pairwise_dids.len());
+    let mut messages = download(pairwise_dids, Some(vec![MessagePending.as_string()]), None)?;
+
+    let answered: Vec<&WaitingObject> = waiting.iter()
+        .filter(|object| object.msg_uid.is_some() && messages.get(&object.pairwise_did).map(|msgs| !msgs.is_empty()).unwrap_or(false))
+        .collect();
+    if !answered.is_empty() {
+        let uids = answered.iter().filter_map(|object| object.msg_uid.clone()).collect();
+        let more = download(sorted_dids(answered.into_iter()), None, Some(uids))?;
+        merge(&mut messages, more);
+    }
+
+    // the agency keeps the answer to an invite as accepted
+    let connections: Vec<&WaitingObject> = waiting.iter()
+        .filter(|object| object.object_type == VcxObjectType::Connection)
+        .collect();
+    if !connections.is_empty() {
+        let more = download(sorted_dids(connections.into_iter()), Some(vec![MessageAccepted.as_string()]), None)?;
+        merge(&mut messages, more);
+    }
+
+    Ok(route(waiting, &messages).len() as u32)
+}
+
+/// Updates the objects waiting on the message a push notification announces. Only the pushed
+/// message and the offers and requests of the objects on its pairwise did are fetched.
+pub fn handle_push_notification(payload: &str) -> Result<Vec<StateChange>, u32> {
+    let notification = push_notification::parse_push_notification(payload)?;
+    debug!("received push notification for message {} on {}", notification.msg_uid, notification.pairwise_did);
+
+    let waiting: Vec<WaitingObject> = waiting_objects().into_iter()
+        .filter(|object| object.pairwise_did == notification.pairwise_did)
+        .collect();
+    if waiting.is_empty() {
+        warn!("no object is waiting on messages for {}", notification.pairwise_did);
+        return Ok(Vec::new());
+    }
+
+    let mut uids = vec![notification.msg_uid.clone()];
+    uids.extend(waiting.iter().filter_map(|object| object.msg_uid.clone()));
+
+    let messages = download(vec![notification.pairwise_did.clone()], None, Some(uids))?;
+
+    Ok(route(waiting, &messages))
+}
+
+/// Starts a thread that polls every interval_ms milliseconds until stop is called
+pub fn start(interval_ms: u32) -> Result<(), u32> {
+    if interval_ms == 0 {
+        return Err(error::INVALID_OPTION.code_num);
+    }
+
+    let mut poller = POLLER.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    if poller.is_some() {
+        return Err(error::DISPATCHER_ALREADY_RUNNING.code_num);
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let thread_running = running.clone();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_millis(interval_ms as u64));
+            if !thread_running.load(Ordering::SeqCst) { break; }
+
+            if let Err(err) = poll() {
+                warn!("dispatcher could not download messages: {}", err);
+            }
+        }
+        debug!("dispatcher stopped");
+    });
+
+    *poller = Some(running);
+    Ok(())
+}
+
+pub fn stop() {
+    if let Ok(mut poller) = POLLER.lock() {
+        if let Some(running) = poller.take() {
+            running.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+pub fn is_running() -> bool {
+    POLLER.lock().map(|poller| poller.is_some()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    lazy_static! {
+        static ref NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+    }
+
+    extern fn count_notifications(_command_handle: u32, object_type: u32, _handle: u32, state: u32) {
+        assert_eq!(object_type, VcxObjectType::Proof as u32);
+        assert_eq!(state, VcxStateType::VcxStateAccepted as u32);
+        NOTIFIED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_registered_callbacks_are_notified() {
+        register_callback(9901, count_notifications).unwrap();
+        notify(VcxObjectType::Proof, 1, VcxStateType::VcxStateAccepted as u32);
+        unregister_callback(9901).unwrap();
+        notify(VcxObjectType::Proof, 1, VcxStateType::VcxStateAccepted as u32);
+
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_start_and_stop() {
+        assert_eq!(start(0), Err(error::INVALID_OPTION.code_num));
+
+        start(60000).unwrap();
+        assert!(is_running());
+        assert_eq!(start(60000), Err(error::DISPATCHER_ALREADY_RUNNING.code_num));
+
+        stop();
+        assert!(!is_running());
+    }
+
+    fn message(uid: &str, status_code: &str, msg_type: &str, ref_msg_id: Option<&str>) -> Message {
+        let mut msg = Message::new();
+        msg.uid = uid.to_string();
+        msg.status_code = status_code.to_string();
+        msg.msg_type = msg_type.to_string();
+        msg.ref_msg_id = ref_msg_id.map(String::from);
+        msg
+    }
+
+    #[test]
+    fn test_answered_uid() {
+        let request = message("request", &MessageAccepted.as_string(), "proofReq", Some("proof"));
+        let proof = message("proof", &MessagePending.as_string(), "proof", None);
+        let mut credential = message("credential", &MessagePending.as_string(), "cred", None);
+        credential.decrypted_payload = Some(r#"{"@type":{"name":"cred","ver":"1.0","fmt":"json"},"@msg":"{}"}"#.to_string());
+        let messages = vec![request, proof, credential];
+
+        let waiting = |object_type, msg_uid: Option<&str>| WaitingObject {
+            object_type, handle: 1, pairwise_did: "did".to_string(), msg_uid: msg_uid.map(String::from),
+        };
+        assert_eq!(answered_uid(&waiting(VcxObjectType::Proof, Some("request")), &messages), Some("proof".to_string()));
+        assert_eq!(answered_uid(&waiting(VcxObjectType::Proof, Some("unknown")), &messages), None);
+        assert_eq!(answered_uid(&waiting(VcxObjectType::Credential, None), &messages), Some("credential".to_string()));
+        assert_eq!(answered_uid(&waiting(VcxObjectType::Connection, None), &messages), None);
+    }
+
+    #[test]
+    fn test_merge_skips_known_messages() {
+        let mut messages: HashMap<String, Vec<Message>> = HashMap::new();
+        messages.insert("did".to_string(), vec![message("a", "MS-103", "proof", None)]);
+        let mut more = HashMap::new();
+        more.insert("did".to_string(), vec![message("a", "MS-103", "proof", None), message("b", "MS-104", "proofReq", None)]);
+        more.insert("other".to_string(), vec![message("c", "MS-103", "cred", None)]);
+
+        merge(&mut messages, more);
+        assert_eq!(messages["did"].len(), 2);
+        assert_eq!(messages["other"].len(), 1);
+    }
+
+    #[test]
+    fn test_push_notification_without_waiting_objects() {
+        assert_eq!(handle_push_notification(r#"{"uid":"ntc2ytb","forDID":"NoObjectOnThisDid"}"#), Ok(Vec::new()));
+        assert_eq!(handle_push_notification(r#"{"uid":"ntc2ytb"}"#), Err(error::INVALID_PUSH_NOTIFICATION.code_num));
+    }
+}
@@ -0,0 +1,79 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use utils::error;
+
+/// Details of the last error raised on the current thread. Causes are ordered from the
+/// outermost error to the root cause (libindy error, agency status, ledger reject, ...).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ErrorDetails {
+    pub error: u32,
+    pub message: String,
+    pub causes: Vec<String>,
+    pub context: BTreeMap<String, String>,
+}
+
+thread_local! {
+    static CURRENT_ERROR: RefCell<Option<ErrorDetails>> = RefCell::new(None);
+}
+
+/// Makes code the current error of the thread and adds cause on top of the cause chain.
+/// Returns code so it can be used in place of the bare error code.
+pub fn set_current_error(code: u32, cause: &str) -> u32 {
+    if code == error::SUCCESS.code_num { return code; }
+
+    CURRENT_ERROR.with(|current| {
+        let mut current = current.borrow_mut();
+        let details = current.get_or_insert_with(ErrorDetails::default);
+
+        details.error = code;
+        details.message = error::error_message(&code);
+        if !cause.is_empty() && details.causes.first().map(String::as_str) != Some(cause) {
+            details.causes.insert(0, cause.to_string());
+        }
+    });
+
+    code
+}
+
+/// Attaches a piece of context (handle, message id, ledger response, agency status) to the current error
+pub fn add_error_context(key: &str, value: &str) {
+    CURRENT_ERROR.with(|current| {
+        current.borrow_mut()
+            .get_or_insert_with(ErrorDetails::default)
+            .context
+            .insert(key.to_string(), value.to_string());
+    });
+}
+
+pub fn get_current_error() -> Option<ErrorDetails> {
+    CURRENT_ERROR.with(|current| current.borrow().clone())
+}
+
+pub fn reset_current_error() {
+    CURRENT_ERROR.with(|current| *current.borrow_mut() = None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_error_chain() {
+        reset_current_error();
+        assert_eq!(get_current_error(), None);
+
+        add_error_context("ledger_response", r#"{"op":"REJECT"}"#);
+        set_current_error(error::LEDGER_REQUEST_REJECTED.code_num, "not allowed");
+        assert_eq!(set_current_error(error::INVALID_SCHEMA_CREATION.code_num, "Schema creation failed"),
+                   error::INVALID_SCHEMA_CREATION.code_num);
+        set_current_error(error::INVALID_SCHEMA_CREATION.code_num, "Schema creation failed");
+
+        let details = get_current_error().unwrap();
+        assert_eq!(details.error, error::INVALID_SCHEMA_CREATION.code_num);
+        assert_eq!(details.causes, vec!["Schema creation failed".to_string(), "not allowed".to_string()]);
+        assert_eq!(details.context.get("ledger_response").unwrap(), r#"{"op":"REJECT"}"#);
+
+        reset_current_error();
+        assert_eq!(get_current_error(), None);
+    }
+}
@@ -8,12 +8,28 @@ pub mod credential;
 pub mod wallet;
 pub mod messages;
 pub mod payment;
+pub mod context;
 
+use std::fmt;
 
 pub trait ToErrorCode {
     fn to_error_code(&self) -> u32;
 }
 
+/// Maps a domain error into the current error of the thread (see vcx_get_current_error)
+pub trait ToErrorDetails: ToErrorCode + fmt::Display {
+    fn record_error(&self) -> u32 {
+        context::set_current_error(self.to_error_code(), &self.to_string())
+    }
+}
+
+impl<T: ToErrorCode + fmt::Display> ToErrorDetails for T {}
+
+/// Same as record_error for the bare error codes of modules without their own error type
+pub fn record_error_code(code: u32) -> u32 {
+    context::set_current_error(code, "")
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
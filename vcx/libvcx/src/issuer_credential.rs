@@ -6,17 +6,20 @@ use api::VcxStateType;
 use messages;
 use settings;
 use messages::{ GeneralMessage, MessageResponseCode::MessageAccepted, send_message::parse_msg_uid };
+use messages::get_message::Message;
+use messages::outbox::Delivery;
 use connection;
-use credential_request::{ CredentialRequest };
+use credential_request::{ CredentialRequest, OutOfBandCredentialRequest };
+use messages::out_of_band::{self, OutOfBandMessage, Service};
 use utils::{error,
             error::INVALID_JSON,
             libindy::{ anoncreds::{ libindy_issuer_create_credential, libindy_issuer_create_credential_offer}, payments },
             constants::CRED_MSG,
-            openssl::encode
+            encoding::AttributeEncodings
 };
 use error::{ issuer_cred::IssuerCredError, ToErrorCode, payment::PaymentError};
-use utils::constants::DEFAULT_SERIALIZE_VERSION;
-use serde_json::Value;
+use utils::serialization::{self, Versioned, Migration};
+use state_machine::{self, ProtocolEvent, Transition, StateTransition, QueuedEvent};
 use object_cache::ObjectCache;
 
 lazy_static! {
@@ -25,14 +28,28 @@ lazy_static! {
 
 static CREDENTIAL_OFFER_ID_KEY: &str = "claim_offer_id";
 
+static ISSUER_CREDENTIAL_TRANSITIONS: &[Transition] = &[
+    Transition { event: ProtocolEvent::Initialize, from: &[VcxStateType::VcxStateNone], to: VcxStateType::VcxStateInitialized },
+    Transition { event: ProtocolEvent::SendOffer, from: &[VcxStateType::VcxStateInitialized], to: VcxStateType::VcxStateOfferSent },
+    Transition { event: ProtocolEvent::ReceiveRequest, from: &[VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateRequestReceived },
+    Transition { event: ProtocolEvent::SendCredential, from: &[VcxStateType::VcxStateRequestReceived], to: VcxStateType::VcxStateAccepted },
+];
+
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct IssuerCredential {
     source_id: String,
     credential_attributes: String,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "AttributeEncodings::is_empty")]
+    attribute_encodings: AttributeEncodings,
     msg_uid: String,
     schema_seq_no: u32,
     issuer_did: String,
     state: VcxStateType,
+    state_history: Vec<StateTransition>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queued_event: Option<QueuedEvent>,
     pub credential_request: Option<CredentialRequest>,
     pub credential_offer: Option<CredentialOffer>,
     credential_name: String,
@@ -48,6 +65,14 @@ pub struct IssuerCredential {
     issued_vk: String,
     remote_did: String, //their_pw_did for this relationship
     remote_vk: String,
+    // the offer was handed out without a connection, the request comes back to issued_did
+    #[serde(default)]
+    #[serde(skip_serializing_if = "::std::ops::Not::not")]
+    out_of_band: bool,
+    // where the holder of an out of band offer wants the credential
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    holder_service: Option<Service>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -63,6 +88,10 @@ pub struct CredentialOffer {
     pub claim_name: String,
     pub claim_id: String,
     pub msg_ref_id: Option<String>,
+    // encodings declared by the issuer, attributes not listed use the legacy encoding
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attr_encodings: Option<AttributeEncodings>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -104,10 +133,7 @@ impl IssuerCredential {
 
     fn send_credential_offer(&mut self, connection_handle: u32) -> Result<u32, IssuerCredError> {
         debug!("sending credential offer for issuer_credential {} to connection handle {}", self.source_id, connection_handle);
-        if self.state != VcxStateType::VcxStateInitialized {
-            warn!("credential {} has invalid state {} for sending credentialOffer", self.source_id, self.state as u32);
-            return Err(IssuerCredError::NotReadyError())
-        }
+        self.check_event(ProtocolEvent::SendOffer)?;
 
         if connection::is_valid_handle(connection_handle) == false {
             warn!("invalid connection handle ({})", connection_handle);
@@ -120,17 +146,8 @@ impl IssuerCredential {
         self.issued_vk = connection::get_pw_verkey(connection_handle).map_err(|x| IssuerCredError::CommonError(x.to_error_code()))?;
         self.remote_vk = connection::get_their_pw_verkey(connection_handle).map_err(|x| IssuerCredError::CommonError(x.to_error_code()))?;
 
-        let payment = self.generate_payment_info()?;
-        let credential_offer = self.generate_credential_offer(&self.issued_did)?;
-        let cred_json = json!(credential_offer);
-        let mut payload = Vec::new();
-
-        if payment.is_some() { payload.push(json!(payment.unwrap())); }
-        payload.push(cred_json);
-        let payload = match serde_json::to_string(&payload) {
-            Ok(p) => p,
-            Err(_) => return Err(IssuerCredError::CommonError(error::INVALID_JSON.code_num))
-        };
+        let to_did = self.issued_did.clone();
+        let (credential_offer, payload) = self.build_credential_offer_payload(&to_did)?;
 
         debug!("credential offer data: {}", payload);
 
@@ -144,27 +161,146 @@ impl IssuerCredential {
             .agent_did(&self.agent_did)
             .agent_vk(&self.agent_vk)
             .status_code(&MessageAccepted.as_string())
-            .send_secure() {
+            .send_secure_or_queue() {
             Err(x) => {
                 warn!("could not send credentialOffer: {}", x);
                 return Err(IssuerCredError::CommonError(x));
             },
-            Ok(response) => {
-                self.msg_uid = parse_msg_uid(&response[0]).map_err(|ec| IssuerCredError::CommonError(ec))?;
-                self.state = VcxStateType::VcxStateOfferSent;
+            Ok(Delivery::Sent(response)) => {
+                self.msg_uid = parse_msg_uid(&response).map_err(|ec| IssuerCredError::CommonError(ec))?;
+                self.apply_event(ProtocolEvent::SendOffer)?;
                 self.credential_offer = Some(credential_offer);
                 debug!("sent credential offer for: {}", self.source_id);
                 return Ok(error::SUCCESS.code_num);
+            },
+            Ok(Delivery::Queued(id)) => {
+                self.queue_event(ProtocolEvent::SendOffer, id)?;
+                self.credential_offer = Some(credential_offer);
+                debug!("queued credential offer for: {}", self.source_id);
+                return Ok(error::SUCCESS.code_num);
             }
         }
     }
 
+    fn build_credential_offer_payload(&mut self, to_did: &str) -> Result<(CredentialOffer, String), IssuerCredError> {
+        let payment = self.generate_payment_info()?;
+        let credential_offer = self.generate_credential_offer(to_did)?;
+        let cred_json = json!(credential_offer);
+        let mut payload = Vec::new();
+
+        if payment.is_some() { payload.push(json!(payment.unwrap())); }
+        payload.push(cred_json);
+        let payload = match serde_json::to_string(&payload) {
+            Ok(p) => p,
+            Err(_) => return Err(IssuerCredError::CommonError(error::INVALID_JSON.code_num))
+        };
+        Ok((credential_offer, payload))
+    }
+
+    // The request comes back to a pairwise key on our agency that is not shared with any
+    // connection, the holder is only known by the reply-to it sends with its request.
+    fn generate_out_of_band_offer(&mut self) -> Result<String, IssuerCredError> {
+        debug!("generating out of band credential offer for issuer_credential {}", self.source_id);
+        self.check_event(ProtocolEvent::SendOffer)?;
+
+        let connection_handle = connection::build_connection(&self.source_id).map_err(|e| IssuerCredError::CommonError(e.to_error_code()))?;
+        self.agent_did = connection::get_agent_did(connection_handle).map_err(|e| IssuerCredError::CommonError(e.to_error_code()))?;
+        self.agent_vk = connection::get_agent_verkey(connection_handle).map_err(|e| IssuerCredError::CommonError(e.to_error_code()))?;
+        self.issued_did = connection::get_pw_did(connection_handle).map_err(|x| IssuerCredError::CommonError(x.to_error_code()))?;
+        self.issued_vk = connection::get_pw_verkey(connection_handle).map_err(|x| IssuerCredError::CommonError(x.to_error_code()))?;
+
+        let (credential_offer, offer) = match self.build_out_of_band_offer() {
+            Ok(offer) => offer,
+            Err(err) => {
+                self.delete_out_of_band_agent();
+                connection::release(connection_handle).ok();
+                return Err(err);
+            }
+        };
+        connection::release(connection_handle).map_err(|x| IssuerCredError::CommonError(x.to_error_code()))?;
+
+        self.credential_offer = Some(credential_offer);
+        self.out_of_band = true;
+        self.apply_event(ProtocolEvent::SendOffer)?;
+        Ok(offer)
+    }
+
+    fn build_out_of_band_offer(&mut self) -> Result<(CredentialOffer, String), IssuerCredError> {
+        let (credential_offer, payload) = self.build_credential_offer_payload("")?;
+        let service = Service::for_pairwise(&self.issued_did, &self.issued_vk, &self.agent_did, &self.agent_vk)
+            .map_err(|ec| IssuerCredError::CommonError(ec))?;
+        let offer = OutOfBandMessage::create("CRED_OFFER", &payload, service)
+            .and_then(|offer| offer.to_string())
+            .map_err(|ec| IssuerCredError::CommonError(ec))?;
+        Ok((credential_offer, offer))
+    }
+
+    // The agent of an out-of-band offer only ever receives the one credential request
+    fn delete_out_of_band_agent(&self) {
+        if let Err(ec) = connection::delete_pairwise_agent(&self.issued_did, &self.issued_vk, &self.agent_did, &self.agent_vk) {
+            warn!("could not delete the agent of out of band credential offer {}: {}", self.source_id, error::error_string(ec));
+        }
+    }
+
+    fn get_out_of_band_request_status(&mut self) -> Result<u32, IssuerCredError> {
+        let received = messages::get_messages()
+            .to(&self.issued_did)
+            .to_vk(&self.issued_vk)
+            .agent_did(&self.agent_did)
+            .agent_vk(&self.agent_vk)
+            .send_secure()
+            .map_err(|ec| IssuerCredError::CommonError(ec))?;
+
+        for message in received.iter() {
+            let payload = match message.payload {
+                Some(ref payload) => messages::to_u8(payload),
+                None => continue,
+            };
+            match out_of_band::parse_reply(&self.issued_vk, &payload) {
+                Ok((ref msg_type, ref request)) if msg_type == "CRED_REQ" => match serde_json::from_str::<OutOfBandCredentialRequest>(request) {
+                    Ok(request) => {
+                        self.msg_uid = message.uid.clone();
+                        self.remote_did = request.credential_request.from_did.clone();
+                        self.holder_service = Some(request.service);
+                        let state = self.receive_credential_request(request.credential_request);
+                        self.delete_out_of_band_agent();
+                        return state;
+                    },
+                    Err(err) => warn!("invalid credential request in message {}: {}", message.uid, err),
+                },
+                _ => debug!("message {} is not a credential request", message.uid),
+            }
+        }
+
+        Ok(self.get_state())
+    }
+
+    fn credential_data(&self, to: &str) -> Result<String, IssuerCredError> {
+        let attrs_with_encodings = self.create_attributes_encodings()?;
+        if settings::test_indy_mode_enabled() { return Ok(CRED_MSG.to_string()) }
+
+        let cred = self.generate_credential(&attrs_with_encodings, to)?;
+        serde_json::to_string(&cred).or(Err(IssuerCredError::InvalidCred()))
+    }
+
+    fn send_credential_to_service(&mut self) -> Result<u32, IssuerCredError> {
+        debug!("sending credential for issuer_credential {} to the holder service", self.source_id);
+        self.check_event(ProtocolEvent::SendCredential)?;
+
+        self.verify_payment().map_err(|e| IssuerCredError::CommonError(e))?;
+
+        let service = self.holder_service.clone().ok_or(IssuerCredError::InvalidCredRequest())?;
+        let data = self.credential_data(&self.remote_did)?;
+        out_of_band::reply(&service, "CRED", &data).map_err(|ec| IssuerCredError::CommonError(ec))?;
+
+        self.apply_event(ProtocolEvent::SendCredential)?;
+        debug!("issued credential: {}", self.source_id);
+        Ok(error::SUCCESS.code_num)
+    }
+
     fn send_credential(&mut self, connection_handle: u32) -> Result<u32, IssuerCredError> {
         debug!("sending credential for issuer_credential {} to connection handle {}", self.source_id, connection_handle);
-        if self.state != VcxStateType::VcxStateRequestReceived {
-            warn!("credential {} has invalid state {} for sending credential", self.source_id, self.state as u32);
-            return Err(IssuerCredError::NotReadyError());
-        }
+        self.check_event(ProtocolEvent::SendCredential)?;
 
         if connection::is_valid_handle(connection_handle) == false {
             warn!("invalid connection handle ({}) in send_credential_offer", connection_handle);
@@ -174,11 +310,7 @@ impl IssuerCredential {
         self.verify_payment().map_err(|e| IssuerCredError::CommonError(e))?;
 
         let to = connection::get_pw_did(connection_handle).map_err(|e| IssuerCredError::CommonError(e.to_error_code()))?;
-        let attrs_with_encodings = self.create_attributes_encodings()?;
-        let data = if settings::test_indy_mode_enabled() { CRED_MSG.to_string()} else {
-            let cred = self.generate_credential(&attrs_with_encodings, &to)?;
-            serde_json::to_string(&cred).or(Err(IssuerCredError::InvalidCred()))?
-        };
+        let data = self.credential_data(&to)?;
 
         debug!("credential data: {}", data);
 
@@ -192,22 +324,34 @@ impl IssuerCredential {
             .edge_agent_payload(&data)
             .agent_did(&self.agent_did)
             .agent_vk(&self.agent_vk)
-            .send_secure() {
+            .send_secure_or_queue() {
             Err(x) => {
                 warn!("could not send credential: {}", x);
                 return Err(IssuerCredError::CommonError(x));
             },
-            Ok(response) => {
-                self.msg_uid = parse_msg_uid(&response[0]).map_err(|ec| IssuerCredError::CommonError(ec))?;
-                self.state = VcxStateType::VcxStateAccepted;
+            Ok(Delivery::Sent(response)) => {
+                self.msg_uid = parse_msg_uid(&response).map_err(|ec| IssuerCredError::CommonError(ec))?;
+                self.apply_event(ProtocolEvent::SendCredential)?;
                 debug!("issued credential: {}", self.source_id);
                 return Ok(error::SUCCESS.code_num);
+            },
+            Ok(Delivery::Queued(id)) => {
+                self.queue_event(ProtocolEvent::SendCredential, id)?;
+                debug!("queued credential: {}", self.source_id);
+                return Ok(error::SUCCESS.code_num);
             }
         }
     }
 
     pub fn create_attributes_encodings(&self) -> Result<String, IssuerCredError> {
-        encode_attributes(&self.credential_attributes)
+        encode_attributes(&self.credential_attributes, &self.attribute_encodings)
+    }
+
+    fn set_attribute_encodings(&mut self, encodings: AttributeEncodings) -> Result<u32, IssuerCredError> {
+        // every value must be encodable before anything is offered
+        encode_attributes(&self.credential_attributes, &encodings)?;
+        self.attribute_encodings = encodings;
+        Ok(error::SUCCESS.code_num)
     }
 
     // TODO: The error arm of this Result is never used in any calling functions.
@@ -217,6 +361,9 @@ impl IssuerCredential {
         if self.state == VcxStateType::VcxStateRequestReceived {
             return Ok(self.get_state());
         }
+        else if self.state == VcxStateType::VcxStateOfferSent && self.out_of_band {
+            return self.get_out_of_band_request_status();
+        }
         else if self.state != VcxStateType::VcxStateOfferSent || self.msg_uid.is_empty() || self.issued_did.is_empty() {
 
             return Ok(self.get_state());
@@ -228,13 +375,57 @@ impl IssuerCredential {
                                                                &self.agent_vk)
             .map_err(|wc|IssuerCredError::CommonError(wc))?;
 
-        self.credential_request = Some(parse_credential_req_payload(&payload)?);
+        let credential_request = parse_credential_req_payload(&payload)?;
+        self.receive_credential_request(credential_request)
+    }
+
+    fn update_state_with_messages(&mut self, messages: &[Message]) -> Result<u32, IssuerCredError> {
+        if self.state != VcxStateType::VcxStateOfferSent || self.msg_uid.is_empty() {
+            return Ok(self.get_state());
+        }
+
+        match messages::get_message::find_ref_msg(&self.msg_uid, messages) {
+            Some(data) => {
+                let credential_request = CredentialRequest::from_str(&data)?;
+                self.receive_credential_request(credential_request)
+            },
+            None => Ok(self.get_state()),
+        }
+    }
+
+    fn receive_credential_request(&mut self, credential_request: CredentialRequest) -> Result<u32, IssuerCredError> {
+        self.credential_request = Some(credential_request);
         debug!("received credential request for credential offer: {}", self.source_id);
-        self.state = VcxStateType::VcxStateRequestReceived;
+        self.apply_event(ProtocolEvent::ReceiveRequest)?;
         Ok(self.get_state())
     }
 
+    fn check_event(&self, event: ProtocolEvent) -> Result<(), IssuerCredError> {
+        state_machine::next_state(ISSUER_CREDENTIAL_TRANSITIONS, self.state, event)
+            .map(|_| ())
+            .map_err(|ec| IssuerCredError::CommonError(ec))
+    }
+
+    fn apply_event(&mut self, event: ProtocolEvent) -> Result<(), IssuerCredError> {
+        state_machine::apply_event(ISSUER_CREDENTIAL_TRANSITIONS, &mut self.state, &mut self.state_history, event)
+            .map_err(|ec| IssuerCredError::CommonError(ec))
+    }
+
+    fn queue_event(&mut self, event: ProtocolEvent, outbox_id: String) -> Result<(), IssuerCredError> {
+        state_machine::queue_event(ISSUER_CREDENTIAL_TRANSITIONS, &mut self.state, &mut self.state_history,
+                                   &mut self.queued_event, event, outbox_id)
+            .map_err(|ec| IssuerCredError::CommonError(ec))
+    }
+
     fn update_state(&mut self) -> Result<u32, IssuerCredError> {
+        let delivered = state_machine::poll_queued_event(ISSUER_CREDENTIAL_TRANSITIONS, &mut self.state,
+                                                         &mut self.state_history, &mut self.queued_event)
+            .map_err(|ec| IssuerCredError::CommonError(ec))?;
+        if let Some(response) = delivered {
+            self.msg_uid = parse_msg_uid(&response).map_err(|ec| IssuerCredError::CommonError(ec))?;
+            debug!("queued message of issuer_credential {} was delivered", self.source_id);
+        }
+
         self.get_credential_offer_status()
         //There will probably be more things here once we do other things with the credential
     }
@@ -294,6 +485,7 @@ impl IssuerCredential {
             claim_id: String::from(self.credential_id.clone()),
             msg_ref_id: None,
             cred_def_id: self.cred_def_id.clone(),
+            attr_encodings: if self.attribute_encodings.is_empty() { None } else { Some(self.attribute_encodings.clone()) },
             libindy_offer,
         })
     }
@@ -337,20 +529,24 @@ impl IssuerCredential {
     }
 
     pub fn to_string(&self) -> String {
-        json!({
-            "version": DEFAULT_SERIALIZE_VERSION,
-            "data": json!(self),
-        }).to_string()
+        serialization::to_versioned_string(self)
     }
 
     fn from_str(s: &str) -> Result<IssuerCredential, IssuerCredError> {
-        let data: Value = serde_json::from_str(&s)?;
-        let cred = serde_json::from_value(data["data"].clone())?;
-        Ok(cred)
+        serialization::from_versioned_str(s).map_err(|err| {
+            if err == error::INVALID_JSON.code_num { IssuerCredError::InvalidJson() } else { IssuerCredError::CommonError(err) }
+        })
     }
 }
 
-pub fn encode_attributes(attributes: &str) -> Result<String, IssuerCredError> {
+impl Versioned for IssuerCredential {
+    fn migrations() -> Vec<Migration> {
+        vec![serialization::unchanged("1.0", "2.0"),
+             Migration { from: "2.0", to: "3.0", migrate: state_machine::add_empty_state_history }]
+    }
+}
+
+pub fn encode_attributes(attributes: &str, encodings: &AttributeEncodings) -> Result<String, IssuerCredError> {
     let mut attributes: serde_json::Value = match serde_json::from_str(attributes) {
         Ok(x) => x,
         Err(e) => {
@@ -384,7 +580,7 @@ pub fn encode_attributes(attributes: &str) -> Result<String, IssuerCredError> {
                 return Err(IssuerCredError::CommonError(error::INVALID_ATTRIBUTES_STRUCTURE.code_num))
             },
         };
-        let encoded = encode(value).map_err(|x| IssuerCredError::CommonError(x))?;
+        let encoded = encodings.encode(attr, value).map_err(|x| IssuerCredError::CommonError(x))?;
         let encoded_as_value: serde_json::Value = serde_json::Value::from(encoded);
         list.push(encoded_as_value);
     }
@@ -404,6 +600,13 @@ pub fn get_encoded_attributes(handle:u32) -> Result<String, IssuerCredError>{
     }).map_err(|ec|IssuerCredError::CommonError(ec))
 }
 
+pub fn set_attribute_encodings(handle: u32, encodings: &str) -> Result<u32, IssuerCredError> {
+    let encodings = AttributeEncodings::from_str(encodings).map_err(|ec| IssuerCredError::CommonError(ec))?;
+    ISSUER_CREDENTIAL_MAP.get_mut(handle, |i| {
+        i.set_attribute_encodings(encodings.clone()).map_err(|ec| ec.to_error_code())
+    }).map_err(|ec| IssuerCredError::CommonError(ec))
+}
+
 pub fn get_offer_uid(handle: u32) -> Result<String,u32> {
     ISSUER_CREDENTIAL_MAP.get(handle,|i|{
         Ok(i.get_offer_uid().clone())
@@ -443,8 +646,11 @@ pub fn issuer_credential_create(cred_def_id: String,
         source_id,
         msg_uid: String::new(),
         credential_attributes: credential_data,
+        attribute_encodings: AttributeEncodings::default(),
         issuer_did,
         state: VcxStateType::VcxStateNone,
+        state_history: Vec::new(),
+        queued_event: None,
         //Todo: Take out schema
         schema_seq_no: 0,
         credential_request: None,
@@ -457,6 +663,8 @@ pub fn issuer_credential_create(cred_def_id: String,
         issued_vk: String::new(),
         remote_did: String::new(),
         remote_vk: String::new(),
+        out_of_band: false,
+        holder_service: None,
         agent_did: String::new(),
         agent_vk: String::new(),
         cred_def_id
@@ -464,7 +672,7 @@ pub fn issuer_credential_create(cred_def_id: String,
 
     new_issuer_credential.validate_credential_offer()?;
 
-    new_issuer_credential.state = VcxStateType::VcxStateInitialized;
+    new_issuer_credential.apply_event(ProtocolEvent::Initialize)?;
 
     let new_handle = ISSUER_CREDENTIAL_MAP.add(new_issuer_credential).map_err(|key|IssuerCredError::CreateError())?;
     debug!("inserting handle {} into credential_issuer table", new_handle);
@@ -481,6 +689,24 @@ pub fn update_state(handle: u32) -> Result<u32, IssuerCredError> {
     }).map_err(|ec|IssuerCredError::CommonError(ec))
 }
 
+/// Handles of credential offers waiting for a credential request, with the did and uid of the offer
+pub fn get_waiting_handles() -> Vec<(u32, String, Option<String>)> {
+    ISSUER_CREDENTIAL_MAP.handles().into_iter().filter_map(|handle| {
+        ISSUER_CREDENTIAL_MAP.get(handle, |i| {
+            // out-of-band offers have no msg_uid, their answer is not on a pairwise did
+            Ok(if i.state == VcxStateType::VcxStateOfferSent && !i.issued_did.is_empty() && !i.msg_uid.is_empty() {
+                Some((handle, i.issued_did.clone(), Some(i.msg_uid.clone())))
+            } else { None })
+        }).ok().and_then(|waiting| waiting)
+    }).collect()
+}
+
+pub fn update_state_with_messages(handle: u32, messages: &[Message]) -> Result<u32, IssuerCredError> {
+    ISSUER_CREDENTIAL_MAP.get_mut(handle, |i| {
+        i.update_state_with_messages(messages).map_err(|err| err.to_error_code())
+    }).map_err(|ec| if ec == error::INVALID_OBJ_HANDLE.code_num { IssuerCredError::InvalidHandle() } else { IssuerCredError::CommonError(ec) })
+}
+
 pub fn get_state(handle: u32) -> Result<u32, u32> {
     ISSUER_CREDENTIAL_MAP.get(handle,|i|{
         Ok(i.get_state())
@@ -524,6 +750,24 @@ pub fn send_credential_offer(handle: u32, connection_handle: u32) -> Result<u32,
     }).map_err(|ec|IssuerCredError::CommonError(ec))
 }
 
+pub fn generate_out_of_band_offer(handle: u32) -> Result<String, IssuerCredError> {
+    ISSUER_CREDENTIAL_MAP.get_mut(handle, |i| {
+        i.generate_out_of_band_offer().map_err(|ec| ec.to_error_code())
+    }).map_err(|ec| IssuerCredError::CommonError(ec))
+}
+
+pub fn send_credential_to_service(handle: u32) -> Result<u32, IssuerCredError> {
+    ISSUER_CREDENTIAL_MAP.get_mut(handle, |i| {
+        i.send_credential_to_service().map_err(|ec| ec.to_error_code())
+    }).map_err(|ec| IssuerCredError::CommonError(ec))
+}
+
+pub fn get_state_history(handle: u32) -> Result<String, IssuerCredError> {
+    ISSUER_CREDENTIAL_MAP.get(handle, |i| {
+        state_machine::history_to_string(&i.state_history)
+    }).map_err(|ec| IssuerCredError::CommonError(ec))
+}
+
 pub fn send_credential(handle: u32, connection_handle: u32) -> Result<u32,IssuerCredError> {
     ISSUER_CREDENTIAL_MAP.get_mut(handle,|i|{
         i.send_credential(connection_handle).map_err(|ec| ec.to_error_code())
@@ -652,10 +896,13 @@ pub mod tests {
             schema_seq_no: 32,
             msg_uid: "1234".to_owned(),
             credential_attributes: CREDENTIAL_DATA.to_owned(),
+            attribute_encodings: AttributeEncodings::default(),
             issuer_did: "QTrbV4raAcND4DWWzBmdsh".to_owned(),
             issued_did: "8XFh8yBzrpJQmNyZzgoTqB".to_owned(),
             issued_vk: VERKEY.to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             credential_name: DEFAULT_CREDENTIAL_NAME.to_owned(),
             credential_request: Some(credential_req.to_owned()),
             credential_offer: Some(credential_offer.to_owned()),
@@ -665,6 +912,8 @@ pub mod tests {
             ref_msg_id: None,
             remote_did: DID.to_string(),
             remote_vk: VERKEY.to_string(),
+            out_of_band: false,
+            holder_service: None,
             agent_did: DID.to_string(),
             agent_vk: VERKEY.to_string(),
             cred_def_id: CRED_DEF_ID.to_string(),
@@ -695,8 +944,11 @@ pub mod tests {
             source_id: "source_id".to_string(),
             msg_uid: String::new(),
             credential_attributes: credential_data.to_string(),
+            attribute_encodings: AttributeEncodings::default(),
             issuer_did,
             state: VcxStateType::VcxStateNone,
+            state_history: Vec::new(),
+            queued_event: None,
             //Todo: Take out schema
             schema_seq_no: 0,
             credential_request: None,
@@ -710,6 +962,8 @@ pub mod tests {
             issued_vk: String::new(),
             remote_did: String::new(),
             remote_vk: String::new(),
+            out_of_band: false,
+            holder_service: None,
             agent_did: String::new(),
             agent_vk: String::new(),
             cred_def_id
@@ -778,6 +1032,109 @@ pub mod tests {
         assert_eq!(get_offer_uid(handle).unwrap(), "ntc2ytb");
     }
 
+    #[test]
+    fn test_update_state_with_downloaded_messages() {
+        set_default_and_enable_test_mode();
+
+        let mut offer = Message::new();
+        offer.uid = "1234".to_string();
+        offer.status_code = MessageAccepted.as_string();
+        offer.ref_msg_id = Some("5678".to_string());
+        let mut request = Message::new();
+        request.uid = "5678".to_string();
+        request.status_code = messages::MessageResponseCode::MessagePending.as_string();
+        request.decrypted_payload = Some(json!({
+            "@type": {"name": "CRED_REQ", "ver": "1.0", "fmt": "json"},
+            "@msg": CREDENTIAL_REQ_STRING,
+        }).to_string());
+
+        let mut credential = create_standard_issuer_credential();
+        credential.credential_request = None;
+        let handle = ISSUER_CREDENTIAL_MAP.add(credential).unwrap();
+        assert!(get_waiting_handles().contains(&(handle, "8XFh8yBzrpJQmNyZzgoTqB".to_string(), Some("1234".to_string()))));
+
+        assert_eq!(update_state_with_messages(handle, &[offer.clone()]).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(update_state_with_messages(handle, &[offer, request]).unwrap(), VcxStateType::VcxStateRequestReceived as u32);
+        assert!(!get_waiting_handles().iter().any(|&(h, _, _)| h == handle));
+    }
+
+    #[test]
+    fn test_state_transitions_are_guarded() {
+        set_default_and_enable_test_mode();
+
+        let connection_handle = build_connection("test_state_transitions_are_guarded").unwrap();
+        let handle = issuer_credential_create(CRED_DEF_ID.to_string(),
+                                              "1".to_string(),
+                                              "8XFh8yBzrpJQmNyZzgoTqB".to_owned(),
+                                              "credential_name".to_string(),
+                                              "{\"attr\":\"value\"}".to_owned(),
+                                              1).unwrap();
+
+        assert_eq!(send_credential(handle, connection_handle).err(),
+                   Some(IssuerCredError::CommonError(error::INVALID_STATE.code_num)));
+        let details = ::error::context::get_current_error().unwrap();
+        assert_eq!(details.causes[0], "SendCredential is not allowed in state VcxStateInitialized");
+
+        send_credential_offer(handle, connection_handle).unwrap();
+        let history: Vec<StateTransition> = serde_json::from_str(&get_state_history(handle).unwrap()).unwrap();
+        assert_eq!(history.iter().map(|t| t.event).collect::<Vec<ProtocolEvent>>(),
+                   vec![ProtocolEvent::Initialize, ProtocolEvent::SendOffer]);
+        assert_eq!(history[1].to, VcxStateType::VcxStateOfferSent);
+    }
+
+    #[test]
+    fn test_queued_credential_offer() {
+        set_default_and_enable_test_mode();
+
+        let handle = issuer_credential_create(CRED_DEF_ID.to_string(),
+                                              "1".to_string(),
+                                              "8XFh8yBzrpJQmNyZzgoTqB".to_owned(),
+                                              "credential_name".to_string(),
+                                              "{\"attr\":\"value\"}".to_owned(),
+                                              1).unwrap();
+
+        let outbox_id = ::messages::outbox::tests::queue_test_message(::messages::outbox::MessageKind::SendMessage);
+        ISSUER_CREDENTIAL_MAP.get_mut(handle, |i| {
+            i.queue_event(ProtocolEvent::SendOffer, outbox_id.clone()).map_err(|err| err.to_error_code())
+        }).unwrap();
+        assert_eq!(update_state(handle).unwrap(), VcxStateType::VcxStateQueued as u32);
+
+        ::messages::outbox::tests::deliver_test_message(&outbox_id, r#"{"uids":["ntc2ytb"]}"#);
+        assert_eq!(update_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(ISSUER_CREDENTIAL_MAP.get(handle, |i| Ok(i.msg_uid.clone())).unwrap(), "ntc2ytb");
+    }
+
+    #[test]
+    fn test_out_of_band_credential_offer() {
+        set_default_and_enable_test_mode();
+
+        let handle = issuer_credential_create(CRED_DEF_ID.to_string(),
+                                              "1".to_string(),
+                                              "8XFh8yBzrpJQmNyZzgoTqB".to_owned(),
+                                              "credential_name".to_string(),
+                                              "{\"attr\":\"value\"}".to_owned(),
+                                              0).unwrap();
+        let offer = generate_out_of_band_offer(handle).unwrap();
+        let offer = OutOfBandMessage::from_str(&offer, "CRED_OFFER").unwrap();
+        assert!(offer.msg.contains("credential_name"));
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(update_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(generate_out_of_band_offer(handle).err(), Some(IssuerCredError::CommonError(error::INVALID_STATE.code_num)));
+
+        let request = OutOfBandCredentialRequest {
+            credential_request: CredentialRequest::from_str(CREDENTIAL_REQ_STRING).unwrap(),
+            service: offer.service.clone(),
+        };
+        ISSUER_CREDENTIAL_MAP.get_mut(handle, |i| {
+            i.holder_service = Some(request.service.clone());
+            i.receive_credential_request(request.credential_request.clone()).map_err(|err| err.to_error_code())
+        }).unwrap();
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateRequestReceived as u32);
+
+        send_credential_to_service(handle).unwrap();
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateAccepted as u32);
+    }
+
     #[cfg(feature = "pool_tests")]
     #[test]
     fn test_generate_cred_offer() {
@@ -847,7 +1204,7 @@ pub mod tests {
 					 1,).unwrap();
         let string = to_string(handle).unwrap();
         let value: serde_json::Value = serde_json::from_str(&string).unwrap();
-        assert_eq!(value["version"], "1.0");
+        assert_eq!(value["version"], ::utils::constants::DEFAULT_SERIALIZE_VERSION);
         assert!(!string.is_empty());
         assert!(release(handle).is_ok());
         let new_handle = from_string(&string).unwrap();
@@ -868,10 +1225,13 @@ pub mod tests {
             schema_seq_no: 32,
             msg_uid: "1234".to_owned(),
             credential_attributes: "nothing".to_owned(),
+            attribute_encodings: AttributeEncodings::default(),
             issuer_did: "QTrbV4raAcND4DWWzBmdsh".to_owned(),
             issued_did: "8XFh8yBzrpJQmNyZzgoTqB".to_owned(),
             issued_vk: VERKEY.to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             credential_request: Some(credential_req.to_owned()),
             credential_offer: Some(credential_offer.to_owned()),
             credential_name: DEFAULT_CREDENTIAL_NAME.to_owned(),
@@ -882,6 +1242,8 @@ pub mod tests {
             payment_address: None,
             remote_did: DID.to_string(),
             remote_vk: VERKEY.to_string(),
+            out_of_band: false,
+            holder_service: None,
             agent_did: DID.to_string(),
             agent_vk: VERKEY.to_string(),
         };
@@ -1051,4 +1413,13 @@ pub mod tests {
         let payment = serde_json::to_string(&credential.get_payment_txn().unwrap()).unwrap();
         assert!(payment.len() > 20);
     }
+
+    #[test]
+    fn test_deserialize_historical_version() {
+        use utils::constants::{DEFAULT_SERIALIZED_ISSUER_CREDENTIAL, DEFAULT_SERIALIZE_VERSION};
+        let obj = IssuerCredential::from_str(DEFAULT_SERIALIZED_ISSUER_CREDENTIAL).unwrap();
+        let serialized = obj.to_string();
+        assert_eq!(serialization::parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(IssuerCredential::from_str(&serialized).unwrap().to_string(), serialized);
+    }
 }
@@ -34,10 +34,16 @@ pub mod proof;
 pub mod schema;
 pub mod credential_def;
 pub mod proof_compliance;
+pub mod proof_template;
+pub mod link_secret;
 pub mod error;
 pub mod credential;
 pub mod object_cache;
+pub mod state_machine;
+pub mod dispatcher;
 pub mod disclosed_proof;
+pub mod credential_selection;
+pub mod nym;
 
 #[allow(unused_imports)]
 #[cfg(test)]
@@ -0,0 +1,164 @@
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use serde_json::Value;
+use settings;
+use utils::error;
+use utils::libindy::anoncreds;
+use utils::libindy::wallet;
+
+static LINK_SECRET_RECORD: &str = "vcx_link_secret";
+static CREDENTIAL_LINK_SECRET_RECORD: &str = "vcx_credential_link_secret";
+static WALLET_GET_OPTIONS: &str = r#"{"retrieveType":false,"retrieveValue":true,"retrieveTags":false}"#;
+// libindy cannot enumerate master secrets, the aliases created here are listed in a single record
+static ALIASES_ID: &str = "aliases";
+
+lazy_static! {
+    // stands in for the wallet in test mode, where wallet records are mocked
+    static ref TEST_RECORDS: Mutex<HashMap<(String, String), String>> = Default::default();
+    // serializes updates of the alias list so concurrent creates are all recorded
+    static ref UPDATE_LOCK: Mutex<()> = Default::default();
+}
+
+fn store_record(xtype: &str, id: &str, value: &str) -> Result<(), u32> {
+    if settings::test_indy_mode_enabled() {
+        TEST_RECORDS.lock().or(Err(error::UNKNOWN_ERROR.code_num))?
+            .insert((xtype.to_string(), id.to_string()), value.to_string());
+        return Ok(());
+    }
+
+    match wallet::add_record(xtype, id, value, "{}") {
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => wallet::update_record_value(xtype, id, value),
+        rc => rc,
+    }
+}
+
+fn get_record(xtype: &str, id: &str) -> Option<String> {
+    if settings::test_indy_mode_enabled() {
+        return TEST_RECORDS.lock().ok()?.get(&(xtype.to_string(), id.to_string())).cloned();
+    }
+
+    let record = wallet::get_record(xtype, id, WALLET_GET_OPTIONS).ok()?;
+    let record: Value = serde_json::from_str(&record).ok()?;
+    record["value"].as_str().map(String::from)
+}
+
+/// Alias of link secrets in wallets created before link secrets could be chosen
+pub fn default_alias() -> String { settings::DEFAULT_LINK_SECRET_ALIAS.to_string() }
+
+/// Aliases of the link secrets in the wallet. The default link secret created at provisioning
+/// is always listed first.
+pub fn list() -> Result<Vec<String>, u32> {
+    let mut aliases = vec![default_alias()];
+    if let Some(created) = get_record(LINK_SECRET_RECORD, ALIASES_ID) {
+        let created: Vec<String> = serde_json::from_str(&created).or(Err(error::INVALID_JSON.code_num))?;
+        aliases.extend(created.into_iter().filter(|alias| alias != settings::DEFAULT_LINK_SECRET_ALIAS));
+    }
+    Ok(aliases)
+}
+
+pub fn exists(alias: &str) -> Result<bool, u32> {
+    Ok(list()?.iter().any(|x| x == alias))
+}
+
+/// Creates a new link secret in the wallet, credentials requested with it cannot be linked to
+/// credentials of any other link secret.
+pub fn create(alias: &str) -> Result<(), u32> {
+    if alias.trim().is_empty() { return Err(error::INVALID_OPTION.code_num) }
+
+    let _guard = UPDATE_LOCK.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    let mut aliases = list()?;
+    if aliases.iter().any(|x| x == alias) {
+        warn!("link secret {} already exists", alias);
+        return Err(error::DUPLICATE_LINK_SECRET.code_num);
+    }
+
+    debug!("creating link secret {}", alias);
+    anoncreds::libindy_prover_create_master_secret(alias)?;
+    aliases.push(alias.to_string());
+    let aliases = serde_json::to_string(&aliases[1..]).or(Err(error::INVALID_JSON.code_num))?;
+    store_record(LINK_SECRET_RECORD, ALIASES_ID, &aliases)
+}
+
+/// Link secret new credentials and proofs use unless another one is chosen for them
+pub fn selected() -> String {
+    settings::get_config_value(settings::CONFIG_LINK_SECRET_ALIAS).unwrap_or(default_alias())
+}
+
+pub fn select(alias: &str) -> Result<(), u32> {
+    if !exists(alias)? { return Err(error::LINK_SECRET_NOT_FOUND.code_num) }
+
+    settings::set_config_value(settings::CONFIG_LINK_SECRET_ALIAS, alias);
+    Ok(())
+}
+
+/// Records the link secret a stored credential was issued for
+pub fn bind_credential(cred_id: &str, alias: &str) -> Result<(), u32> {
+    store_record(CREDENTIAL_LINK_SECRET_RECORD, cred_id, alias)
+}
+
+/// Link secret of a stored credential, None for credentials stored before it was recorded
+pub fn credential_link_secret(cred_id: &str) -> Option<String> {
+    get_record(CREDENTIAL_LINK_SECRET_RECORD, cred_id)
+}
+
+/// The link secret all of cred_ids are bound to, None when cred_ids is empty. Credentials stored
+/// before it was recorded were issued for the default link secret. A proof is created with a
+/// single link secret, so credentials bound to different ones cannot be used together.
+pub fn bound_link_secret(cred_ids: &[&str]) -> Result<Option<String>, u32> {
+    let mut rtn: Option<String> = None;
+    for cred_id in cred_ids {
+        let alias = credential_link_secret(cred_id).unwrap_or(default_alias());
+        match rtn {
+            Some(ref bound) if &alias != bound => {
+                warn!("credential {} is bound to link secret {}, other credentials to {}", cred_id, alias, bound);
+                ::error::context::add_error_context("credential", cred_id);
+                return Err(error::LINK_SECRET_MISMATCH.code_num);
+            },
+            Some(_) => (),
+            None => rtn = Some(alias),
+        }
+    }
+    Ok(rtn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_select_link_secret() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        assert_eq!(list().unwrap()[0], "main");
+        assert_eq!(create("main"), Err(error::DUPLICATE_LINK_SECRET.code_num));
+        assert_eq!(select("test_select_unknown"), Err(error::LINK_SECRET_NOT_FOUND.code_num));
+
+        create("test_select_work").unwrap();
+        assert!(list().unwrap().contains(&"test_select_work".to_string()));
+        assert_eq!(create("test_select_work"), Err(error::DUPLICATE_LINK_SECRET.code_num));
+
+        select("test_select_work").unwrap();
+        assert_eq!(selected(), "test_select_work");
+        select("main").unwrap();
+    }
+
+    #[test]
+    fn test_bound_link_secret() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        bind_credential("test_bound_cred_1", "work").unwrap();
+        bind_credential("test_bound_cred_2", "work").unwrap();
+        bind_credential("test_bound_cred_3", "personal").unwrap();
+
+        assert_eq!(bound_link_secret(&["test_bound_cred_1", "test_bound_cred_2"]).unwrap(), Some("work".to_string()));
+        assert_eq!(bound_link_secret(&["test_bound_unknown"]).unwrap(), Some(default_alias()));
+        assert_eq!(bound_link_secret(&[]).unwrap(), None);
+        assert_eq!(bound_link_secret(&["test_bound_cred_1", "test_bound_cred_3"]), Err(error::LINK_SECRET_MISMATCH.code_num));
+        // credentials stored before link secrets were recorded belong to the default one
+        assert_eq!(bound_link_secret(&["test_bound_cred_1", "test_bound_unknown"]), Err(error::LINK_SECRET_MISMATCH.code_num));
+    }
+}
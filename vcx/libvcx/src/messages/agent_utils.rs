@@ -1,10 +1,13 @@
 extern crate serde;
 extern crate rmp_serde;
 extern crate libc;
+extern crate serde_json;
+extern crate base64;
 
 use self::rmp_serde::encode;
 use self::rmp_serde::Deserializer;
 use serde::Deserialize;
+use serde_json::Value;
 use settings;
 use utils::constants::*;
 use utils::error;
@@ -12,6 +15,8 @@ use utils::libindy::wallet;
 use utils::libindy::signus::create_and_store_my_did;
 use utils::httpclient;
 use messages::{Bundled, MsgType, bundle_for_agency, unbundle_from_agency};
+use messages::validation::validate_verkey;
+use connection::MovedConnections;
 
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
@@ -22,6 +27,8 @@ struct ConnectMsg {
     from_did: String,
     #[serde(rename = "fromDIDVerKey")]
     from_vk: String,
+    #[serde(rename = "provisionToken", skip_serializing_if = "Option::is_none")]
+    provision_token: Option<ProvisionToken>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
@@ -40,6 +47,14 @@ struct GenericMsg {
     msg_type: MsgType,
 }
 
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
+struct SignUpMsg {
+    #[serde(rename = "@type")]
+    msg_type: MsgType,
+    #[serde(rename = "provisionToken", skip_serializing_if = "Option::is_none")]
+    provision_token: Option<ProvisionToken>,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
 struct RegisterResponse {
     #[serde(rename = "@type")]
@@ -62,6 +77,146 @@ struct ComMethod {
     value: String,
 }
 
+/// Token issued by a sponsor that allows an agent to be provisioned on agencies that restrict sign-ups.
+/// The sponsor signs the nonce, timestamp and sponsee id with the key registered at the agency.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, PartialOrd)]
+pub struct ProvisionToken {
+    #[serde(rename = "sponsorId")]
+    sponsor_id: String,
+    #[serde(rename = "sponseeId")]
+    sponsee_id: String,
+    nonce: String,
+    timestamp: String,
+    sig: String,
+    #[serde(rename = "sponsorVerKey")]
+    sponsor_vk: String,
+}
+
+// length of an ed25519 signature
+const SIGNATURE_LEN: usize = 64;
+
+impl ProvisionToken {
+    /// Checks the token is well formed before it is sent, the agency checks the signature itself
+    fn validate(&self) -> Result<(), u32> {
+        let invalid = |field: &str| {
+            warn!("provision token has an invalid {}", field);
+            ::error::context::add_error_context("provision_token", field);
+            error::INVALID_PROVISION_TOKEN.code_num
+        };
+
+        if self.sponsor_id.is_empty() { return Err(invalid("sponsorId")) }
+        if self.sponsee_id.is_empty() { return Err(invalid("sponseeId")) }
+        if self.nonce.is_empty() { return Err(invalid("nonce")) }
+        if self.timestamp.is_empty() { return Err(invalid("timestamp")) }
+
+        validate_verkey(&self.sponsor_vk).map_err(|_| invalid("sponsorVerKey"))?;
+
+        match base64::decode(&self.sig) {
+            Ok(ref sig) if sig.len() == SIGNATURE_LEN => Ok(()),
+            _ => Err(invalid("sig")),
+        }
+    }
+}
+
+// Status codes the agency responds with when it refuses to provision an agent
+static AGENCY_REJECTION_CODES: &[(&str, &error::Error)] = &[
+    ("PT-101", &error::PROVISION_TOKEN_REQUIRED),
+    ("PT-102", &error::INVALID_PROVISION_TOKEN),
+    ("PT-103", &error::PROVISION_TOKEN_EXPIRED),
+    ("PT-104", &error::PROVISION_TOKEN_ALREADY_USED),
+];
+
+/// Maps the body of a failed agency response to a specific error code
+pub fn map_agency_error(response: &str) -> u32 {
+    let status_code = match serde_json::from_str::<Value>(response) {
+        Ok(ref body) => body["statusCode"].as_str().unwrap_or("").to_string(),
+        Err(_) => return error::INVALID_HTTP_RESPONSE.code_num,
+    };
+
+    ::error::context::add_error_context("agency_status", &status_code);
+
+    let err = match AGENCY_REJECTION_CODES.iter().find(|&&(code, _)| code == status_code) {
+        Some(&(_, err)) => err.code_num,
+        None => error::INVALID_HTTP_RESPONSE.code_num,
+    };
+
+    ::error::context::set_current_error(err, response)
+}
+
+fn invalid_response<E: ::std::fmt::Display>(step: &str, err: E) -> u32 {
+    warn!("could not deserialize {} response: {}", step, err);
+    ::error::context::set_current_error(error::INVALID_HTTP_RESPONSE.code_num, &format!("{} response: {}", step, err))
+}
+
+/// Runs the CONNECT, SIGNUP and CREATE_AGENT exchange with the agency for the given sdk keys
+/// and returns the did and verkey of the newly created cloud agent
+fn register_with_agency(agency_did: &str,
+                        my_did: &str,
+                        my_vk: &str,
+                        provision_token: Option<ProvisionToken>) -> Result<(String, String), u32> {
+    if let Some(ref token) = provision_token { token.validate()?; }
+
+    if settings::test_agency_mode_enabled() {
+        httpclient::set_next_u8_response(PROVISION_RESPONSE.to_vec());
+        httpclient::set_next_u8_response(REGISTER_RESPONSE.to_vec());
+        httpclient::set_next_u8_response(PROVISION_RESPONSE.to_vec());
+    }
+
+    /* STEP 1 - CONNECT */
+
+    trace!("Connecting to Agency");
+    let payload = ConnectMsg {
+        msg_type: MsgType { name: "CONNECT".to_string(), ver: "1.0".to_string(), },
+        from_did: my_did.to_string(),
+        from_vk: my_vk.to_string(),
+        provision_token: provision_token.clone(),
+    };
+    let data = Bundled::create(encode::to_vec_named(&payload).unwrap()).encode()?;
+    let data = bundle_for_agency(data, &agency_did)?;
+    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e| map_agency_error(&e))?)?;
+
+    trace!("deserializing connect response: {:?}", data);
+    let mut de = Deserializer::new(&data[0][..]);
+    let response: ConnectResponseMsg = Deserialize::deserialize(&mut de).map_err(|e| invalid_response("connect", e))?;
+    //self.my_vk = Some(connection::get_pw_verkey(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
+    let agency_pw_vk = response.from_vk.to_owned();
+    let agency_pw_did = response.from_did.to_owned();
+
+    settings::set_config_value(settings::CONFIG_REMOTE_TO_SDK_VERKEY,&agency_pw_vk);
+
+    /* STEP 2 - REGISTER */
+
+    let payload = SignUpMsg {
+        msg_type: MsgType { name: "SIGNUP".to_string(), ver: "1.0".to_string(), },
+        provision_token,
+    };
+
+    let data = encode::to_vec_named(&payload).unwrap();
+    let data = Bundled::create(data).encode().unwrap();
+    let data = bundle_for_agency(data, &agency_pw_did)?;
+    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e| map_agency_error(&e))?)?;
+
+    trace!("deserializing register response: {:?}", data);
+    let mut de = Deserializer::new(&data[0][..]);
+    let _response: RegisterResponse = Deserialize::deserialize(&mut de).map_err(|e| invalid_response("register", e))?;
+
+    /* STEP 3 - CREATE AGENT */
+    let payload = GenericMsg {
+        msg_type: MsgType { name: "CREATE_AGENT".to_string(), ver: "1.0".to_string(), },
+    };
+
+    let data = encode::to_vec_named(&payload).unwrap();
+    let data = Bundled::create(data).encode().unwrap();
+    let data = bundle_for_agency(data, &agency_pw_did)?;
+    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e| map_agency_error(&e))?)?;
+
+    trace!("deserializing provision response: {:?}", data);
+    let mut de = Deserializer::new(&data[0][..]);
+    let response: ConnectResponseMsg = Deserialize::deserialize(&mut de).map_err(|e| invalid_response("provision", e))?;
+
+    Ok((response.from_did, response.from_vk))
+}
+
 pub fn connect_register_provision(endpoint: &str,
                                   agency_did: &str,
                                   agency_vk: &str,
@@ -71,7 +226,8 @@ pub fn connect_register_provision(endpoint: &str,
                                   wallet_key: &str,
                                   name: Option<String>,
                                   logo: Option<String>,
-                                  path: Option<String>) -> Result<String,u32> {
+                                  path: Option<String>,
+                                  provision_token: Option<ProvisionToken>) -> Result<String,u32> {
 
     trace!("***Registering with agency");
     let (wallet_name_string, wallet_name) = match wallet_name {
@@ -128,63 +284,7 @@ pub fn connect_register_provision(endpoint: &str,
     settings::set_config_value(settings::CONFIG_INSTITUTION_DID,&my_did);
     settings::set_config_value(settings::CONFIG_SDK_TO_REMOTE_VERKEY,&my_vk);
 
-    if settings::test_agency_mode_enabled() {
-        httpclient::set_next_u8_response(PROVISION_RESPONSE.to_vec());
-        httpclient::set_next_u8_response(REGISTER_RESPONSE.to_vec());
-        httpclient::set_next_u8_response(PROVISION_RESPONSE.to_vec());
-    }
-
-    /* STEP 1 - CONNECT */
-
-    trace!("Connecting to Agency");
-    let payload = ConnectMsg {
-        msg_type: MsgType { name: "CONNECT".to_string(), ver: "1.0".to_string(), },
-        from_did: my_did.to_string(),
-        from_vk: my_vk.to_string(),
-    };
-    let data = Bundled::create(encode::to_vec_named(&payload).unwrap()).encode()?;
-    let data = bundle_for_agency(data, &agency_did)?;
-    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e|error::INVALID_HTTP_RESPONSE.code_num).unwrap())?;
-
-    trace!("deserializing connect response: {:?}", data);
-    let mut de = Deserializer::new(&data[0][..]);
-    let response: ConnectResponseMsg = Deserialize::deserialize(&mut de).map_err(|ec| {error::INVALID_OPTION.code_num}).unwrap();
-    //self.my_vk = Some(connection::get_pw_verkey(connection_handle).map_err(|ec| CredentialError::CommonError(ec.to_error_code()))?);
-    let agency_pw_vk = response.from_vk.to_owned();
-    let agency_pw_did = response.from_did.to_owned();
-
-    settings::set_config_value(settings::CONFIG_REMOTE_TO_SDK_VERKEY,&agency_pw_vk);
-
-    /* STEP 2 - REGISTER */
-
-    let payload = GenericMsg {
-        msg_type: MsgType { name: "SIGNUP".to_string(), ver: "1.0".to_string(), },
-    };
-
-    let data = encode::to_vec_named(&payload).unwrap();
-    let data = Bundled::create(data).encode().unwrap();
-    let data = bundle_for_agency(data, &agency_pw_did)?;
-    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e|error::INVALID_HTTP_RESPONSE.code_num).unwrap())?;
-
-    trace!("deserializing register response: {:?}", data);
-    let mut de = Deserializer::new(&data[0][..]);
-    let response: RegisterResponse = Deserialize::deserialize(&mut de).map_err(|e|error::INVALID_HTTP_RESPONSE.code_num).unwrap();
-
-    /* STEP 3 - CREATE AGENT */
-    let payload = GenericMsg {
-        msg_type: MsgType { name: "CREATE_AGENT".to_string(), ver: "1.0".to_string(), },
-    };
-
-    let data = encode::to_vec_named(&payload).unwrap();
-    let data = Bundled::create(data).encode().unwrap();
-    let data = bundle_for_agency(data, &agency_pw_did)?;
-    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e|error::INVALID_HTTP_RESPONSE.code_num).unwrap())?;
-
-    trace!("deserializing provision response: {:?}", data);
-    let mut de = Deserializer::new(&data[0][..]);
-    let response: ConnectResponseMsg = Deserialize::deserialize(&mut de).map_err(|e|error::INVALID_HTTP_RESPONSE.code_num).unwrap();
-    let agent_did = response.from_did;
-    let agent_vk = response.from_vk;
+    let (agent_did, agent_vk) = register_with_agency(agency_did, &my_did, &my_vk, provision_token)?;
 
     let final_config = format!("{{\
     \"wallet_key\":\"{}\",\
@@ -222,6 +322,72 @@ pub fn connect_register_provision(endpoint: &str,
     Ok(final_config.to_owned())
 }
 
+/// Registers an already provisioned wallet with a (possibly new) agency.
+/// The wallet, sdk keys and institution did from the existing config are reused, only the
+/// agency settings and the cloud agent keys change. The serialized connections are moved to
+/// agents on the new agency. Uses the wallet already opened by vcx_init, otherwise opens it for
+/// the duration of the call. Returns the updated config and the connections.
+pub fn connect_register_reprovision(config: &str,
+                                    endpoint: &str,
+                                    agency_did: &str,
+                                    agency_vk: &str,
+                                    provision_token: Option<ProvisionToken>,
+                                    connections: &[String]) -> Result<(String, MovedConnections), u32> {
+
+    trace!("***Re-registering with agency {}", endpoint);
+    let mut config: Value = serde_json::from_str(config).or(Err(error::INVALID_JSON.code_num))?;
+    if !config.is_object() { return Err(error::INVALID_JSON.code_num) }
+
+    let wallet_name = config[settings::CONFIG_WALLET_NAME].as_str()
+        .unwrap_or(settings::DEFAULT_WALLET_NAME).to_string();
+    let wallet_key = config[settings::CONFIG_WALLET_KEY].as_str()
+        .ok_or(error::MISSING_WALLET_KEY.code_num)?.to_string();
+    let my_did = config[settings::CONFIG_SDK_TO_REMOTE_DID].as_str()
+        .ok_or(error::INVALID_CONFIGURATION.code_num)?.to_string();
+    let my_vk = config[settings::CONFIG_SDK_TO_REMOTE_VERKEY].as_str()
+        .ok_or(error::INVALID_CONFIGURATION.code_num)?.to_string();
+    config[settings::CONFIG_INSTITUTION_DID].as_str()
+        .ok_or(error::INVALID_CONFIGURATION.code_num)?;
+
+    settings::set_config_value(settings::CONFIG_AGENCY_ENDPOINT, endpoint);
+    settings::set_config_value(settings::CONFIG_WALLET_NAME, &wallet_name);
+    settings::set_config_value(settings::CONFIG_AGENCY_DID, agency_did);
+    settings::set_config_value(settings::CONFIG_AGENCY_VERKEY, agency_vk);
+    settings::set_config_value(settings::CONFIG_REMOTE_TO_SDK_VERKEY, agency_vk);
+    settings::set_config_value(settings::CONFIG_WALLET_KEY, &wallet_key);
+    settings::set_config_value(settings::CONFIG_SDK_TO_REMOTE_VERKEY, &my_vk);
+
+    // libindy refuses to open a wallet twice
+    let opened = wallet::get_wallet_handle() == 0;
+    if opened {
+        wallet::open_wallet(&wallet_name)?;
+        trace!("opened existing wallet");
+    }
+
+    let result = register_with_agency(agency_did, &my_did, &my_vk, provision_token).map(|(agent_did, agent_vk)| {
+        settings::set_config_value(settings::CONFIG_SDK_TO_REMOTE_DID, &my_did);
+        settings::set_config_value(settings::CONFIG_REMOTE_TO_SDK_DID, &agent_did);
+        settings::set_config_value(settings::CONFIG_REMOTE_TO_SDK_VERKEY, &agent_vk);
+        for key in &[settings::CONFIG_INSTITUTION_NAME, settings::CONFIG_INSTITUTION_LOGO_URL] {
+            if let Some(value) = config[*key].as_str() { settings::set_config_value(*key, value); }
+        }
+
+        (agent_did, agent_vk, ::connection::recreate_agents(connections))
+    });
+
+    if opened { wallet::close_wallet()?; }
+
+    let (agent_did, agent_vk, connections) = result?;
+
+    config[settings::CONFIG_AGENCY_ENDPOINT] = json!(endpoint);
+    config[settings::CONFIG_AGENCY_DID] = json!(agency_did);
+    config[settings::CONFIG_AGENCY_VERKEY] = json!(agency_vk);
+    config[settings::CONFIG_REMOTE_TO_SDK_DID] = json!(agent_did);
+    config[settings::CONFIG_REMOTE_TO_SDK_VERKEY] = json!(agent_vk);
+
+    Ok((config.to_string(), connections))
+}
+
 pub fn update_agent_info(id: &str, value: &str) -> Result<(), u32> {
     let new_config = UpdateAgentMsg {
         msg_type: MsgType { name: "UPDATE_COM_METHOD".to_string(), ver: "1.0".to_string(), },
@@ -238,7 +404,7 @@ pub fn update_agent_info(id: &str, value: &str) -> Result<(), u32> {
     let data = encode::to_vec_named(&new_config).unwrap();
     let data = Bundled::create(data).encode().unwrap();
     let data = bundle_for_agency(data, &to_did)?;
-    let data = unbundle_from_agency(httpclient::post_u8(&data).map_err(|e|error::INVALID_HTTP_RESPONSE.code_num).unwrap())?;
+    unbundle_from_agency(httpclient::post_u8(&data).map_err(|e| map_agency_error(&e))?)?;
 
     Ok(())
 }
@@ -247,7 +413,7 @@ pub fn update_agent_info(id: &str, value: &str) -> Result<(), u32> {
 mod tests {
 
     use super::*;
-    use utils::constants::{DEMO_ISSUER_PW_SEED};
+    use utils::constants::{DEMO_ISSUER_PW_SEED, PROVISION_TOKEN};
 
     #[test]
     fn test_connect_register_provision() {
@@ -268,6 +434,7 @@ mod tests {
                                                 wallet_key,
                                                 None,
                                                 None,
+                                                None,
                                                 None).unwrap();
         assert!(result.len() > 0);
         wallet::delete_wallet("test_connect_register_provision").unwrap();
@@ -292,6 +459,7 @@ mod tests {
                                                 "key",
                                                 None,
                                                 None,
+                                                None,
                                                 None).unwrap();
         assert!(result.len() > 0);
         println!("result: {}", result);
@@ -299,6 +467,94 @@ mod tests {
         wallet::delete_wallet(&wallet_name).unwrap();
     }
 
+    #[test]
+    fn test_connect_register_reprovision() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+
+        let agency_did = "Ab8TvZa3Q19VNkQVzAWVL7";
+        let agency_vk = "5LXaR43B1aQyeh94VBP8LG1Sgvjk7aNfqiksBCSjwqbf";
+        let host = "http://www.new-agency.org";
+        let wallet_name = "test_connect_register_reprovision";
+
+        let config = connect_register_provision("http://www.whocares.org", agency_did, agency_vk,
+                                                Some(wallet_name.to_string()), None, None, "test_key",
+                                                None, None, None, None).unwrap();
+
+        let handle = ::connection::build_connection("test_connect_register_reprovision").unwrap();
+        let connection = ::connection::to_string(handle).unwrap();
+        ::connection::release(handle).unwrap();
+
+        let (result, connections) = connect_register_reprovision(&config, host, agency_did, agency_vk, None,
+                                                                 &[connection]).unwrap();
+        let old: Value = serde_json::from_str(&config).unwrap();
+        let new: Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(new[settings::CONFIG_AGENCY_ENDPOINT], json!(host));
+        assert_eq!(new[settings::CONFIG_INSTITUTION_DID], old[settings::CONFIG_INSTITUTION_DID]);
+        assert_eq!(new[settings::CONFIG_SDK_TO_REMOTE_DID], old[settings::CONFIG_SDK_TO_REMOTE_DID]);
+        assert_eq!(connections.moved.len(), 1);
+        assert!(connections.not_moved.is_empty());
+
+        assert_eq!(connect_register_reprovision(r#"{"wallet_key":"key"}"#, host, agency_did, agency_vk, None, &[]),
+                   Err(error::INVALID_CONFIGURATION.code_num));
+
+        wallet::delete_wallet(wallet_name).unwrap();
+    }
+
+    #[test]
+    fn test_map_agency_error() {
+        assert_eq!(map_agency_error(r#"{"statusCode":"PT-101","statusMsg":"provision token required"}"#),
+                   error::PROVISION_TOKEN_REQUIRED.code_num);
+        assert_eq!(map_agency_error(r#"{"statusCode":"PT-102"}"#), error::INVALID_PROVISION_TOKEN.code_num);
+        assert_eq!(map_agency_error(r#"{"statusCode":"PT-103"}"#), error::PROVISION_TOKEN_EXPIRED.code_num);
+        assert_eq!(map_agency_error(r#"{"statusCode":"PT-104"}"#), error::PROVISION_TOKEN_ALREADY_USED.code_num);
+        assert_eq!(map_agency_error(r#"{"statusCode":"GNR-101"}"#), error::INVALID_HTTP_RESPONSE.code_num);
+        assert_eq!(map_agency_error("POST failed"), error::INVALID_HTTP_RESPONSE.code_num);
+    }
+
+    #[test]
+    fn test_provision_with_token() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE,"true");
+
+        let agency_did = "Ab8TvZa3Q19VNkQVzAWVL7";
+        let agency_vk = "5LXaR43B1aQyeh94VBP8LG1Sgvjk7aNfqiksBCSjwqbf";
+        let host = "http://www.whocares.org";
+
+        let token: ProvisionToken = serde_json::from_str(PROVISION_TOKEN).unwrap();
+        let result = connect_register_provision(host, agency_did, agency_vk, None, None, None,
+                                                "test_key", None, None, None, Some(token.clone())).unwrap();
+        assert!(result.len() > 0);
+
+        let mut bad_token = token.clone();
+        bad_token.sig = String::new();
+        assert_eq!(connect_register_provision(host, agency_did, agency_vk, None, None, None,
+                                              "test_key", None, None, None, Some(bad_token)),
+                   Err(error::INVALID_PROVISION_TOKEN.code_num));
+    }
+
+    #[test]
+    fn test_validate_provision_token() {
+        let token: ProvisionToken = serde_json::from_str(PROVISION_TOKEN).unwrap();
+        assert_eq!(token.validate(), Ok(()));
+
+        let mut bad_token = token.clone();
+        bad_token.sponsor_vk = "not a verkey".to_string();
+        assert_eq!(bad_token.validate(), Err(error::INVALID_PROVISION_TOKEN.code_num));
+
+        let mut bad_token = token.clone();
+        bad_token.sponsor_vk = "Ab8TvZa3Q19VNkQVzAWVL7".to_string();
+        assert_eq!(bad_token.validate(), Err(error::INVALID_PROVISION_TOKEN.code_num));
+
+        let mut bad_token = token.clone();
+        bad_token.sig = "not base64!".to_string();
+        assert_eq!(bad_token.validate(), Err(error::INVALID_PROVISION_TOKEN.code_num));
+
+        let mut bad_token = token;
+        bad_token.sig = "c2lnbmF0dXJl".to_string();
+        assert_eq!(bad_token.validate(), Err(error::INVALID_PROVISION_TOKEN.code_num));
+    }
+
     #[test]
     fn test_update_agent_info() {
         settings::set_defaults();
@@ -25,6 +25,31 @@ struct GetMessagesPayload{
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "pairwiseDIDs")]
     pairwise_dids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "types")]
+    msg_types: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "fromTimestamp")]
+    since: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "toTimestamp")]
+    until: Option<u64>,
+}
+
+/// Paging and additional filters of a message download. since and until are unix timestamps.
+/// msg_types is applied to the downloaded messages when the agency does not filter them, the
+/// others need an agency that supports paging and fail with MESSAGE_PAGING_NOT_SUPPORTED otherwise.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct DownloadOptions {
+    pub limit: Option<u32>,
+    pub cursor: Option<String>,
+    pub msg_types: Option<Vec<String>>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
 }
 
 #[derive(Serialize, Debug, PartialEq, PartialOrd, Clone)]
@@ -57,6 +82,11 @@ impl GetMessages{
                 exclude_payload: None,
                 status_codes: None,
                 pairwise_dids: None,
+                limit: None,
+                cursor: None,
+                msg_types: None,
+                since: None,
+                until: None,
             },
             agent_payload: String::new(),
             validate_rc: error::SUCCESS.code_num,
@@ -83,6 +113,28 @@ impl GetMessages{
         self
     }
 
+    pub fn limit(&mut self, limit: Option<u32>) -> &mut Self {
+        self.payload.limit = limit;
+        self
+    }
+
+    /// Continues the download after the last page, cursor is the next_cursor of the previous response
+    pub fn cursor(&mut self, cursor: Option<String>) -> &mut Self {
+        self.payload.cursor = cursor;
+        self
+    }
+
+    pub fn msg_types(&mut self, msg_types: Option<Vec<String>>) -> &mut Self {
+        self.payload.msg_types = msg_types;
+        self
+    }
+
+    pub fn date_range(&mut self, since: Option<u64>, until: Option<u64>) -> &mut Self {
+        self.payload.since = since;
+        self.payload.until = until;
+        self
+    }
+
     pub fn include_edge_payload(&mut self, payload: &str) -> &mut Self {
         //todo: is this a json value, String??
         self.payload.exclude_payload = Some(payload.to_string());
@@ -119,14 +171,23 @@ impl GetMessages{
         let to_did = settings::get_config_value(settings::CONFIG_REMOTE_TO_SDK_DID).unwrap();
         let data = bundle_for_agency(msg, &to_did)?;
 
-        match httpclient::post_u8(&data) {
+        let (connection_messages, paged) = match httpclient::post_u8(&data) {
             Err(_) => return Err(error::POST_MSG_FAILURE.code_num),
             Ok(response) => if settings::test_agency_mode_enabled() && response.len() == 0 {
                 return Ok(Vec::new());
             } else {
-                parse_get_connection_messages_response(response)
+                parse_get_connection_messages_response(response)?
             },
+        };
+
+        // an agency that ignores them would return every message as if it were the only page
+        if !paged && self.payload.uses_paging() {
+            warn!("agency does not support paging, limit, cursor and date range were ignored");
+            return Err(::error::context::set_current_error(error::MESSAGE_PAGING_NOT_SUPPORTED.code_num,
+                                                            "agency response has no message totals"));
         }
+
+        Ok(filter_msg_types(connection_messages, self.payload.msg_types.as_ref()))
     }
 }
 
@@ -255,12 +316,31 @@ fn parse_get_messages_response(response: Vec<u8>) -> Result<Vec<Message>, u32> {
     Ok(response.msgs.to_owned())
 }
 
+impl GetMessagesPayload {
+    fn uses_paging(&self) -> bool {
+        self.limit.is_some() || self.cursor.is_some() || self.since.is_some() || self.until.is_some()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct GetConnectionMessagesResponse {
     #[serde(rename = "@type")]
     msg_type: MsgType,
-    msgs_by_conns: Vec<ConnectionMessages>,
+    msgs_by_conns: Vec<AgencyConnectionMessages>,
+}
+
+// ConnectionMessages as sent by the agency, only agencies that support paging report totals
+#[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Clone)]
+struct AgencyConnectionMessages {
+    #[serde(rename = "pairwiseDID")]
+    pairwise_did: String,
+    msgs: Vec<Message>,
+    #[serde(default)]
+    total: Option<u32>,
+    #[serde(rename = "nextCursor")]
+    #[serde(default)]
+    next_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Clone)]
@@ -268,9 +348,34 @@ pub struct ConnectionMessages {
     #[serde(rename = "pairwiseDID")]
     pub pairwise_did: String,
     pub msgs: Vec<Message>,
+    // number of messages matching the filters over all pages
+    #[serde(default)]
+    pub total: u32,
+    #[serde(rename = "nextCursor")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+// agencies that do not support filtering by type return every message
+fn filter_msg_types(connection_messages: Vec<ConnectionMessages>, msg_types: Option<&Vec<String>>) -> Vec<ConnectionMessages> {
+    let msg_types = match msg_types {
+        Some(x) => x,
+        None => return connection_messages,
+    };
+
+    connection_messages.into_iter().map(|mut connection| {
+        let before = connection.msgs.len();
+        connection.msgs.retain(|msg| msg_types.contains(&msg.msg_type));
+        if connection.msgs.len() < before && connection.total as usize == before {
+            connection.total = connection.msgs.len() as u32;
+        }
+        connection
+    }).collect()
 }
 
-fn parse_get_connection_messages_response(response: Vec<u8>) -> Result<Vec<ConnectionMessages>, u32> {
+// Returns the messages and whether the agency paged them
+fn parse_get_connection_messages_response(response: Vec<u8>) -> Result<(Vec<ConnectionMessages>, bool), u32> {
     let data = unbundle_from_agency(response)?;
 
     trace!("parse_get_connection_message response: {:?}", data[0]);
@@ -284,6 +389,7 @@ fn parse_get_connection_messages_response(response: Vec<u8>) -> Result<Vec<Conne
         },
     };
 
+    let paged = response.msgs_by_conns.iter().all(|connection| connection.total.is_some());
     let mut connection_messages = Vec::new();
     for connection in response.msgs_by_conns.iter() {
         let vk = ::utils::libindy::signus::get_local_verkey(&connection.pairwise_did)?;
@@ -291,12 +397,16 @@ fn parse_get_connection_messages_response(response: Vec<u8>) -> Result<Vec<Conne
         for message in connection.msgs.iter() {
             new_messages.push(message.decrypt(&vk));
         }
+        // agencies without paging do not report totals, the page is then everything there is
+        let total = connection.total.unwrap_or(new_messages.len() as u32);
         connection_messages.push(ConnectionMessages {
             pairwise_did: connection.pairwise_did.clone(),
             msgs: new_messages,
+            total,
+            next_cursor: connection.next_cursor.clone(),
         })
     }
-    Ok(connection_messages)
+    Ok((connection_messages, paged))
 }
 
 pub fn get_connection_messages(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str, msg_uid: Option<Vec<String>>) -> Result<Vec<Message>, u32> {
@@ -324,7 +434,8 @@ pub fn get_connection_messages(pw_did: &str, pw_vk: &str, agent_did: &str, agent
 }
 
 pub fn get_ref_msg(msg_id: &str, pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str) -> Result<Vec<u8>, u32> {
-    let message = get_connection_messages(pw_did, pw_vk, agent_did, agent_vk, Some(vec![msg_id.to_string()]))?;
+    let message = get_connection_messages(pw_did, pw_vk, agent_did, agent_vk, Some(vec![msg_id.to_string()]))
+        .map_err(|err| { ::error::context::add_error_context("msg_id", msg_id); err })?;
     trace!("checking for ref_msg: {:?}", message);
     let msg_id;
     if message[0].status_code == MessageAccepted.as_string() && !message[0].ref_msg_id.is_none() {
@@ -350,7 +461,32 @@ pub fn get_ref_msg(msg_id: &str, pw_did: &str, pw_vk: &str, agent_did: &str, age
     }
 }
 
+/// Returns the json message of a decrypted payload from download_messages
+pub fn decrypted_msg(message: &Message) -> Option<String> {
+    message.decrypted_payload.as_ref()
+        .and_then(|payload| serde_json::from_str::<Payload>(payload).ok())
+        .map(|payload| payload.msg)
+}
+
+/// Same lookup as get_ref_msg but among messages already fetched by download_messages
+pub fn find_ref_msg(msg_id: &str, messages: &[Message]) -> Option<String> {
+    let ref_msg_id = messages.iter()
+        .find(|msg| msg.uid == msg_id && msg.status_code == MessageAccepted.as_string())
+        .and_then(|msg| msg.ref_msg_id.clone())?;
+
+    messages.iter()
+        .find(|msg| msg.uid == ref_msg_id && msg.status_code == MessagePending.as_string())
+        .and_then(decrypted_msg)
+}
+
 pub fn download_messages(pairwise_dids: Option<Vec<String>>, status_codes: Option<Vec<String>>, uids: Option<Vec<String>>) -> Result<Vec<ConnectionMessages>, u32> {
+    download_messages_with_options(pairwise_dids, status_codes, uids, &DownloadOptions::default())
+}
+
+pub fn download_messages_with_options(pairwise_dids: Option<Vec<String>>,
+                                      status_codes: Option<Vec<String>>,
+                                      uids: Option<Vec<String>>,
+                                      options: &DownloadOptions) -> Result<Vec<ConnectionMessages>, u32> {
 
     if settings::test_agency_mode_enabled() {
         ::utils::httpclient::set_next_u8_response(::utils::constants::GET_ALL_MESSAGES_RESPONSE.to_vec());
@@ -360,13 +496,18 @@ pub fn download_messages(pairwise_dids: Option<Vec<String>>, status_codes: Optio
         .uid(uids)
         .status_codes(status_codes)
         .pairwise_dids(pairwise_dids)
+        .limit(options.limit)
+        .cursor(options.cursor.clone())
+        .msg_types(options.msg_types.clone())
+        .date_range(options.since, options.until)
         .download_messages() {
+        Err(x) if x == error::MESSAGE_PAGING_NOT_SUPPORTED.code_num => Err(x),
         Err(x) => {
             error!("could not post get_messages: {}", x);
             Err(error::POST_MSG_FAILURE.code_num)
         },
         Ok(response) => {
-            trace!("message returned: {:?}", response[0]);
+            trace!("message returned: {:?}", response);
             Ok(response)
         },
     }
@@ -393,8 +534,85 @@ mod tests {
 
         let json = to_json(&GET_ALL_MESSAGES_RESPONSE.to_vec()).unwrap();
         println!("{}", json);
-        let result = parse_get_connection_messages_response(GET_ALL_MESSAGES_RESPONSE.to_vec()).unwrap();
-        assert_eq!(result.len(), 1)
+        let (result, paged) = parse_get_connection_messages_response(GET_ALL_MESSAGES_RESPONSE.to_vec()).unwrap();
+        assert!(!paged);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].total, result[0].msgs.len() as u32);
+        assert_eq!(result[0].next_cursor, None);
+    }
+
+    #[test]
+    fn test_filter_msg_types() {
+        let mut cred_req = Message::new();
+        cred_req.msg_type = "credReq".to_string();
+        let mut proof = Message::new();
+        proof.msg_type = "proof".to_string();
+        let messages = vec![ConnectionMessages {
+            pairwise_did: "8XFh8yBzrpJQmNyZzgoTqB".to_string(),
+            msgs: vec![cred_req, proof],
+            total: 2,
+            next_cursor: None,
+        }];
+
+        assert_eq!(filter_msg_types(messages.clone(), None), messages);
+
+        let filtered = filter_msg_types(messages, Some(&vec!["proof".to_string()]));
+        assert_eq!(filtered[0].msgs.len(), 1);
+        assert_eq!(filtered[0].msgs[0].msg_type, "proof");
+        assert_eq!(filtered[0].total, 1);
+    }
+
+    #[test]
+    fn test_paging_needs_agency_support() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        // the test agency response has no totals, like an agency without paging
+        let options = DownloadOptions { limit: Some(10), ..DownloadOptions::default() };
+        assert_eq!(download_messages_with_options(None, None, None, &options),
+                   Err(error::MESSAGE_PAGING_NOT_SUPPORTED.code_num));
+
+        let options = DownloadOptions { since: Some(1537000000), ..DownloadOptions::default() };
+        assert_eq!(download_messages_with_options(None, None, None, &options),
+                   Err(error::MESSAGE_PAGING_NOT_SUPPORTED.code_num));
+
+        let options = DownloadOptions { msg_types: Some(vec!["unknownType".to_string()]), ..DownloadOptions::default() };
+        let result = download_messages_with_options(None, None, None, &options).unwrap();
+        assert_eq!(result[0].msgs.len(), 0);
+    }
+
+    #[test]
+    fn test_download_options_are_sent_to_agency() {
+        let mut get_messages = get_messages();
+        get_messages.limit(Some(100))
+            .cursor(Some("mgrmngq".to_string()))
+            .msg_types(Some(vec!["proof".to_string()]))
+            .date_range(Some(1537000000), None);
+
+        let payload = serde_json::to_value(&get_messages.payload).unwrap();
+        assert_eq!(payload["limit"], json!(100));
+        assert_eq!(payload["cursor"], json!("mgrmngq"));
+        assert_eq!(payload["types"], json!(["proof"]));
+        assert_eq!(payload["fromTimestamp"], json!(1537000000));
+        assert!(payload.get("toTimestamp").is_none());
+    }
+
+    #[test]
+    fn test_find_ref_msg() {
+        let payload = json!({"@type": {"name": "PROOF", "ver": "1.0", "fmt": "json"}, "@msg": "{\"proof\":1}"});
+        let mut offer = Message::new();
+        offer.uid = "offer".to_string();
+        offer.status_code = MessageAccepted.as_string();
+        offer.ref_msg_id = Some("answer".to_string());
+        let mut answer = Message::new();
+        answer.uid = "answer".to_string();
+        answer.status_code = MessagePending.as_string();
+        answer.decrypted_payload = Some(payload.to_string());
+
+        let messages = vec![answer, offer];
+        assert_eq!(find_ref_msg("offer", &messages), Some("{\"proof\":1}".to_string()));
+        assert_eq!(find_ref_msg("answer", &messages), None);
+        assert_eq!(find_ref_msg("unknown", &messages), None);
     }
 
     #[test]
@@ -9,6 +9,7 @@ use utils::httpclient;
 use utils::error;
 use messages::*;
 use messages::send_message::CreateMessagePayload;
+use messages::outbox::{self, Delivery, MessageKind};
 use utils::constants::*;
 use serde::Deserialize;
 use self::rmp_serde::Deserializer;
@@ -243,6 +244,16 @@ impl SendInvite{
 
         Ok(result.to_owned())
     }
+
+    /// Same as send_secure, but the invite is kept in the outbox when the agency is unreachable
+    pub fn send_secure_or_queue(&mut self) -> Result<Delivery, u32> {
+        let data = self.msgpack()?;
+
+        if settings::test_agency_mode_enabled() { httpclient::set_next_u8_response(SEND_INVITE_RESPONSE.to_vec()); }
+
+        outbox::post_or_queue(&data, MessageKind::SendInvite)
+    }
+
     fn print_info(&self) {
 //    TODO: This could go away
         println!("\n****\n**** message pack: Send Invite");
@@ -343,6 +354,15 @@ impl AcceptInvite{
             },
         }
     }
+
+    /// Same as send_secure, but the answer is kept in the outbox when the agency is unreachable
+    pub fn send_secure_or_queue(&mut self) -> Result<Delivery, u32> {
+        let data = self.msgpack()?;
+
+        if settings::test_agency_mode_enabled() { httpclient::set_next_u8_response(ACCEPT_INVITE_RESPONSE.to_vec()); }
+
+        outbox::post_or_queue(&data, MessageKind::AcceptInvite)
+    }
 }
 
 
@@ -419,7 +439,7 @@ impl GeneralMessage for AcceptInvite{
     }
 }
 
-fn parse_response(response: Vec<u8>) -> Result<String, u32> {
+pub fn parse_response(response: Vec<u8>) -> Result<String, u32> {
     let data = unbundle_from_agency(response)?;
 
     if data.len() != 3 {
@@ -444,7 +464,7 @@ fn parse_response(response: Vec<u8>) -> Result<String, u32> {
 }
 
 const ACCEPT_BUNDLE_LEN: usize = 2;
-fn parse_send_accept_response(response: Vec<u8>) -> Result<String, u32> {
+pub fn parse_send_accept_response(response: Vec<u8>) -> Result<String, u32> {
     let data = unbundle_from_agency(response)?;
 
     if data.len() != ACCEPT_BUNDLE_LEN {
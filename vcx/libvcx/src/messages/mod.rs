@@ -12,6 +12,9 @@ pub mod proofs;
 pub mod agent_utils;
 pub mod update_connection;
 pub mod update_message;
+pub mod push_notification;
+pub mod outbox;
+pub mod out_of_band;
 
 use std::u8;
 use settings;
@@ -0,0 +1,296 @@
+extern crate serde_json;
+extern crate rmp_serde;
+extern crate base64;
+
+use settings;
+use utils::error;
+use utils::httpclient;
+use utils::libindy::crypto;
+use utils::libindy::wallet;
+use messages::{Bundled, Forward, MsgInfo, MsgType, Payload, extract_json_payload};
+use messages::send_message::{CreateMessagePayload, MessageDetailPayload};
+use self::rmp_serde::encode;
+
+/// Where the reply to an out-of-band message is delivered: the agency at endpoint forwards it to
+/// the cloud agent of a pairwise relationship of the sender, which stores it for recipient_did.
+/// Only the recipient key can read the reply.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Service {
+    pub endpoint: String,
+    pub routing_key: String,
+    pub recipient_did: String,
+    pub recipient_key: String,
+    #[serde(default)]
+    pub agent_did: String,
+    #[serde(default)]
+    pub agent_vk: String,
+}
+
+impl Service {
+    /// Service of a pairwise key and its agent on the agency this wallet is provisioned with
+    pub fn for_pairwise(pw_did: &str, pw_vk: &str, agent_did: &str, agent_vk: &str) -> Result<Service, u32> {
+        Ok(Service {
+            endpoint: settings::get_config_value(settings::CONFIG_AGENCY_ENDPOINT)?,
+            routing_key: settings::get_config_value(settings::CONFIG_AGENCY_VERKEY)?,
+            recipient_did: pw_did.to_string(),
+            recipient_key: pw_vk.to_string(),
+            agent_did: agent_did.to_string(),
+            agent_vk: agent_vk.to_string(),
+        })
+    }
+}
+
+// Message type the agent stores a reply as, the same the reply has on a connection
+fn agency_msg_type(msg_type: &str) -> Result<&'static str, u32> {
+    match msg_type {
+        "PROOF" => Ok("proof"),
+        "CRED_REQ" => Ok("credReq"),
+        "CRED" => Ok("cred"),
+        _ => Err(invalid(&format!("no reply of type {}", msg_type))),
+    }
+}
+
+/// Self-contained message exchanged without a connection, e.g. through a QR code or deep link.
+/// The signature covers the message and the service so neither can be swapped.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutOfBandMessage {
+    #[serde(rename = "@type")]
+    pub msg_type: String,
+    #[serde(rename = "@msg")]
+    pub msg: String,
+    pub service: Service,
+    pub signer_did: String,
+    pub signer_vk: String,
+    pub signature: String,
+}
+
+fn signed_data(msg_type: &str, msg: &str, service: &Service, signer_did: &str) -> Result<Vec<u8>, u32> {
+    serde_json::to_vec(&(msg_type, msg, service, signer_did)).or(Err(error::INVALID_JSON.code_num))
+}
+
+// The key a message must be signed with, the signer_vk carried in the message proves nothing
+// on its own. The ledger is mocked in test mode, where the wallet key of the did stands in.
+fn signer_verkey(signer_did: &str) -> Result<String, u32> {
+    if settings::test_indy_mode_enabled() { return ::utils::libindy::signus::get_local_verkey(signer_did); }
+
+    ::nym::get_verkey(signer_did)
+}
+
+fn invalid(reason: &str) -> u32 {
+    warn!("{}: {}", error::INVALID_OUT_OF_BAND_MESSAGE.message, reason);
+    error::INVALID_OUT_OF_BAND_MESSAGE.code_num
+}
+
+impl OutOfBandMessage {
+    /// Signs msg and service with the institution key
+    pub fn create(msg_type: &str, msg: &str, service: Service) -> Result<OutOfBandMessage, u32> {
+        let signer_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+        let signer_vk = ::utils::libindy::signus::get_local_verkey(&signer_did)?;
+
+        let data = signed_data(msg_type, msg, &service, &signer_did)?;
+        let signature = crypto::sign(wallet::get_wallet_handle(), &signer_vk, &data)?;
+
+        Ok(OutOfBandMessage {
+            msg_type: msg_type.to_string(),
+            msg: msg.to_string(),
+            service,
+            signer_did,
+            signer_vk,
+            signature: base64::encode(&signature),
+        })
+    }
+
+    /// Parses an out-of-band message of type msg_type and checks it is signed with the ledger
+    /// verkey of signer_did
+    pub fn from_str(message: &str, msg_type: &str) -> Result<OutOfBandMessage, u32> {
+        let message: OutOfBandMessage = serde_json::from_str(message).map_err(|err| invalid(&err.to_string()))?;
+        if message.msg_type != msg_type {
+            return Err(invalid(&format!("expected {} but got {}", msg_type, message.msg_type)));
+        }
+
+        let signer_vk = signer_verkey(&message.signer_did).map_err(|ec| {
+            ::error::context::add_error_context("signer_did", &message.signer_did);
+            ec
+        })?;
+        if signer_vk != message.signer_vk {
+            return Err(invalid(&format!("{} is not the verkey of {}", message.signer_vk, message.signer_did)));
+        }
+
+        let data = signed_data(&message.msg_type, &message.msg, &message.service, &message.signer_did)?;
+        let signature = base64::decode(&message.signature).map_err(|_| invalid("signature is not base64"))?;
+        if !crypto::verify(&message.signer_vk, &data, &signature)? {
+            return Err(invalid(&format!("signature does not match {}", message.signer_vk)));
+        }
+
+        Ok(message)
+    }
+
+    pub fn to_string(&self) -> Result<String, u32> {
+        serde_json::to_string(self).or(Err(error::INVALID_JSON.code_num))
+    }
+}
+
+/// Sends msg to the service of an out-of-band message, as a message created on the agent of the
+/// service. Only the recipient can read it and the sender stays anonymous, as there is no
+/// pairwise relationship to authenticate with.
+///
+/// The CREATE_MSG is anoncrypted to the agent, so this needs an agency that accepts messages
+/// from senders it cannot authenticate on pairwise agents. Agencies that only accept authcrypted
+/// messages from the owner of the agent reject the reply.
+pub fn reply(service: &Service, msg_type: &str, msg: &str) -> Result<(), u32> {
+    if service.agent_did.is_empty() || service.agent_vk.is_empty() {
+        return Err(invalid("service has no agent"));
+    }
+
+    let payload = Payload {
+        msg_info: MsgInfo { name: msg_type.to_string(), ver: "1.0".to_string(), fmt: "json".to_string(), },
+        msg: msg.to_string(),
+    };
+    let payload = encode::to_vec_named(&payload).or(Err(error::INVALID_MSGPACK.code_num))?;
+    let payload = crypto::prep_anonymous_msg(&service.recipient_key, &payload)?;
+
+    let create = CreateMessagePayload {
+        msg_type: MsgType { name: "CREATE_MSG".to_string(), ver: "1.0".to_string(), },
+        mtype: agency_msg_type(msg_type)?.to_string(),
+        reply_to_msg_id: None,
+        send_msg: false,
+    };
+    let detail = MessageDetailPayload {
+        msg_type: MsgType { name: "MSG_DETAIL".to_string(), ver: "1.0".to_string(), },
+        msg: payload,
+        title: None,
+        detail: None,
+    };
+    let mut bundle = Bundled::create(encode::to_vec_named(&create).or(Err(error::INVALID_MSGPACK.code_num))?);
+    bundle.bundled.push(encode::to_vec_named(&detail).or(Err(error::INVALID_MSGPACK.code_num))?);
+    let msg = crypto::prep_anonymous_msg(&service.agent_vk, &bundle.encode()?)?;
+
+    debug!("forwarding out of band reply to agent {} at {}", service.agent_did, service.endpoint);
+    let forward = Forward {
+        msg_type: MsgType { name: "FWD".to_string(), ver: "1.0".to_string(), },
+        fwd: service.agent_did.clone(),
+        msg,
+    };
+    let forward = encode::to_vec_named(&forward).or(Err(error::INVALID_MSGPACK.code_num))?;
+    let bundle = Bundled::create(forward).encode()?;
+    let data = crypto::prep_anonymous_msg(&service.routing_key, &bundle)?;
+
+    httpclient::post_u8_to(&service.endpoint, &data).map_err(|err| {
+        warn!("could not deliver out of band reply: {}", err);
+        error::POST_MSG_FAILURE.code_num
+    })?;
+    Ok(())
+}
+
+/// Type and content of a reply received on recipient_key
+pub fn parse_reply(recipient_key: &str, data: &Vec<u8>) -> Result<(String, String), u32> {
+    let payload = crypto::parse_anonymous_msg(wallet::get_wallet_handle(), recipient_key, data)?;
+    let msg_type = ::messages::to_json(&payload)?["@type"]["name"].as_str().unwrap_or_default().to_string();
+    Ok((msg_type, extract_json_payload(&payload)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_out_of_band_message_signature() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let service = Service::for_pairwise("8XFh8yBzrpJQmNyZzgoTqB", "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A",
+                                            "8XFh8yBzrpJQmNyZzgoTqB", "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A").unwrap();
+        let message = OutOfBandMessage::create("PROOF_REQUEST", r#"{"nonce":"123"}"#, service).unwrap();
+        let message = message.to_string().unwrap();
+
+        let parsed = OutOfBandMessage::from_str(&message, "PROOF_REQUEST").unwrap();
+        assert_eq!(parsed.msg, r#"{"nonce":"123"}"#);
+        assert_eq!(parsed.service.recipient_did, "8XFh8yBzrpJQmNyZzgoTqB");
+        assert_eq!(OutOfBandMessage::from_str(&message, "CRED_OFFER").err(), Some(error::INVALID_OUT_OF_BAND_MESSAGE.code_num));
+
+        // redirecting the reply to another endpoint without signing again breaks the signature
+        let forged = message.replace(&settings::get_config_value(settings::CONFIG_AGENCY_ENDPOINT).unwrap(), "https://evil.example.com");
+        assert_eq!(OutOfBandMessage::from_str(&forged, "PROOF_REQUEST").err(), Some(error::INVALID_OUT_OF_BAND_MESSAGE.code_num));
+
+        // signing again with another key gives a valid signature, but not by the signer did
+        let mut forged: OutOfBandMessage = serde_json::from_str(&forged).unwrap();
+        forged.signer_vk = "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A".to_string();
+        let data = signed_data(&forged.msg_type, &forged.msg, &forged.service, &forged.signer_did).unwrap();
+        forged.signature = base64::encode(&crypto::sign(wallet::get_wallet_handle(), &forged.signer_vk, &data).unwrap());
+        assert!(crypto::verify(&forged.signer_vk, &data, &base64::decode(&forged.signature).unwrap()).unwrap());
+        assert_eq!(OutOfBandMessage::from_str(&forged.to_string().unwrap(), "PROOF_REQUEST").err(),
+                   Some(error::INVALID_OUT_OF_BAND_MESSAGE.code_num));
+    }
+
+    #[test]
+    fn test_parse_reply() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let payload = Payload {
+            msg_info: MsgInfo { name: "PROOF".to_string(), ver: "1.0".to_string(), fmt: "json".to_string(), },
+            msg: "{}".to_string(),
+        };
+        let payload = encode::to_vec_named(&payload).unwrap();
+        assert_eq!(parse_reply("EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A", &payload).unwrap(),
+                   ("PROOF".to_string(), "{}".to_string()));
+    }
+
+    #[test]
+    fn test_reply() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let mut service = Service::for_pairwise("8XFh8yBzrpJQmNyZzgoTqB", "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A",
+                                                "8XFh8yBzrpJQmNyZzgoTqB", "EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A").unwrap();
+        assert!(reply(&service, "PROOF", "{}").is_ok());
+        assert_eq!(reply(&service, "PROOF_REQUEST", "{}"), Err(error::INVALID_OUT_OF_BAND_MESSAGE.code_num));
+
+        // services of messages created before replies went through the agent
+        service.agent_did = String::new();
+        assert_eq!(reply(&service, "PROOF", "{}"), Err(error::INVALID_OUT_OF_BAND_MESSAGE.code_num));
+    }
+
+    #[cfg(feature = "pool_tests")]
+    #[test]
+    fn test_reply_round_trip() {
+        use std::thread;
+        use std::time::Duration;
+        use connection;
+
+        let wallet_name = "test_reply_round_trip";
+        settings::set_defaults();
+        ::utils::devsetup::tests::setup_local_env(wallet_name);
+
+        let handle = connection::build_connection("test_reply_round_trip").unwrap();
+        let pw_did = connection::get_pw_did(handle).unwrap();
+        let pw_vk = connection::get_pw_verkey(handle).unwrap();
+        let agent_did = connection::get_agent_did(handle).unwrap();
+        let agent_vk = connection::get_agent_verkey(handle).unwrap();
+        let service = Service::for_pairwise(&pw_did, &pw_vk, &agent_did, &agent_vk).unwrap();
+        let message = OutOfBandMessage::create("PROOF_REQUEST", r#"{"nonce":"123"}"#, service)
+            .and_then(|message| message.to_string()).unwrap();
+
+        ::utils::devsetup::tests::set_consumer();
+        let message = OutOfBandMessage::from_str(&message, "PROOF_REQUEST").unwrap();
+        reply(&message.service, "PROOF", r#"{"proof":"123"}"#).unwrap();
+        thread::sleep(Duration::from_millis(2000));
+
+        ::utils::devsetup::tests::set_institution();
+        let received = ::messages::get_messages()
+            .to(&pw_did)
+            .to_vk(&pw_vk)
+            .agent_did(&agent_did)
+            .agent_vk(&agent_vk)
+            .send_secure()
+            .unwrap();
+        let replies: Vec<(String, String)> = received.iter()
+            .filter_map(|message| message.payload.as_ref())
+            .filter_map(|payload| parse_reply(&pw_vk, &::messages::to_u8(payload)).ok())
+            .collect();
+        ::utils::devsetup::tests::cleanup_dev_env(wallet_name);
+
+        assert_eq!(received[0].msg_type, "proof");
+        assert_eq!(replies, vec![("PROOF".to_string(), r#"{"proof":"123"}"#.to_string())]);
+    }
+}
@@ -0,0 +1,455 @@
+extern crate rand;
+extern crate serde_json;
+
+use self::rand::Rng;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use settings;
+use utils::error;
+use utils::httpclient;
+use utils::libindy::wallet;
+
+static OUTBOX_RECORD: &str = "vcx_outbox";
+static OUTBOX_INDEX_RECORD: &str = "vcx_outbox_index";
+static OUTBOX_INDEX_ID: &str = "ids";
+static WALLET_GET_OPTIONS: &str = r#"{"retrieveType":false,"retrieveValue":true,"retrieveTags":false}"#;
+
+// delay before the first retry, doubled on every attempt up to RETRY_MAX_DELAY
+static RETRY_BASE_DELAY: u64 = 5;
+static RETRY_MAX_DELAY: u64 = 3600;
+
+/// Agency message held by an outbox entry, decides how the agency response is parsed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum MessageKind {
+    SendMessage,
+    SendInvite,
+    AcceptInvite,
+}
+
+impl MessageKind {
+    fn parse_response(&self, response: Vec<u8>) -> Result<String, u32> {
+        match *self {
+            MessageKind::SendMessage => ::messages::send_message::parse_send_message_response(response),
+            MessageKind::SendInvite => ::messages::invite::parse_response(response),
+            MessageKind::AcceptInvite => ::messages::invite::parse_send_accept_response(response),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum OutboxStatus {
+    Pending,
+    Delivered,
+    Failed,
+    Expired,
+    Cancelled,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct OutboxEntry {
+    id: String,
+    kind: MessageKind,
+    // bundle encrypted for the agency, exactly as it would have been posted
+    bundle: Vec<u8>,
+    status: OutboxStatus,
+    created_at: u64,
+    expires_at: u64,
+    attempts: u32,
+    next_attempt_at: u64,
+    // parsed agency response once delivered
+    response: Option<String>,
+    last_error: Option<u32>,
+    // set while a retry is posting the bundle, so the background loop and retry_now never post it twice
+    #[serde(skip)]
+    in_flight: bool,
+}
+
+/// Outbox entry as reported to the application, without the bundle
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutboxSummary {
+    pub id: String,
+    pub kind: MessageKind,
+    pub status: OutboxStatus,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    pub last_error: Option<u32>,
+}
+
+/// Result of a send that may have been deferred to the outbox
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    // parsed agency response
+    Sent(String),
+    // id of the outbox entry
+    Queued(String),
+}
+
+lazy_static! {
+    static ref OUTBOX: RwLock<HashMap<String, OutboxEntry>> = Default::default();
+    static ref RETRYING: AtomicBool = AtomicBool::new(false);
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn ttl() -> u64 {
+    settings::get_config_value(settings::CONFIG_OUTBOX_TTL)
+        .unwrap_or(settings::DEFAULT_OUTBOX_TTL.to_string())
+        .parse::<u64>()
+        .unwrap_or(0)
+}
+
+fn backoff(attempts: u32) -> u64 {
+    let delay = RETRY_BASE_DELAY.saturating_mul(1u64 << ::std::cmp::min(attempts, 20));
+    ::std::cmp::min(delay, RETRY_MAX_DELAY)
+}
+
+pub fn is_enabled() -> bool {
+    settings::get_config_value(settings::CONFIG_OUTBOX_ENABLED)
+        .map(|v| v == "true")
+        .unwrap_or(false)
+}
+
+fn persist(entry: &OutboxEntry) {
+    if settings::test_indy_mode_enabled() { return }
+
+    let value = match serde_json::to_string(entry) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+
+    match wallet::add_record(OUTBOX_RECORD, &entry.id, &value, "{}") {
+        Ok(_) => (),
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => {
+            if let Err(ec) = wallet::update_record_value(OUTBOX_RECORD, &entry.id, &value) {
+                warn!("could not update outbox message {} in wallet: {}", entry.id, ec);
+            }
+        },
+        Err(ec) => warn!("could not store outbox message {} in wallet: {}", entry.id, ec),
+    }
+}
+
+fn persist_index(outbox: &HashMap<String, OutboxEntry>) {
+    if settings::test_indy_mode_enabled() { return }
+
+    let ids: Vec<&String> = outbox.keys().collect();
+    let value = json!(ids).to_string();
+
+    match wallet::add_record(OUTBOX_INDEX_RECORD, OUTBOX_INDEX_ID, &value, "{}") {
+        Ok(_) => (),
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => {
+            if let Err(ec) = wallet::update_record_value(OUTBOX_INDEX_RECORD, OUTBOX_INDEX_ID, &value) {
+                warn!("could not update outbox index in wallet: {}", ec);
+            }
+        },
+        Err(ec) => warn!("could not store outbox index in wallet: {}", ec),
+    }
+}
+
+fn get_record_value(xtype: &str, id: &str) -> Option<String> {
+    let record = wallet::get_record(xtype, id, WALLET_GET_OPTIONS).ok()?;
+    let record: Value = serde_json::from_str(&record).ok()?;
+    record["value"].as_str().map(String::from)
+}
+
+/// Restores the outbox stored in the wallet, ie after the application restarted
+pub fn load() {
+    if settings::test_indy_mode_enabled() { return }
+
+    let ids: Vec<String> = match get_record_value(OUTBOX_INDEX_RECORD, OUTBOX_INDEX_ID)
+        .and_then(|ids| serde_json::from_str(&ids).ok()) {
+        Some(x) => x,
+        None => return,
+    };
+
+    let mut pending = false;
+    if let Ok(mut outbox) = OUTBOX.write() {
+        for id in ids {
+            match get_record_value(OUTBOX_RECORD, &id).and_then(|entry| serde_json::from_str::<OutboxEntry>(&entry).ok()) {
+                Some(entry) => {
+                    pending = pending || entry.status == OutboxStatus::Pending;
+                    outbox.insert(id, entry);
+                },
+                None => warn!("outbox message {} is missing from the wallet", id),
+            }
+        }
+    }
+
+    if pending { ensure_retrying(); }
+}
+
+fn enqueue(bundle: &Vec<u8>, kind: MessageKind) -> Result<String, u32> {
+    let created_at = now();
+    let entry = OutboxEntry {
+        id: format!("{}-{}", created_at, rand::thread_rng().gen::<u32>()),
+        kind,
+        bundle: bundle.clone(),
+        status: OutboxStatus::Pending,
+        created_at,
+        expires_at: created_at + ttl(),
+        attempts: 0,
+        next_attempt_at: created_at + backoff(0),
+        response: None,
+        last_error: Some(error::POST_MSG_FAILURE.code_num),
+        in_flight: false,
+    };
+
+    let id = entry.id.clone();
+    {
+        let mut outbox = OUTBOX.write().or(Err(error::UNKNOWN_ERROR.code_num))?;
+        persist(&entry);
+        outbox.insert(id.clone(), entry);
+        persist_index(&outbox);
+    }
+    warn!("agency unreachable, {:?} queued in outbox as {}", kind, id);
+
+    ensure_retrying();
+    Ok(id)
+}
+
+/// Posts the bundle to the agency. When the agency cannot be reached and the outbox is enabled
+/// the bundle is stored and retried in the background instead of failing.
+pub fn post_or_queue(bundle: &Vec<u8>, kind: MessageKind) -> Result<Delivery, u32> {
+    match httpclient::post_u8(bundle) {
+        Ok(response) => Ok(Delivery::Sent(kind.parse_response(response)?)),
+        Err(ref err) if is_enabled() && err == httpclient::CONNECTION_FAILED => {
+            let id = enqueue(bundle, kind)?;
+            // the caller succeeds, the failed post is not its error
+            ::error::context::reset_current_error();
+            Ok(Delivery::Queued(id))
+        },
+        Err(_) => Err(error::POST_MSG_FAILURE.code_num),
+    }
+}
+
+fn update_entry<F>(id: &str, closure: F) where F: FnOnce(&mut OutboxEntry) {
+    if let Ok(mut outbox) = OUTBOX.write() {
+        if let Some(entry) = outbox.get_mut(id) {
+            closure(entry);
+            persist(entry);
+        }
+    }
+}
+
+// Applies the outcome of a retry to an entry that is still pending, an entry cancelled while it
+// was posted keeps its status
+fn finish_attempt<F>(id: &str, closure: F) where F: FnOnce(&mut OutboxEntry) {
+    update_entry(id, |entry| {
+        entry.in_flight = false;
+        if entry.status == OutboxStatus::Pending {
+            closure(entry);
+        } else {
+            debug!("outbox message {} is {:?}, retry outcome ignored", entry.id, entry.status);
+        }
+    });
+}
+
+fn retry(force: bool) -> usize {
+    let time = now();
+    // due entries are claimed under the write lock, a concurrent retry skips them
+    let due: Vec<(String, MessageKind, Vec<u8>, u64)> = match OUTBOX.write() {
+        Ok(mut outbox) => outbox.values_mut()
+            .filter(|entry| entry.status == OutboxStatus::Pending && !entry.in_flight
+                && (force || entry.next_attempt_at <= time || entry.expires_at <= time))
+            .map(|entry| {
+                entry.in_flight = true;
+                (entry.id.clone(), entry.kind, entry.bundle.clone(), entry.expires_at)
+            })
+            .collect(),
+        Err(_) => return 0,
+    };
+
+    for (id, kind, bundle, expires_at) in due {
+        if expires_at <= time {
+            warn!("outbox message {} expired before it could be delivered", id);
+            finish_attempt(&id, |entry| entry.status = OutboxStatus::Expired);
+            continue;
+        }
+
+        let result = httpclient::post_u8(&bundle);
+        finish_attempt(&id, |entry| {
+            entry.attempts += 1;
+            match result {
+                Ok(response) => match kind.parse_response(response) {
+                    Ok(response) => {
+                        debug!("outbox message {} delivered after {} attempts", entry.id, entry.attempts);
+                        entry.status = OutboxStatus::Delivered;
+                        entry.response = Some(response);
+                        entry.last_error = None;
+                    },
+                    Err(ec) => {
+                        entry.status = OutboxStatus::Failed;
+                        entry.last_error = Some(ec);
+                    },
+                },
+                Err(ref err) if err == httpclient::CONNECTION_FAILED => {
+                    entry.next_attempt_at = now() + backoff(entry.attempts);
+                },
+                Err(_) => {
+                    warn!("agency rejected outbox message {}", entry.id);
+                    entry.status = OutboxStatus::Failed;
+                    entry.last_error = Some(error::POST_MSG_FAILURE.code_num);
+                },
+            }
+        });
+    }
+
+    OUTBOX.read()
+        .map(|outbox| outbox.values().filter(|entry| entry.status == OutboxStatus::Pending).count())
+        .unwrap_or(0)
+}
+
+fn ensure_retrying() {
+    // mocked agency responses are consumed in order, a background retry would steal them from tests
+    if settings::test_agency_mode_enabled() { return }
+    if RETRYING.swap(true, Ordering::SeqCst) { return }
+
+    thread::spawn(|| {
+        while retry(false) > 0 {
+            thread::sleep(Duration::from_secs(1));
+        }
+        RETRYING.store(false, Ordering::SeqCst);
+        debug!("outbox is empty, retry thread stopped");
+    });
+}
+
+/// Tries to deliver every pending message now, whatever its backoff.
+/// Returns the number of messages still pending.
+pub fn retry_now() -> usize {
+    retry(true)
+}
+
+pub fn list() -> Vec<OutboxSummary> {
+    let mut entries: Vec<OutboxSummary> = match OUTBOX.read() {
+        Ok(outbox) => outbox.values().map(|entry| OutboxSummary {
+            id: entry.id.clone(),
+            kind: entry.kind,
+            status: entry.status,
+            created_at: entry.created_at,
+            expires_at: entry.expires_at,
+            attempts: entry.attempts,
+            next_attempt_at: entry.next_attempt_at,
+            last_error: entry.last_error,
+        }).collect(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort_by_key(|entry| entry.created_at);
+    entries
+}
+
+/// Returns the status of an entry and the agency response once it was delivered
+pub fn get_status(id: &str) -> Option<(OutboxStatus, Option<String>)> {
+    OUTBOX.read().ok()?
+        .get(id)
+        .map(|entry| (entry.status, entry.response.clone()))
+}
+
+/// Stops retrying a pending message. The object that sent it goes back to its previous state on its next update.
+/// A retry posting the message at the same time may still deliver it, its outcome is not recorded.
+pub fn cancel(id: &str) -> Result<(), u32> {
+    let mut outbox = OUTBOX.write().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    let entry = outbox.get_mut(id).ok_or(error::OUTBOX_ENTRY_NOT_FOUND.code_num)?;
+
+    if entry.status != OutboxStatus::Pending {
+        return Err(error::OUTBOX_ENTRY_NOT_PENDING.code_num);
+    }
+
+    entry.status = OutboxStatus::Cancelled;
+    persist(entry);
+    Ok(())
+}
+
+/// Forgets an entry once the object that queued it has consumed its final status
+pub fn remove(id: &str) {
+    if let Ok(mut outbox) = OUTBOX.write() {
+        if outbox.remove(id).is_some() && !settings::test_indy_mode_enabled() {
+            if let Err(ec) = wallet::delete_record(OUTBOX_RECORD, id) {
+                warn!("could not delete outbox message {} from wallet: {}", id, ec);
+            }
+            persist_index(&outbox);
+        }
+    }
+}
+
+/// Clears the in-memory outbox, the wallet keeps the entries for the next load
+pub fn clear() {
+    if let Ok(mut outbox) = OUTBOX.write() {
+        outbox.clear();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn queue_test_message(kind: MessageKind) -> String {
+        enqueue(&vec![1, 2, 3], kind).unwrap()
+    }
+
+    pub fn deliver_test_message(id: &str, response: &str) {
+        update_entry(id, |entry| {
+            entry.status = OutboxStatus::Delivered;
+            entry.response = Some(response.to_string());
+        });
+    }
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(0), RETRY_BASE_DELAY);
+        assert_eq!(backoff(1), RETRY_BASE_DELAY * 2);
+        assert_eq!(backoff(64), RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_cancel() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let id = queue_test_message(MessageKind::SendMessage);
+        assert_eq!(get_status(&id), Some((OutboxStatus::Pending, None)));
+        assert!(list().iter().any(|entry| entry.id == id));
+
+        cancel(&id).unwrap();
+        assert_eq!(get_status(&id), Some((OutboxStatus::Cancelled, None)));
+        assert_eq!(cancel(&id), Err(error::OUTBOX_ENTRY_NOT_PENDING.code_num));
+
+        remove(&id);
+        assert_eq!(get_status(&id), None);
+        assert_eq!(cancel(&id), Err(error::OUTBOX_ENTRY_NOT_FOUND.code_num));
+    }
+
+    #[test]
+    fn test_retry_claims_entries_and_keeps_cancelled() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let id = queue_test_message(MessageKind::SendMessage);
+        update_entry(&id, |entry| entry.in_flight = true);
+        retry(true);
+        assert_eq!(list().iter().find(|entry| entry.id == id).unwrap().attempts, 0);
+
+        cancel(&id).unwrap();
+        finish_attempt(&id, |entry| entry.status = OutboxStatus::Delivered);
+        assert_eq!(get_status(&id), Some((OutboxStatus::Cancelled, None)));
+        retry(true);
+        assert_eq!(get_status(&id), Some((OutboxStatus::Cancelled, None)));
+        remove(&id);
+    }
+
+    #[test]
+    fn test_post_or_queue_without_outbox() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        httpclient::set_next_u8_response(::utils::constants::SEND_MESSAGE_RESPONSE.to_vec());
+        assert_eq!(post_or_queue(&vec![1, 2, 3], MessageKind::SendMessage).unwrap(),
+                   Delivery::Sent(r#"{"@type":{"name":"MSG_SENT","ver":"1.0"},"uids":["ntc2ytb"]}"#.to_string()));
+    }
+}
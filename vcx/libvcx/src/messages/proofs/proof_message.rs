@@ -10,6 +10,27 @@ static PROVER_DID: &'static str = "prover_did";
 static MSG_FROM_API: &str = r#"{"proofs":{"claim::71b6070f-14ba-45fa-876d-1fe8491fe5d4":{"proof":{"primary_proof":{"eq_proof":{"revealed_attrs":{"sex":"5944657099558967239210949258394887428692050081607692519917050011144233115103","name":"1139481716457488690172217916278103335"},"a_prime":"55115757663642844902979276276581544287881791112969892277372135316353511833640150801244335663890109536491278379177551666081054765286807563008348637104046950934828407012194403360724040287698135607556244297972578864339500981366412262454282194811242239615009347165118318516694216754501345324782597475927199400880006212632553233049354866295429520527445980181939247828351677971991914388778860092824318440481574181300185829423762990910739241691289976584754979812272223819007422499654272590946235912914032826994670588466080422906806402660885408376207875827950805200378568062518210110828954480363081643567615791016011737856977","e":"34976147138641338975844073241645969211530343885520088294714132974884138611036204288689212378023649179372520412699253155486970203797562324","v":"961473607552945346906354315658276499450491951690969023699851664262072769313929148332129868528140265952852653009499943891795293148107502144091334703992581737220352761140064276811372868396353572957613845323343723271098601244774874235526135299483412285009916812621185291842845156342501611029106982811773616231232684804116984093651972537804480090649736612551759833591251845595059217608938213987633789344584340351801507541774726753840600143685051258161251666953243698589585559347435011414292427590918153421953579895479604685390401357681887618798200391305919594609949167659780330698000168295871428737686822637913218269005987492318466661186509308179489615192663542904993253626728197630057096161118638090776180812895097232529119979970798938360220605280817954648588493778338816318524451785027916181454650102696493927306340658666852294316562458212054696739343800993703515542777264448535624584845146378512183572107830260813929222999","m":{},"m1":"75548120024969192086664289521241751069844239013520403238642886571169851979005373784309432586593371476370934469326730539754613694936161784687213609047455188306625204249706249661640538349287762196100659095340756990269587317065862046598569445591945049204366911309949910119711238973099702616527117177036784698661","m2":"287944186286321709724396773443214682376883853676549188669693055373059354657799325692443906346632814001611911026063358134413175852024773765930829079850890920811398176944587192618"},"ge_proofs":[]},"non_revoc_proof":null},"schema_seq_no":103,"issuer_did":"V4SGRU86Z58d6TV7PBUe6f"}},"aggregated_proof":{"c_hash":"63330487197040957750863022608534150304998351350639315143102570772502292901825","c_list":[[1,180,153,212,162,132,5,189,14,181,140,112,236,109,182,76,91,6,161,215,62,207,205,135,86,211,49,197,215,198,104,201,14,22,48,6,112,170,31,191,110,118,121,15,62,114,126,249,221,107,114,161,163,234,19,233,150,236,182,217,195,6,218,217,193,6,94,160,33,23,103,147,109,221,81,38,138,20,225,141,68,37,142,10,225,79,164,119,168,250,188,186,47,229,165,8,237,230,14,35,53,176,97,28,82,105,87,210,117,16,154,222,66,11,96,172,90,13,239,190,29,71,11,88,53,36,219,139,67,21,136,58,161,164,97,106,56,230,55,157,59,35,187,235,154,194,111,93,168,135,67,15,97,136,38,169,87,142,32,255,50,247,111,83,44,88,251,99,6,226,182,170,146,229,118,164,118,228,235,51,137,168,135,50,1,14,1,201,72,175,102,241,149,117,88,83,84,37,205,130,26,155,124,158,211,89,112,33,46,24,94,93,202,8,127,172,214,178,6,156,79,188,132,223,239,127,200,158,95,247,139,101,51,162,168,175,74,1,67,201,94,108,192,14,130,109,217,248,193,10,142,37,95,231,227,251,209]]},"requested_proof":{"revealed_attrs":{"attr2_uuid":["claim::71b6070f-14ba-45fa-876d-1fe8491fe5d4","male","5944657099558967239210949258394887428692050081607692519917050011144233115103"],"attr1_uuid":["claim::71b6070f-14ba-45fa-876d-1fe8491fe5d4","Alex","1139481716457488690172217916278103335"]},"unrevealed_attrs":{},"self_attested_attrs":{},"predicates":{}},"remoteDid":"KP8AaEBc368CMK1PqZaEzX","userPairwiseDid":"PofTCeegEXT7S2aAePhM6a"}"#;
 
 
+/// How a requested referent was answered by the prover
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ReferentStatus {
+    Revealed,
+    Unrevealed,
+    SelfAttested,
+    Predicate,
+}
+
+/// Answer to one referent of the proof request. value is set for revealed and self attested
+/// attributes, the identifiers for every referent proven from a credential.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ReferentResult {
+    pub referent: String,
+    pub status: ReferentStatus,
+    pub value: Option<String>,
+    pub schema_id: Option<String>,
+    pub cred_def_id: Option<String>,
+    pub issuer_did: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct ProofMessage{
     version: Option<String>,
@@ -73,6 +94,52 @@ impl ProofMessage {
         Ok(rtn)
     }
 
+    /// Typed view of requested_proof, with the credential identifiers of each referent
+    pub fn get_results(&self) -> Result<Vec<ReferentResult>, ProofError> {
+        let proof: Value = serde_json::from_str(&self.libindy_proof)
+            .or(Err(ProofError::CommonError(error::INVALID_JSON.code_num)))?;
+        let requested_proof = &proof["requested_proof"];
+        let identifiers = &proof["identifiers"];
+
+        let mut rtn = Vec::new();
+        for &(section, status) in &[("revealed_attrs", ReferentStatus::Revealed),
+                                    ("unrevealed_attrs", ReferentStatus::Unrevealed),
+                                    ("predicates", ReferentStatus::Predicate)] {
+            if let Value::Object(ref map) = requested_proof[section] {
+                for (referent, value) in map {
+                    let identifier = value["sub_proof_index"].as_u64()
+                        .map(|index| &identifiers[index as usize])
+                        .ok_or(ProofError::CommonError(error::INVALID_JSON.code_num))?;
+                    let cred_def_id = identifier["cred_def_id"].as_str().map(String::from);
+
+                    rtn.push(ReferentResult {
+                        referent: referent.to_string(),
+                        status,
+                        value: value["raw"].as_str().map(String::from),
+                        schema_id: identifier["schema_id"].as_str().map(String::from),
+                        issuer_did: cred_def_id.as_ref().and_then(|id| id.split(':').next()).map(String::from),
+                        cred_def_id,
+                    });
+                }
+            }
+        }
+
+        if let Value::Object(ref map) = requested_proof["self_attested_attrs"] {
+            for (referent, value) in map {
+                rtn.push(ReferentResult {
+                    referent: referent.to_string(),
+                    status: ReferentStatus::SelfAttested,
+                    value: value.as_str().map(String::from),
+                    schema_id: None,
+                    cred_def_id: None,
+                    issuer_did: None,
+                });
+            }
+        }
+
+        rtn.sort_by(|a, b| a.referent.cmp(&b.referent));
+        Ok(rtn)
+    }
 }
 
 #[cfg(test)]
@@ -112,4 +179,28 @@ pub mod tests {
         assert_eq!(&credential_data[0].1, "NcYxiDXkpYi6ov5FcYDi1e:3:CL:NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0");
         assert_eq!(&credential_data[0].2, "");
     }
+
+    #[test]
+    fn test_get_results() {
+        ::settings::set_defaults();
+        let proof = create_default_proof();
+        let results = proof.get_results().unwrap();
+
+        assert_eq!(results.iter().map(|r| (r.referent.as_str(), r.status)).collect::<Vec<_>>(),
+                   vec![("attr1_referent", ReferentStatus::Revealed),
+                        ("attr2_referent", ReferentStatus::Unrevealed),
+                        ("attr3_referent", ReferentStatus::SelfAttested),
+                        ("predicate1_referent", ReferentStatus::Predicate)]);
+        assert_eq!(results[0].value, Some("Alex".to_string()));
+        assert_eq!(results[0].schema_id, Some("NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0".to_string()));
+        assert_eq!(results[0].issuer_did, Some("NcYxiDXkpYi6ov5FcYDi1e".to_string()));
+        assert_eq!(results[1].value, None);
+        assert_eq!(results[2].value, Some("8-800-300".to_string()));
+        assert_eq!(results[2].cred_def_id, None);
+        assert_eq!(results[3].cred_def_id, Some("NcYxiDXkpYi6ov5FcYDi1e:3:CL:NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0".to_string()));
+
+        let mut proof = ProofMessage::new();
+        proof.libindy_proof = r#"{"requested_proof":{"revealed_attrs":{"attr1":{"raw":"Alex"}}}}"#.to_string();
+        assert_eq!(proof.get_results(), Err(ProofError::CommonError(error::INVALID_JSON.code_num)));
+    }
 }
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use std::vec::Vec;
 use utils::error;
 use messages::validation;
+use utils::encoding::AttributeEncoding;
 
 static PROOF_REQUEST: &str = "PROOF_REQUEST";
 static PROOF_DATA: &str = "proof_request_data";
@@ -29,6 +30,10 @@ pub struct AttrInfo {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub restrictions: Option<Vec<Filter>>,
+    // encoding the issuer declared for the attribute, the revealed value is checked against it
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<AttributeEncoding>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
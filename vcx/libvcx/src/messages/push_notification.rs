@@ -0,0 +1,79 @@
+extern crate serde_json;
+
+use serde_json::{Map, Value};
+use utils::error;
+
+// keys used for the pairwise did and message uid by the agency push com methods and webhooks
+static PAIRWISE_DID_KEYS: &[&str] = &["forDID", "pairwiseDID", "forDid"];
+static MSG_UID_KEYS: &[&str] = &["uid", "msgUid", "msgId"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushNotification {
+    pub pairwise_did: String,
+    pub msg_uid: String,
+}
+
+fn first_string(map: &Map<String, Value>, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|key| map.get(*key).and_then(Value::as_str))
+        .next()
+        .map(String::from)
+}
+
+fn find_notification(value: &Value) -> Option<PushNotification> {
+    match *value {
+        // FCM data values and some webhooks carry the message as a json string
+        Value::String(ref x) => serde_json::from_str::<Value>(x).ok().and_then(|x| find_notification(&x)),
+        Value::Object(ref map) => {
+            match (first_string(map, PAIRWISE_DID_KEYS), first_string(map, MSG_UID_KEYS)) {
+                (Some(pairwise_did), Some(msg_uid)) => Some(PushNotification { pairwise_did, msg_uid }),
+                _ => map.values().filter_map(find_notification).next(),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Finds the pairwise did and message uid in a push payload. Accepts FCM data ({"data": {...}}),
+/// APNs payloads (custom keys next to "aps") and webhook bodies.
+pub fn parse_push_notification(payload: &str) -> Result<PushNotification, u32> {
+    let value: Value = serde_json::from_str(payload).or(Err(error::INVALID_JSON.code_num))?;
+
+    find_notification(&value).ok_or_else(|| {
+        warn!("push notification does not identify a message: {}", payload);
+        error::INVALID_PUSH_NOTIFICATION.code_num
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected() -> PushNotification {
+        PushNotification { pairwise_did: "8XFh8yBzrpJQmNyZzgoTqB".to_string(), msg_uid: "ntc2ytb".to_string() }
+    }
+
+    #[test]
+    fn test_parse_fcm_payload() {
+        let payload = r#"{"data":{"uid":"ntc2ytb","forDID":"8XFh8yBzrpJQmNyZzgoTqB","type":"credReq"}}"#;
+        assert_eq!(parse_push_notification(payload).unwrap(), expected());
+
+        let payload = r#"{"data":{"message":"{\"msgUid\":\"ntc2ytb\",\"pairwiseDID\":\"8XFh8yBzrpJQmNyZzgoTqB\"}"}}"#;
+        assert_eq!(parse_push_notification(payload).unwrap(), expected());
+    }
+
+    #[test]
+    fn test_parse_apns_and_webhook_payloads() {
+        let payload = r#"{"aps":{"alert":"New proof"},"msgId":"ntc2ytb","forDID":"8XFh8yBzrpJQmNyZzgoTqB"}"#;
+        assert_eq!(parse_push_notification(payload).unwrap(), expected());
+
+        let payload = r#"{"uid":"ntc2ytb","forDID":"8XFh8yBzrpJQmNyZzgoTqB"}"#;
+        assert_eq!(parse_push_notification(payload).unwrap(), expected());
+    }
+
+    #[test]
+    fn test_parse_invalid_payload() {
+        assert_eq!(parse_push_notification("not json"), Err(error::INVALID_JSON.code_num));
+        assert_eq!(parse_push_notification(r#"{"aps":{"alert":"hi"}}"#), Err(error::INVALID_PUSH_NOTIFICATION.code_num));
+    }
+}
@@ -9,6 +9,7 @@ use utils::error;
 use serde::Deserialize;
 use self::rmp_serde::Deserializer;
 use messages::*;
+use messages::outbox::{self, Delivery, MessageKind};
 
 pub struct SendMessage {
     message: String,
@@ -42,13 +43,13 @@ pub struct CreateMessagePayload {
 #[serde(rename_all = "camelCase")]
 pub struct MessageDetailPayload {
     #[serde(rename = "@type")]
-    msg_type: MsgType,
+    pub msg_type: MsgType,
     #[serde(rename = "@msg")]
-    msg: Vec<u8>,
+    pub msg: Vec<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    title: Option<String>,
+    pub title: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    detail: Option<String>,
+    pub detail: Option<String>,
 }
 
 impl SendMessage{
@@ -122,6 +123,18 @@ impl SendMessage{
         Ok(result.to_owned())
     }
 
+    /// Same as send_secure, but the message is kept in the outbox when the agency is unreachable
+    pub fn send_secure_or_queue(&mut self) -> Result<Delivery, u32> {
+        let data = self.msgpack()?;
+
+        debug!("sending secure message to agency");
+        if settings::test_agency_mode_enabled() {
+            return Ok(Delivery::Sent(parse_send_message_response(::utils::constants::SEND_MESSAGE_RESPONSE.to_vec())?));
+        }
+
+        outbox::post_or_queue(&data, MessageKind::SendMessage)
+    }
+
     pub fn set_title(&mut self, title: &str) -> &mut Self {
         self.title = Some(title.to_string());
         self
@@ -176,7 +189,7 @@ pub struct SendMessageResponse {
     uids: Vec<String>,
 }
 
-fn parse_send_message_response(response: Vec<u8>) -> Result<String, u32> {
+pub fn parse_send_message_response(response: Vec<u8>) -> Result<String, u32> {
     let data = unbundle_from_agency(response)?;
 
     if data.len() <= 1 {
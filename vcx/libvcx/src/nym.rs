@@ -0,0 +1,193 @@
+extern crate serde_json;
+extern crate rust_base58;
+
+use serde_json::Value;
+use self::rust_base58::{FromBase58, ToBase58};
+use settings;
+use messages::validation;
+use utils::error;
+use utils::constants::{ NYM_TXN_TYPE, ATTRIB_TXN_TYPE, GET_NYM_RESPONSE, GET_ATTRIB_RESPONSE };
+use utils::libindy::ledger::{
+    libindy_build_nym_request,
+    libindy_build_get_nym_request,
+    libindy_build_attrib_request,
+    libindy_build_get_attrib_request,
+    libindy_submit_read_request,
+    check_txn_response,
+};
+use utils::libindy::payments::{pay_for_txn, PaymentTxn};
+
+static ROLES: &[&str] = &["TRUSTEE", "STEWARD", "TRUST_ANCHOR", "NETWORK_MONITOR"];
+
+fn validate_role(role: Option<&str>) -> Result<(), u32> {
+    match role {
+        // an empty role removes the current role of the did
+        Some(x) if !x.is_empty() && !ROLES.contains(&x) => Err(error::INVALID_OPTION.code_num),
+        _ => Ok(()),
+    }
+}
+
+fn parse_get_data(response: &str) -> Result<String, u32> {
+    check_txn_response(response)?;
+
+    let response: Value = serde_json::from_str(response)
+        .or(Err(error::INVALID_JSON.code_num))?;
+
+    match response["result"]["data"].as_str() {
+        Some(data) => Ok(data.to_string()),
+        None => Err(error::LEDGER_ITEM_NOT_FOUND.code_num),
+    }
+}
+
+/// Returns the nym data -> {"dest":"","identifier":"","role":"","seqNo":0,"txnTime":0,"verkey":""}
+pub fn parse_get_nym_response(response: &str) -> Result<String, u32> {
+    parse_get_data(response)
+}
+
+/// Returns the raw attribute json -> {"endpoint":{"ha":"127.0.0.1:5555"}}
+pub fn parse_get_attrib_response(response: &str) -> Result<String, u32> {
+    parse_get_data(response)
+}
+
+/// Registers a new did or updates the verkey/role of an existing one. The institution did submits
+/// the transaction and pays for it if the ledger charges fees for NYM.
+pub fn write_nym(target_did: &str, verkey: Option<&str>, role: Option<&str>) -> Result<Option<PaymentTxn>, u32> {
+    validation::validate_did(target_did)?;
+    // abbreviated verkeys (~...) are resolved by the ledger against the did
+    if let Some(verkey) = verkey {
+        if !verkey.starts_with('~') { validation::validate_verkey(verkey)?; }
+    }
+    validate_role(role)?;
+
+    let submitter_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+
+    let request = libindy_build_nym_request(&submitter_did, target_did, verkey, None, role)?;
+
+    let (payment, response) = pay_for_txn(&request, NYM_TXN_TYPE)?;
+
+    check_txn_response(&response)?;
+
+    Ok(payment)
+}
+
+pub fn get_nym(did: &str) -> Result<String, u32> {
+    validation::validate_did(did)?;
+
+    if settings::test_indy_mode_enabled() { return parse_get_nym_response(GET_NYM_RESPONSE); }
+
+    let submitter_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+
+    let request = libindy_build_get_nym_request(&submitter_did, did)?;
+
+    let response = libindy_submit_read_request(did, &request)?;
+
+    parse_get_nym_response(&response)
+}
+
+/// Full verkey of an abbreviated one (~...), which holds the key bytes that follow the did bytes
+pub fn expand_verkey(did: &str, verkey: &str) -> Result<String, u32> {
+    if !verkey.starts_with('~') { return validation::validate_verkey(verkey); }
+
+    let mut key = did.from_base58().or(Err(error::NOT_BASE58.code_num))?;
+    key.extend(verkey[1..].from_base58().or(Err(error::NOT_BASE58.code_num))?);
+    validation::validate_verkey(&key.to_base58())
+}
+
+/// Current verkey of did on the ledger
+pub fn get_verkey(did: &str) -> Result<String, u32> {
+    let nym: Value = serde_json::from_str(&get_nym(did)?).or(Err(error::INVALID_JSON.code_num))?;
+    let verkey = nym["verkey"].as_str().ok_or(error::INVALID_VERKEY.code_num)?;
+    expand_verkey(did, verkey)
+}
+
+/// Sets a raw attribute (ie, service endpoint) on a did owned by the institution
+pub fn write_attrib(target_did: &str, attrib_json: &str) -> Result<Option<PaymentTxn>, u32> {
+    validation::validate_did(target_did)?;
+    let attrib: Value = serde_json::from_str(attrib_json)
+        .or(Err(error::INVALID_JSON.code_num))?;
+    if !attrib.is_object() { return Err(error::INVALID_JSON.code_num); }
+
+    let submitter_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+
+    let request = libindy_build_attrib_request(&submitter_did, target_did, None, Some(attrib_json), None)?;
+
+    let (payment, response) = pay_for_txn(&request, ATTRIB_TXN_TYPE)?;
+
+    check_txn_response(&response)?;
+
+    Ok(payment)
+}
+
+pub fn get_attrib(did: &str, attrib_name: &str) -> Result<String, u32> {
+    validation::validate_did(did)?;
+
+    if settings::test_indy_mode_enabled() { return parse_get_attrib_response(GET_ATTRIB_RESPONSE); }
+
+    let submitter_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+
+    let request = libindy_build_get_attrib_request(&submitter_did, did, Some(attrib_name), None, None)?;
+
+    let response = libindy_submit_read_request(did, &request)?;
+
+    parse_get_attrib_response(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TARGET_DID: &str = "V4SGRU86Z58d6TV7PBUe6f";
+
+    fn set_default_and_enable_test_mode() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+    }
+
+    #[test]
+    fn test_write_nym() {
+        set_default_and_enable_test_mode();
+
+        assert!(write_nym(TARGET_DID, Some(settings::DEFAULT_VERKEY), Some("TRUST_ANCHOR")).unwrap().is_some());
+        assert!(write_nym(TARGET_DID, None, Some("")).is_ok());
+        assert_eq!(write_nym(TARGET_DID, None, Some("KING")), Err(error::INVALID_OPTION.code_num));
+        assert_eq!(write_nym("2hoqvcwupRTU", None, None), Err(error::INVALID_DID.code_num));
+    }
+
+    #[test]
+    fn test_get_nym() {
+        set_default_and_enable_test_mode();
+
+        let nym: Value = serde_json::from_str(&get_nym(TARGET_DID).unwrap()).unwrap();
+        assert_eq!(nym["dest"], json!(TARGET_DID));
+        assert_eq!(nym["role"], json!("101"));
+    }
+
+    #[test]
+    fn test_get_verkey() {
+        set_default_and_enable_test_mode();
+
+        let verkey = get_verkey(TARGET_DID).unwrap();
+        assert_eq!(verkey.from_base58().unwrap()[..16], TARGET_DID.from_base58().unwrap()[..]);
+        assert_eq!(expand_verkey(TARGET_DID, settings::DEFAULT_VERKEY).unwrap(), settings::DEFAULT_VERKEY);
+        assert_eq!(expand_verkey(TARGET_DID, "~0OIl"), Err(error::NOT_BASE58.code_num));
+    }
+
+    #[test]
+    fn test_write_and_get_attrib() {
+        set_default_and_enable_test_mode();
+
+        assert!(write_attrib(TARGET_DID, r#"{"endpoint":{"ha":"127.0.0.1:5555"}}"#).is_ok());
+        assert_eq!(write_attrib(TARGET_DID, "[]"), Err(error::INVALID_JSON.code_num));
+
+        let attrib: Value = serde_json::from_str(&get_attrib(TARGET_DID, "endpoint").unwrap()).unwrap();
+        assert_eq!(attrib["endpoint"]["ha"], json!("127.0.0.1:5555"));
+    }
+
+    #[test]
+    fn test_parse_responses() {
+        assert_eq!(parse_get_nym_response(r#"{"op":"REPLY","result":{"data":null}}"#),
+                   Err(error::LEDGER_ITEM_NOT_FOUND.code_num));
+        assert_eq!(parse_get_attrib_response(r#"{"op":"REJECT","reason":"not allowed"}"#),
+                   Err(error::LEDGER_REQUEST_REJECTED.code_num));
+    }
+}
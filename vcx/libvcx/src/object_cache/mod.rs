@@ -47,10 +47,16 @@ impl<T> ObjectCache<T> {
         let store = self._lock_store()?;
         match store.get(&handle) {
             Some(m) => match m.lock() {
-                Ok(obj) => closure(obj.deref()),
+                Ok(obj) => closure(obj.deref()).map_err(|err| {
+                    ::error::context::add_error_context("handle", &handle.to_string());
+                    err
+                }),
                 Err(err) => return Err(10) //TODO better error
             },
-            None => return Err(error::INVALID_OBJ_HANDLE.code_num)
+            None => {
+                ::error::context::add_error_context("handle", &handle.to_string());
+                return Err(error::INVALID_OBJ_HANDLE.code_num)
+            }
         }
     }
 
@@ -60,10 +66,16 @@ impl<T> ObjectCache<T> {
         let mut store = self._lock_store()?;
         match store.get_mut(&handle) {
             Some(m) => match m.lock() {
-                Ok(mut obj) => closure(obj.deref_mut()),
+                Ok(mut obj) => closure(obj.deref_mut()).map_err(|err| {
+                    ::error::context::add_error_context("handle", &handle.to_string());
+                    err
+                }),
                 Err(err) => return Err(10) //TODO better error
             },
-            None => return Err(error::INVALID_OBJ_HANDLE.code_num)
+            None => {
+                ::error::context::add_error_context("handle", &handle.to_string());
+                return Err(error::INVALID_OBJ_HANDLE.code_num)
+            }
         }
     }
 
@@ -92,6 +104,13 @@ impl<T> ObjectCache<T> {
         }
     }
 
+    pub fn handles(&self) -> Vec<u32> {
+        match self._lock_store() {
+            Ok(store) => store.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
     pub fn drain(&self) -> Result<(), u32> {
         let mut store = self._lock_store()?;
         Ok(store.clear())
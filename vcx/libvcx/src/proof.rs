@@ -1,32 +1,45 @@
 extern crate rand;
 extern crate serde_json;
 extern crate libc;
-extern crate openssl;
 
-use self::openssl::bn::{ BigNum, BigNumRef };
 use settings;
 use connection;
 use api::{ VcxStateType, ProofStateType };
 use std::collections::HashMap;
-use messages::proofs::proof_message::{ProofMessage};
+use messages::proofs::proof_message::{ProofMessage, ReferentResult};
 use messages;
 use messages::proofs::proof_request::{ ProofRequestMessage };
 use messages::GeneralMessage;
+use messages::get_message::Message;
+use messages::outbox::Delivery;
+use messages::out_of_band::{self, OutOfBandMessage};
 use utils::error;
 use utils::constants::*;
+use utils::nonce::{self, generate_nonce};
 use utils::libindy::anoncreds::libindy_verifier_verify_proof;
 use credential_def::{ retrieve_credential_def };
 use schema::{ LedgerSchema };
 use error::proof::ProofError;
+use proof_compliance::proof_compliance;
 use error::ToErrorCode;
-use serde_json::Value;
-use utils::constants::DEFAULT_SERIALIZE_VERSION;
+use utils::serialization::{self, Versioned, Migration};
+use state_machine::{self, ProtocolEvent, Transition, StateTransition, QueuedEvent};
 use object_cache::ObjectCache;
 
 lazy_static! {
     static ref PROOF_MAP: ObjectCache<Proof> = Default::default();
 }
 
+// a proof that fails validation goes back to RequestReceived, a request that is not answered
+// before its response deadline expires
+static PROOF_TRANSITIONS: &[Transition] = &[
+    Transition { event: ProtocolEvent::Initialize, from: &[VcxStateType::VcxStateNone], to: VcxStateType::VcxStateInitialized },
+    Transition { event: ProtocolEvent::SendRequest, from: &[VcxStateType::VcxStateInitialized], to: VcxStateType::VcxStateOfferSent },
+    Transition { event: ProtocolEvent::ReceiveProof, from: &[VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateAccepted },
+    Transition { event: ProtocolEvent::ReceiveInvalidProof, from: &[VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateRequestReceived },
+    Transition { event: ProtocolEvent::Expire, from: &[VcxStateType::VcxStateOfferSent], to: VcxStateType::VcxStateExpired },
+];
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Proof {
     source_id: String,
@@ -37,10 +50,22 @@ pub struct Proof {
     prover_did: String,
     prover_vk: String,
     state: VcxStateType,
+    state_history: Vec<StateTransition>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queued_event: Option<QueuedEvent>,
     proof_state: ProofStateType,
     name: String,
     version: String,
     nonce: String,
+    // unix time after which the proof request expires
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_deadline: Option<u64>,
+    // the request was handed out without a connection and the proof comes back to prover_did
+    #[serde(default)]
+    #[serde(skip_serializing_if = "::std::ops::Not::not")]
+    out_of_band: bool,
     proof: Option<ProofMessage>, // Refactoring this name to 'proof_message' causes some tests to fail.
     proof_request: Option<ProofRequestMessage>,
     remote_did: String,
@@ -175,15 +200,36 @@ impl Proof {
         debug!("*******\n{}\n********", schemas_json);
         debug!("*******\n{}\n********", proof_json);
         debug!("*******\n{}\n********", proof_req_json);
-//        proof_compliance(&proof_req_msg.proof_request_data, &proof_msg)?;
-        self.validate_proof_indy(&proof_req_json, &proof_json, &schemas_json, &credential_def_msg, "{}", "{}")
+        proof_compliance(&proof_req_msg.proof_request_data, &proof_msg)?;
+        let rc = self.validate_proof_indy(&proof_req_json, &proof_json, &schemas_json, &credential_def_msg, "{}", "{}")?;
+
+        // the proof is bound to the nonce of the request it was verified against
+        if self.proof_state != ProofStateType::ProofInvalid {
+            self.issue_missing_nonce()?;
+            nonce::consume(&proof_req_msg.proof_request_data.nonce).map_err(|ec| ProofError::CommonError(ec))?;
+        }
+        Ok(rc)
+    }
+
+    fn build_proof_request(&self) -> Result<(ProofRequestMessage, String), ProofError> {
+        let data_version = "0.1";
+        let mut proof_obj = messages::proof_request();
+        let proof_request = proof_obj
+            .type_version(&self.version)
+            .tid(1)
+            .mid(9)
+            .nonce(&self.nonce)
+            .proof_name(&self.name)
+            .proof_data_version(data_version)
+            .requested_attrs(&self.requested_attrs)
+            .requested_predicates(&self.requested_predicates)
+            .serialize_message()
+            .map_err(|ec| ProofError::ProofMessageError(ec))?;
+        Ok((proof_obj, proof_request))
     }
 
     fn send_proof_request(&mut self, connection_handle: u32) -> Result<u32, ProofError> {
-        if self.state != VcxStateType::VcxStateInitialized {
-            warn!("proof {} has invalid state {} for sending proofRequest", self.source_id, self.state as u32);
-            return Err(ProofError::ProofNotReadyError())
-        }
+        self.check_event(ProtocolEvent::SendRequest)?;
         debug!("sending proof request with proof: {}, and connection {}", self.source_id, connection_handle);
         self.prover_did = connection::get_pw_did(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
         self.agent_did = connection::get_agent_did(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
@@ -198,20 +244,9 @@ impl Proof {
                self.remote_vk,
                self.prover_vk);
 
-        let data_version = "0.1";
-        let mut proof_obj = messages::proof_request();
-        let proof_request = proof_obj
-            .type_version(&self.version)
-            .tid(1)
-            .mid(9)
-            .nonce(&self.nonce)
-            .proof_name(&self.name)
-            .proof_data_version(data_version)
-            .requested_attrs(&self.requested_attrs)
-            .requested_predicates(&self.requested_predicates)
-            .serialize_message()
-            .map_err(|ec| ProofError::ProofMessageError(ec))?;
+        let (proof_obj, proof_request) = self.build_proof_request()?;
 
+        nonce::issue(&self.nonce, self.response_deadline).map_err(|ec| ProofError::CommonError(ec))?;
         self.proof_request = Some(proof_obj);
         let data = connection::generate_encrypted_payload(&self.prover_vk, &self.remote_vk, &proof_request, "PROOF_REQUEST").map_err(|_| ProofError::ProofConnectionError())?;
         let title = format!("{} wants you to share {}", settings::get_config_value(settings::CONFIG_INSTITUTION_NAME).unwrap(), self.name);
@@ -224,10 +259,15 @@ impl Proof {
             .set_detail(&title)
             .agent_vk(&self.agent_vk)
             .edge_agent_payload(&data)
-            .send_secure() {
-            Ok(response) => {
-                self.msg_uid = get_proof_details(&response[0])?;
-                self.state = VcxStateType::VcxStateOfferSent;
+            .send_secure_or_queue() {
+            Ok(Delivery::Sent(response)) => {
+                self.msg_uid = get_proof_details(&response)?;
+                self.apply_event(ProtocolEvent::SendRequest)?;
+                return Ok(error::SUCCESS.code_num)
+            },
+            Ok(Delivery::Queued(id)) => {
+                self.queue_event(ProtocolEvent::SendRequest, id)?;
+                debug!("queued proof request for: {}", self.source_id);
                 return Ok(error::SUCCESS.code_num)
             },
             Err(x) => {
@@ -237,15 +277,101 @@ impl Proof {
         }
     }
 
+    // The proof comes back to a pairwise key on our agency that is not shared with any
+    // connection, the signed request tells the prover where to send it.
+    fn generate_out_of_band_request(&mut self) -> Result<String, ProofError> {
+        self.check_event(ProtocolEvent::SendRequest)?;
+
+        let connection_handle = connection::build_connection(&self.source_id).map_err(|ec| ProofError::ProofConnectionError())?;
+        self.prover_did = connection::get_pw_did(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
+        self.prover_vk = connection::get_pw_verkey(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
+        self.agent_did = connection::get_agent_did(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
+        self.agent_vk = connection::get_agent_verkey(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
+
+        let (proof_obj, request) = match self.build_out_of_band_request() {
+            Ok(request) => request,
+            Err(err) => {
+                self.delete_out_of_band_agent();
+                connection::release(connection_handle).ok();
+                return Err(err);
+            }
+        };
+        connection::release(connection_handle).map_err(|ec| ProofError::InvalidConnection())?;
+
+        self.proof_request = Some(proof_obj);
+        self.out_of_band = true;
+        self.apply_event(ProtocolEvent::SendRequest)?;
+        debug!("generated out of band proof request for: {}", self.source_id);
+        Ok(request)
+    }
+
+    fn build_out_of_band_request(&self) -> Result<(ProofRequestMessage, String), ProofError> {
+        let (proof_obj, proof_request) = self.build_proof_request()?;
+        let service = out_of_band::Service::for_pairwise(&self.prover_did, &self.prover_vk, &self.agent_did, &self.agent_vk)
+            .map_err(|ec| ProofError::CommonError(ec))?;
+        let request = OutOfBandMessage::create("PROOF_REQUEST", &proof_request, service)
+            .and_then(|request| request.to_string())
+            .map_err(|ec| ProofError::CommonError(ec))?;
+
+        nonce::issue(&self.nonce, self.response_deadline).map_err(|ec| ProofError::CommonError(ec))?;
+        Ok((proof_obj, request))
+    }
+
+    // The agent of an out-of-band request only ever receives the one proof
+    fn delete_out_of_band_agent(&self) {
+        if let Err(ec) = connection::delete_pairwise_agent(&self.prover_did, &self.prover_vk, &self.agent_did, &self.agent_vk) {
+            warn!("could not delete the agent of out of band proof request {}: {}", self.source_id, error::error_string(ec));
+        }
+    }
+
+    fn get_out_of_band_proof_status(&mut self) -> Result<u32, ProofError> {
+        let received = messages::get_messages()
+            .to(&self.prover_did)
+            .to_vk(&self.prover_vk)
+            .agent_did(&self.agent_did)
+            .agent_vk(&self.agent_vk)
+            .send_secure()
+            .map_err(|ec| ProofError::ProofMessageError(ec))?;
+
+        for message in received.iter() {
+            let payload = match message.payload {
+                Some(ref payload) => messages::to_u8(payload),
+                None => continue,
+            };
+            match out_of_band::parse_reply(&self.prover_vk, &payload) {
+                Ok((ref msg_type, ref proof)) if msg_type == "PROOF" => match ProofMessage::from_str(proof) {
+                    Ok(x) => {
+                        self.msg_uid = message.uid.clone();
+                        let state = self.receive_proof(x);
+                        self.delete_out_of_band_agent();
+                        return state;
+                    },
+                    Err(err) => warn!("invalid proof in message {}: {}", message.uid, err),
+                },
+                _ => debug!("message {} is not a proof", message.uid),
+            }
+        }
+
+        Ok(self.get_state())
+    }
+
     fn get_proof(&self) -> Result<String, ProofError> {
         Ok(self.proof.as_ref().ok_or(ProofError::InvalidHandle())?.libindy_proof.clone())
     }
 
+    fn get_results(&self) -> Result<ProofResults, ProofError> {
+        let proof = self.proof.as_ref().ok_or(ProofError::InvalidHandle())?;
+        Ok(ProofResults { proof_state: self.proof_state, referents: proof.get_results()? })
+    }
+
     fn get_proof_request_status(&mut self) -> Result<u32, ProofError> {
         debug!("updating state for proof {}", self.source_id);
         if self.state == VcxStateType::VcxStateAccepted {
             return Ok(self.get_state());
         }
+        else if self.state == VcxStateType::VcxStateOfferSent && self.out_of_band {
+            return self.get_out_of_band_proof_status();
+        }
         else if self.state != VcxStateType::VcxStateOfferSent || self.msg_uid.is_empty() || self.prover_did.is_empty() {
             return Ok(self.get_state());
         }
@@ -255,22 +381,43 @@ impl Proof {
                                                          &self.agent_vk)
             .map_err(|ec| ProofError::ProofMessageError(ec))?;
 
-        self.proof = match parse_proof_payload(&payload) {
-            Err(err) => return Ok(self.get_state()),
-            Ok(x) => Some(x),
-        };
+        match parse_proof_payload(&payload) {
+            Err(err) => Ok(self.get_state()),
+            Ok(x) => self.receive_proof(x),
+        }
+    }
+
+    fn update_state_with_messages(&mut self, messages: &[Message]) -> Result<u32, ProofError> {
+        self.check_response_deadline()?;
+        if self.state != VcxStateType::VcxStateOfferSent || self.msg_uid.is_empty() {
+            return Ok(self.get_state());
+        }
+
+        match messages::get_message::find_ref_msg(&self.msg_uid, messages) {
+            Some(data) => match ProofMessage::from_str(&data) {
+                Ok(x) => self.receive_proof(x),
+                Err(err) => {
+                    warn!("invalid json {}", err);
+                    Ok(self.get_state())
+                },
+            },
+            None => Ok(self.get_state()),
+        }
+    }
 
-        self.state = VcxStateType::VcxStateAccepted;
+    fn receive_proof(&mut self, proof: ProofMessage) -> Result<u32, ProofError> {
+        self.proof = Some(proof);
 
         match self.proof_validation() {
             Ok(x) => {
+                self.apply_event(ProtocolEvent::ReceiveProof)?;
                 if self.proof_state != ProofStateType::ProofInvalid {
                     debug!("Proof format was validated for proof {}", self.source_id);
                     self.proof_state = ProofStateType::ProofValidated;
                 }
             }
             Err(x) => {
-                self.state = VcxStateType::VcxStateRequestReceived;
+                self.apply_event(ProtocolEvent::ReceiveInvalidProof)?;
                 if x == ProofError::CommonError(error::TIMEOUT_LIBINDY_ERROR.code_num) {
                     warn!("Proof {} unable to be validated", self.source_id);
                     self.proof_state = ProofStateType::ProofUndefined;
@@ -285,9 +432,68 @@ impl Proof {
     }
 
     fn update_state(&mut self) -> Result<u32, ProofError> {
+        let delivered = state_machine::poll_queued_event(PROOF_TRANSITIONS, &mut self.state,
+                                                         &mut self.state_history, &mut self.queued_event)
+            .map_err(|ec| ProofError::CommonError(ec))?;
+        if let Some(response) = delivered {
+            self.msg_uid = get_proof_details(&response)?;
+            debug!("queued proof request {} was delivered", self.source_id);
+        }
+
+        self.check_response_deadline()?;
         self.get_proof_request_status()
     }
 
+    fn set_response_deadline(&mut self, deadline: Option<u64>) -> Result<u32, ProofError> {
+        self.response_deadline = deadline;
+        // a request already sent keeps its nonce, only its expiry changes
+        if self.state == VcxStateType::VcxStateOfferSent {
+            nonce::issue(&self.nonce, deadline).map_err(|ec| ProofError::CommonError(ec))?;
+        }
+        Ok(error::SUCCESS.code_num)
+    }
+
+    // Requests sent before nonces were recorded have none in the wallet. Their nonce is recorded
+    // when their proof arrives, so the proof they are waiting for is still accepted once.
+    fn issue_missing_nonce(&self) -> Result<(), ProofError> {
+        if self.state != VcxStateType::VcxStateOfferSent { return Ok(()) }
+        if nonce::get(&self.nonce).map_err(|ec| ProofError::CommonError(ec))?.is_some() { return Ok(()) }
+
+        debug!("recording nonce of proof request {} sent before nonces were recorded", self.source_id);
+        nonce::issue(&self.nonce, self.response_deadline).map_err(|ec| ProofError::CommonError(ec))
+    }
+
+    fn check_response_deadline(&mut self) -> Result<(), ProofError> {
+        if self.state != VcxStateType::VcxStateOfferSent { return Ok(()) }
+
+        match self.response_deadline {
+            Some(deadline) if nonce::now() > deadline => {
+                warn!("proof request {} was not answered before {}", self.source_id, deadline);
+                self.apply_event(ProtocolEvent::Expire)?;
+                if self.out_of_band { self.delete_out_of_band_agent() }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn check_event(&self, event: ProtocolEvent) -> Result<(), ProofError> {
+        state_machine::next_state(PROOF_TRANSITIONS, self.state, event)
+            .map(|_| ())
+            .map_err(|ec| ProofError::CommonError(ec))
+    }
+
+    fn apply_event(&mut self, event: ProtocolEvent) -> Result<(), ProofError> {
+        state_machine::apply_event(PROOF_TRANSITIONS, &mut self.state, &mut self.state_history, event)
+            .map_err(|ec| ProofError::CommonError(ec))
+    }
+
+    fn queue_event(&mut self, event: ProtocolEvent, outbox_id: String) -> Result<(), ProofError> {
+        state_machine::queue_event(PROOF_TRANSITIONS, &mut self.state, &mut self.state_history,
+                                   &mut self.queued_event, event, outbox_id)
+            .map_err(|ec| ProofError::CommonError(ec))
+    }
+
     fn get_state(&self) -> u32 {let state = self.state as u32; state}
 
     fn get_proof_state(&self) -> u32 {let state = self.proof_state as u32; state}
@@ -297,22 +503,24 @@ impl Proof {
     fn get_source_id(&self) -> &String { &self.source_id }
 
     fn to_string(&self) -> String {
-        json!({
-            "version": DEFAULT_SERIALIZE_VERSION,
-            "data": json!(self),
-        }).to_string()
+        serialization::to_versioned_string(self)
     }
 
     fn from_str(s: &str) -> Result<Proof, ProofError> {
-        let s:Value = serde_json::from_str(&s)
-            .or(Err(ProofError::InvalidJson()))?;
-        let proof: Proof = serde_json::from_value(s["data"].clone())
-            .or(Err(ProofError::InvalidJson()))?;
-        Ok(proof)
+        serialization::from_versioned_str(s).map_err(|err| {
+            if err == error::INVALID_JSON.code_num { ProofError::InvalidJson() } else { ProofError::CommonError(err) }
+        })
     }
 
 }
 
+impl Versioned for Proof {
+    fn migrations() -> Vec<Migration> {
+        vec![serialization::unchanged("1.0", "2.0"),
+             Migration { from: "2.0", to: "3.0", migrate: state_machine::add_empty_state_history }]
+    }
+}
+
 pub fn create_proof(source_id: String,
                     requested_attrs: String,
                     requested_predicates: String,
@@ -335,10 +543,14 @@ pub fn create_proof(source_id: String,
         prover_did: String::new(),
         prover_vk: String::new(),
         state: VcxStateType::VcxStateNone,
+        state_history: Vec::new(),
+        queued_event: None,
         proof_state: ProofStateType::ProofUndefined,
         name,
         version: String::from("1.0"),
         nonce: generate_nonce().map_err(|ec| ProofError::CommonError(ec))?,
+        response_deadline: None,
+        out_of_band: false,
         proof: None,
         proof_request: None,
         remote_did: String::new(),
@@ -349,7 +561,7 @@ pub fn create_proof(source_id: String,
 
     new_proof.validate_proof_request().map_err(|ec| ProofError::CommonError(ec))?;
 
-    new_proof.state = VcxStateType::VcxStateInitialized;
+    new_proof.apply_event(ProtocolEvent::Initialize)?;
 
     let new_handle = PROOF_MAP.add(new_proof).map_err(|ec|ProofError::CreateProofError())?;
 
@@ -369,12 +581,44 @@ pub fn update_state(handle: u32) -> Result<u32, ProofError> {
     }).map_err(|ec|ProofError::CommonError(ec))
 }
 
+/// Handles of proof requests waiting for a proof, with the did and uid of the request
+pub fn get_waiting_handles() -> Vec<(u32, String, Option<String>)> {
+    PROOF_MAP.handles().into_iter().filter_map(|handle| {
+        PROOF_MAP.get(handle, |p| {
+            // out-of-band requests have no msg_uid, their answer is not on a pairwise did
+            Ok(if p.state == VcxStateType::VcxStateOfferSent && !p.prover_did.is_empty() && !p.msg_uid.is_empty() {
+                Some((handle, p.prover_did.clone(), Some(p.msg_uid.clone())))
+            } else { None })
+        }).ok().and_then(|waiting| waiting)
+    }).collect()
+}
+
+pub fn update_state_with_messages(handle: u32, messages: &[Message]) -> Result<u32, ProofError> {
+    PROOF_MAP.get_mut(handle, |p| {
+        p.update_state_with_messages(messages).map_err(|err| err.to_error_code())
+    }).map_err(|ec| if ec == error::INVALID_OBJ_HANDLE.code_num { ProofError::InvalidHandle() } else { ProofError::CommonError(ec) })
+}
+
 pub fn get_state(handle: u32) -> Result<u32, ProofError> {
     PROOF_MAP.get(handle,|p|{
         Ok(p.get_state())
     }).map_err(|ec|ProofError::CommonError(ec))
 }
 
+/// Referents of the received proof and whether it was validated
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ProofResults {
+    pub proof_state: ProofStateType,
+    pub referents: Vec<ReferentResult>,
+}
+
+pub fn get_proof_results(handle: u32) -> Result<String, ProofError> {
+    PROOF_MAP.get(handle, |p| {
+        let results = p.get_results().map_err(|ec| ec.to_error_code())?;
+        serde_json::to_string(&results).or(Err(error::INVALID_JSON.code_num))
+    }).map_err(|ec| ProofError::CommonError(ec))
+}
+
 pub fn get_proof_state(handle: u32) -> Result<u32, ProofError> {
     PROOF_MAP.get(handle,|p|{
         Ok(p.get_proof_state())
@@ -418,12 +662,24 @@ pub fn from_string(proof_data: &str) -> Result<u32, ProofError> {
     Ok(new_handle)
 }
 
+pub fn get_state_history(handle: u32) -> Result<String, ProofError> {
+    PROOF_MAP.get(handle, |p| {
+        state_machine::history_to_string(&p.state_history)
+    }).map_err(|ec| ProofError::CommonError(ec))
+}
+
 pub fn send_proof_request(handle: u32, connection_handle: u32) -> Result<u32, ProofError> {
     PROOF_MAP.get_mut(handle,|p|{
         p.send_proof_request(connection_handle).map_err(|ec|ec.to_error_code())
     }).map_err(|ec|ProofError::CommonError(ec))
 }
 
+pub fn generate_out_of_band_request(handle: u32) -> Result<String, ProofError> {
+    PROOF_MAP.get_mut(handle,|p|{
+        p.generate_out_of_band_request().map_err(|ec|ec.to_error_code())
+    }).map_err(|ec|ProofError::CommonError(ec))
+}
+
 fn get_proof_details(response: &str) -> Result<String, ProofError> {
     match serde_json::from_str(response) {
         Ok(json) => {
@@ -467,19 +723,21 @@ pub fn get_proof(handle: u32) -> Result<String, ProofError> {
     }).map_err(|ec|ProofError::CommonError(ec))
 }
 
-// TODO: This doesnt feel like it should be here (maybe utils?)
-pub fn generate_nonce() -> Result<String, u32> {
-    let mut bn = BigNum::new().map_err(|err| error::BIG_NUMBER_ERROR.code_num)?;
-
-    BigNumRef::rand(&mut bn, LARGE_NONCE as i32, openssl::bn::MsbOption::MAYBE_ZERO, false)
-        .map_err(|_| error::BIG_NUMBER_ERROR.code_num)?;
-    Ok(bn.to_dec_str().map_err(|err| error::BIG_NUMBER_ERROR.code_num)?.to_string())
+/// Sets the unix time after which an unanswered proof request moves to VcxStateExpired, None
+/// removes the deadline
+pub fn set_response_deadline(handle: u32, deadline: Option<u64>) -> Result<u32, ProofError> {
+    PROOF_MAP.get_mut(handle, |p| {
+        p.set_response_deadline(deadline).map_err(|ec| ec.to_error_code())
+    }).map_err(|ec| ProofError::CommonError(ec))
 }
 
 #[cfg(test)]
 mod tests {
+    extern crate openssl;
     use super::*;
+    use self::openssl::bn::BigNum;
     use utils::httpclient;
+    use serde_json::Value;
     use connection::build_connection;
     use utils::libindy::{pool, set_libindy_rc};
     static PROOF_MSG: &str = r#"{"msg_type":"proof","version":"0.1","to_did":"BnRXf8yDMUwGyZVDkSENeq","from_did":"GxtnGN6ypZYgEqcftSQFnC","proof_request_id":"cCanHnpFAD","proofs":{"claim::e5fec91f-d03d-4513-813c-ab6db5715d55":{"proof":{"primary_proof":{"eq_proof":{"revealed_attrs":{"state":"96473275571522321025213415717206189191162"},"a_prime":"22605045280481376895214546474258256134055560453004805058368015338423404000586901936329279496160366852115900235316791489357953785379851822281248296428005020302405076144264617943389810572564188437603815231794326272302243703078443007359698858400857606408856314183672828086906560155576666631125808137726233827430076624897399072853872527464581329767287002222137559918765406079546649258389065217669558333867707240780369514832185660287640444094973804045885379406641474693993903268791773620198293469768106363470543892730424494655747935463337367735239405840517696064464669905860189004121807576749786474060694597244797343224031","e":"70192089123105616042684481760592174224585053817450673797400202710878562748001698340846985261463026529360990669802293480312441048965520897","v":"1148619141217957986496757711054111791862691178309410923416837802801708689012670430650138736456223586898110113348220116209094530854607083005898964558239710027534227973983322542548800291320747321452329327824406430787211689678096549398458892087551551587767498991043777397791000822007896620414888602588897806008609113730393639807814070738699614969916095861363383223421727858670289337712185089527052065958362840287749622133424503902085247641830693297082507827948006947829401008622239294382186995101394791468192083810475776455445579931271665980788474331866572497866962452476638881287668931141052552771328556458489781734943404258692308937784221642452132005267809852656378394530342203469943982066011466088478895643800295937901139711103301249691253510784029114718919483272055970725860849610885050165709968510696738864528287788491998027072378656038991754015693216663830793243584350961586874315757599094357535856429087122365865868729","m":{"address2":"11774234640096848605908744857306447015748098256395922562149769943967941106193320512788344020652220849708117081570187385467979956319507248530701654682748372348387275979419669108338","city":"4853213962270369118453000522408430296589146124488849630769837449684434138367659379663124155088827069418193027370932024893343033367076071757003149452226758383807126385017161888440","address1":"12970590675851114145396120869959510754345567924518524026685086869487243290925032320159287997675756075512889990901552679591155319959039145119122576164798225386578339739435869622811","zip":"8333721522340131864419931745588776943042067606218561135102011966361165456174036379901390244538991611895455576519950813910672825465382312504250936740379785802177629077591444977329"},"m1":"92853615502250003546205004470333326341901175168428906399291824325990659330595200000112546157141090642053863739870044907457400076448073272490169488870502566172795456430489790324815765612798273406119873266684053517977802902202155082987833343670942161987285661291655743810590661447300059024966135828466539810035","m2":"14442362430453309930284822850357071315613831915865367971974791350454381198894252834180803515368579729220423713315556807632571621646127926114010380486713602821529657583905131582938"},"ge_proofs":[]},"non_revoc_proof":null},"schema_seq_no":15,"issuer_did":"4fUDR9R7fjwELRvH9JT6HH"}},"aggregated_proof":{"c_hash":"68430476900085482958838239880418115228681348197588159723604944078288347793331","c_list":[[179,17,2,242,194,227,92,203,28,32,255,113,112,20,5,243,9,111,220,111,21,210,116,12,167,119,253,181,37,40,143,215,140,42,179,97,75,229,96,94,54,248,206,3,48,14,61,219,160,122,139,227,166,183,37,43,197,200,28,220,217,10,65,42,6,195,124,44,164,65,114,206,51,231,254,156,170,141,21,153,50,251,237,65,147,97,243,17,157,116,213,201,80,119,106,70,88,60,55,36,33,160,135,106,60,212,191,235,116,57,78,177,61,86,44,226,205,100,134,118,93,6,26,58,220,66,232,166,202,62,90,174,231,207,19,239,233,223,70,191,199,100,157,62,139,176,28,184,9,70,116,199,142,237,198,183,12,32,53,84,207,202,77,56,97,177,154,169,223,201,212,163,212,101,184,255,215,167,16,163,136,44,25,123,49,15,229,41,149,133,159,86,106,208,234,73,207,154,194,162,141,63,159,145,94,47,174,51,225,91,243,2,221,202,59,11,212,243,197,208,116,42,242,131,221,137,16,169,203,215,239,78,254,150,42,169,202,132,172,106,179,130,178,130,147,24,173,213,151,251,242,44,54,47,208,223]]},"requested_proof":{"revealed_attrs":{"sdf":["claim::e5fec91f-d03d-4513-813c-ab6db5715d55","UT","96473275571522321025213415717206189191162"]},"unrevealed_attrs":{},"self_attested_attrs":{},"predicates":{}}}"#;
@@ -504,10 +762,14 @@ mod tests {
             prover_did: String::from("GxtnGN6ypZYgEqcftSQFnC"),
             prover_vk: VERKEY.to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             proof_state: ProofStateType::ProofUndefined,
             name: String::new(),
             version: String::from("1.0"),
             nonce: generate_nonce().unwrap(),
+            response_deadline: None,
+            out_of_band: false,
             proof: None,
             proof_request: None,
             remote_did: DID.to_string(),
@@ -639,10 +901,14 @@ mod tests {
             prover_did: String::from("GxtnGN6ypZYgEqcftSQFnC"),
             prover_vk: VERKEY.to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             proof_state: ProofStateType::ProofUndefined,
             name: String::new(),
             version: String::from("1.0"),
             nonce: generate_nonce().unwrap(),
+            response_deadline: None,
+            out_of_band: false,
             proof: None,
             proof_request: None,
             remote_did: DID.to_string(),
@@ -674,10 +940,14 @@ mod tests {
             prover_did: String::from("GxtnGN6ypZYgEqcftSQFnC"),
             prover_vk: VERKEY.to_string(),
             state: VcxStateType::VcxStateOfferSent,
+            state_history: Vec::new(),
+            queued_event: None,
             proof_state: ProofStateType::ProofUndefined,
             name: String::new(),
             version: String::from("1.0"),
             nonce: generate_nonce().unwrap(),
+            response_deadline: None,
+            out_of_band: false,
             proof: None,
             proof_request: None,
             remote_did: DID.to_string(),
@@ -749,6 +1019,141 @@ mod tests {
         assert_eq!(&proof_str, PROOF_JSON);
     }
 
+    #[test]
+    fn test_get_results() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let mut proof = create_boxed_proof();
+        assert_eq!(proof.get_results(), Err(ProofError::InvalidHandle()));
+
+        let mut proof_msg_obj = ProofMessage::new();
+        proof_msg_obj.libindy_proof = PROOF_JSON.to_string();
+        proof.proof = Some(proof_msg_obj);
+        proof.proof_state = ProofStateType::ProofValidated;
+
+        let results = proof.get_results().unwrap();
+        assert_eq!(results.proof_state, ProofStateType::ProofValidated);
+        assert_eq!(results.referents.len(), 3);
+        assert_eq!(results.referents[0].referent, "height_1");
+        assert_eq!(results.referents[0].value, Some("4'11".to_string()));
+        assert_eq!(results.referents[0].cred_def_id, Some("2hoqvcwupRTUNkXn6ArYzs:3:CL:2471".to_string()));
+        assert_eq!(results.referents[1].referent, "self_attest_3");
+        assert_eq!(results.referents[2].issuer_did, Some("2hoqvcwupRTUNkXn6ArYzs".to_string()));
+    }
+
+    #[test]
+    fn test_receive_proof_with_forged_raw_value() {
+        set_default_and_enable_test_mode();
+
+        let mut proof_msg = ProofMessage::new();
+        proof_msg.libindy_proof = PROOF_JSON.replace(r#""raw":"87121""#, r#""raw":"87122""#);
+
+        let mut proof = create_boxed_proof();
+        proof.proof_request = Some(ProofRequestMessage::create());
+        proof.receive_proof(proof_msg).unwrap();
+
+        assert_eq!(proof.get_state(), VcxStateType::VcxStateRequestReceived as u32);
+        assert_eq!(proof.get_proof_state(), ProofStateType::ProofInvalid as u32);
+        assert_eq!(::error::context::get_current_error().unwrap().context.get("mismatched_referents").unwrap(), "zip_2");
+    }
+
+    #[test]
+    fn test_replayed_proof_is_rejected() {
+        set_default_and_enable_test_mode();
+
+        let nonce = generate_nonce().unwrap();
+        let mut proof_request = ProofRequestMessage::create();
+        proof_request.nonce(&nonce);
+        nonce::issue(&nonce, None).unwrap();
+
+        let mut proof_msg = ProofMessage::new();
+        proof_msg.libindy_proof = PROOF_JSON.to_string();
+
+        let mut proof = create_boxed_proof();
+        proof.proof_request = Some(proof_request.clone());
+        proof.receive_proof(proof_msg.clone()).unwrap();
+        assert_eq!(proof.get_state(), VcxStateType::VcxStateAccepted as u32);
+        assert_eq!(proof.get_proof_state(), ProofStateType::ProofValidated as u32);
+
+        let mut replayed = create_boxed_proof();
+        replayed.proof_request = Some(proof_request);
+        replayed.receive_proof(proof_msg).unwrap();
+        assert_eq!(replayed.get_state(), VcxStateType::VcxStateRequestReceived as u32);
+        assert_eq!(replayed.get_proof_state(), ProofStateType::ProofInvalid as u32);
+    }
+
+    #[test]
+    fn test_nonce_of_restored_request_is_recorded_with_its_proof() {
+        set_default_and_enable_test_mode();
+
+        let mut proof = create_boxed_proof();
+        let mut proof_request = ProofRequestMessage::create();
+        proof_request.nonce(&proof.nonce);
+        proof.proof_request = Some(proof_request);
+        assert_eq!(nonce::get(&proof.nonce), Ok(None));
+
+        // restoring a proof does not need the wallet
+        let handle = from_string(&proof.to_string()).unwrap();
+        assert_eq!(nonce::get(&proof.nonce), Ok(None));
+
+        let mut proof_msg = ProofMessage::new();
+        proof_msg.libindy_proof = PROOF_JSON.to_string();
+        PROOF_MAP.get_mut(handle, |p| p.receive_proof(proof_msg.clone()).map_err(|ec| ec.to_error_code())).unwrap();
+        assert_eq!(get_proof_state(handle).unwrap(), ProofStateType::ProofValidated as u32);
+        assert_eq!(nonce::get(&proof.nonce).unwrap().unwrap().status, nonce::NonceStatus::Consumed);
+
+        // restoring it again does not reopen the nonce
+        let replayed = from_string(&proof.to_string()).unwrap();
+        PROOF_MAP.get_mut(replayed, |p| p.receive_proof(proof_msg.clone()).map_err(|ec| ec.to_error_code())).unwrap();
+        assert_eq!(get_proof_state(replayed).unwrap(), ProofStateType::ProofInvalid as u32);
+        assert_eq!(nonce::get(&proof.nonce).unwrap().unwrap().status, nonce::NonceStatus::Consumed);
+        release(handle).unwrap();
+        release(replayed).unwrap();
+    }
+
+    #[test]
+    fn test_response_deadline_expires_request() {
+        set_default_and_enable_test_mode();
+
+        let mut proof = create_boxed_proof();
+        // nothing to download from the agency
+        proof.msg_uid = String::new();
+        proof.set_response_deadline(Some(nonce::now() + 100)).unwrap();
+        assert_eq!(nonce::get(&proof.nonce).unwrap().unwrap().expires_at, proof.response_deadline);
+        proof.update_state().unwrap();
+        assert_eq!(proof.get_state(), VcxStateType::VcxStateOfferSent as u32);
+
+        proof.set_response_deadline(Some(nonce::now() - 1)).unwrap();
+        proof.update_state().unwrap();
+        assert_eq!(proof.get_state(), VcxStateType::VcxStateExpired as u32);
+    }
+
+    #[test]
+    fn test_generate_out_of_band_request() {
+        set_default_and_enable_test_mode();
+
+        let handle = create_proof("1".to_string(), REQUESTED_ATTRS.to_owned(), REQUESTED_PREDICATES.to_owned(), "Optional".to_owned()).unwrap();
+        let request = generate_out_of_band_request(handle).unwrap();
+        assert_eq!(get_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+
+        let request = OutOfBandMessage::from_str(&request, "PROOF_REQUEST").unwrap();
+        let proof_request: ProofRequestMessage = serde_json::from_str(&request.msg).unwrap();
+        let proof_nonce = proof_request.proof_request_data.nonce;
+        assert_eq!(nonce::get(&proof_nonce).unwrap().unwrap().status, nonce::NonceStatus::Outstanding);
+        assert_eq!(request.service.endpoint, settings::get_config_value(settings::CONFIG_AGENCY_ENDPOINT).unwrap());
+        assert!(to_string(handle).unwrap().contains(r#""out_of_band":true"#));
+
+        // nothing was sent back yet
+        assert_eq!(update_state(handle).unwrap(), VcxStateType::VcxStateOfferSent as u32);
+        assert_eq!(generate_out_of_band_request(handle).err(), Some(ProofError::CommonError(error::INVALID_STATE.code_num)));
+
+        // the agent of the request is deleted when it expires
+        PROOF_MAP.get_mut(handle, |p| p.set_response_deadline(Some(nonce::now() - 1)).map_err(|ec| ec.to_error_code())).unwrap();
+        assert_eq!(update_state(handle).unwrap(), VcxStateType::VcxStateExpired as u32);
+        release(handle).unwrap();
+    }
+
     #[test]
     fn test_release_all() {
         settings::set_defaults();
@@ -778,6 +1183,7 @@ mod tests {
         let proof_msg: ProofMessage = serde_json::from_str(proof_libindy).unwrap();
         let mut proof_req_msg = ProofRequestMessage::create();
         proof_req_msg.proof_request_data = serde_json::from_str(proof_req).unwrap();
+        nonce::issue(&proof_req_msg.proof_request_data.nonce, None).unwrap();
         let mut proof = Proof {
             source_id: "12".to_string(),
             msg_uid: String::from("1234"),
@@ -787,10 +1193,14 @@ mod tests {
             prover_did: String::from("GxtnGN6ypZYgEqcftSQFnC"),
             prover_vk: VERKEY.to_string(),
             state: VcxStateType::VcxStateRequestReceived,
+            state_history: Vec::new(),
+            queued_event: None,
             proof_state: ProofStateType::ProofUndefined,
             name: String::new(),
             version: String::from("1.0"),
             nonce: generate_nonce().unwrap(),
+            response_deadline: None,
+            out_of_band: false,
             proof: Some(proof_msg),
             proof_request: Some(proof_req_msg),
             remote_did: DID.to_string(),
@@ -880,7 +1290,7 @@ mod tests {
         let bad_handle = 100000;
         // TODO: Do something to guarantee that this handle is bad
         assert_eq!(proof.send_proof_request(bad_handle).err(),
-                   Some(ProofError::ProofNotReadyError()));
+                   Some(ProofError::CommonError(error::INVALID_STATE.code_num)));
         // TODO: Add test that returns a INVALID_PROOF_CREDENTIAL_DATA
         assert_eq!(proof.get_proof_request_status().err(),
                    Some(ProofError::ProofMessageError(POST_MSG_FAILURE.code_num)));
@@ -912,6 +1322,7 @@ mod tests {
 
         let mut proof_req_obj = ProofRequestMessage::create();
         proof_req_obj.proof_request_data = serde_json::from_str(&proof_req).unwrap();
+        nonce::issue(&proof_req_obj.proof_request_data.nonce, None).unwrap();
 
         let mut proof_msg = ProofMessage::new();
         proof_msg.libindy_proof = proof;
@@ -938,6 +1349,7 @@ mod tests {
 
         let mut proof_req_obj = ProofRequestMessage::create();
         proof_req_obj.proof_request_data = serde_json::from_str(&proof_req).unwrap();
+        nonce::issue(&proof_req_obj.proof_request_data.nonce, None).unwrap();
 
         let mut proof_msg = ProofMessage::new();
         proof_msg.libindy_proof = proof;
@@ -953,5 +1365,12 @@ mod tests {
         assert!(rc.is_ok());
         assert_eq!(proof.proof_state,ProofStateType::ProofValidated);
     }
-}
 
+    #[test]
+    fn test_deserialize_historical_version() {
+        let obj = Proof::from_str(PROOF_OFFER_SENT).unwrap();
+        let serialized = obj.to_string();
+        assert_eq!(serialization::parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(Proof::from_str(&serialized).unwrap().to_string(), serialized);
+    }
+}
@@ -1,3 +1,101 @@
+extern crate serde_json;
+
+use messages::proofs::proof_message::ProofMessage;
+use messages::proofs::proof_request::ProofRequestData;
+use error::proof::ProofError;
+use serde_json::Value;
+use utils::error;
+
+/// Checks what libindy does not verify in a proof. libindy only proves the encoded values, so the
+/// raw values of revealed attributes are encoded again with the encoding the request declares for
+/// them. Attributes without one use the legacy encoding, as they do when issued.
+pub fn proof_compliance(request: &ProofRequestData, proof: &ProofMessage) -> Result<(), ProofError> {
+    let mismatched = mismatched_referents(request, proof)?;
+
+    if !mismatched.is_empty() {
+        warn!("Proof Compliance: raw values do not match their encoding for {:?}", mismatched);
+        ::error::context::add_error_context("mismatched_referents", &mismatched.join(","));
+        return Err(ProofError::FailedProofCompliance())
+    }
+    Ok(())
+}
+
+/// Referents of requested_proof.revealed_attrs whose raw value does not encode to the proven value
+fn mismatched_referents(request: &ProofRequestData, proof: &ProofMessage) -> Result<Vec<String>, ProofError> {
+    let proof: Value = serde_json::from_str(&proof.libindy_proof)
+        .or(Err(ProofError::CommonError(error::INVALID_JSON.code_num)))?;
+
+    let mut rtn = Vec::new();
+    if let Value::Object(ref revealed_attrs) = proof["requested_proof"]["revealed_attrs"] {
+        for (referent, attr) in revealed_attrs {
+            let raw = attr["raw"].as_str().ok_or(ProofError::CommonError(error::INVALID_JSON.code_num))?;
+            let encoded = attr["encoded"].as_str().ok_or(ProofError::CommonError(error::INVALID_JSON.code_num))?;
+
+            let encoding = request.requested_attributes.get(referent)
+                .and_then(|attr| attr.encoding)
+                .unwrap_or_default();
+            if !encoding.verify(raw, encoded) {
+                rtn.push(referent.to_string());
+            }
+        }
+    }
+    Ok(rtn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use messages::proofs::proof_request::{AttrInfo, ProofRequestMessage};
+    use utils::constants::PROOF_JSON;
+    use utils::encoding::AttributeEncoding;
+
+    fn proof(libindy_proof: &str) -> ProofMessage {
+        let mut proof = ProofMessage::new();
+        proof.libindy_proof = libindy_proof.to_string();
+        proof
+    }
+
+    #[test]
+    fn test_proof_compliance() {
+        let request = ProofRequestMessage::create().proof_request_data;
+        assert_eq!(proof_compliance(&request, &proof(PROOF_JSON)), Ok(()));
+    }
+
+    #[test]
+    fn test_proof_compliance_fails_on_forged_raw_value() {
+        let request = ProofRequestMessage::create().proof_request_data;
+        let forged = PROOF_JSON.replace(r#""raw":"4'11""#, r#""raw":"6'2""#)
+            .replace(r#""raw":"87121""#, r#""raw":"87122""#);
+        assert_ne!(forged, PROOF_JSON);
+
+        assert_eq!(proof_compliance(&request, &proof(&forged)), Err(ProofError::FailedProofCompliance()));
+        assert_eq!(mismatched_referents(&request, &proof(&forged)).unwrap(), vec!["height_1".to_string(), "zip_2".to_string()]);
+    }
+
+    #[test]
+    fn test_proof_compliance_with_typed_encoding() {
+        let mut request = ProofRequestMessage::create().proof_request_data;
+        let dated = PROOF_JSON.replace(r#""raw":"4'11","encoded":"25730877424947290072821310314181366395232879096832067784637233452620527354832""#,
+                                       r#""raw":"2018-10-19","encoded":"17823""#);
+        assert_ne!(dated, PROOF_JSON);
+
+        // a date is not a legacy value
+        assert_eq!(proof_compliance(&request, &proof(&dated)), Err(ProofError::FailedProofCompliance()));
+
+        request.requested_attributes.insert("height_1".to_string(), AttrInfo {
+            name: "height".to_string(),
+            restrictions: None,
+            encoding: Some(AttributeEncoding::Date),
+        });
+        assert_eq!(proof_compliance(&request, &proof(&dated)), Ok(()));
+        assert_eq!(proof_compliance(&request, &proof(&dated.replace("2018-10-19", "2018-10-20"))), Err(ProofError::FailedProofCompliance()));
+        // the same number written as another type is not the value the issuer signed
+        assert_eq!(proof_compliance(&request, &proof(&dated.replace(r#""raw":"2018-10-19""#, r#""raw":"17823""#))),
+                   Err(ProofError::FailedProofCompliance()));
+    }
+}
+
+// Checks written for the previous proof format, kept for reference until they are ported
 /*use messages::proofs::proof_message::{ ProofMessage, Attr, Identifier };
 use messages::proofs::proof_request::{ Filter, ProofRequestData };
 use std::collections::HashMap;
@@ -0,0 +1,352 @@
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use serde_json::{Map, Value};
+use settings;
+use proof;
+use error::proof::ProofError;
+use utils::error;
+use utils::libindy::wallet;
+
+static TEMPLATE_RECORD: &str = "vcx_proof_template";
+// versions stored for each template id
+static VERSIONS_RECORD: &str = "vcx_proof_template_versions";
+static WALLET_GET_OPTIONS: &str = r#"{"retrieveType":false,"retrieveValue":true,"retrieveTags":false}"#;
+
+/// Proof request definition shared between verifiers. String values of requested_attrs,
+/// requested_predicates and name may hold {{parameter}} placeholders, filled in when a proof is
+/// created from the template. A value made of a single placeholder takes the type of the
+/// parameter, so "p_value":"{{threshold}}" becomes a number.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProofTemplate {
+    pub id: String,
+    pub version: String,
+    pub name: String,
+    pub requested_attrs: Value,
+    #[serde(default = "empty_array")]
+    pub requested_predicates: Value,
+    // values used for parameters the caller does not provide
+    #[serde(default)]
+    pub defaults: Map<String, Value>,
+}
+
+fn empty_array() -> Value { json!([]) }
+
+lazy_static! {
+    // stands in for the wallet in test mode, where wallet records are mocked
+    static ref TEST_TEMPLATES: Mutex<HashMap<(String, String), String>> = Default::default();
+    // serializes updates of the version index so the latest version stays consistent
+    static ref UPDATE_LOCK: Mutex<()> = Default::default();
+}
+
+fn invalid(reason: &str) -> u32 {
+    warn!("{}: {}", error::INVALID_PROOF_TEMPLATE.message, reason);
+    error::INVALID_PROOF_TEMPLATE.code_num
+}
+
+// the latest version of a template is also stored under its bare id
+fn record_id(id: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("{}:{}", id, version),
+        None => id.to_string(),
+    }
+}
+
+fn store_record(xtype: &str, id: &str, value: &str) -> Result<(), u32> {
+    if settings::test_indy_mode_enabled() {
+        TEST_TEMPLATES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?
+            .insert((xtype.to_string(), id.to_string()), value.to_string());
+        return Ok(());
+    }
+
+    match wallet::add_record(xtype, id, value, "{}") {
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => wallet::update_record_value(xtype, id, value),
+        rc => rc,
+    }
+}
+
+fn get_record(xtype: &str, id: &str) -> Result<String, u32> {
+    if settings::test_indy_mode_enabled() {
+        return TEST_TEMPLATES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?
+            .get(&(xtype.to_string(), id.to_string())).cloned().ok_or(error::PROOF_TEMPLATE_NOT_FOUND.code_num);
+    }
+
+    let record = wallet::get_record(xtype, id, WALLET_GET_OPTIONS)
+        .or(Err(error::PROOF_TEMPLATE_NOT_FOUND.code_num))?;
+    let record: Value = serde_json::from_str(&record).or(Err(error::INVALID_JSON.code_num))?;
+    record["value"].as_str().map(String::from).ok_or(error::INVALID_JSON.code_num)
+}
+
+fn delete_record(xtype: &str, id: &str) -> Result<(), u32> {
+    if settings::test_indy_mode_enabled() {
+        return TEST_TEMPLATES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?
+            .remove(&(xtype.to_string(), id.to_string())).map(|_| ()).ok_or(error::PROOF_TEMPLATE_NOT_FOUND.code_num);
+    }
+
+    wallet::delete_record(xtype, id).or(Err(error::PROOF_TEMPLATE_NOT_FOUND.code_num))
+}
+
+// numeric parts compare as numbers, so 10.0 is newer than 9.0
+fn version_order(version: &str) -> Vec<(u64, String)> {
+    version.split('.').map(|part| (part.parse().unwrap_or(0), part.to_string())).collect()
+}
+
+fn versions(id: &str) -> Result<Vec<String>, u32> {
+    match get_record(VERSIONS_RECORD, id) {
+        Ok(versions) => serde_json::from_str(&versions).or(Err(error::INVALID_JSON.code_num)),
+        // templates stored before versions were indexed only have their latest version under the bare id
+        Err(ec) if ec == error::PROOF_TEMPLATE_NOT_FOUND.code_num => match get_record(TEMPLATE_RECORD, id) {
+            Ok(latest) => {
+                let latest: ProofTemplate = serde_json::from_str(&latest).or(Err(error::INVALID_JSON.code_num))?;
+                Ok(vec![latest.version])
+            },
+            Err(ec) if ec == error::PROOF_TEMPLATE_NOT_FOUND.code_num => Ok(Vec::new()),
+            Err(ec) => Err(ec),
+        },
+        Err(ec) => Err(ec),
+    }
+}
+
+// stores the version index of id and the highest of its versions under the bare id
+fn update_latest(id: &str, mut versions: Vec<String>) -> Result<(), u32> {
+    versions.sort_by_key(|version| version_order(version));
+    versions.dedup();
+
+    match versions.last() {
+        Some(latest) => {
+            let template = get_record(TEMPLATE_RECORD, &record_id(id, Some(latest)))?;
+            store_record(TEMPLATE_RECORD, &record_id(id, None), &template)?;
+            let versions = serde_json::to_string(&versions).or(Err(error::INVALID_JSON.code_num))?;
+            store_record(VERSIONS_RECORD, id, &versions)
+        },
+        None => {
+            delete_record(TEMPLATE_RECORD, &record_id(id, None)).ok();
+            delete_record(VERSIONS_RECORD, id).ok();
+            Ok(())
+        },
+    }
+}
+
+impl ProofTemplate {
+    pub fn from_str(template: &str) -> Result<ProofTemplate, u32> {
+        let template: ProofTemplate = serde_json::from_str(template).or(Err(error::INVALID_JSON.code_num))?;
+
+        if template.id.is_empty() || template.id.contains(':') { return Err(invalid("id must be set and cannot contain ':'")) }
+        if template.version.is_empty() { return Err(invalid("version must be set")) }
+        if !template.requested_attrs.is_array() { return Err(invalid("requested_attrs must be an array")) }
+        if !template.requested_predicates.is_array() { return Err(invalid("requested_predicates must be an array")) }
+
+        Ok(template)
+    }
+
+    /// Name, requested_attrs and requested_predicates with every placeholder filled from params
+    pub fn fill(&self, params: &Map<String, Value>) -> Result<(String, String, String), u32> {
+        let mut values = self.defaults.clone();
+        values.extend(params.clone());
+
+        let name = match fill_value(&Value::String(self.name.clone()), &values)? {
+            Value::String(name) => name,
+            name => name.to_string(),
+        };
+        let requested_attrs = fill_value(&self.requested_attrs, &values)?;
+        let requested_predicates = fill_value(&self.requested_predicates, &values)?;
+
+        Ok((name, requested_attrs.to_string(), requested_predicates.to_string()))
+    }
+}
+
+fn parameter<'a>(name: &str, values: &'a Map<String, Value>) -> Result<&'a Value, u32> {
+    values.get(name).ok_or_else(|| {
+        warn!("{}: {}", error::MISSING_TEMPLATE_PARAMETER.message, name);
+        ::error::context::add_error_context("parameter", name);
+        error::MISSING_TEMPLATE_PARAMETER.code_num
+    })
+}
+
+fn fill_string(s: &str, values: &Map<String, Value>) -> Result<Value, u32> {
+    let trimmed = s.trim();
+    if trimmed.starts_with("{{") && trimmed.ends_with("}}") && trimmed.len() >= 4 {
+        let name = &trimmed[2..trimmed.len() - 2];
+        if !name.contains("{{") && !name.contains("}}") {
+            return parameter(name.trim(), values).map(|value| value.clone());
+        }
+    }
+
+    let mut rtn = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or_else(|| invalid(&format!("unterminated placeholder in {}", s)))? + start;
+        rtn.push_str(&rest[..start]);
+        match *parameter(rest[start + 2..end].trim(), values)? {
+            Value::String(ref value) => rtn.push_str(value),
+            ref value => rtn.push_str(&value.to_string()),
+        }
+        rest = &rest[end + 2..];
+    }
+    rtn.push_str(rest);
+
+    Ok(Value::String(rtn))
+}
+
+fn fill_value(value: &Value, values: &Map<String, Value>) -> Result<Value, u32> {
+    match *value {
+        Value::String(ref s) => fill_string(s, values),
+        Value::Array(ref list) => list.iter()
+            .map(|x| fill_value(x, values))
+            .collect::<Result<Vec<Value>, u32>>()
+            .map(Value::Array),
+        Value::Object(ref map) => {
+            let mut rtn = Map::new();
+            for (key, x) in map {
+                rtn.insert(key.clone(), fill_value(x, values)?);
+            }
+            Ok(Value::Object(rtn))
+        },
+        _ => Ok(value.clone()),
+    }
+}
+
+/// Stores template under its id and version. The highest stored version of an id is its latest version.
+pub fn store(template: &str) -> Result<(), u32> {
+    let template = ProofTemplate::from_str(template)?;
+    let value = serde_json::to_string(&template).or(Err(error::INVALID_JSON.code_num))?;
+
+    let _guard = UPDATE_LOCK.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    let mut versions = versions(&template.id)?;
+    store_record(TEMPLATE_RECORD, &record_id(&template.id, Some(&template.version)), &value)?;
+    versions.push(template.version);
+    update_latest(&template.id, versions)
+}
+
+/// Template by "id" for its latest version or "id:version" for a specific one
+pub fn get(template_id: &str) -> Result<ProofTemplate, u32> {
+    let template = get_record(TEMPLATE_RECORD, template_id)?;
+    serde_json::from_str(&template).or(Err(error::INVALID_JSON.code_num))
+}
+
+/// Deletes a single version with "id:version", or the latest version with "id". The highest
+/// remaining version becomes the latest one.
+pub fn delete(template_id: &str) -> Result<(), u32> {
+    let _guard = UPDATE_LOCK.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    let (id, version) = match template_id.find(':') {
+        Some(i) => (&template_id[..i], template_id[i + 1..].to_string()),
+        None => (template_id, get(template_id)?.version),
+    };
+
+    let mut versions = versions(id)?;
+    delete_record(TEMPLATE_RECORD, &record_id(id, Some(&version)))?;
+    versions.retain(|x| x != &version);
+    update_latest(id, versions)
+}
+
+/// Creates a proof from the template template_id, params is a json object of parameter values
+pub fn create_proof_from_template(source_id: String, template_id: &str, params: &str) -> Result<u32, ProofError> {
+    let params: Map<String, Value> = if params.trim().is_empty() { Map::new() } else {
+        serde_json::from_str(params).or(Err(ProofError::CommonError(error::INVALID_JSON.code_num)))?
+    };
+
+    let template = get(template_id).map_err(|ec| ProofError::CommonError(ec))?;
+    let (name, requested_attrs, requested_predicates) = template.fill(&params)
+        .map_err(|ec| ProofError::CommonError(ec))?;
+
+    debug!("creating proof {} from template {} version {}", source_id, template.id, template.version);
+    proof::create_proof(source_id, requested_attrs, requested_predicates, name)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub static AGE_TEMPLATE: &str = r#"{
+        "id": "age_check",
+        "version": "1.0",
+        "name": "Age check for {{venue}}",
+        "requested_attrs": [{"name": "name", "restrictions": [{"issuer_did": "{{issuer_did}}"}]}],
+        "requested_predicates": [{"name": "age", "p_type": "GE", "p_value": "{{threshold}}",
+                                  "restrictions": [{"issuer_did": "{{issuer_did}}"}]}],
+        "defaults": {"threshold": 18}
+    }"#;
+
+    fn params(params: Value) -> Map<String, Value> {
+        params.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_fill_template() {
+        let template = ProofTemplate::from_str(AGE_TEMPLATE).unwrap();
+
+        let (name, attrs, predicates) = template.fill(&params(json!({
+            "venue": "Bar", "issuer_did": "V4SGRU86Z58d6TV7PBUe6f", "threshold": 21
+        }))).unwrap();
+        assert_eq!(name, "Age check for Bar");
+        assert_eq!(attrs, r#"[{"name":"name","restrictions":[{"issuer_did":"V4SGRU86Z58d6TV7PBUe6f"}]}]"#);
+        let predicates: Value = serde_json::from_str(&predicates).unwrap();
+        assert_eq!(predicates[0]["p_value"], json!(21));
+
+        let (_, _, predicates) = template.fill(&params(json!({"venue": "Bar", "issuer_did": "V4SGRU86Z58d6TV7PBUe6f"}))).unwrap();
+        let predicates: Value = serde_json::from_str(&predicates).unwrap();
+        assert_eq!(predicates[0]["p_value"], json!(18));
+
+        assert_eq!(template.fill(&params(json!({"venue": "Bar"}))).err(), Some(error::MISSING_TEMPLATE_PARAMETER.code_num));
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        let mut template: Value = serde_json::from_str(AGE_TEMPLATE).unwrap();
+        template["id"] = json!("age:check");
+        assert_eq!(ProofTemplate::from_str(&template.to_string()).err(), Some(error::INVALID_PROOF_TEMPLATE.code_num));
+
+        let template = AGE_TEMPLATE.replace("Age check for {{venue}}", "Age check for {{venue");
+        let template = ProofTemplate::from_str(&template).unwrap();
+        assert_eq!(template.fill(&params(json!({"venue": "Bar", "issuer_did": "did"}))).err(), Some(error::INVALID_PROOF_TEMPLATE.code_num));
+    }
+
+    #[test]
+    fn test_create_proof_from_template() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        store(AGE_TEMPLATE).unwrap();
+        let newer = AGE_TEMPLATE.replace(r#""version": "1.0""#, r#""version": "2.0""#);
+        store(&newer).unwrap();
+        assert_eq!(get("age_check").unwrap().version, "2.0");
+        assert_eq!(get("age_check:1.0").unwrap().version, "1.0");
+
+        let handle = create_proof_from_template("1".to_string(), "age_check:1.0",
+                                                r#"{"venue":"Bar","issuer_did":"V4SGRU86Z58d6TV7PBUe6f"}"#).unwrap();
+        assert!(proof::to_string(handle).unwrap().contains("Age check for Bar"));
+        proof::release(handle).unwrap();
+
+        assert_eq!(create_proof_from_template("1".to_string(), "unknown", "{}").err(),
+                   Some(ProofError::CommonError(error::PROOF_TEMPLATE_NOT_FOUND.code_num)));
+
+        delete("age_check:2.0").unwrap();
+        assert_eq!(get("age_check:2.0").err(), Some(error::PROOF_TEMPLATE_NOT_FOUND.code_num));
+    }
+
+    #[test]
+    fn test_latest_is_highest_version() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let version = |version: &str| AGE_TEMPLATE.replace(r#""id": "age_check""#, r#""id": "test_latest""#)
+            .replace(r#""version": "1.0""#, &format!(r#""version": "{}""#, version));
+
+        store(&version("10.0")).unwrap();
+        store(&version("9.0")).unwrap();
+        store(&version("2.0")).unwrap();
+        assert_eq!(get("test_latest").unwrap().version, "10.0");
+
+        delete("test_latest:10.0").unwrap();
+        assert_eq!(get("test_latest").unwrap().version, "9.0");
+
+        delete("test_latest").unwrap();
+        assert_eq!(get("test_latest:9.0").err(), Some(error::PROOF_TEMPLATE_NOT_FOUND.code_num));
+        assert_eq!(get("test_latest").unwrap().version, "2.0");
+
+        delete("test_latest:2.0").unwrap();
+        assert_eq!(get("test_latest").err(), Some(error::PROOF_TEMPLATE_NOT_FOUND.code_num));
+        assert_eq!(delete("test_latest").err(), Some(error::PROOF_TEMPLATE_NOT_FOUND.code_num));
+    }
+}
@@ -6,20 +6,26 @@ use settings;
 use std::fmt;
 use std::string::ToString;
 use utils::error;
-use utils::constants::{ SCHEMA_ID, SCHEMA_JSON, SCHEMA_TXN_TYPE };
+use utils::ledger_cache;
+use utils::author_agreement::append_txn_author_agreement_to_request;
+use utils::constants::{ SCHEMA_ID, SCHEMA_JSON, SCHEMA_TXN_TYPE, SCHEMA_REQUEST_FOR_ENDORSER };
 use utils::libindy::{
     ledger::{
         libindy_build_get_schema_request,
-        libindy_submit_request,
+        libindy_submit_read_request,
         libindy_build_schema_request,
         libindy_parse_get_schema_response,
+        multisign_request,
     },
     anoncreds::libindy_issuer_create_schema,
     payments::{pay_for_txn, PaymentTxn},
+    pool,
 };
 use error::schema::SchemaError;
-use utils::constants::DEFAULT_SERIALIZE_VERSION;
+use error::ToErrorCode;
+use utils::serialization::{self, Versioned, Migration};
 use object_cache::ObjectCache;
+use api::PublicEntityStateType;
 
 lazy_static! {
     static ref SCHEMA_MAP: ObjectCache<CreateSchema> = Default::default();
@@ -48,6 +54,10 @@ pub struct CreateSchema {
     source_id: String,
     sequence_num: u32,
     payment_txn: Option<PaymentTxn>,
+    state: PublicEntityStateType,
+    // named pool the object was written to, None is the pool opened at init
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool_name: Option<String>,
 }
 
 impl Default for CreateSchema {
@@ -60,6 +70,8 @@ impl Default for CreateSchema {
             source_id: String::new(),
             sequence_num: 0,
             payment_txn: None,
+            state: PublicEntityStateType::Published,
+            pool_name: None,
         }
     }
 }
@@ -74,7 +86,7 @@ pub trait Schema: ToString {
         let get_schema_req = libindy_build_get_schema_request(submitter_did, schema_id)
             .or(Err(SchemaError::InvalidSchemaSeqNo()))?;
 
-        let get_schema_response = libindy_submit_request(&get_schema_req)
+        let get_schema_response = libindy_submit_read_request(schema_id, &get_schema_req)
             .map_err(|err| SchemaError::CommonError(err))?;
 
         libindy_parse_get_schema_response(&get_schema_response)
@@ -103,6 +115,30 @@ pub trait Schema: ToString {
         Ok((id, payment))
     }
 
+    /// Builds the schema request signed by the author, it still has to be endorsed before it can be written
+    fn build_schema_request_for_endorser(author_did: &str,
+                                         name: &str,
+                                         version: &str,
+                                         data: &str) -> Result<(String, String), SchemaError> {
+        if settings::test_indy_mode_enabled() {
+            return Ok((SCHEMA_ID.to_string(), SCHEMA_REQUEST_FOR_ENDORSER.to_string()));
+        }
+
+        let (id, create_schema) = libindy_issuer_create_schema(author_did, name, version, data)
+            .or(Err(SchemaError::InvalidSchemaCreation()))?;
+
+        let request = libindy_build_schema_request(author_did, &create_schema)
+            .or(Err(SchemaError::InvalidSchemaCreation()))?;
+
+        let request = append_txn_author_agreement_to_request(&request)
+            .map_err(|err| SchemaError::CommonError(err))?;
+
+        let request = multisign_request(author_did, &request)
+            .map_err(|err| SchemaError::CommonError(err))?;
+
+        Ok((id, request))
+    }
+
     fn check_submit_schema_response(txn: &str) -> Result<(), SchemaError> {
         let txn_val:  Value = serde_json::from_str(txn)
             .or(Err(SchemaError::CommonError(error::INVALID_JSON.code_num)))?;
@@ -167,9 +203,22 @@ impl LedgerSchema {
 
     pub fn new_from_ledger(id: &str) -> Result<LedgerSchema, SchemaError>
     {
+        LedgerSchema::fetch(id, false)
+    }
+
+    /// Reads the schema through the ledger cache, bypass_cache forces a fresh read from the pool
+    pub fn fetch(id: &str, bypass_cache: bool) -> Result<LedgerSchema, SchemaError>
+    {
+        if !bypass_cache {
+            if let Some((schema_id, schema_json)) = ledger_cache::get(ledger_cache::SCHEMA_CACHE, id) {
+                return Ok(LedgerSchema { schema_id, schema_json });
+            }
+        }
+
         //Todo: find out what submitter did needs to be
         let submitter_did = &settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
         let (schema_id, schema_json) = LedgerSchema::retrieve_schema(submitter_did, id)?;
+        ledger_cache::put(ledger_cache::SCHEMA_CACHE, id, &schema_id, &schema_json);
         Ok(LedgerSchema{
             schema_id,
             schema_json,
@@ -195,19 +244,45 @@ impl CreateSchema {
         }
     }
 
+    fn get_state(&self) -> u32 { self.state as u32 }
+
+    fn update_state(&mut self) -> Result<u32, SchemaError> {
+        if self.state == PublicEntityStateType::PendingEndorsement {
+            let schema = pool::with_pool(self.pool_name.as_ref().map(String::as_str),
+                                         || LedgerSchema::fetch(&self.schema_id, true))
+                .map_err(|err| SchemaError::CommonError(err))?;
+
+            match schema {
+                Ok(_) => self.state = PublicEntityStateType::Published,
+                Err(_) => debug!("schema {} has not been written to the ledger yet", self.schema_id),
+            }
+        }
+        Ok(self.get_state())
+    }
+
     fn to_string_with_version(&self) -> String {
-        json!({
-            "version": DEFAULT_SERIALIZE_VERSION,
-            "data": json!(self),
-        }).to_string()
+        serialization::to_versioned_string(self)
     }
 
     fn from_str(data: &str) -> Result<CreateSchema, SchemaError> {
-        let data:Value = serde_json::from_str(&data)
-            .or(Err(SchemaError::InvalidSchemaCreation()))?;
-        let schema: CreateSchema = serde_json::from_value(data["data"].clone())
-            .or(Err(SchemaError::InvalidSchemaCreation()))?;
-        Ok(schema)
+        serialization::from_versioned_str(data).map_err(|err| {
+            if err == error::INVALID_JSON.code_num { SchemaError::InvalidSchemaCreation() } else { SchemaError::CommonError(err) }
+        })
+    }
+}
+
+// schemas serialized before 2.0 have no state, they were always written to the ledger
+fn add_published_state(mut data: Value) -> Result<Value, u32> {
+    if data.get("state").is_none() {
+        data["state"] = json!(PublicEntityStateType::Published);
+    }
+    Ok(data)
+}
+
+impl Versioned for CreateSchema {
+    fn migrations() -> Vec<Migration> {
+        vec![Migration { from: "1.0", to: "2.0", migrate: add_published_state },
+             serialization::unchanged("2.0", "3.0")]
     }
 }
 
@@ -233,6 +308,8 @@ pub fn create_new_schema(source_id: &str,
         //Todo: Take sequence number out. Id will be used instead
         sequence_num: 0,
         payment_txn,
+        state: PublicEntityStateType::Published,
+        pool_name: pool::get_write_pool_name(),
     };
 
     let new_handle = SCHEMA_MAP.add(new_schema).map_err(|key|SchemaError::InvalidSchemaCreation())?;
@@ -241,6 +318,37 @@ pub fn create_new_schema(source_id: &str,
 }
 
 
+/// Prepares a schema as a transaction author. The returned transaction must be endorsed and
+/// submitted by an endorser (see ledger::endorse_transaction), the schema stays PendingEndorsement
+/// until update_state finds it on the ledger.
+pub fn prepare_schema_for_endorser(source_id: &str,
+                                   author_did: String,
+                                   name: String,
+                                   version: String,
+                                   data: String) -> Result<(u32, String), SchemaError> {
+    debug!("preparing schema for endorser with source_id: {}, name: {}, author_did: {}", source_id, name, author_did);
+    let (schema_id, request) = LedgerSchema::build_schema_request_for_endorser(&author_did,
+                                                                               &name,
+                                                                               &version,
+                                                                               &data)?;
+
+    let new_schema = CreateSchema {
+        source_id: source_id.to_string(),
+        name,
+        data: serde_json::from_str(&data).unwrap_or_default(),
+        version,
+        schema_id,
+        sequence_num: 0,
+        payment_txn: None,
+        state: PublicEntityStateType::PendingEndorsement,
+        pool_name: pool::get_write_pool_name(),
+    };
+
+    let new_handle = SCHEMA_MAP.add(new_schema).map_err(|key|SchemaError::InvalidSchemaCreation())?;
+
+    Ok((new_handle, request))
+}
+
 pub fn get_schema_attrs(source_id: String, schema_id: String) -> Result<(u32, String), SchemaError> {
     let submitter_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
 
@@ -255,6 +363,8 @@ pub fn get_schema_attrs(source_id: String, schema_id: String) -> Result<(u32, St
         version: schema_data.version,
         data: schema_data.attr_names,
         payment_txn: None,
+        state: PublicEntityStateType::Published,
+        pool_name: None,
     };
 
     let new_handle = SCHEMA_MAP.add(new_schema).map_err(|key|SchemaError::InvalidSchemaCreation())?;
@@ -296,6 +406,18 @@ pub fn get_payment_txn(handle: u32) -> Result<PaymentTxn, SchemaError> {
     }).or(Err(SchemaError::NoPaymentInformation()))
 }
 
+pub fn get_state(handle: u32) -> Result<u32, SchemaError> {
+    SCHEMA_MAP.get(handle,|s|{
+        Ok(s.get_state())
+    }).map_err(|ec|SchemaError::CommonError(ec))
+}
+
+pub fn update_state(handle: u32) -> Result<u32, SchemaError> {
+    SCHEMA_MAP.get_mut(handle,|s|{
+        s.update_state().map_err(|e| e.to_error_code())
+    }).map_err(|ec|SchemaError::CommonError(ec))
+}
+
 pub fn from_string(schema_data: &str) -> Result<u32, SchemaError> {
     let derived_schema: CreateSchema = CreateSchema::from_str(schema_data)
         .map_err(|_| {
@@ -352,22 +474,46 @@ pub mod tests {
             name: "schema_name".to_string(),
             sequence_num: 306,
             payment_txn: None,
+            state: PublicEntityStateType::Published,
+            pool_name: None,
         };
-        let create_schema_str = r#"{"data":["name","age","sex","height"],"version":"1.0","schema_id":"2hoqvcwupRTUNkXn6ArYzs:2:test-licence:4.4.4","name":"schema_name","source_id":"testId","sequence_num":306,"payment_txn":null}"#;
+        let create_schema_str = r#"{"data":["name","age","sex","height"],"version":"1.0","schema_id":"2hoqvcwupRTUNkXn6ArYzs:2:test-licence:4.4.4","name":"schema_name","source_id":"testId","sequence_num":306,"payment_txn":null,"state":1}"#;
         assert_eq!(create_schema.to_string(), create_schema_str.to_string());
         let value: serde_json::Value = serde_json::from_str(&create_schema.to_string_with_version()).unwrap();
-        assert_eq!(value["version"], "1.0");
+        assert_eq!(value["version"], ::utils::constants::DEFAULT_SERIALIZE_VERSION);
         let create_schema:CreateSchema = serde_json::from_str(&value["data"].to_string()).unwrap();
         assert_eq!(create_schema.source_id, source_id);
         use utils::constants::SCHEMA_WITH_VERSION;
         let handle = from_string(SCHEMA_WITH_VERSION).unwrap();
         let schema_str = to_string(handle).unwrap();
         let value: serde_json::Value = serde_json::from_str(&schema_str).unwrap();
-        assert_eq!(value["version"], "1.0");
+        assert_eq!(value["version"], ::utils::constants::DEFAULT_SERIALIZE_VERSION);
         let data = value["data"].clone();
         let schema:CreateSchema = serde_json::from_str(&data.to_string()).unwrap();
     }
 
+    #[test]
+    fn test_prepare_schema_for_endorser(){
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        let data = r#"["name","male"]"#;
+        let (handle, request) = prepare_schema_for_endorser("1",
+                                                            "VsKV7grR1BUE29mG2Fm2kX".to_string(),
+                                                            "name".to_string(),
+                                                            "1.0".to_string(),
+                                                            data.to_string()).unwrap();
+        assert!(request.len() > 0);
+        assert_eq!(get_state(handle).unwrap(), PublicEntityStateType::PendingEndorsement as u32);
+        assert!(get_payment_txn(handle).is_err());
+
+        let serialized = to_string(handle).unwrap();
+        let handle2 = from_string(&serialized).unwrap();
+        assert_eq!(get_state(handle2).unwrap(), PublicEntityStateType::PendingEndorsement as u32);
+
+        assert_eq!(update_state(handle).unwrap(), PublicEntityStateType::Published as u32);
+        assert_eq!(get_state(handle).unwrap(), PublicEntityStateType::Published as u32);
+    }
+
     #[test]
     fn test_create_schema_success(){
         settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
@@ -539,4 +685,17 @@ pub mod tests {
         let data: SchemaData = serde_json::from_str(SCHEMA_JSON).unwrap();
         assert_eq!(data.name, "test-licence".to_string());
     }
+
+    #[test]
+    fn test_deserialize_historical_version() {
+        use utils::constants::{SCHEMA_WITH_VERSION, DEFAULT_SERIALIZE_VERSION};
+        let obj = CreateSchema::from_str(SCHEMA_WITH_VERSION).unwrap();
+        assert_eq!(obj.get_state(), PublicEntityStateType::Published as u32);
+        let serialized = obj.to_string_with_version();
+        assert_eq!(serialization::parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(CreateSchema::from_str(&serialized).unwrap().to_string_with_version(), serialized);
+
+        assert_eq!(CreateSchema::from_str(r#"{"version":"0.1","data":{}}"#).err(),
+                   Some(SchemaError::CommonError(error::UNSUPPORTED_SERIALIZATION_VERSION.code_num)));
+    }
 }
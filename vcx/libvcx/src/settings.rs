@@ -32,6 +32,11 @@ pub static CONFIG_LOG_CONFIG: &str = "log_config";
 pub static CONFIG_LINK_SECRET_ALIAS: &str = "link_secret_alias";
 pub static CONFIG_EXPORTED_WALLET_PATH: &str = "exported_wallet_path";
 pub static CONFIG_WALLET_BACKUP_KEY: &str = "backup_key";
+pub static CONFIG_LEDGER_CACHE_TTL: &str = "ledger_cache_ttl";
+pub static CONFIG_LEDGER_CACHE_PERSIST: &str = "ledger_cache_persist";
+pub static CONFIG_TXN_AUTHOR_AGREEMENT: &str = "author_agreement";
+pub static CONFIG_OUTBOX_ENABLED: &str = "outbox_enabled";
+pub static CONFIG_OUTBOX_TTL: &str = "outbox_ttl";
 
 pub static UNINITIALIZED_WALLET_KEY: &str = "<KEY_IS_NOT_SET>";
 pub static UNINITIALIZED_BACKUP_KEY: &str = "<KEY_IS_NOT_SET>";
@@ -40,6 +45,8 @@ pub static DEFAULT_EXPORTED_WALLET_PATH: &str = "/tmp/wallet.txn";
 pub static DEFAULT_WALLET_NAME: &str = "LIBVCX_SDK_WALLET";
 pub static DEFAULT_POOL_NAME: &str = "pool1";
 pub static DEFAULT_LINK_SECRET_ALIAS: &str = "main";
+pub static DEFAULT_LEDGER_CACHE_TTL: &str = "86400";
+pub static DEFAULT_OUTBOX_TTL: &str = "86400";
 pub static DEFAULT_DEFAULT: &str = "default";
 pub static DEFAULT_URL: &str = "http://127.0.0.1:8080";
 pub static DEFAULT_DID: &str = "2hoqvcwupRTUNkXn6ArYzs";
@@ -85,6 +92,8 @@ pub fn set_defaults() -> u32 {
     settings.insert(CONFIG_LINK_SECRET_ALIAS.to_string(), DEFAULT_LINK_SECRET_ALIAS.to_string());
     settings.insert(CONFIG_EXPORTED_WALLET_PATH.to_string(), DEFAULT_EXPORTED_WALLET_PATH.to_string());
     settings.insert(CONFIG_WALLET_BACKUP_KEY.to_string(), UNINITIALIZED_BACKUP_KEY.to_string());
+    settings.insert(CONFIG_LEDGER_CACHE_TTL.to_string(), DEFAULT_LEDGER_CACHE_TTL.to_string());
+    settings.insert(CONFIG_OUTBOX_TTL.to_string(), DEFAULT_OUTBOX_TTL.to_string());
 
     error::SUCCESS.code_num
 }
@@ -112,6 +121,11 @@ pub fn validate_config(config: &HashMap<String, String>) -> Result<u32, u32> {
     validate_optional_config_val(config.get(CONFIG_AGENCY_ENDPOINT), error::INVALID_URL.code_num, Url::parse)?;
     validate_optional_config_val(config.get(CONFIG_INSTITUTION_LOGO_URL), error::INVALID_URL.code_num, Url::parse)?;
 
+    validate_optional_config_val(config.get(CONFIG_LEDGER_CACHE_TTL), error::INVALID_CONFIGURATION.code_num, |ttl: &str| ttl.parse::<u64>())?;
+    validate_optional_config_val(config.get(CONFIG_OUTBOX_TTL), error::INVALID_CONFIGURATION.code_num, |ttl: &str| ttl.parse::<u64>())?;
+    validate_optional_config_val(config.get(CONFIG_TXN_AUTHOR_AGREEMENT), error::INVALID_AUTHOR_AGREEMENT.code_num,
+                                 |meta: &str| serde_json::from_str::<::utils::author_agreement::TxnAuthorAgreementAcceptanceData>(meta))?;
+
 
     Ok(error::SUCCESS.code_num)
 }
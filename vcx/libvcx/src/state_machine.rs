@@ -0,0 +1,249 @@
+extern crate serde_json;
+
+use api::VcxStateType;
+use error::context::{set_current_error, add_error_context};
+use messages::outbox::{self, OutboxStatus};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+use utils::error;
+
+/// Protocol events that move Connection, IssuerCredential, Credential, Proof and DisclosedProof
+/// objects between states. Each object declares which events it accepts in which states.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ProtocolEvent {
+    Initialize,
+    SendInvite,
+    ReceiveInvite,
+    AcceptInvite,
+    ReceiveInviteAcceptance,
+    Delete,
+    SendOffer,
+    ReceiveOffer,
+    SendRequest,
+    ReceiveRequest,
+    SendCredential,
+    ReceiveCredential,
+    SendProof,
+    ReceiveProof,
+    ReceiveInvalidProof,
+    QueueMessage,
+    DropQueuedMessage,
+    Expire,
+}
+
+/// Declares that event is valid in any of the from states and leads to the to state
+pub struct Transition {
+    pub event: ProtocolEvent,
+    pub from: &'static [VcxStateType],
+    pub to: VcxStateType,
+}
+
+/// Entry of the transition history kept by every protocol object
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateTransition {
+    pub event: ProtocolEvent,
+    pub from: VcxStateType,
+    pub to: VcxStateType,
+    pub timestamp: u64,
+}
+
+/// Event whose agency message is waiting in the outbox. The object stays in VcxStateQueued
+/// until the message is delivered, then completes the event from the state it was queued in.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QueuedEvent {
+    pub event: ProtocolEvent,
+    pub from: VcxStateType,
+    pub outbox_id: String,
+}
+
+pub static ALL_STATES: &[VcxStateType] = &[
+    VcxStateType::VcxStateNone,
+    VcxStateType::VcxStateInitialized,
+    VcxStateType::VcxStateOfferSent,
+    VcxStateType::VcxStateRequestReceived,
+    VcxStateType::VcxStateAccepted,
+    VcxStateType::VcxStateUnfulfilled,
+    VcxStateType::VcxStateExpired,
+    VcxStateType::VcxStateRevoked,
+    VcxStateType::VcxStateQueued,
+];
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Returns the state event leads to, or INVALID_STATE with the rejected event recorded in the
+/// error details when event is not allowed in state
+pub fn next_state(transitions: &[Transition], state: VcxStateType, event: ProtocolEvent) -> Result<VcxStateType, u32> {
+    match transitions.iter().find(|t| t.event == event && t.from.contains(&state)) {
+        Some(t) => Ok(t.to),
+        None => {
+            warn!("{:?} is not allowed in state {:?}", event, state);
+            add_error_context("state", &format!("{:?}", state));
+            add_error_context("event", &format!("{:?}", event));
+            Err(set_current_error(error::INVALID_STATE.code_num,
+                                  &format!("{:?} is not allowed in state {:?}", event, state)))
+        }
+    }
+}
+
+/// Moves state along the transition declared for event and records it in history
+pub fn apply_event(transitions: &[Transition],
+                   state: &mut VcxStateType,
+                   history: &mut Vec<StateTransition>,
+                   event: ProtocolEvent) -> Result<(), u32> {
+    let to = next_state(transitions, *state, event)?;
+
+    debug!("{:?}: {:?} -> {:?}", event, state, to);
+    history.push(StateTransition { event, from: *state, to, timestamp: now() });
+    *state = to;
+
+    Ok(())
+}
+
+/// Records that the message sent for event was queued in the outbox entry outbox_id and moves
+/// the object to VcxStateQueued. Fails like apply_event when event is not allowed in state.
+pub fn queue_event(transitions: &[Transition],
+                   state: &mut VcxStateType,
+                   history: &mut Vec<StateTransition>,
+                   queued: &mut Option<QueuedEvent>,
+                   event: ProtocolEvent,
+                   outbox_id: String) -> Result<(), u32> {
+    next_state(transitions, *state, event)?;
+
+    debug!("{:?} queued in outbox as {}", event, outbox_id);
+    history.push(StateTransition { event: ProtocolEvent::QueueMessage, from: *state, to: VcxStateType::VcxStateQueued, timestamp: now() });
+    *queued = Some(QueuedEvent { event, from: *state, outbox_id });
+    *state = VcxStateType::VcxStateQueued;
+
+    Ok(())
+}
+
+/// Checks the outbox entry of a queued event. Once delivered the event is completed and the
+/// agency response returned, if the message failed, expired or was cancelled the object goes back
+/// to the state it was queued in. Returns None while the message is still pending, or while its
+/// entry is not loaded: the outbox is loaded asynchronously and the message may still be sent.
+pub fn poll_queued_event(transitions: &[Transition],
+                         state: &mut VcxStateType,
+                         history: &mut Vec<StateTransition>,
+                         queued: &mut Option<QueuedEvent>) -> Result<Option<String>, u32> {
+    let (event, from, outbox_id) = match *queued {
+        Some(ref q) => (q.event, q.from, q.outbox_id.clone()),
+        None => return Ok(None),
+    };
+
+    match outbox::get_status(&outbox_id) {
+        Some((OutboxStatus::Pending, _)) => return Ok(None),
+        Some((OutboxStatus::Delivered, Some(response))) => {
+            let to = next_state(transitions, from, event)?;
+
+            debug!("{:?} delivered from outbox: {:?} -> {:?}", event, state, to);
+            history.push(StateTransition { event, from: *state, to, timestamp: now() });
+            *state = to;
+            *queued = None;
+            outbox::remove(&outbox_id);
+            Ok(Some(response))
+        },
+        Some((status @ OutboxStatus::Failed, _)) |
+        Some((status @ OutboxStatus::Expired, _)) |
+        Some((status @ OutboxStatus::Cancelled, _)) => {
+            warn!("queued {:?} was not delivered ({:?}), back to {:?}", event, status, from);
+            history.push(StateTransition { event: ProtocolEvent::DropQueuedMessage, from: *state, to: from, timestamp: now() });
+            *state = from;
+            *queued = None;
+            outbox::remove(&outbox_id);
+            Ok(None)
+        },
+        status => {
+            debug!("queued {:?} is not known in the outbox yet ({:?}), still queued", event, status);
+            Ok(None)
+        },
+    }
+}
+
+pub fn history_to_string(history: &[StateTransition]) -> Result<String, u32> {
+    serde_json::to_string(history).or(Err(error::INVALID_JSON.code_num))
+}
+
+/// Migration for objects serialized before the transition history was kept
+pub fn add_empty_state_history(mut data: Value) -> Result<Value, u32> {
+    if data.get("state_history").is_none() {
+        data["state_history"] = json!([]);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TRANSITIONS: &[Transition] = &[
+        Transition { event: ProtocolEvent::Initialize, from: &[VcxStateType::VcxStateNone], to: VcxStateType::VcxStateInitialized },
+        Transition { event: ProtocolEvent::SendOffer, from: &[VcxStateType::VcxStateInitialized], to: VcxStateType::VcxStateOfferSent },
+    ];
+
+    #[test]
+    fn test_apply_event() {
+        let mut state = VcxStateType::VcxStateNone;
+        let mut history = Vec::new();
+
+        apply_event(TRANSITIONS, &mut state, &mut history, ProtocolEvent::Initialize).unwrap();
+        apply_event(TRANSITIONS, &mut state, &mut history, ProtocolEvent::SendOffer).unwrap();
+        assert_eq!(state, VcxStateType::VcxStateOfferSent);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].from, VcxStateType::VcxStateInitialized);
+        assert_eq!(history[1].event, ProtocolEvent::SendOffer);
+    }
+
+    #[test]
+    fn test_queued_event() {
+        ::settings::set_defaults();
+        ::settings::set_config_value(::settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let mut state = VcxStateType::VcxStateInitialized;
+        let mut history = Vec::new();
+        let mut queued = None;
+
+        let id = outbox::tests::queue_test_message(outbox::MessageKind::SendMessage);
+        queue_event(TRANSITIONS, &mut state, &mut history, &mut queued, ProtocolEvent::SendOffer, id.clone()).unwrap();
+        assert_eq!(state, VcxStateType::VcxStateQueued);
+        assert_eq!(poll_queued_event(TRANSITIONS, &mut state, &mut history, &mut queued), Ok(None));
+        assert_eq!(state, VcxStateType::VcxStateQueued);
+
+        outbox::tests::deliver_test_message(&id, "response");
+        assert_eq!(poll_queued_event(TRANSITIONS, &mut state, &mut history, &mut queued), Ok(Some("response".to_string())));
+        assert_eq!(state, VcxStateType::VcxStateOfferSent);
+        assert_eq!(queued, None);
+        assert_eq!(history[1].from, VcxStateType::VcxStateQueued);
+
+        let mut state = VcxStateType::VcxStateInitialized;
+        let id = outbox::tests::queue_test_message(outbox::MessageKind::SendMessage);
+        queue_event(TRANSITIONS, &mut state, &mut history, &mut queued, ProtocolEvent::SendOffer, id.clone()).unwrap();
+        outbox::cancel(&id).unwrap();
+        assert_eq!(poll_queued_event(TRANSITIONS, &mut state, &mut history, &mut queued), Ok(None));
+        assert_eq!(state, VcxStateType::VcxStateInitialized);
+        assert_eq!(outbox::get_status(&id), None);
+
+        // an entry that is not loaded may still be delivered, the event stays queued
+        let mut state = VcxStateType::VcxStateInitialized;
+        queue_event(TRANSITIONS, &mut state, &mut history, &mut queued, ProtocolEvent::SendOffer, "not_loaded".to_string()).unwrap();
+        assert_eq!(poll_queued_event(TRANSITIONS, &mut state, &mut history, &mut queued), Ok(None));
+        assert_eq!(state, VcxStateType::VcxStateQueued);
+        assert!(queued.is_some());
+    }
+
+    #[test]
+    fn test_event_not_allowed() {
+        let mut state = VcxStateType::VcxStateNone;
+        let mut history = Vec::new();
+
+        assert_eq!(apply_event(TRANSITIONS, &mut state, &mut history, ProtocolEvent::SendOffer),
+                   Err(error::INVALID_STATE.code_num));
+        assert_eq!(state, VcxStateType::VcxStateNone);
+        assert!(history.is_empty());
+
+        let details = ::error::context::get_current_error().unwrap();
+        assert_eq!(details.causes[0], "SendOffer is not allowed in state VcxStateNone");
+        assert_eq!(details.context.get("state").unwrap(), "VcxStateNone");
+    }
+}
@@ -0,0 +1,160 @@
+extern crate serde_json;
+extern crate openssl;
+
+use self::openssl::sha::sha256;
+use serde_json::Value;
+use settings;
+use utils::error;
+
+static SECONDS_PER_DAY: u64 = 86400;
+
+/// Acceptance of the Transaction Author Agreement as stored in the settings.
+/// Either the text and version of the agreement or its digest must be provided.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TxnAuthorAgreementAcceptanceData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(rename = "taaDigest")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub taa_digest: Option<String>,
+    #[serde(rename = "acceptanceMechanismType")]
+    pub acceptance_mechanism_type: String,
+    #[serde(rename = "timeOfAcceptance")]
+    pub time_of_acceptance: u64,
+}
+
+impl TxnAuthorAgreementAcceptanceData {
+    fn digest(&self) -> Result<String, u32> {
+        match (&self.text, &self.version, &self.taa_digest) {
+            (_, _, &Some(ref digest)) => Ok(digest.to_string()),
+            (&Some(ref text), &Some(ref version), &None) => Ok(calculate_digest(text, version)),
+            _ => Err(error::INVALID_AUTHOR_AGREEMENT.code_num),
+        }
+    }
+}
+
+/// sha256 of the agreement version concatenated with its text, hex encoded
+pub fn calculate_digest(text: &str, version: &str) -> String {
+    sha256(format!("{}{}", version, text).as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn set_txn_author_agreement(text: Option<String>,
+                                version: Option<String>,
+                                taa_digest: Option<String>,
+                                acceptance_mechanism_type: String,
+                                time_of_acceptance: u64) -> Result<(), u32> {
+    let meta = TxnAuthorAgreementAcceptanceData {
+        text,
+        version,
+        taa_digest,
+        acceptance_mechanism_type,
+        time_of_acceptance,
+    };
+
+    if meta.acceptance_mechanism_type.is_empty() { return Err(error::INVALID_AUTHOR_AGREEMENT.code_num); }
+    meta.digest()?;
+
+    let meta = serde_json::to_string(&meta).or(Err(error::INVALID_JSON.code_num))?;
+
+    settings::set_config_value(settings::CONFIG_TXN_AUTHOR_AGREEMENT, &meta);
+
+    Ok(())
+}
+
+pub fn get_txn_author_agreement() -> Result<Option<TxnAuthorAgreementAcceptanceData>, u32> {
+    match settings::get_config_value(settings::CONFIG_TXN_AUTHOR_AGREEMENT) {
+        Ok(meta) => serde_json::from_str(&meta)
+            .map(Some)
+            .or(Err(error::INVALID_AUTHOR_AGREEMENT.code_num)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Builds the taaAcceptance block the ledger expects on write requests, None when no agreement was accepted
+pub fn taa_acceptance() -> Result<Option<Value>, u32> {
+    let meta = match get_txn_author_agreement()? {
+        Some(x) => x,
+        None => return Ok(None),
+    };
+
+    // the ledger only accepts a time of acceptance rounded to the start of the day
+    let time = meta.time_of_acceptance / SECONDS_PER_DAY * SECONDS_PER_DAY;
+
+    Ok(Some(json!({
+        "taaDigest": meta.digest()?,
+        "mechanism": meta.acceptance_mechanism_type,
+        "time": time,
+    })))
+}
+
+/// Attaches the accepted agreement to a ledger write request, the request is returned unchanged
+/// when no agreement was accepted
+pub fn append_txn_author_agreement_to_request(request_json: &str) -> Result<String, u32> {
+    let acceptance = match taa_acceptance()? {
+        Some(x) => x,
+        None => return Ok(request_json.to_string()),
+    };
+
+    let mut request: Value = serde_json::from_str(request_json)
+        .or(Err(error::INVALID_JSON.code_num))?;
+
+    match request.as_object_mut() {
+        Some(x) => x.insert("taaAcceptance".to_string(), acceptance),
+        None => return Err(error::INVALID_JSON.code_num),
+    };
+
+    Ok(request.to_string())
+}
+
+/// Extra json for payment requests carrying the accepted agreement
+pub fn payment_extra() -> Result<Option<String>, u32> {
+    Ok(taa_acceptance()?.map(|acceptance| json!({ "taaAcceptance": acceptance }).to_string()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn accept_test_agreement() {
+        set_txn_author_agreement(Some("indy agreement".to_string()),
+                                 Some("1.0.0".to_string()),
+                                 None,
+                                 "on_file".to_string(),
+                                 123456789).unwrap();
+    }
+
+    #[test]
+    fn test_set_txn_author_agreement() {
+        settings::set_defaults();
+
+        assert_eq!(set_txn_author_agreement(Some("indy agreement".to_string()), None, None, "on_file".to_string(), 1),
+                   Err(error::INVALID_AUTHOR_AGREEMENT.code_num));
+        assert_eq!(set_txn_author_agreement(None, None, Some("abcd".to_string()), "".to_string(), 1),
+                   Err(error::INVALID_AUTHOR_AGREEMENT.code_num));
+
+        accept_test_agreement();
+        let meta = get_txn_author_agreement().unwrap().unwrap();
+        assert_eq!(meta.version, Some("1.0.0".to_string()));
+        assert_eq!(meta.acceptance_mechanism_type, "on_file");
+    }
+
+    #[test]
+    fn test_append_txn_author_agreement_to_request() {
+        settings::set_defaults();
+        accept_test_agreement();
+
+        let request = append_txn_author_agreement_to_request(r#"{"reqId":1,"operation":{"type":"101"}}"#).unwrap();
+        let request: Value = serde_json::from_str(&request).unwrap();
+        assert_eq!(request["taaAcceptance"]["taaDigest"], json!(calculate_digest("indy agreement", "1.0.0")));
+        assert_eq!(request["taaAcceptance"]["mechanism"], json!("on_file"));
+        assert_eq!(request["taaAcceptance"]["time"], json!(123379200));
+        assert_eq!(request["operation"]["type"], json!("101"));
+
+        assert_eq!(append_txn_author_agreement_to_request("[]"), Err(error::INVALID_JSON.code_num));
+    }
+}
@@ -2,7 +2,7 @@ macro_rules! check_useful_c_callback {
     ($x:ident, $e:expr) => {
         let $x = match $x {
             Some($x) => $x,
-            None => return $e
+            None => return ::error::record_error_code($e)
         };
     }
 }
\ No newline at end of file
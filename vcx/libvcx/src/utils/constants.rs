@@ -8,7 +8,7 @@ pub static SCHEMA_TYPE: &str = "101";
 pub static SCHEMA_DATA: &str = r#"{"name":"gvt","version":"1.0","attr_names":["address1","address2","zip","city","state"]}"#;
 pub static SCHEMAS_JSON: &str = r#"{"seqNo":22,"dest":"2hoqvcwupRTUNkXn6ArYzs","data":{"name":"gvt","version":"1.0","attr_names":["address1","address2","zip","city","state"]}}"#;
 pub static SCHEMA_WITH_VERSION: &str = r#"{"data":{"data":["name","dob"],"name":"TestSchema-546716196","payment_txn":{"amount":2,"inputs":["pay:null:1_ceAXEYIC68WAwI3"],"outputs":[{"amount":998,"extra":null,"recipient":"pay:null:wxfQyJUZJfCijhW"}]},"schema_id":"V4SGRU86Z58d6TV7PBUe6f:2:TestSchema-546716196:0.0.0","sequence_num":0,"source_id":"Test Source ID","version":"0.0.0"},"version":"1.0"}"#;
-pub static DEFAULT_SERIALIZE_VERSION: &str = "1.0";
+pub static DEFAULT_SERIALIZE_VERSION: &str = "3.0";
 pub static SCHEMA_TXN: &str = r#"{"seqNo":344,"identifier":"VsKV7grR1BUE29mG2Fm2kX","txnTime":1516284381,"type":"101","data":{"name":"get schema attrs","version":"1.0","attr_names":["test","get","schema","attrs"]}}"#;
 pub static DEFAULT_SCHEMA_ATTRS: &str = r#"["address1","address2","zip","city","state"]"#;
 pub static DEFAULT_SCHEMA_ID: &str = "2hoqvcwupRTUNkXn6ArYzs:2:test-licence:4.4.4";
@@ -50,6 +50,8 @@ pub const PROOF_RESPONSE: &'static [u8; 18153] = &[129, 167, 98, 117, 110, 100,
 pub const DELETE_CONNECTION_ENCRYPTED_RESPONSE: &'static [u8; 301] = &[168, 213, 210, 163, 182, 121, 79, 64, 89, 151, 13, 184, 100, 184, 23, 134, 143, 106, 240, 184, 43, 53, 79, 240, 69, 117, 97, 155, 204, 243, 194, 58, 103, 213, 47, 156, 211, 208, 136, 116, 145, 118, 20, 225, 67, 190, 217, 13, 5, 249, 84, 101, 215, 17, 150, 69, 152, 16, 64, 19, 190, 65, 43, 171, 187, 58, 8, 37, 243, 198, 55, 227, 174, 151, 172, 218, 241, 16, 43, 15, 180, 44, 119, 163, 56, 126, 200, 181, 178, 185, 133, 93, 35, 24, 87, 90, 83, 235, 1, 210, 48, 8, 141, 215, 56, 243, 67, 79, 183, 225, 238, 195, 120, 206, 42, 114, 210, 82, 205, 35, 167, 121, 200, 36, 0, 121, 153, 68, 29, 114, 96, 144, 65, 192, 115, 215, 120, 6, 187, 94, 165, 203, 207, 86, 248, 135, 148, 201, 220, 91, 130, 43, 190, 232, 127, 110, 186, 128, 29, 99, 66, 5, 41, 125, 236, 37, 234, 46, 35, 130, 253, 244, 221, 47, 32, 235, 12, 190, 147, 184, 81, 26, 148, 206, 182, 228, 175, 0, 205, 11, 120, 149, 170, 123, 169, 181, 32, 70, 127, 119, 19, 43, 200, 190, 170, 164, 230, 182, 223, 203, 192, 4, 175, 200, 28, 206, 241, 217, 243, 75, 120, 33, 233, 2, 140, 143, 178, 90, 214, 250, 191, 118, 130, 27, 6, 161, 50, 37, 237, 234, 144, 209, 242, 44, 80, 48, 200, 15, 111, 24, 8, 107, 84, 56, 44, 241, 203, 39, 133, 92, 123, 112, 144, 178, 182, 43, 230, 95, 197, 46, 186, 101, 174, 30, 58, 209, 191, 243, 224, 227, 206, 253, 199, 59, 242, 81, 77, 6, 48, 128, 42, 59, 89, 14, 14, 2, 96, 28, 217, 67, 7];
 #[cfg(test)]
 pub const DEFAULT_SERIALIZED_ISSUER_CREDENTIAL: &'static str = r#"{"data" :{"source_id":"1","credential_attributes":"{\"attr\":\"value\"}","msg_uid":"","schema_seq_no":0,"issuer_did":"8XFh8yBzrpJQmNyZzgoTqB","state":1,"credential_request":null,"credential_offer":null,"credential_name":"credential_name","credential_id":"2936720225","cred_def_id":"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766","price":0,"payment_address":null,"ref_msg_id":null,"agent_did":"","agent_vk":"","issued_did":"","issued_vk":"","remote_did":"","remote_vk":""}, "version": "1.0"}"#;
+pub static DEFAULT_SERIALIZED_DISCLOSED_PROOF: &str = r#"{"version":"1.0","data":{"source_id":"test_disclosed_proof","my_did":null,"my_vk":null,"state":3,"proof_request":null,"proof":null,"link_secret_alias":"main","their_did":null,"their_vk":null,"agent_did":null,"agent_vk":null}}"#;
+pub static DEFAULT_SERIALIZED_CREDENTIAL_DEF: &str = r#"{"version":"1.0","data":{"id":"2hoqvcwupRTUNkXn6ArYzs:3:CL:1697","tag":"tag","name":"Test Credential Definition","source_id":"SourceId","payment_txn":null}}"#;
 pub const DEFAULT_SERIALIZED_CREDENTIAL: &str = r#"{"version": "1.0","data":{"source_id":"test_credential_serialize_deserialize","state":3,"credential_name":null,"credential_request":null,"credential_offer":{"msg_type":"CRED_OFFER","version":"0.1","to_did":"8XFh8yBzrpJQmNyZzgoTqB","from_did":"8XFh8yBzrpJQmNyZzgoTqB","libindy_offer":"{\"schema_id\":\"2hoqvcwupRTUNkXn6ArYzs:2:schema_name:0.0.11\",\"cred_def_id\":\"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766\",\"key_correctness_proof\":{\"c\":\"81455034389059130581506970475392033040313255495112570189348030990050944959723\",\"xz_cap\":\"313645697267968767252234073635675430449902008059550004460259716107399731378591839990019486954341409015811398444145390509019258403747288031702507727573872041899321045924287139508392740014051146807378366748171039375722083582850094590251566094137198468729226768809401256609008814847622114541957109991869490323195581928533376835343922482073783968747913611549869005687592623346914265913612170394649557294382253996246104002213172081216651539025706643350612557508228429410997102814965307308636524874409734625285377555470610010065029649043789306111101285927931757335536116856245613021564584847709796772325323716389295248332887528840195072737364278387101996545501723112970168561425282691953586374723401\",\"xr_cap\":{\"age\":\"882754630824080045376337358848444600715931719237593270810742883245639461185815851876695993155364347227577960272007297643455666310248109151421699898719086697252758726897984721300131927517824869533193272729923436764134176057310403382007926964744387461941410106739551156849252510593074993038770740497381973934250838808938096281745915721201706218145129356389886319652075267352853728443472451999347485331725183791798330085570375973775830893185375873153450320600510970851511952771344003741169784422212142610068911032856394030732377780807267819554991221318614567131747542069695452212861957610989952712388162117309870024706736915145245688230386906705817571265829695877232812698581971245658766976413035\",\"height\":\"987637616420540109240639213457114631238834322455397854134075974962516028070241761486895351636137675737583463907200584608953198912009428606796987435233170230262246507002244616435810064614719873830573727071246389627645604379157359983051337498205555868770767724876429776832782322071025598605854225056296405802351270140259313942108556513054492873024197036931111152136704979025907027537437514085689067466225661223523070057146052814725207863140129032189711026590245299845102901392525049014890473357388530510591717159458757929233202259332009161834669583439224425159885860519286698297401104830776447810193871233628235105641793685350321428066559473844839135685992587694149460959649026855973744322255314\",\"name\":\"1546639434545851623074023662485597065284112939224695559955181790271051962463722945049040324831863838273446566781589598791986646525127962031342679728936610678403807319789934638790962870799709103831307094501191346766422178361730723105585107221227683700136793784629414737866344469139276697568820727798174438114746109084012381033673759358527018948810066386903378176283974585934466197449653414224049202874335628877153172622300824161652402616917051692229112366954543190460604470158025596786552965425465904108943932508335616457348969058666355825158659883154681844070175331759147881082936624886840666700175491257446990494466033687900546604556189308597860524376648979247121908124398665458633017197827236\",\"sex\":\"716474787042335984121980741678479956610893721743783933016481046646620232719875607171626872246169633453851120125820240948330986140162546620706675695953306343625792456607323180362022779776451183315417053730047607706403536921566872327898942782065882640264019040337889347226013768331343768976174940163847488834059250858062959921604207705933170308295671034308248661208253191415678118624962846251281290296191433330052514696549137940098226268222146864337521249047457556625050919427268119508782974114298993324181252788789806496387982332099887944556949042187369539832351477275159404450154234059063271817130338030393531532967222197942953924825232879558249711884940237537025210406407183892784259089230597\"}},\"nonce\":\"161126724054910446992163\"}","cred_def_id":"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766","credential_attrs":{"address1":["101 Tela Lane"],"address2":["101 Wilson Lane"],"city":["SLC"],"state":["UT"],"zip":["87121"]},"schema_seq_no":1487,"claim_name":"Credential","claim_id":"defaultCredentialId","msg_ref_id":"abcd"},"msg_uid":null,"agent_did":null,"agent_vk":null,"my_did":null,"my_vk":null,"their_did":null,"their_vk":null,"cred_id":null,"credential":null,"payment_info":null,"payment_txn":null}}"#;
 pub const FULL_CREDENTIAL_SERIALIZED: &'static str = r#"{"version": "1.0", "data": {"source_id":"TEST_CREDENTIAL","state":4,"credential_name":null,"credential_request":{"libindy_cred_req":"{\"libindy_cred_req\":\"{\\\"prover_did\\\":\\\"2hoqvcwupRTUNkXn6ArYzs\\\",\\\"cred_def_id\\\":\\\"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766\\\",\\\"blinded_ms\\\":{\\\"u\\\":\\\"8732071602357015307810566138808197234658312581785137109788113302982640059349967050965447489217593298616209988826723701562661343443517589847218013366407845073616266391756009264980040238952349445643778936575656535779015458023493903785780518101975701982901383514030208868847307622362696880263163343848494510595690307613204277848599695882210459126941797459019913953592724097855109613611647709745072773427626720401442235193011557232562555622244156336806151662441234847773393387649719209243455960347563274791229126202016215550120934775060992031280966045894859557271641817491943416048075445449722000591059568013176905304195\\\",\\\"ur\\\":null},\\\"blinded_ms_correctness_proof\\\":{\\\"c\\\":\\\"26530740026507431379491385424781000855170637402280225419270466226736067904512\\\",\\\"v_dash_cap\\\":\\\"143142764256221649591394190756594263575252787336888260277569702754606119430149731374696604981582865909586330696038557351486556018124278706293019764236792379930773289730781387402321307275066512629558473696520197393762713894449968058415758200647216768004242460019909604733610794104180629190082978779757591726666340720737832809779281945323437475154340615798778337960748836468199407007775031657682302038533398039806427675709453395148841959462470861915712789403465722659960342165041260269463103782446132475688821810775202828210979373826636650138063942962121467854349698464501455098258293105554402435773328031261630390919907379686173528652481917022556931483089035786146580024468924714494948737711000361399753716101561779590\\\",\\\"ms_cap\\\":\\\"6713785684292289748157544902063599004332363811033155861083956757033688921010462943169460951559595511857618896433311745591610892377735569122165958960965808330552472093346163460366\\\"},\\\"nonce\\\":\\\"1154549882365416803296713\\\"}\",\"libindy_cred_req_meta\":\"{\\\"master_secret_blinding_data\\\":{\\\"v_prime\\\":\\\"19573554835481719662327485122688893711456991477879921695470731620175963787279917341526369852398210114401207141951797741891847253211319668203346462590568438671120943726162783813341598838616013039004762423956877028539225355867586807673681018234178116101643797916210905197387018359780257940149589162122784199178788814187547780152684853122014747482921656188183260370150999742557975345375106137123621426061675848590309427394874048446416740808489978625893734432529086470382099078632291038405367083882596203500659091849643476443635802557200596085378755820180062431900445542883509174786917819553164472263849777903881905876531213020487201635195790520\\\",\\\"vr_prime\\\":null},\\\"nonce\\\":\\\"143213049816807095013964\\\",\\\"master_secret_name\\\":\\\"main\\\"}\",\"cred_def_id\":\"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766\",\"tid\":\"cCanHnpFAD\",\"to_did\":\"BnRXf8yDMUwGyZVDkSENeq\",\"from_did\":\"GxtnGN6ypZYgEqcftSQFnC\",\"version\":\"0.1\",\"mid\":\"\"}","libindy_cred_req_meta":"","cred_def_id":"2hoqvcwupRTUNkXn6ArYzs:3:CL:2471","tid":"","to_did":"","from_did":"8XFh8yBzrpJQmNyZzgoTqB","version":"0.1","mid":""},"credential_offer":{"msg_type":"CRED_OFFER","version":"0.1","to_did":"8XFh8yBzrpJQmNyZzgoTqB","from_did":"8XFh8yBzrpJQmNyZzgoTqB","libindy_offer":"{\"issuer_did\":\"2hoqvcwupRTUNkXn6ArYzs\",\"schema_key\":{\"name\":\"Home Address\",\"version\":\"1.4\",\"did\":\"2hoqvcwupRTUNkXn6ArYzs\"},\"key_correctness_proof\":{\"c\":\"8555253541554245344305351079388313043821365069629297255640200538622329722556\",\"xz_cap\":\"64818256731588984794575029881576438712171978148821994354569423109505883511370051539530363090404289097908646608544866367046312481771587336183036163818849360474523320055058050733772575227932313793985470881830147160471852946598089626822740951538444260248405680001410943962258653118246973446307071417314391910474888369634752642195173997916292806072016186810315308257756689251031806948447462801785007243395079942815166817065271733596477143189406957903952991335446968764832960906258373699575234207180135806072152726528786138816315911998387303385565913657745597433033756984505440643451253917452841385494947936404135348354895376751800590086535707370194450915965147666804363452357419799188104044508109\",\"xr_cap\":{\"address1\":\"8236425893392219787423825014385198460820517586004442204287421088285469674020926840448786131806503567730307555837801319715555107413533966776756997088003362401505821396887204933829958258785093075846810980429322007441122948459832086015057507926262051365966017173045228232337530339680355717180291794733363148324101203340879842496879728996183974739507710337122557429529832639384077022317326079678153237524335334790193774589523155338216849532635731123476861074950940938322358853287805286272076498390452028019829082291826739453475976800681550225322996208089503815975750152834370138410964418644082923687817510140143620366818252076463572791466640135793621279863114074326681043782582123182032344081138\",\"address2\":\"30414471804770994051376437296525278254597585112268783700020054398847238843189530750793146903722533375657200785297557019465948393596156534191847866989266176618709331559949972729939131388887244366321127743968836991526071402029914419405781596054783690896660703606768577825229647587998380728894419570361864769440309185637967429191914824558483741394914212983254247799137730101941670911547714088499696084822272226072237693975774997990116374449197382931059877141968595755981160846810650806105803130004361523114137045586548743326078945833123588843296375692506658736851641735658969617721427932961073974202337608798761064528676757519926255271724266286989825397405029723387126754299497661658557574216867\",\"city\":\"159795636056543233530021344623621334175753173834199599499234503024224170089287815725788337040803537786795901100564559891075793321268703839671526386175533087941057761454903389990043254221508542663884105491028667931433093528378567035675241504608287341705758154859625863922110474313370021277749973041267871971965548396722681397958408458464210449202419266126608057284371794186889175339171087558861231355840830361110708993602208821778313069364112399404445977187422249127909803315019664537899385297653020295835898441614009217452024854561288538496889400595485884757791655246945196819845725103196695608534259378231125159518322706097470964698852674734436475238855630473478573401236177640541599034507313\",\"state\":\"215512468490315112938301657833926278136808116594771729699898320102646611321724434471863048390556908138905125523936043735201882025532772433002153410083708215401917118972375534193847316461794285777665177963351136804949997738950645361626956052973425101611071191598827068821964513860723502996877635652196651818308886110840798493982976675792164313480547213301748933952971154819253513296456319475340952940914757162158069252461973054465657233683514169036627218211903327888618365019366708902832859985119776192696909319524217085945724819529037625577237504453097885607411726308520123962852327787736505753002450290943635652415448032216336431217538662448209579828135627648861641446385394343199453905348086\",\"zip\":\"238413810148929820131063264189691178282858328114757399256193590161266006646670344870416481980522447923115217342582281807424862378687793299109363839238237538377362459559820681904274866049652851183765153471969318096511161665533190643665261284892951569998678113101193901664492159340828270692168345719923300987213287650281559452357368956472066676438018575401605560388568884399190765464134955117933339552804676602790359330495723485338924295339609987825045590507344961620812843451249916254642836938597183261177212672766675968705705261108413829152581548433386403050115216912797280460237259161451733151284615735871654022007177671460429253488906111387740833557450384941388970535365310270275589048348152\"}},\"nonce\":\"45815185447169282124747\"}","cred_def_id":"2hoqvcwupRTUNkXn6ArYzs:3:CL:2471","credential_attrs":{"attr":"value"},"schema_seq_no":0,"claim_name":"credential_name","claim_id":"450220479","msg_ref_id":"zjcynmq"},"msg_uid":"ntc2ytb","agent_did":"U5LXs4U7P9msh647kToezy","agent_vk":"FktSZg8idAVzyQZrdUppK6FTrfAzW3wWVzAjJAfdUvJq","my_did":"8XFh8yBzrpJQmNyZzgoTqB","my_vk":"EkVTa7SCJ5SntpYyX7CSb2pcBhiVGT9kWSagA8a9T69A","their_did":"","their_vk":"","credential":"{\"libindy_cred\":\"{\\\"schema_id\\\":\\\"2hoqvcwupRTUNkXn6ArYzs:2:schema_name:0.0.11\\\",\\\"cred_def_id\\\":\\\"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766\\\",\\\"rev_reg_id\\\":null,\\\"values\\\":{\\\"height\\\":{\\\"raw\\\":\\\"4'11\\\",\\\"encoded\\\":\\\"25730877424947290072821310314181366395232879096832067784637233452620527354832\\\"},\\\"age\\\":{\\\"raw\\\":\\\"111\\\",\\\"encoded\\\":\\\"111\\\"},\\\"sex\\\":{\\\"raw\\\":\\\"male\\\",\\\"encoded\\\":\\\"5944657099558967239210949258394887428692050081607692519917050011144233115103\\\"},\\\"name\\\":{\\\"raw\\\":\\\"Bob\\\",\\\"encoded\\\":\\\"93006290325627508022776103386395994712401809437930957652111221015872244345185\\\"}},\\\"signature\\\":{\\\"p_credential\\\":{\\\"m_2\\\":\\\"31700338570294708736115754102769589522052428093121126330650183539696104868123\\\",\\\"a\\\":\\\"10777649052904447971899236694871368615157106927137883243983784973724349774915878204190601305259309589996988012735617563373033607709078907060449376941073338535388140957053144620511038735390585352398809313788054894621970622962097379200139814737879331234340443432491207812590825372988648847512086844348645931065426804353485775147746746850653036793108739563282161226029489872217064496590096990996410375663681099302996640966261859643618526555960651408715258210076488491742907031110655225420976262789193112889439595882291621507322209956202063945312604763840144988947123849968934460179482607183670360949359821454772971820091\\\",\\\"e\\\":\\\"259344723055062059907025491480697571938277889515152306249728583105665800713306759149981690559193987143012367913206299323899696942213235956742930226791220947134161266625378239251141\\\",\\\"v\\\":\\\"7160478880681489530685862580760235815690858242009735266356112612110531077619018018744043866754230561900079704139707892346188258175742757433839011445634508511903733556129176372339461109974281812535163844969408357722949237345514578452747464596905366813789049296203267070506446149025051172379040959761841090727724798945098115057584230172106482217166053964970319273092742014147315899348492822710345385893980919314665444664429040406728835710379367961220002388750496482799359536328531598808901788276665120213222621777995199496941581486741108559747481685600546431381718103907044451091043539280123106649794109913982938490727549883865702810163586252395063675836236109438822521688323977901722596298354615024259460544081204452640402841343661689933452404340637850647367306402860427277243229048988096792242220128886520666550722580134\\\"},\\\"r_credential\\\":null},\\\"signature_correctness_proof\\\":{\\\"se\\\":\\\"18062198058440854120810610625451590757963491440128115225422806009942350909306158512388123981508665484690252474351987301071051987945324357904950477248786064911802058100232994530383060574083901579841043178811110977049370234755264015098980663707351501791255018614113725688784339672744140961024678477581256784699212629381680205164276918652123852380815787068178236680303525175583034099258492257694970206425311460126364558914028413305726055746979620971531243376004995305402532327579706724246771202804790956714271731586625803214770535637064539642240021230950311414542750938384933247250254390883187655604134427458185611530857\\\",\\\"c\\\":\\\"7209681799349211936664438159371869376664205836919843273264901864121026304303\\\"},\\\"rev_reg\\\":null,\\\"witness\\\":null}\",\"rev_reg_def_json\":\"\",\"cred_def_id\":\"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766\",\"msg_type\":\"CLAIM\",\"claim_offer_id\":\"1234\",\"version\":\"0.1\",\"from_did\":\"44oqvcwupRTUNkXn6ArYzs\"}","cred_id":"cred_id","payment_info":{"payment_required":"one-time","payment_addr":"pay:null:J81AxU9hVHYFtJc","price":1},"payment_txn":{"amount":1,"inputs":["pay:null:9UFgyjuJxi1i1HD"],"outputs":[{"recipient":"pay:null:xkIsxem0YNtHrRO","amount":4,"extra":null}]}}}"#;
 pub const DEFAULT_SERIALIZED_CREDENTIAL_PAYMENT_REQUIRED: &'static str = r#"{"version": "1.0", "data": {"source_id":"test_credential_serialize_deserialize","state":3,"credential_name":null,"credential_request":null,"credential_offer":{"msg_type":"CRED_OFFER","version":"0.1","to_did":"8XFh8yBzrpJQmNyZzgoTqB","from_did":"8XFh8yBzrpJQmNyZzgoTqB","libindy_offer":"{\"schema_id\":\"2hoqvcwupRTUNkXn6ArYzs:2:schema_name:0.0.11\",\"cred_def_id\":\"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766\",\"key_correctness_proof\":{\"c\":\"81455034389059130581506970475392033040313255495112570189348030990050944959723\",\"xz_cap\":\"313645697267968767252234073635675430449902008059550004460259716107399731378591839990019486954341409015811398444145390509019258403747288031702507727573872041899321045924287139508392740014051146807378366748171039375722083582850094590251566094137198468729226768809401256609008814847622114541957109991869490323195581928533376835343922482073783968747913611549869005687592623346914265913612170394649557294382253996246104002213172081216651539025706643350612557508228429410997102814965307308636524874409734625285377555470610010065029649043789306111101285927931757335536116856245613021564584847709796772325323716389295248332887528840195072737364278387101996545501723112970168561425282691953586374723401\",\"xr_cap\":{\"age\":\"882754630824080045376337358848444600715931719237593270810742883245639461185815851876695993155364347227577960272007297643455666310248109151421699898719086697252758726897984721300131927517824869533193272729923436764134176057310403382007926964744387461941410106739551156849252510593074993038770740497381973934250838808938096281745915721201706218145129356389886319652075267352853728443472451999347485331725183791798330085570375973775830893185375873153450320600510970851511952771344003741169784422212142610068911032856394030732377780807267819554991221318614567131747542069695452212861957610989952712388162117309870024706736915145245688230386906705817571265829695877232812698581971245658766976413035\",\"height\":\"987637616420540109240639213457114631238834322455397854134075974962516028070241761486895351636137675737583463907200584608953198912009428606796987435233170230262246507002244616435810064614719873830573727071246389627645604379157359983051337498205555868770767724876429776832782322071025598605854225056296405802351270140259313942108556513054492873024197036931111152136704979025907027537437514085689067466225661223523070057146052814725207863140129032189711026590245299845102901392525049014890473357388530510591717159458757929233202259332009161834669583439224425159885860519286698297401104830776447810193871233628235105641793685350321428066559473844839135685992587694149460959649026855973744322255314\",\"name\":\"1546639434545851623074023662485597065284112939224695559955181790271051962463722945049040324831863838273446566781589598791986646525127962031342679728936610678403807319789934638790962870799709103831307094501191346766422178361730723105585107221227683700136793784629414737866344469139276697568820727798174438114746109084012381033673759358527018948810066386903378176283974585934466197449653414224049202874335628877153172622300824161652402616917051692229112366954543190460604470158025596786552965425465904108943932508335616457348969058666355825158659883154681844070175331759147881082936624886840666700175491257446990494466033687900546604556189308597860524376648979247121908124398665458633017197827236\",\"sex\":\"716474787042335984121980741678479956610893721743783933016481046646620232719875607171626872246169633453851120125820240948330986140162546620706675695953306343625792456607323180362022779776451183315417053730047607706403536921566872327898942782065882640264019040337889347226013768331343768976174940163847488834059250858062959921604207705933170308295671034308248661208253191415678118624962846251281290296191433330052514696549137940098226268222146864337521249047457556625050919427268119508782974114298993324181252788789806496387982332099887944556949042187369539832351477275159404450154234059063271817130338030393531532967222197942953924825232879558249711884940237537025210406407183892784259089230597\"}},\"nonce\":\"161126724054910446992163\"}","cred_def_id":"2hoqvcwupRTUNkXn6ArYzs:3:CL:1766","credential_attrs":{"address1":["101 Tela Lane"],"address2":["101 Wilson Lane"],"city":["SLC"],"state":["UT"],"zip":["87121"]},"schema_seq_no":1487,"claim_name":"Credential","claim_id":"defaultCredentialId","msg_ref_id":"abcd"},"link_secret_alias":"main","msg_uid":null,"agent_did":null,"agent_vk":null,"my_did":null,"my_vk":null,"their_did":null,"their_vk":null,"cred_id":null,"credential":null,"payment_info":{"payment_required":"one-time","payment_addr":"pov:null:OsdjtGKavZDBuG2xFw2QunVwwGs5IB3j","price":1}}}"#;
@@ -128,3 +130,11 @@ pub static DEFAULT_SEARCH_HANDLE: u32 = 1;
 pub static DEFAULT_SEARCH_RECORD: &str = r#"{"id":"RecordId","type":null,"value":"RecordValue","tags":"{\"tagName1\":\"str1\",\"tagName2\":\"5\",\"tagName3\":\"12\"}"}"#;
 pub static SCHEMA_TXN_TYPE: &str = r#"101"#;
 pub static CRED_DEF_TXN_TYPE: &str = r#"102"#;
+pub static NYM_TXN_TYPE: &str = r#"1"#;
+pub static ATTRIB_TXN_TYPE: &str = r#"100"#;
+pub static GET_NYM_RESPONSE: &str = r#"{"op":"REPLY","result":{"type":"105","identifier":"2hoqvcwupRTUNkXn6ArYzs","reqId":1536252617716937003,"dest":"V4SGRU86Z58d6TV7PBUe6f","seqNo":3,"txnTime":1536252600,"data":"{\"dest\":\"V4SGRU86Z58d6TV7PBUe6f\",\"identifier\":\"2hoqvcwupRTUNkXn6ArYzs\",\"role\":\"101\",\"seqNo\":3,\"txnTime\":1536252600,\"verkey\":\"~CoRER63DVYnWZtK8uAzNbx\"}"}}"#;
+pub static GET_ATTRIB_RESPONSE: &str = r#"{"op":"REPLY","result":{"type":"104","identifier":"2hoqvcwupRTUNkXn6ArYzs","reqId":1536252618263128722,"dest":"V4SGRU86Z58d6TV7PBUe6f","raw":"endpoint","seqNo":4,"txnTime":1536252601,"data":"{\"endpoint\":{\"ha\":\"127.0.0.1:5555\"}}"}}"#;
+pub static PROVISION_TOKEN: &str = r#"{"sponsorId":"evernym-test-sponsor","sponseeId":"LIBVCX_SDK_WALLET","nonce":"123456","timestamp":"2018-09-04T15:12:59.386952+00:00","sig":"ZkejifRr3txh7NrKokC5l2K2WXrhhNcr+4qoDsq+aVMo3wIt6ChU0Bhj9Ex7GWeBNXn9qzTgdEMK0dNv/Rm2Ag==","sponsorVerKey":"GJ1SzoWzavQYfNL9XkaJdrQejfztN4XqdsiV4ct3LXKL"}"#;
+pub static SCHEMA_REQUEST_FOR_ENDORSER: &str = r#"{"reqId":1536927058427127400,"identifier":"2hoqvcwupRTUNkXn6ArYzs","operation":{"data":{"attr_names":["name","age","sex","height"],"name":"test-licence","version":"4.4.4"},"type":"101"},"protocolVersion":2,"signatures":{"2hoqvcwupRTUNkXn6ArYzs":"4Aq8PQv5C1dXeE2tXTBM5Ed4cdHB9WgcMsfF1HDRG1HqcTNQRmQ3LS9p4JGiBdwxvWDv3JLadB8pdbaBvnmRvC7B"}}"#;
+pub static CRED_DEF_REQUEST_FOR_ENDORSER: &str = r#"{"reqId":1536927058498238500,"identifier":"2hoqvcwupRTUNkXn6ArYzs","operation":{"ref":15,"data":{"primary":{"n":"1","s":"2","r":{"name":"3"},"rctxt":"4","z":"5"}},"type":"102","signature_type":"CL","tag":"tag1"},"protocolVersion":2,"signatures":{"2hoqvcwupRTUNkXn6ArYzs":"3T8gBfNXmD4XUV1ywVbshhCdXSYMUtcUubf8yfCXDGDaKo8dCjkTGMhfb3ffvf8GmyE9MtGvgFXgHyZt9FKLKJ9y"}}"#;
+pub static DEFAULT_AUTHOR_AGREEMENT: &str = r#"{"text":"Default indy agreement","version":"1.0.0","aml":{"on_file":"Agreement is on file","wallet_agreement":"Accepted in the wallet"},"amlContext":"https://example.org/aml-context"}"#;
@@ -32,11 +32,11 @@ macro_rules! check_useful_c_str {
     ($x:ident, $e:expr) => {
         let $x = match CStringUtils::c_str_to_string($x) {
             Ok(Some(val)) => val,
-            _ => return $e,
+            _ => return ::error::record_error_code($e),
         };
 
         if $x.is_empty() {
-            return $e
+            return ::error::record_error_code($e)
         }
     }
 }
@@ -45,7 +45,7 @@ macro_rules! check_useful_opt_c_str {
     ($x:ident, $e:expr) => {
         let $x = match CStringUtils::c_str_to_string($x) {
             Ok(opt_val) => opt_val,
-            Err(_) => return $e
+            Err(_) => return ::error::record_error_code($e)
         };
     }
 }
@@ -100,7 +100,8 @@ pub mod tests {
                                                                          settings::TEST_WALLET_KEY,
                                                                          Some("institution".to_string()),
                                                                          Some("http://www.logo.com".to_string()),
-                                                                         Some(constants::GENESIS_PATH.to_string())).unwrap();
+                                                                         Some(constants::GENESIS_PATH.to_string()),
+                                                                         None).unwrap();
 
         unsafe {
             INSTITUTION_CONFIG = CONFIG_STRING.add(config).unwrap();
@@ -117,7 +118,8 @@ pub mod tests {
                                                                          settings::TEST_WALLET_KEY,
                                                                          Some("consumer".to_string()),
                                                                          Some("http://www.logo.com".to_string()),
-                                                                         Some(constants::GENESIS_PATH.to_string())).unwrap();
+                                                                         Some(constants::GENESIS_PATH.to_string()),
+                                                                         None).unwrap();
 
         unsafe {
             CONSUMER_CONFIG = CONFIG_STRING.add(config).unwrap();
@@ -179,7 +181,8 @@ pub mod tests {
                                                                          settings::TEST_WALLET_KEY,
                                                                          Some("another_institution".to_string()),
                                                                          Some("http://www.logo.com".to_string()),
-                                                                         Some(constants::GENESIS_PATH.to_string())).unwrap();
+                                                                         Some(constants::GENESIS_PATH.to_string()),
+                                                                         None).unwrap();
 
         unsafe {
             INSTITUTION_CONFIG = CONFIG_STRING.add(config).unwrap();
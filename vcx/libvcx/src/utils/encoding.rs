@@ -0,0 +1,181 @@
+extern crate chrono;
+extern crate serde_json;
+
+use self::chrono::{Duration, NaiveDate};
+use std::collections::BTreeMap;
+use utils::error;
+use utils::openssl;
+
+// bumped whenever the encoding of an existing scheme changes
+pub static ENCODING_VERSION: &str = "1.0";
+static DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// How the raw value of a credential attribute is turned into the number signed by the issuer.
+/// Only typed encodings keep the value usable in predicates.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeEncoding {
+    // values parsing as u32 are kept, anything else is hashed with sha256
+    Legacy,
+    Int32,
+    Int64,
+    // ISO 8601 date (YYYY-MM-DD) as the number of days since 1970-01-01
+    Date,
+    // true as 1, false as 0
+    Bool,
+}
+
+impl Default for AttributeEncoding {
+    fn default() -> AttributeEncoding { AttributeEncoding::Legacy }
+}
+
+fn epoch() -> NaiveDate { NaiveDate::from_ymd(1970, 1, 1) }
+
+fn invalid(value: &str, encoding: &AttributeEncoding) -> u32 {
+    warn!("{}: {} as {:?}", error::INVALID_ATTRIBUTE_ENCODING.message, value, encoding);
+    error::INVALID_ATTRIBUTE_ENCODING.code_num
+}
+
+impl AttributeEncoding {
+    pub fn encode(&self, raw: &str) -> Result<String, u32> {
+        match *self {
+            AttributeEncoding::Legacy => openssl::encode(raw),
+            AttributeEncoding::Int32 => raw.parse::<i32>().map(|x| x.to_string()).map_err(|_| invalid(raw, self)),
+            AttributeEncoding::Int64 => raw.parse::<i64>().map(|x| x.to_string()).map_err(|_| invalid(raw, self)),
+            AttributeEncoding::Date => NaiveDate::parse_from_str(raw, DATE_FORMAT)
+                .map(|date| date.signed_duration_since(epoch()).num_days().to_string())
+                .map_err(|_| invalid(raw, self)),
+            AttributeEncoding::Bool => match raw {
+                "true" => Ok("1".to_string()),
+                "false" => Ok("0".to_string()),
+                _ => Err(invalid(raw, self)),
+            },
+        }
+    }
+
+    /// Raw value of an encoded attribute. Hashed legacy values cannot be decoded.
+    pub fn decode(&self, encoded: &str) -> Result<String, u32> {
+        match *self {
+            AttributeEncoding::Legacy => encoded.parse::<u32>().map(|x| x.to_string()).map_err(|_| invalid(encoded, self)),
+            AttributeEncoding::Int32 => encoded.parse::<i32>().map(|x| x.to_string()).map_err(|_| invalid(encoded, self)),
+            AttributeEncoding::Int64 => encoded.parse::<i64>().map(|x| x.to_string()).map_err(|_| invalid(encoded, self)),
+            AttributeEncoding::Date => encoded.parse::<i32>().ok()
+                .and_then(|days| epoch().checked_add_signed(Duration::days(days as i64)))
+                .map(|date| date.format(DATE_FORMAT).to_string())
+                .ok_or_else(|| invalid(encoded, self)),
+            AttributeEncoding::Bool => match encoded {
+                "1" => Ok("true".to_string()),
+                "0" => Ok("false".to_string()),
+                _ => Err(invalid(encoded, self)),
+            },
+        }
+    }
+
+    /// Checks raw is the value that was encoded. Typed values must also be written the way they
+    /// decode, or "+30" and "030" would pass for 30.
+    pub fn verify(&self, raw: &str, encoded: &str) -> bool {
+        if !self.encode(raw).map(|x| x == encoded).unwrap_or(false) { return false }
+
+        match *self {
+            AttributeEncoding::Legacy => true,
+            _ => self.decode(encoded).map(|x| x == raw).unwrap_or(false),
+        }
+    }
+}
+
+/// Encodings declared by the issuer per attribute, attributes not listed use the legacy encoding
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AttributeEncodings {
+    pub version: String,
+    pub attrs: BTreeMap<String, AttributeEncoding>,
+}
+
+impl Default for AttributeEncodings {
+    fn default() -> AttributeEncodings {
+        AttributeEncodings { version: ENCODING_VERSION.to_string(), attrs: BTreeMap::new() }
+    }
+}
+
+impl AttributeEncodings {
+    /// Parses a declaration like {"age":"int32","dob":"date"}
+    pub fn from_str(attrs: &str) -> Result<AttributeEncodings, u32> {
+        let attrs = serde_json::from_str(attrs).or(Err(error::INVALID_JSON.code_num))?;
+        Ok(AttributeEncodings { version: ENCODING_VERSION.to_string(), attrs })
+    }
+
+    pub fn is_empty(&self) -> bool { self.attrs.is_empty() }
+
+    pub fn get(&self, attr: &str) -> Result<AttributeEncoding, u32> {
+        if self.version != ENCODING_VERSION {
+            warn!("unsupported attribute encoding version: {}", self.version);
+            return Err(error::INVALID_ATTRIBUTE_ENCODING.code_num);
+        }
+        Ok(self.attrs.get(attr).cloned().unwrap_or_default())
+    }
+
+    pub fn encode(&self, attr: &str, raw: &str) -> Result<String, u32> {
+        self.get(attr)?.encode(raw)
+    }
+
+    pub fn decode(&self, attr: &str, encoded: &str) -> Result<String, u32> {
+        self.get(attr)?.decode(encoded)
+    }
+
+    pub fn verify(&self, attr: &str, raw: &str, encoded: &str) -> bool {
+        self.get(attr).map(|encoding| encoding.verify(raw, encoded)).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_typed_encodings() {
+        assert_eq!(AttributeEncoding::Int32.encode("-42").unwrap(), "-42");
+        assert_eq!(AttributeEncoding::Int32.encode("4294967296").err(), Some(error::INVALID_ATTRIBUTE_ENCODING.code_num));
+        assert_eq!(AttributeEncoding::Int64.encode("4294967296").unwrap(), "4294967296");
+        assert_eq!(AttributeEncoding::Date.encode("1970-01-02").unwrap(), "1");
+        assert_eq!(AttributeEncoding::Date.encode("1969-12-31").unwrap(), "-1");
+        assert_eq!(AttributeEncoding::Date.encode("2000-02-30").err(), Some(error::INVALID_ATTRIBUTE_ENCODING.code_num));
+        assert_eq!(AttributeEncoding::Bool.encode("true").unwrap(), "1");
+        assert_eq!(AttributeEncoding::Bool.encode("yes").err(), Some(error::INVALID_ATTRIBUTE_ENCODING.code_num));
+        assert_eq!(AttributeEncoding::Legacy.encode("-42").unwrap(), openssl::encode("-42").unwrap());
+    }
+
+    #[test]
+    fn test_decode_and_verify() {
+        for &(encoding, raw) in &[(AttributeEncoding::Int32, "-42"),
+                                  (AttributeEncoding::Int64, "-9000000000"),
+                                  (AttributeEncoding::Date, "2018-10-19"),
+                                  (AttributeEncoding::Bool, "false"),
+                                  (AttributeEncoding::Legacy, "87121")] {
+            let encoded = encoding.encode(raw).unwrap();
+            assert_eq!(encoding.decode(&encoded).unwrap(), raw);
+            assert!(encoding.verify(raw, &encoded));
+        }
+        assert_eq!(AttributeEncoding::Legacy.decode(&openssl::encode("Alex").unwrap()).err(), Some(error::INVALID_ATTRIBUTE_ENCODING.code_num));
+        assert!(!AttributeEncoding::Date.verify("2018-10-19", "17824"));
+        // only the canonical form of a typed value verifies
+        assert!(!AttributeEncoding::Int32.verify("+30", "30"));
+        assert!(!AttributeEncoding::Int32.verify("030", "30"));
+        assert!(!AttributeEncoding::Date.verify("2018-1-9", "17540"));
+        // the same number is a different value under another encoding
+        assert!(!AttributeEncoding::Int32.verify("1970-01-31", "30"));
+        assert!(!AttributeEncoding::Date.verify("30", "30"));
+        assert!(!AttributeEncoding::Int32.verify("true", "1"));
+    }
+
+    #[test]
+    fn test_attribute_encodings() {
+        let encodings = AttributeEncodings::from_str(r#"{"age":"int32","dob":"date"}"#).unwrap();
+        assert_eq!(encodings.encode("age", "-1").unwrap(), "-1");
+        assert_eq!(encodings.encode("name", "87121").unwrap(), "87121");
+        assert!(encodings.verify("dob", "1970-01-11", "10"));
+        assert_eq!(AttributeEncodings::from_str(r#"{"age":"float"}"#).err(), Some(error::INVALID_JSON.code_num));
+
+        let mut future = encodings.clone();
+        future.version = "2.0".to_string();
+        assert_eq!(future.encode("age", "1").err(), Some(error::INVALID_ATTRIBUTE_ENCODING.code_num));
+    }
+}
@@ -94,6 +94,30 @@ pub static WALLET_NOT_FOUND: Error = Error { code_num: 1079, message: "Wallet No
 pub static LIBINDY_INVALID_STRUCTURE: Error = Error { code_num: 1080, message: "Object (json, config, key, credential and etc...) passed to libindy has invalid structure"};
 pub static INVALID_STATE: Error = Error { code_num: 1081, message: "Object is in invalid state for requested operation"};
 pub static INVALID_LEDGER_RESPONSE: Error = Error {code_num: 1082, message: "Invalid response from ledger for paid transaction"};
+pub static PROVISION_TOKEN_REQUIRED: Error = Error {code_num: 1083, message: "Agency requires a provisioning token"};
+pub static INVALID_PROVISION_TOKEN: Error = Error {code_num: 1084, message: "Provisioning token is invalid or was not issued by a known sponsor"};
+pub static PROVISION_TOKEN_EXPIRED: Error = Error {code_num: 1085, message: "Provisioning token has expired"};
+pub static PROVISION_TOKEN_ALREADY_USED: Error = Error {code_num: 1086, message: "Provisioning token has already been used"};
+pub static LEDGER_REQUEST_REJECTED: Error = Error {code_num: 1087, message: "Ledger rejected the transaction"};
+pub static LEDGER_ITEM_NOT_FOUND: Error = Error {code_num: 1088, message: "Requested item was not found on the ledger"};
+pub static INVALID_AUTHOR_AGREEMENT: Error = Error {code_num: 1089, message: "Invalid transaction author agreement acceptance"};
+pub static POOL_ALREADY_OPEN: Error = Error {code_num: 1090, message: "A pool with this name is already open"};
+pub static POOL_LEDGER_CONNECT: Error = Error {code_num: 1091, message: "Connection to the pool ledger was lost or timed out"};
+pub static UNSUPPORTED_SERIALIZATION_VERSION: Error = Error {code_num: 1092, message: "Serialized object has a version that cannot be migrated"};
+pub static DISPATCHER_ALREADY_RUNNING: Error = Error {code_num: 1093, message: "Message dispatcher is already running"};
+pub static INVALID_PUSH_NOTIFICATION: Error = Error {code_num: 1094, message: "Push notification does not identify a pairwise DID and message"};
+pub static OUTBOX_ENTRY_NOT_FOUND: Error = Error {code_num: 1095, message: "No message with this id in the outbox"};
+pub static OUTBOX_ENTRY_NOT_PENDING: Error = Error {code_num: 1096, message: "Outbox message was already delivered, expired or cancelled"};
+pub static INVALID_ATTRIBUTE_ENCODING: Error = Error {code_num: 1097, message: "Attribute value does not match the declared encoding"};
+pub static PROOF_NONCE_REJECTED: Error = Error {code_num: 1098, message: "Proof nonce does not match an outstanding proof request"};
+pub static INVALID_PROOF_TEMPLATE: Error = Error {code_num: 1099, message: "Invalid proof request template"};
+pub static PROOF_TEMPLATE_NOT_FOUND: Error = Error {code_num: 1100, message: "No proof request template with this id"};
+pub static MISSING_TEMPLATE_PARAMETER: Error = Error {code_num: 1101, message: "A parameter of the proof request template has no value"};
+pub static INVALID_OUT_OF_BAND_MESSAGE: Error = Error {code_num: 1102, message: "Out of band message is malformed or its signature does not verify"};
+pub static LINK_SECRET_NOT_FOUND: Error = Error {code_num: 1103, message: "No link secret with this alias in the wallet"};
+pub static DUPLICATE_LINK_SECRET: Error = Error {code_num: 1104, message: "A link secret with this alias already exists"};
+pub static LINK_SECRET_MISMATCH: Error = Error {code_num: 1105, message: "Credentials are bound to different link secrets"};
+pub static MESSAGE_PAGING_NOT_SUPPORTED: Error = Error {code_num: 1106, message: "Agency does not support paging or date filters of message downloads"};
 
 
 lazy_static! {
@@ -182,6 +206,30 @@ lazy_static! {
         insert_c_message(&mut m, &LIBINDY_INVALID_STRUCTURE);
         insert_c_message(&mut m, &INVALID_STATE);
         insert_c_message(&mut m, &INVALID_LEDGER_RESPONSE);
+        insert_c_message(&mut m, &PROVISION_TOKEN_REQUIRED);
+        insert_c_message(&mut m, &INVALID_PROVISION_TOKEN);
+        insert_c_message(&mut m, &PROVISION_TOKEN_EXPIRED);
+        insert_c_message(&mut m, &PROVISION_TOKEN_ALREADY_USED);
+        insert_c_message(&mut m, &LEDGER_REQUEST_REJECTED);
+        insert_c_message(&mut m, &LEDGER_ITEM_NOT_FOUND);
+        insert_c_message(&mut m, &INVALID_AUTHOR_AGREEMENT);
+        insert_c_message(&mut m, &POOL_ALREADY_OPEN);
+        insert_c_message(&mut m, &POOL_LEDGER_CONNECT);
+        insert_c_message(&mut m, &UNSUPPORTED_SERIALIZATION_VERSION);
+        insert_c_message(&mut m, &DISPATCHER_ALREADY_RUNNING);
+        insert_c_message(&mut m, &INVALID_PUSH_NOTIFICATION);
+        insert_c_message(&mut m, &OUTBOX_ENTRY_NOT_FOUND);
+        insert_c_message(&mut m, &OUTBOX_ENTRY_NOT_PENDING);
+        insert_c_message(&mut m, &INVALID_ATTRIBUTE_ENCODING);
+        insert_c_message(&mut m, &PROOF_NONCE_REJECTED);
+        insert_c_message(&mut m, &INVALID_PROOF_TEMPLATE);
+        insert_c_message(&mut m, &PROOF_TEMPLATE_NOT_FOUND);
+        insert_c_message(&mut m, &MISSING_TEMPLATE_PARAMETER);
+        insert_c_message(&mut m, &INVALID_OUT_OF_BAND_MESSAGE);
+        insert_c_message(&mut m, &LINK_SECRET_NOT_FOUND);
+        insert_c_message(&mut m, &DUPLICATE_LINK_SECRET);
+        insert_c_message(&mut m, &LINK_SECRET_MISMATCH);
+        insert_c_message(&mut m, &MESSAGE_PAGING_NOT_SUPPORTED);
        m
     };
 }
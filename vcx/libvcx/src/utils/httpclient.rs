@@ -4,13 +4,21 @@ use std::sync::Mutex;
 use reqwest;
 use reqwest::header::{ContentType};
 use std::env;
+// error of post_u8 when the agency could not be reached at all
+pub static CONNECTION_FAILED: &str = "could not connect";
+
 lazy_static!{
     static ref NEXT_U8_RESPONSE: Mutex<Vec<Vec<u8>>> = Mutex::new(vec![]);
 }
 
 pub fn post_u8(body_content: &Vec<u8>) -> Result<Vec<u8>,String> {
+    post_u8_to(&settings::get_config_value(settings::CONFIG_AGENCY_ENDPOINT).unwrap(), body_content)
+}
+
+// posts to the agency at endpoint, which may not be the agency this wallet is provisioned with
+pub fn post_u8_to(endpoint: &str, body_content: &Vec<u8>) -> Result<Vec<u8>,String> {
 
-    let url = format!("{}/agency/msg", settings::get_config_value(settings::CONFIG_AGENCY_ENDPOINT).unwrap());
+    let url = format!("{}/agency/msg", endpoint);
 
     //Setting SSL Certs location. This is needed on android platform. Or openssl will fail to verify the certs
     if cfg!(target_os = "android") {
@@ -27,18 +35,23 @@ pub fn post_u8(body_content: &Vec<u8>) -> Result<Vec<u8>,String> {
         },
         Err(err) => {
             error!("error: {}", err);
-            return Err("could not connect".to_string())
+            ::error::context::add_error_context("agency_endpoint", &url);
+            ::error::context::set_current_error(::utils::error::POST_MSG_FAILURE.code_num, &err.to_string());
+            return Err(CONNECTION_FAILED.to_string())
         },
     };
 
     trace!("Response Header: {:?}", response);
     if !response.status().is_success() {
+        ::error::context::add_error_context("agency_http_status", &response.status().to_string());
         let mut content = String::new();
         match response.read_to_string(&mut content) {
             Ok(x) => info!("Request failed: {}", content),
             Err(x) => info!("could not read response"),
         };
-        return Err("POST failed".to_string());
+        // the body carries the agency status code, callers can map it to a specific error
+        if content.is_empty() { return Err("POST failed".to_string()); }
+        return Err(content);
     }
 
     let mut content = Vec::new();
@@ -0,0 +1,229 @@
+extern crate serde_json;
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use settings;
+use utils::error;
+use utils::libindy::{wallet, pool};
+use error::ToErrorCode;
+
+pub static SCHEMA_CACHE: &str = "ledger_cache_schema";
+pub static CRED_DEF_CACHE: &str = "ledger_cache_cred_def";
+
+static WALLET_GET_OPTIONS: &str = r#"{"retrieveType":false,"retrieveValue":true,"retrieveTags":false}"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct CacheEntry {
+    id: String,
+    json: String,
+    cached_at: u64,
+}
+
+lazy_static! {
+    static ref LEDGER_CACHE: RwLock<HashMap<String, CacheEntry>> = Default::default();
+}
+
+// the same id can resolve to different objects on different networks, entries are kept per pool
+fn cache_key(xtype: &str, id: &str) -> String { key(xtype, &record_id(id)) }
+
+fn key(xtype: &str, record_id: &str) -> String { format!("{}:{}", xtype, record_id) }
+
+fn record_id(id: &str) -> String { record_id_in_pool(&pool::get_pool_name_for_id(id), id) }
+
+fn record_id_in_pool(pool_name: &str, id: &str) -> String { format!("{}:{}", pool_name, id) }
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn ttl() -> u64 {
+    settings::get_config_value(settings::CONFIG_LEDGER_CACHE_TTL)
+        .unwrap_or(settings::DEFAULT_LEDGER_CACHE_TTL.to_string())
+        .parse::<u64>()
+        .unwrap_or(0)
+}
+
+// Mocked ledger reads are never cached so tests stay independent from each other
+pub fn is_enabled() -> bool { !settings::test_indy_mode_enabled() && ttl() > 0 }
+
+fn persist_enabled() -> bool {
+    settings::get_config_value(settings::CONFIG_LEDGER_CACHE_PERSIST)
+        .map(|v| v == "true")
+        .unwrap_or(false)
+}
+
+fn is_fresh(entry: &CacheEntry) -> bool { now().saturating_sub(entry.cached_at) < ttl() }
+
+fn get_from_wallet(xtype: &str, id: &str) -> Option<CacheEntry> {
+    let record = wallet::get_record(xtype, id, WALLET_GET_OPTIONS).ok()?;
+    let record: Value = serde_json::from_str(&record).ok()?;
+    serde_json::from_str(record["value"].as_str()?).ok()
+}
+
+fn put_in_wallet(xtype: &str, id: &str, entry: &CacheEntry) {
+    let value = match serde_json::to_string(entry) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+
+    match wallet::add_record(xtype, id, &value, "{}") {
+        Ok(_) => (),
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => {
+            if let Err(ec) = wallet::update_record_value(xtype, id, &value) {
+                warn!("could not update cached ledger object {} in wallet: {}", id, ec);
+            }
+        },
+        Err(ec) => warn!("could not persist cached ledger object {} in wallet: {}", id, ec),
+    }
+}
+
+/// Returns the cached (ledger id, json) pair if it is present and has not expired.
+/// Falls back to the wallet when persistence is enabled.
+pub fn get(xtype: &str, id: &str) -> Option<(String, String)> {
+    if !is_enabled() { return None }
+
+    let key = cache_key(xtype, id);
+    let cached = LEDGER_CACHE.read().unwrap().get(&key).cloned();
+    let entry = match cached {
+        Some(x) => x,
+        None => {
+            if !persist_enabled() { return None }
+            let entry = get_from_wallet(xtype, &record_id(id))?;
+            LEDGER_CACHE.write().unwrap().insert(key, entry.clone());
+            entry
+        },
+    };
+
+    if !is_fresh(&entry) {
+        debug!("cached ledger object {} expired", id);
+        invalidate(xtype, id);
+        return None;
+    }
+
+    trace!("ledger cache hit for {}", id);
+    Some((entry.id, entry.json))
+}
+
+pub fn put(xtype: &str, id: &str, ledger_id: &str, json: &str) {
+    if !is_enabled() { return }
+
+    let entry = CacheEntry { id: ledger_id.to_string(), json: json.to_string(), cached_at: now() };
+
+    if persist_enabled() { put_in_wallet(xtype, &record_id(id), &entry); }
+
+    LEDGER_CACHE.write().unwrap().insert(cache_key(xtype, id), entry);
+}
+
+/// Removes an id from both the schema and credential definition caches of every open pool
+pub fn invalidate_id(id: &str) {
+    let mut pool_names = pool::list_named_pools();
+    pool_names.push(pool::main_pool_name());
+
+    for pool_name in pool_names {
+        remove(SCHEMA_CACHE, &record_id_in_pool(&pool_name, id));
+        remove(CRED_DEF_CACHE, &record_id_in_pool(&pool_name, id));
+    }
+}
+
+/// Removes an id from the cache of the pool its reads go to
+pub fn invalidate(xtype: &str, id: &str) {
+    remove(xtype, &record_id(id));
+}
+
+fn remove(xtype: &str, record_id: &str) {
+    LEDGER_CACHE.write().unwrap().remove(&key(xtype, record_id));
+
+    if persist_enabled() {
+        match wallet::delete_record(xtype, record_id) {
+            Ok(_) => (),
+            Err(_) => (), // nothing was persisted for this id
+        };
+    }
+}
+
+/// Drops the in memory cache. Entries persisted in the wallet are kept and still expire by TTL.
+pub fn clear() {
+    LEDGER_CACHE.write().unwrap().clear();
+}
+
+/// Fetches the given schemas and credential definitions from the ledger and stores them in the cache
+pub fn warm_up(schema_ids: &Vec<String>, cred_def_ids: &Vec<String>) -> Result<(), u32> {
+    for id in schema_ids {
+        ::schema::LedgerSchema::fetch(id, true).map_err(|e| e.to_error_code())?;
+    }
+
+    for id in cred_def_ids {
+        ::credential_def::fetch_credential_def(id, true).map_err(|e| e.to_error_code())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_cache_settings(ttl: &str) {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "false");
+        settings::set_config_value(settings::CONFIG_LEDGER_CACHE_TTL, ttl);
+    }
+
+    #[test]
+    fn test_put_get_invalidate() {
+        set_cache_settings("100");
+        let id = "test_put_get_invalidate_id";
+
+        assert_eq!(get(SCHEMA_CACHE, id), None);
+        put(SCHEMA_CACHE, id, id, "{}");
+        assert_eq!(get(SCHEMA_CACHE, id), Some((id.to_string(), "{}".to_string())));
+        assert_eq!(get(CRED_DEF_CACHE, id), None);
+
+        invalidate_id(id);
+        assert_eq!(get(SCHEMA_CACHE, id), None);
+    }
+
+    #[test]
+    fn test_expired_entries_are_dropped() {
+        set_cache_settings("100");
+        let id = "test_expired_entries_are_dropped_id";
+
+        LEDGER_CACHE.write().unwrap().insert(cache_key(CRED_DEF_CACHE, id),
+                                             CacheEntry { id: id.to_string(), json: "{}".to_string(), cached_at: now() - 200 });
+        assert_eq!(get(CRED_DEF_CACHE, id), None);
+        assert!(LEDGER_CACHE.read().unwrap().get(&cache_key(CRED_DEF_CACHE, id)).is_none());
+    }
+
+    #[test]
+    fn test_entries_are_kept_per_pool() {
+        set_cache_settings("100");
+        let pool_name = "test_entries_are_kept_per_pool";
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        pool::open_named_pool(pool_name, "", vec![]).unwrap();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "false");
+        let id = "test_entries_are_kept_per_pool_id";
+
+        put(SCHEMA_CACHE, id, id, "{}");
+        assert_eq!(pool::with_pool(Some(pool_name), || get(SCHEMA_CACHE, id)), Ok(None));
+        pool::with_pool(Some(pool_name), || put(SCHEMA_CACHE, id, id, "[]")).unwrap();
+        assert_eq!(get(SCHEMA_CACHE, id), Some((id.to_string(), "{}".to_string())));
+
+        invalidate_id(id);
+        assert_eq!(get(SCHEMA_CACHE, id), None);
+        assert_eq!(pool::with_pool(Some(pool_name), || get(SCHEMA_CACHE, id)), Ok(None));
+
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        pool::close_named_pool(pool_name).unwrap();
+    }
+
+    #[test]
+    fn test_disabled_with_zero_ttl() {
+        set_cache_settings("0");
+        let id = "test_disabled_with_zero_ttl_id";
+
+        put(SCHEMA_CACHE, id, id, "{}");
+        assert_eq!(get(SCHEMA_CACHE, id), None);
+    }
+}
@@ -94,15 +94,15 @@ pub fn libindy_prover_get_credentials_for_proof_req(proof_req: &str) -> Result<S
 
 pub fn libindy_prover_create_credential_req(prover_did: &str,
                                             credential_offer_json: &str,
-                                            credential_def_json: &str) -> Result<(String, String), u32> {
+                                            credential_def_json: &str,
+                                            master_secret_id: &str) -> Result<(String, String), u32> {
     if settings::test_indy_mode_enabled() { return Ok((::utils::constants::CREDENTIAL_REQ_STRING.to_owned(), String::new())); }
 
-    let master_secret_name = settings::DEFAULT_LINK_SECRET_ALIAS;
     Prover::create_credential_req(get_wallet_handle(),
                                   prover_did,
                                   credential_offer_json,
                                   credential_def_json,
-                                  master_secret_name)
+                                  master_secret_id)
         .map_err(map_rust_indy_sdk_error_code)
 }
 
@@ -123,7 +123,7 @@ pub fn libindy_prover_store_credential(cred_id: Option<&str>,
 }
 
 pub fn libindy_prover_create_master_secret(master_secret_id: &str) -> Result<String, u32> {
-    if settings::test_indy_mode_enabled() { return Ok(settings::DEFAULT_LINK_SECRET_ALIAS.to_string()); }
+    if settings::test_indy_mode_enabled() { return Ok(master_secret_id.to_string()); }
 
     Prover::create_master_secret(get_wallet_handle(),
                                  Some(master_secret_id))
@@ -212,7 +212,7 @@ pub mod tests {
     pub fn create_credential_req() -> (String, String, String, String, String, String, String) {
         let (schema_id, schema_json, cred_def_id, cred_def_json, offer) = create_credential_offer();
         let institution_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
-        let (req, req_meta) = ::utils::libindy::anoncreds::libindy_prover_create_credential_req(&institution_did, &offer, &cred_def_json).unwrap();
+        let (req, req_meta) = ::utils::libindy::anoncreds::libindy_prover_create_credential_req(&institution_did, &offer, &cred_def_json, settings::DEFAULT_LINK_SECRET_ALIAS).unwrap();
         (schema_id, schema_json, cred_def_id, cred_def_json, offer, req, req_meta)
     }
 
@@ -222,7 +222,7 @@ pub mod tests {
 
         /* create cred */
         let credential_data = r#"{"address1": ["123 Main St"], "address2": ["Suite 3"], "city": ["Draper"], "state": ["UT"], "zip": ["84000"]}"#;
-        let encoded_attributes = ::issuer_credential::encode_attributes(&credential_data).unwrap();
+        let encoded_attributes = ::issuer_credential::encode_attributes(&credential_data, &::utils::encoding::AttributeEncodings::default()).unwrap();
         let (cred, _, _) = ::utils::libindy::anoncreds::libindy_issuer_create_credential(&offer, &req, &encoded_attributes, None, None).unwrap();
         /* store cred */
         let cred_id = ::utils::libindy::anoncreds::libindy_prover_store_credential(None, &req_meta, &cred, &cred_def_json, None).unwrap();
@@ -40,3 +40,9 @@ pub fn sign(wallet_handle: i32, my_vk: &str, msg: &[u8]) -> Result<Vec<u8>, u32>
 
     Crypto::sign(wallet_handle, my_vk, msg).map_err(map_rust_indy_sdk_error_code)
 }
+
+pub fn verify(their_vk: &str, msg: &[u8], signature: &[u8]) -> Result<bool, u32> {
+    if settings::test_indy_mode_enabled() {return Ok(msg == signature)}
+
+    Crypto::verify(their_vk, msg, signature).map_err(map_rust_indy_sdk_error_code)
+}
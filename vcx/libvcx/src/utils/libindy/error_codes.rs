@@ -3,6 +3,7 @@ extern crate num_traits;
 use indy::ErrorCode;
 use utils::error;
 use std::ffi::NulError;
+use error::context::{set_current_error, add_error_context};
 use self::num_traits::int::PrimInt;
 
 pub fn map_indy_error<T, C: PrimInt>(rtn: T, error_code: C) -> Result<T, u32> {
@@ -22,8 +23,9 @@ pub fn map_rust_indy_sdk_error_code(error_code: ErrorCode) -> u32 {
     }
 
     warn!("indy-sdk error code: {}", error_code);
+    add_error_context("libindy_error", &error_code.to_string());
 
-    match error_code {
+    let vcx_error = match error_code {
         100 ... 111 => error::INVALID_LIBINDY_PARAM.code_num,
         113 => error::LIBINDY_INVALID_STRUCTURE.code_num,
         114 => error::IOERROR.code_num,
@@ -33,11 +35,14 @@ pub fn map_rust_indy_sdk_error_code(error_code: ErrorCode) -> u32 {
         206 =>  error::WALLET_ALREADY_OPEN.code_num,
         212 => error::WALLET_RECORD_NOT_FOUND.code_num,
         213 => error::DUPLICATE_WALLET_RECORD.code_num,
+        301 | 302 | 307 => error::POOL_LEDGER_CONNECT.code_num,
         306 =>  error::CREATE_POOL_CONFIG.code_num,
         407 =>  error::CREDENTIAL_DEF_ALREADY_CREATED.code_num,
         702 =>  error::INSUFFICIENT_TOKEN_AMOUNT.code_num,
         _ =>    error::UNKNOWN_LIBINDY_ERROR.code_num
-    }
+    };
+
+    set_current_error(vcx_error, &format!("libindy returned error code {}", error_code))
 }
 
 pub fn map_indy_error_code<C: PrimInt>(error_code: C) -> u32 {
@@ -62,6 +67,7 @@ pub fn map_indy_error_code<C: PrimInt>(error_code: C) -> u32 {
         206 =>  error::WALLET_ALREADY_OPEN.code_num,
         212 => error::WALLET_RECORD_NOT_FOUND.code_num,
         213 => error::DUPLICATE_WALLET_RECORD.code_num,
+        301 | 302 | 307 => error::POOL_LEDGER_CONNECT.code_num,
         306 =>  error::CREATE_POOL_CONFIG.code_num,
         407 =>  error::CREDENTIAL_DEF_ALREADY_CREATED.code_num,
         702 =>  error::INSUFFICIENT_TOKEN_AMOUNT.code_num,
@@ -1,13 +1,18 @@
 extern crate libc;
+extern crate serde_json;
 
 use settings;
 use utils::libindy::{
-    pool::get_pool_handle,
+    pool,
     wallet::get_wallet_handle,
 };
 use utils::error;
+use utils::constants::{SUBMIT_SCHEMA_RESPONSE, DEFAULT_AUTHOR_AGREEMENT};
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
 use indy::ledger::Ledger;
 use utils::libindy::error_codes::map_rust_indy_sdk_error_code;
+use error::context::{set_current_error, add_error_context};
 use utils::timeout::TimeoutUtils;
 
 pub fn multisign_request(did: &str, request: &str) -> Result<String, u32> {
@@ -15,23 +20,158 @@ pub fn multisign_request(did: &str, request: &str) -> Result<String, u32> {
        .map_err(map_rust_indy_sdk_error_code)
 }
 
+/// Adds the endorser signature (institution did) to a transaction prepared by an author and submits it
+pub fn endorse_transaction(transaction_json: &str) -> Result<String, u32> {
+    if settings::test_indy_mode_enabled() { return Ok(SUBMIT_SCHEMA_RESPONSE.to_string()); }
+
+    let endorser_did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+
+    let request = multisign_request(&endorser_did, transaction_json)?;
+
+    let response = libindy_submit_request(&request)?;
+
+    check_txn_response(&response)?;
+
+    Ok(response)
+}
+
+pub fn check_txn_response(response: &str) -> Result<(), u32> {
+    let response: Value = serde_json::from_str(response)
+        .or(Err(error::INVALID_JSON.code_num))?;
+
+    match response["op"].as_str() {
+        Some("REPLY") => Ok(()),
+        Some("REJECT") | Some("REQNACK") => {
+            warn!("ledger rejected transaction: {}", response["reason"]);
+            add_error_context("ledger_response", &response.to_string());
+            Err(set_current_error(error::LEDGER_REQUEST_REJECTED.code_num,
+                                  response["reason"].as_str().unwrap_or("")))
+        },
+        _ => {
+            add_error_context("ledger_response", &response.to_string());
+            Err(error::INVALID_LEDGER_RESPONSE.code_num)
+        },
+    }
+}
+
+static GET_TXN_AUTHOR_AGREEMENT_TXN_TYPE: &str = "6";
+static GET_ACCEPTANCE_MECHANISMS_TXN_TYPE: &str = "7";
+static REQUEST_PROTOCOL_VERSION: u32 = 2;
+
+// the wrapped libindy version has no builders for the author agreement reads
+fn build_get_request(submitter_did: &str, txn_type: &str) -> Result<String, u32> {
+    let req_id = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64)
+        .or(Err(error::UNKNOWN_ERROR.code_num))?;
+
+    Ok(json!({
+        "reqId": req_id,
+        "identifier": submitter_did,
+        "operation": { "type": txn_type },
+        "protocolVersion": REQUEST_PROTOCOL_VERSION,
+    }).to_string())
+}
+
+pub fn build_get_txn_author_agreement_request(submitter_did: &str) -> Result<String, u32> {
+    build_get_request(submitter_did, GET_TXN_AUTHOR_AGREEMENT_TXN_TYPE)
+}
+
+pub fn build_get_acceptance_mechanisms_request(submitter_did: &str) -> Result<String, u32> {
+    build_get_request(submitter_did, GET_ACCEPTANCE_MECHANISMS_TXN_TYPE)
+}
+
+fn get_reply_data(response: &str) -> Result<Value, u32> {
+    check_txn_response(response)?;
+
+    let response: Value = serde_json::from_str(response)
+        .or(Err(error::INVALID_JSON.code_num))?;
+
+    match response["result"]["data"].clone() {
+        Value::Null => Err(error::LEDGER_ITEM_NOT_FOUND.code_num),
+        data => Ok(data),
+    }
+}
+
+/// Returns the active Transaction Author Agreement and the acceptance mechanisms of the pool
+/// -> {"text":"","version":"","aml":{"<mechanism>":"<description>"},"amlContext":""}
+pub fn get_txn_author_agreement() -> Result<String, u32> {
+    if settings::test_indy_mode_enabled() { return Ok(DEFAULT_AUTHOR_AGREEMENT.to_string()); }
+
+    let did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID)?;
+
+    let request = build_get_txn_author_agreement_request(&did)?;
+    let agreement = get_reply_data(&libindy_submit_request(&request)?)?;
+
+    let request = build_get_acceptance_mechanisms_request(&did)?;
+    let mechanisms = get_reply_data(&libindy_submit_request(&request)?)?;
+
+    Ok(json!({
+        "text": agreement["text"],
+        "version": agreement["version"],
+        "aml": mechanisms["aml"],
+        "amlContext": mechanisms["amlContext"],
+    }).to_string())
+}
+
 pub fn libindy_sign_request(did: &str, request: &str) -> Result<String,u32> {
     Ledger::sign_request(get_wallet_handle(), did, request)
         .map_err(map_rust_indy_sdk_error_code)
 }
 
+/// Runs the request on the given pool (None is the pool opened at init) and, when the connection
+/// was lost or the pool is not open, reopens that pool and retries once with the new handle.
+fn submit_with_reconnect<F>(pool_name: Option<String>, submit: F) -> Result<String, u32>
+    where F: Fn(i32) -> Result<String, u32> {
+    let pool_name = pool_name.as_ref().map(String::as_str);
+
+    let result = match pool::get_pool_handle_by_name(pool_name) {
+        Ok(pool_handle) => match submit(pool_handle) {
+            Err(err) if pool::is_connection_error(err) => {
+                warn!("pool connection failed ({}), reconnecting", err);
+                pool::record_ledger_failure(err);
+                pool::reconnect(pool_name, Some(pool_handle))
+                    .and_then(|_| pool::get_pool_handle_by_name(pool_name))
+                    .and_then(|pool_handle| submit(pool_handle))
+            },
+            result => result,
+        },
+        Err(err) if err == error::NO_POOL_OPEN.code_num => {
+            warn!("pool {:?} is not open, reconnecting", pool_name);
+            pool::reconnect(pool_name, None)
+                .and_then(|_| pool::get_pool_handle_by_name(pool_name))
+                .and_then(|pool_handle| submit(pool_handle))
+        },
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(_) => pool::record_ledger_success(),
+        Err(err) => pool::record_ledger_failure(err),
+    };
+
+    result
+}
+
 pub fn libindy_sign_and_submit_request(issuer_did: &str, request_json: &str) -> Result<String, u32> {
     if settings::test_indy_mode_enabled() { return Ok(r#"{"rc":"success"}"#.to_string()); }
-    let pool_handle = get_pool_handle().or(Err(error::NO_POOL_OPEN.code_num))?;
-    let wallet_handle = get_wallet_handle();
 
-    Ledger::sign_and_submit_request(pool_handle, wallet_handle, issuer_did, request_json)
-        .map_err(map_rust_indy_sdk_error_code)
+    submit_with_reconnect(pool::get_write_pool_name(), |pool_handle| {
+        Ledger::sign_and_submit_request(pool_handle, get_wallet_handle(), issuer_did, request_json)
+            .map_err(map_rust_indy_sdk_error_code)
+    })
 }
 
 pub fn libindy_submit_request(request_json: &str) -> Result<String, u32> {
-    let pool_handle = get_pool_handle().or(Err(error::NO_POOL_OPEN.code_num))?;
-    Ledger::submit_request_timeout(pool_handle, request_json, TimeoutUtils::long_timeout()).map_err(map_rust_indy_sdk_error_code)
+    submit_with_reconnect(pool::get_write_pool_name(), |pool_handle| {
+        Ledger::submit_request_timeout(pool_handle, request_json, TimeoutUtils::long_timeout()).map_err(map_rust_indy_sdk_error_code)
+    })
+}
+
+/// Submits a read request to the pool serving the namespace of the id (did, schema id or cred def id)
+pub fn libindy_submit_read_request(id: &str, request_json: &str) -> Result<String, u32> {
+    submit_with_reconnect(pool::get_named_pool_for_id(id), |pool_handle| {
+        Ledger::submit_request_timeout(pool_handle, request_json, TimeoutUtils::long_timeout()).map_err(map_rust_indy_sdk_error_code)
+    })
 }
 
 pub fn libindy_build_get_txn_request(submitter_did: &str, sequence_num: i32) -> Result<String, u32> {
@@ -66,3 +206,102 @@ pub fn libindy_build_create_credential_def_txn(submitter_did: &str,
                                                credential_def_json: &str)  -> Result<String, u32>{
     Ledger::build_cred_def_request(submitter_did, credential_def_json).map_err(map_rust_indy_sdk_error_code)
 }
+
+pub fn libindy_build_nym_request(submitter_did: &str,
+                                 target_did: &str,
+                                 verkey: Option<&str>,
+                                 alias: Option<&str>,
+                                 role: Option<&str>) -> Result<String, u32> {
+    Ledger::build_nym_request(submitter_did, target_did, verkey, alias, role)
+        .map_err(map_rust_indy_sdk_error_code)
+}
+
+pub fn libindy_build_get_nym_request(submitter_did: &str, target_did: &str) -> Result<String, u32> {
+    Ledger::build_get_nym_request(submitter_did, target_did)
+        .map_err(map_rust_indy_sdk_error_code)
+}
+
+pub fn libindy_build_attrib_request(submitter_did: &str,
+                                    target_did: &str,
+                                    hash: Option<&str>,
+                                    raw: Option<&str>,
+                                    enc: Option<&str>) -> Result<String, u32> {
+    Ledger::build_attrib_request(submitter_did, target_did, hash, raw, enc)
+        .map_err(map_rust_indy_sdk_error_code)
+}
+
+pub fn libindy_build_get_attrib_request(submitter_did: &str,
+                                        target_did: &str,
+                                        raw: Option<&str>,
+                                        hash: Option<&str>,
+                                        enc: Option<&str>) -> Result<String, u32> {
+    Ledger::build_get_attrib_request(submitter_did, target_did, raw, hash, enc)
+        .map_err(map_rust_indy_sdk_error_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_txn_response() {
+        assert_eq!(check_txn_response(SUBMIT_SCHEMA_RESPONSE), Ok(()));
+        assert_eq!(check_txn_response(r#"{"op":"REJECT","reason":"not allowed"}"#),
+                   Err(error::LEDGER_REQUEST_REJECTED.code_num));
+        assert_eq!(check_txn_response(r#"{"op":"REQNACK","reason":"bad request"}"#),
+                   Err(error::LEDGER_REQUEST_REJECTED.code_num));
+        assert_eq!(check_txn_response("{}"), Err(error::INVALID_LEDGER_RESPONSE.code_num));
+    }
+
+    #[test]
+    fn test_build_get_txn_author_agreement_request() {
+        let request: Value = serde_json::from_str(&build_get_txn_author_agreement_request(::settings::DEFAULT_DID).unwrap()).unwrap();
+        assert_eq!(request["operation"]["type"], json!("6"));
+        assert_eq!(request["identifier"], json!(::settings::DEFAULT_DID));
+
+        let request: Value = serde_json::from_str(&build_get_acceptance_mechanisms_request(::settings::DEFAULT_DID).unwrap()).unwrap();
+        assert_eq!(request["operation"]["type"], json!("7"));
+    }
+
+    #[test]
+    fn test_get_reply_data() {
+        assert_eq!(get_reply_data(r#"{"op":"REPLY","result":{"data":null}}"#), Err(error::LEDGER_ITEM_NOT_FOUND.code_num));
+        assert_eq!(get_reply_data(r#"{"op":"REPLY","result":{"data":{"version":"1.0"}}}"#).unwrap()["version"], json!("1.0"));
+    }
+
+    #[test]
+    fn test_submit_with_reconnect() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        let pool_name = "test_submit_with_reconnect";
+        let pool_handle = pool::open_named_pool(pool_name, "", vec![]).unwrap() as i32;
+
+        let attempts = ::std::cell::Cell::new(0);
+        let result = submit_with_reconnect(Some(pool_name.to_string()), |handle| {
+            assert_eq!(handle, pool_handle);
+            attempts.set(attempts.get() + 1);
+            if attempts.get() == 1 { Err(error::POOL_LEDGER_CONNECT.code_num) } else { Ok("{}".to_string()) }
+        });
+        assert_eq!(result, Ok("{}".to_string()));
+        assert_eq!(attempts.get(), 2);
+
+        let attempts = ::std::cell::Cell::new(0);
+        let result = submit_with_reconnect(Some(pool_name.to_string()), |_| {
+            attempts.set(attempts.get() + 1);
+            Err(error::LEDGER_REQUEST_REJECTED.code_num)
+        });
+        assert_eq!(result, Err(error::LEDGER_REQUEST_REJECTED.code_num));
+        assert_eq!(attempts.get(), 1);
+
+        pool::close_named_pool(pool_name).unwrap();
+        assert_eq!(submit_with_reconnect(Some(pool_name.to_string()), |_| Ok("{}".to_string())),
+                   Err(error::NO_POOL_OPEN.code_num));
+    }
+
+    #[test]
+    fn test_endorse_transaction() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+        assert!(endorse_transaction(::utils::constants::SCHEMA_REQUEST_FOR_ENDORSER).is_ok());
+    }
+}
@@ -7,6 +7,7 @@ use utils::libindy::error_codes::map_rust_indy_sdk_error_code;
 #[allow(unused_imports)]
 use utils::libindy::ledger::{libindy_submit_request, libindy_sign_and_submit_request, libindy_sign_request};
 use utils::error;
+use utils::author_agreement::{append_txn_author_agreement_to_request, payment_extra};
 use error::payment::PaymentError;
 use error::ToErrorCode;
 
@@ -218,18 +219,20 @@ pub fn pay_for_txn(req: &str, txn_type: &str) -> Result<(Option<PaymentTxn>, Str
     debug!("pay_for_txn(req: {}, txn_type: {})", req, txn_type);
     if settings::test_indy_mode_enabled() { return Ok((Some(PaymentTxn::from_parts(r#"["pay:null:9UFgyjuJxi1i1HD"]"#,r#"[{"amount":4,"extra":null,"recipient":"pay:null:xkIsxem0YNtHrRO"}]"#,1).unwrap()), SUBMIT_SCHEMA_RESPONSE.to_string())); }
 
+    let req = append_txn_author_agreement_to_request(req)?;
+
     let txn_price = get_txn_price(txn_type)?;
 
     if txn_price == 0 {
         let did = settings::get_config_value(settings::CONFIG_INSTITUTION_DID).unwrap();
-        let txn_response = libindy_sign_and_submit_request(&did, req)?;
+        let txn_response = libindy_sign_and_submit_request(&did, &req)?;
         Ok((None, txn_response))
     } else {
         let (refund, inputs) = inputs(txn_price).map_err(|e| e.to_error_code())?;
 
         let output = outputs(refund, None, None).map_err(|e| e.to_error_code())?;
 
-        let (fee_response, txn_response) = _submit_fees_request(req, &inputs, &output)?;
+        let (fee_response, txn_response) = _submit_fees_request(&req, &inputs, &output)?;
 
         let payment = PaymentTxn::from_parts(&inputs, &output, txn_price)?;
         Ok((Some(payment), txn_response))
@@ -272,7 +275,9 @@ pub fn pay_a_payee(price: u64, address: &str) -> Result<(PaymentTxn, String), Pa
 
     if settings::test_indy_mode_enabled() { return Ok((PaymentTxn::from_parts(r#"["pay:null:9UFgyjuJxi1i1HD"]"#,r#"[{"amount":4,"extra":null,"recipient":"pay:null:xkIsxem0YNtHrRO"}]"#,1).unwrap(), SUBMIT_SCHEMA_RESPONSE.to_string())); }
 
-    match Payment::build_payment_req(get_wallet_handle(), &my_did, &input, &output, None) {
+    let extra = payment_extra().map_err(|ec| PaymentError::CommonError(ec))?;
+
+    match Payment::build_payment_req(get_wallet_handle(), &my_did, &input, &output, extra.as_ref().map(String::as_str)) {
         Ok((request, payment_method)) => {
             let result = libindy_submit_request( &request).map_err(|ec| PaymentError::CommonError(ec))?;
             Ok((payment, result))
@@ -1,14 +1,52 @@
 extern crate libc;
 
 use utils::{ error, timeout::TimeoutUtils };
-use std::sync::RwLock;
+use std::sync::{Mutex, RwLock};
+use std::cell::RefCell;
+use std::mem;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 use settings;
 use indy::pool::Pool;
 use indy::ErrorCode;
 use utils::libindy::error_codes::map_rust_indy_sdk_error_code;
 
+#[derive(Debug, Clone, PartialEq)]
+struct NamedPool {
+    handle: i32,
+    // DIDs or namespaces (ie, did:sov:staging:) whose ledger reads go to this pool
+    namespaces: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct PoolHealth {
+    last_successful_read: Option<u64>,
+    last_refresh: Option<u64>,
+    last_error: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoolStatus {
+    pub open: bool,
+    pub pool_name: String,
+    pub genesis_path: Option<String>,
+    pub last_successful_read: Option<u64>,
+    pub last_refresh: Option<u64>,
+    pub last_error: Option<u32>,
+    pub named_pools: Vec<String>,
+}
+
 lazy_static! {
     static ref POOL_HANDLE: RwLock<Option<i32>> = RwLock::new(None);
+    static ref POOL_HEALTH: RwLock<PoolHealth> = Default::default();
+    static ref NAMED_POOLS: RwLock<HashMap<String, NamedPool>> = Default::default();
+    static ref DEFAULT_WRITE_POOL: RwLock<Option<String>> = RwLock::new(None);
+    // serializes reopening pools, the handles stay readable while a pool is reopened
+    static ref REOPEN_LOCK: Mutex<()> = Default::default();
+}
+
+thread_local! {
+    static CALL_POOL: RefCell<Option<String>> = RefCell::new(None);
 }
 
 pub fn change_pool_handle(handle: Option<i32>){
@@ -64,7 +102,21 @@ pub fn delete(pool_name: &str) -> Result<(), u32> {
     Pool::delete(pool_name).map_err(map_rust_indy_sdk_error_code)
 }
 
+/// Handle of the pool ledger writes go to: the pool selected for the current call (see with_pool),
+/// then the default write pool, then the pool opened at init
 pub fn get_pool_handle() -> Result<i32, u32> {
+    get_pool_handle_by_name(get_write_pool_name().as_ref().map(String::as_str))
+}
+
+/// Handle of a named pool, None is the pool opened at init
+pub fn get_pool_handle_by_name(pool_name: Option<&str>) -> Result<i32, u32> {
+    match pool_name {
+        Some(name) => get_named_pool_handle(name),
+        None => get_main_pool_handle(),
+    }
+}
+
+fn get_main_pool_handle() -> Result<i32, u32> {
     let h = POOL_HANDLE.read().unwrap();
     if h.is_none() {
         Err(error::NO_POOL_OPEN.code_num)
@@ -74,6 +126,255 @@ pub fn get_pool_handle() -> Result<i32, u32> {
     }
 }
 
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn record_ledger_success() {
+    let mut health = POOL_HEALTH.write().unwrap();
+    health.last_successful_read = Some(now());
+    health.last_error = None;
+}
+
+pub fn record_ledger_failure(err: u32) {
+    POOL_HEALTH.write().unwrap().last_error = Some(err);
+}
+
+/// Errors after which reopening the pool may help, libindy reports a closed handle (ie, left by a
+/// failed reopen) as a lost connection as well
+pub fn is_connection_error(err: u32) -> bool {
+    err == error::POOL_LEDGER_CONNECT.code_num || err == error::IOERROR.code_num
+}
+
+pub fn get_pool_status() -> PoolStatus {
+    let health = POOL_HEALTH.read().unwrap().clone();
+
+    PoolStatus {
+        open: settings::test_indy_mode_enabled() || get_main_pool_handle().is_ok(),
+        pool_name: main_pool_name(),
+        genesis_path: settings::get_config_value(settings::CONFIG_GENESIS_PATH).ok(),
+        last_successful_read: health.last_successful_read,
+        last_refresh: health.last_refresh,
+        last_error: health.last_error,
+        named_pools: list_named_pools(),
+    }
+}
+
+// Closes the pool and opens it again under the same name. The caller holds REOPEN_LOCK and
+// stores the new handle once it is open, until then requests keep failing on the old handle
+// and reconnect again.
+fn reopen(pool_name: &str, handle: Option<i32>, genesis_path: Option<&str>) -> Result<i32, u32> {
+    if let Some(handle) = handle {
+        if let Err(e) = Pool::close_timeout(handle, TimeoutUtils::medium_timeout()) {
+            warn!("could not close pool {} before refresh: {:?}", pool_name, e);
+        }
+    }
+
+    if let Some(path) = genesis_path {
+        if let Err(e) = Pool::delete(pool_name) {
+            debug!("no pool configuration to replace for {}: {:?}", pool_name, e);
+        }
+        create_pool_ledger_config(pool_name, path)?;
+    }
+
+    info!("reopening pool {}", pool_name);
+    set_protocol_version();
+    Pool::open_ledger_timeout(pool_name, None, TimeoutUtils::medium_timeout())
+        .map_err(map_rust_indy_sdk_error_code)
+        .map_err(|e| { record_ledger_failure(e); e })
+}
+
+// Stores the handle of a reopened pool, a named pool closed in the meantime is closed again
+fn replace_handle(pool_name: Option<&str>, handle: i32) -> Result<(), u32> {
+    match pool_name {
+        None => change_pool_handle(Some(handle)),
+        Some(name) => {
+            if let Some(pool) = NAMED_POOLS.write().unwrap().get_mut(name) {
+                pool.handle = handle;
+                return Ok(());
+            }
+
+            warn!("pool {} was closed while it was reopened", name);
+            Pool::close_timeout(handle, TimeoutUtils::medium_timeout()).map_err(map_rust_indy_sdk_error_code)?;
+            return Err(error::NO_POOL_OPEN.code_num);
+        },
+    }
+    Ok(())
+}
+
+/// Closes and reopens the pool configured at init. A new genesis file replaces the stored
+/// pool configuration (ie, after nodes were added to the network).
+pub fn refresh(genesis_path: Option<&str>) -> Result<(), u32> {
+    if !settings::test_indy_mode_enabled() {
+        let _reopening = REOPEN_LOCK.lock().unwrap();
+        let current = get_main_pool_handle().ok();
+        let genesis_path = match genesis_path {
+            Some(path) => {
+                settings::set_config_value(settings::CONFIG_GENESIS_PATH, path);
+                Some(path.to_string())
+            },
+            // a pool that is not open may not have a configuration yet
+            None if current.is_none() => Some(settings::get_config_value(settings::CONFIG_GENESIS_PATH)?),
+            None => None,
+        };
+
+        let handle = reopen(&main_pool_name(), current, genesis_path.as_ref().map(String::as_str))?;
+        replace_handle(None, handle)?;
+    }
+
+    POOL_HEALTH.write().unwrap().last_refresh = Some(now());
+    Ok(())
+}
+
+/// Reopens a pool (None is the pool opened at init) after a request on failed_handle lost the
+/// connection, failed_handle is None when the pool was not open. Nothing is done when another
+/// call has already replaced that handle. If reopening fails the pool is reconnected again by
+/// the next request.
+pub fn reconnect(pool_name: Option<&str>, failed_handle: Option<i32>) -> Result<(), u32> {
+    if !settings::test_indy_mode_enabled() {
+        let _reopening = REOPEN_LOCK.lock().unwrap();
+        let current = match get_pool_handle_by_name(pool_name) {
+            Ok(handle) => Some(handle),
+            Err(ec) if pool_name.is_none() && ec == error::NO_POOL_OPEN.code_num => None,
+            Err(ec) => return Err(ec),
+        };
+        if current != failed_handle { return Ok(()) }
+
+        let name = pool_name.map(String::from).unwrap_or(main_pool_name());
+        // the pool opened at init may never have been opened, its configuration comes from the genesis file
+        let genesis_path = match current {
+            Some(_) => None,
+            None => Some(settings::get_config_value(settings::CONFIG_GENESIS_PATH).or(Err(error::NO_POOL_OPEN.code_num))?),
+        };
+
+        let handle = reopen(&name, current, genesis_path.as_ref().map(String::as_str))?;
+        replace_handle(pool_name, handle)?;
+    }
+
+    POOL_HEALTH.write().unwrap().last_refresh = Some(now());
+    Ok(())
+}
+
+/// Opens an additional pool next to the one opened at init. Reads of ids starting with one of the
+/// namespaces are routed to it.
+pub fn open_named_pool(pool_name: &str, genesis_path: &str, namespaces: Vec<String>) -> Result<u32, u32> {
+    if NAMED_POOLS.read().unwrap().contains_key(pool_name) {
+        return Err(error::POOL_ALREADY_OPEN.code_num);
+    }
+
+    let handle = if settings::test_indy_mode_enabled() {
+        ::utils::generate_command_handle()
+    } else {
+        create_pool_ledger_config(pool_name, genesis_path)?;
+        set_protocol_version();
+        Pool::open_ledger_timeout(pool_name, None, TimeoutUtils::medium_timeout())
+            .map_err(map_rust_indy_sdk_error_code)?
+    };
+
+    debug!("opened pool {} with handle {} for namespaces {:?}", pool_name, handle, namespaces);
+    NAMED_POOLS.write().unwrap().insert(pool_name.to_string(), NamedPool { handle, namespaces });
+    Ok(handle as u32)
+}
+
+pub fn close_named_pool(pool_name: &str) -> Result<(), u32> {
+    let pool = NAMED_POOLS.write().unwrap().remove(pool_name)
+        .ok_or(error::NO_POOL_OPEN.code_num)?;
+
+    {
+        let mut default = DEFAULT_WRITE_POOL.write().unwrap();
+        if default.as_ref().map(String::as_str) == Some(pool_name) { *default = None; }
+    }
+
+    if settings::test_indy_mode_enabled() { return Ok(()) }
+
+    Pool::close_timeout(pool.handle, TimeoutUtils::medium_timeout()).map_err(map_rust_indy_sdk_error_code)
+}
+
+pub fn close_all_named_pools() {
+    let names: Vec<String> = NAMED_POOLS.read().unwrap().keys().cloned().collect();
+    for name in names {
+        if let Err(e) = close_named_pool(&name) {
+            warn!("could not close pool {}: {}", name, e);
+        }
+    }
+}
+
+pub fn get_named_pool_handle(pool_name: &str) -> Result<i32, u32> {
+    NAMED_POOLS.read().unwrap().get(pool_name)
+        .map(|pool| pool.handle)
+        .ok_or(error::NO_POOL_OPEN.code_num)
+}
+
+pub fn list_named_pools() -> Vec<String> {
+    NAMED_POOLS.read().unwrap().keys().cloned().collect()
+}
+
+/// Sets the pool ledger writes go to when no pool is selected for the call, None restores the pool opened at init
+pub fn set_default_write_pool(pool_name: Option<&str>) -> Result<(), u32> {
+    if let Some(name) = pool_name { get_named_pool_handle(name)?; }
+
+    *DEFAULT_WRITE_POOL.write().unwrap() = pool_name.map(String::from);
+    Ok(())
+}
+
+pub fn get_default_write_pool() -> Option<String> {
+    DEFAULT_WRITE_POOL.read().unwrap().clone()
+}
+
+/// Runs the closure with the ledger reads and writes of the current thread going to the given pool.
+/// None keeps the default routing.
+pub fn with_pool<T, F>(pool_name: Option<&str>, f: F) -> Result<T, u32>
+    where F: FnOnce() -> T {
+    let pool_name = match pool_name {
+        Some(x) => x,
+        None => return Ok(f()),
+    };
+
+    get_named_pool_handle(pool_name)?;
+
+    let previous = CALL_POOL.with(|p| mem::replace(&mut *p.borrow_mut(), Some(pool_name.to_string())));
+    let result = f();
+    CALL_POOL.with(|p| *p.borrow_mut() = previous);
+
+    Ok(result)
+}
+
+/// Name of the pool writes of the current thread go to, None is the pool opened at init
+pub fn get_write_pool_name() -> Option<String> {
+    CALL_POOL.with(|p| p.borrow().clone())
+        .or(get_default_write_pool())
+}
+
+/// Picks the pool for reading a ledger id (did, schema id or credential definition id): the pool selected
+/// for the current call, else the named pool registered for the longest matching namespace, else the pool opened at init.
+pub fn get_pool_handle_for_id(id: &str) -> Result<i32, u32> {
+    get_pool_handle_by_name(get_named_pool_for_id(id).as_ref().map(String::as_str))
+}
+
+/// Name of the pool reads of a ledger id go to (see get_pool_handle_for_id)
+pub fn get_pool_name_for_id(id: &str) -> String {
+    get_named_pool_for_id(id).unwrap_or(main_pool_name())
+}
+
+/// Named pool reads of a ledger id go to, None is the pool opened at init
+pub fn get_named_pool_for_id(id: &str) -> Option<String> {
+    if let Some(name) = CALL_POOL.with(|p| p.borrow().clone()) {
+        return Some(name);
+    }
+
+    NAMED_POOLS.read().unwrap().iter()
+        .flat_map(|(name, pool)| pool.namespaces.iter().map(move |ns| (ns, name)))
+        .filter(|&(ns, _)| !ns.is_empty() && id.starts_with(ns.as_str()))
+        .max_by_key(|&(ns, _)| ns.len())
+        .map(|(_, name)| name.clone())
+}
+
+/// Name of the pool opened at init
+pub fn main_pool_name() -> String {
+    settings::get_config_value(settings::CONFIG_POOL_NAME)
+        .unwrap_or(settings::DEFAULT_POOL_NAME.to_string())
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -104,6 +405,51 @@ pub mod tests {
         f.sync_all().unwrap();
     }
 
+    #[test]
+    fn test_pool_status_and_refresh() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        record_ledger_failure(error::POOL_LEDGER_CONNECT.code_num);
+        assert_eq!(get_pool_status().last_error, Some(error::POOL_LEDGER_CONNECT.code_num));
+
+        refresh(None).unwrap();
+        record_ledger_success();
+
+        let status = get_pool_status();
+        assert!(status.open);
+        assert_eq!(status.last_error, None);
+        assert!(status.last_refresh.is_some());
+        assert!(status.last_successful_read.is_some());
+
+        assert!(is_connection_error(error::POOL_LEDGER_CONNECT.code_num));
+        assert!(!is_connection_error(error::LEDGER_REQUEST_REJECTED.code_num));
+    }
+
+    #[test]
+    fn test_named_pools_routing() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let staging = open_named_pool("test_named_pools_staging", GENESIS_PATH, vec!["V4SGRU86Z58d6TV7PBUe6f".to_string(), "did:sov:staging:".to_string()]).unwrap() as i32;
+        let production = open_named_pool("test_named_pools_production", GENESIS_PATH, vec!["did:sov:".to_string()]).unwrap() as i32;
+        assert_eq!(open_named_pool("test_named_pools_production", GENESIS_PATH, vec![]), Err(error::POOL_ALREADY_OPEN.code_num));
+
+        assert_eq!(get_pool_handle_for_id("V4SGRU86Z58d6TV7PBUe6f:2:name:1.0"), Ok(staging));
+        assert_eq!(get_pool_handle_for_id("did:sov:staging:V4SGRU86Z58d6TV7PBUe6f"), Ok(staging));
+        assert_eq!(get_pool_handle_for_id("did:sov:2hoqvcwupRTUNkXn6ArYzs"), Ok(production));
+        assert_eq!(get_pool_name_for_id("did:sov:2hoqvcwupRTUNkXn6ArYzs"), "test_named_pools_production");
+        assert_eq!(get_pool_name_for_id("2hoqvcwupRTUNkXn6ArYzs:2:name:1.0"), settings::DEFAULT_POOL_NAME);
+
+        assert_eq!(with_pool(Some("test_named_pools_staging"), || get_pool_handle()), Ok(Ok(staging)));
+        assert_eq!(with_pool(Some("test_named_pools_staging"), || get_pool_handle_for_id("did:sov:2hoqvcwupRTUNkXn6ArYzs")), Ok(Ok(staging)));
+        assert_eq!(with_pool(Some("unknown_pool"), || ()), Err(error::NO_POOL_OPEN.code_num));
+
+        close_named_pool("test_named_pools_staging").unwrap();
+        close_named_pool("test_named_pools_production").unwrap();
+        assert_eq!(get_named_pool_handle("test_named_pools_staging"), Err(error::NO_POOL_OPEN.code_num));
+    }
+
     #[cfg(feature = "pool_tests")]
     #[test]
     fn test_open_close_pool() {
@@ -113,4 +459,28 @@ pub mod tests {
         assert!(get_pool_handle().unwrap() > 0);
         ::utils::devsetup::tests::cleanup_dev_env(wallet_name);
     }
+
+    #[cfg(feature = "pool_tests")]
+    #[test]
+    fn test_reconnect_replaces_failed_handle_once() {
+        let wallet_name = "test_reconnect_replaces_failed_handle_once";
+        ::utils::devsetup::tests::setup_ledger_env(wallet_name);
+
+        let failed = get_pool_handle().unwrap();
+        reconnect(None, Some(failed)).unwrap();
+        let reopened = get_pool_handle().unwrap();
+        assert_ne!(reopened, failed);
+        // a concurrent request that failed on the same handle keeps the reopened pool
+        reconnect(None, Some(failed)).unwrap();
+        assert_eq!(get_pool_handle().unwrap(), reopened);
+
+        // a pool that is not open is opened again from the genesis file
+        close().unwrap();
+        reconnect(None, None).unwrap();
+        assert!(get_pool_handle().is_ok());
+
+        refresh(None).unwrap();
+        assert!(get_pool_handle().is_ok());
+        ::utils::devsetup::tests::cleanup_dev_env(wallet_name);
+    }
 }
@@ -12,9 +12,14 @@ pub mod httpclient;
 pub mod constants;
 pub mod timeout;
 pub mod openssl;
+pub mod encoding;
+pub mod nonce;
 pub mod json;
 pub mod libindy;
 pub mod devsetup;
+pub mod ledger_cache;
+pub mod author_agreement;
+pub mod serialization;
 
 use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
 lazy_static! {
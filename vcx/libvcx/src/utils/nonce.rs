@@ -0,0 +1,277 @@
+extern crate openssl;
+extern crate serde_json;
+
+use self::openssl::bn::{BigNum, BigNumRef, MsbOption};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use settings;
+use utils::constants::LARGE_NONCE;
+use utils::error;
+use utils::libindy::wallet;
+
+static NONCE_RECORD: &str = "vcx_proof_nonce";
+// libindy cannot enumerate records, the recorded nonces are listed in a single record for pruning
+static NONCE_INDEX_RECORD: &str = "vcx_proof_nonce_index";
+static NONCE_INDEX_ID: &str = "nonces";
+// consumed nonces of requests that never expire are kept this long to reject replays
+pub const CONSUMED_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+static WALLET_GET_OPTIONS: &str = r#"{"retrieveType":false,"retrieveValue":true,"retrieveTags":false}"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum NonceStatus {
+    // sent with a proof request, no proof was accepted for it yet
+    Outstanding,
+    // a valid proof was accepted for it, any other proof using it is a replay
+    Consumed,
+}
+
+/// Nonce of a proof request sent by this verifier
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NonceRecord {
+    pub status: NonceStatus,
+    pub issued_at: u64,
+    // unix time after which proofs are no longer accepted, None when the request never expires
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub consumed_at: Option<u64>,
+}
+
+impl NonceRecord {
+    // a pruned nonce is rejected like an unknown one, so a record is only needed while a proof
+    // for it could otherwise still be accepted
+    fn is_prunable(&self, now: u64) -> bool {
+        if self.expires_at.map(|deadline| now > deadline).unwrap_or(false) { return true }
+
+        self.consumed_at.map(|consumed_at| now > consumed_at + CONSUMED_RETENTION_SECS).unwrap_or(false)
+    }
+}
+
+lazy_static! {
+    // stands in for the wallet in test mode, where wallet records are mocked
+    static ref TEST_NONCES: Mutex<HashMap<String, NonceRecord>> = Default::default();
+    // serializes read-modify-write of records so a nonce cannot be consumed twice concurrently
+    static ref UPDATE_LOCK: Mutex<()> = Default::default();
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Random nonce of LARGE_NONCE bits drawn from the OpenSSL CSPRNG
+pub fn generate_nonce() -> Result<String, u32> {
+    let mut bn = BigNum::new().map_err(|_| error::BIG_NUMBER_ERROR.code_num)?;
+
+    BigNumRef::rand(&mut bn, LARGE_NONCE as i32, MsbOption::MAYBE_ZERO, false)
+        .map_err(|_| error::BIG_NUMBER_ERROR.code_num)?;
+    Ok(bn.to_dec_str().map_err(|_| error::BIG_NUMBER_ERROR.code_num)?.to_string())
+}
+
+/// The record of nonce, None when no request was sent with it. Fails when the record cannot be
+/// read, callers must not take an unreadable record for a new nonce.
+pub fn get(nonce: &str) -> Result<Option<NonceRecord>, u32> {
+    if settings::test_indy_mode_enabled() {
+        return Ok(TEST_NONCES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?.get(nonce).cloned());
+    }
+
+    let record = match wallet::get_record(NONCE_RECORD, nonce, WALLET_GET_OPTIONS) {
+        Ok(x) => x,
+        Err(ec) if ec == error::WALLET_RECORD_NOT_FOUND.code_num => return Ok(None),
+        Err(ec) => return Err(ec),
+    };
+    let record: Value = serde_json::from_str(&record).or(Err(error::INVALID_JSON.code_num))?;
+    record["value"].as_str()
+        .and_then(|value| serde_json::from_str(value).ok())
+        .map(Some)
+        .ok_or(error::INVALID_JSON.code_num)
+}
+
+// Adds the record of a nonce not recorded yet, fails with DUPLICATE_WALLET_RECORD rather than
+// replacing an existing one
+fn add(nonce: &str, record: &NonceRecord) -> Result<(), u32> {
+    if settings::test_indy_mode_enabled() {
+        let mut nonces = TEST_NONCES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+        if nonces.contains_key(nonce) { return Err(error::DUPLICATE_WALLET_RECORD.code_num) }
+        nonces.insert(nonce.to_string(), record.clone());
+        return Ok(());
+    }
+
+    let value = serde_json::to_string(record).or(Err(error::INVALID_JSON.code_num))?;
+    wallet::add_record(NONCE_RECORD, nonce, &value, "{}")?;
+
+    let mut index = get_index()?;
+    index.push(nonce.to_string());
+    store_index(&index)
+}
+
+fn update(nonce: &str, record: &NonceRecord) -> Result<(), u32> {
+    if settings::test_indy_mode_enabled() {
+        TEST_NONCES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?.insert(nonce.to_string(), record.clone());
+        return Ok(());
+    }
+
+    let value = serde_json::to_string(record).or(Err(error::INVALID_JSON.code_num))?;
+    wallet::update_record_value(NONCE_RECORD, nonce, &value)
+}
+
+fn get_index() -> Result<Vec<String>, u32> {
+    let record = match wallet::get_record(NONCE_INDEX_RECORD, NONCE_INDEX_ID, WALLET_GET_OPTIONS) {
+        Ok(x) => x,
+        Err(ec) if ec == error::WALLET_RECORD_NOT_FOUND.code_num => return Ok(Vec::new()),
+        Err(ec) => return Err(ec),
+    };
+    let record: Value = serde_json::from_str(&record).or(Err(error::INVALID_JSON.code_num))?;
+    record["value"].as_str()
+        .and_then(|value| serde_json::from_str(value).ok())
+        .ok_or(error::INVALID_JSON.code_num)
+}
+
+fn store_index(index: &[String]) -> Result<(), u32> {
+    let value = serde_json::to_string(index).or(Err(error::INVALID_JSON.code_num))?;
+    match wallet::add_record(NONCE_INDEX_RECORD, NONCE_INDEX_ID, &value, "{}") {
+        Err(ec) if ec == error::DUPLICATE_WALLET_RECORD.code_num => wallet::update_record_value(NONCE_INDEX_RECORD, NONCE_INDEX_ID, &value),
+        rc => rc,
+    }
+}
+
+/// Records nonce as sent in a proof request. Sending the same request again only updates its
+/// expiry, a nonce a proof was already accepted for cannot be issued again.
+pub fn issue(nonce: &str, expires_at: Option<u64>) -> Result<(), u32> {
+    let _guard = UPDATE_LOCK.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    match get(nonce)? {
+        Some(NonceRecord { status: NonceStatus::Consumed, .. }) => {
+            warn!("nonce {} was already used by an accepted proof", nonce);
+            Err(error::PROOF_NONCE_REJECTED.code_num)
+        },
+        Some(record) => update(nonce, &NonceRecord { expires_at, ..record }),
+        None => add(nonce, &NonceRecord { status: NonceStatus::Outstanding, issued_at: now(), expires_at, consumed_at: None }),
+    }
+}
+
+/// Marks nonce as used by an accepted proof. Fails when no outstanding, unexpired request was
+/// sent with nonce, so a proof captured for another request or replayed is rejected.
+pub fn consume(nonce: &str) -> Result<(), u32> {
+    let _guard = UPDATE_LOCK.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    let mut record = match get(nonce)? {
+        Some(record) => record,
+        None => {
+            warn!("nonce {} does not belong to a proof request", nonce);
+            return Err(error::PROOF_NONCE_REJECTED.code_num);
+        }
+    };
+
+    if record.status == NonceStatus::Consumed {
+        warn!("nonce {} was already used by an accepted proof", nonce);
+        return Err(error::PROOF_NONCE_REJECTED.code_num);
+    }
+
+    if record.expires_at.map(|deadline| now() > deadline).unwrap_or(false) {
+        warn!("proof request with nonce {} expired", nonce);
+        return Err(error::PROOF_NONCE_REJECTED.code_num);
+    }
+
+    record.status = NonceStatus::Consumed;
+    record.consumed_at = Some(now());
+    update(nonce, &record)
+}
+
+/// Deletes the records of nonces whose request expired, and of consumed nonces of requests that
+/// never expire after CONSUMED_RETENTION_SECS. Proofs for them are rejected as for unknown nonces.
+/// Returns the number of records deleted.
+pub fn prune() -> Result<u32, u32> {
+    let _guard = UPDATE_LOCK.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+    let now = now();
+
+    if settings::test_indy_mode_enabled() {
+        let mut nonces = TEST_NONCES.lock().or(Err(error::UNKNOWN_ERROR.code_num))?;
+        let before = nonces.len();
+        nonces.retain(|_, record| !record.is_prunable(now));
+        return Ok((before - nonces.len()) as u32);
+    }
+
+    let index = get_index()?;
+    let mut kept = Vec::new();
+    for nonce in index.iter() {
+        match get(nonce) {
+            Ok(Some(ref record)) if record.is_prunable(now) => wallet::delete_record(NONCE_RECORD, nonce)?,
+            Ok(None) => (),
+            // a record that cannot be read is kept, it may still protect a request
+            _ => kept.push(nonce.to_string()),
+        }
+    }
+
+    store_index(&kept)?;
+    debug!("pruned {} proof nonces", index.len() - kept.len());
+    Ok((index.len() - kept.len()) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_nonce() {
+        let nonce = generate_nonce().unwrap();
+        assert!(BigNum::from_dec_str(&nonce).unwrap().num_bits() <= LARGE_NONCE as i32);
+        assert_ne!(nonce, generate_nonce().unwrap());
+    }
+
+    #[test]
+    fn test_nonce_is_consumed_once() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let nonce = generate_nonce().unwrap();
+        assert_eq!(consume(&nonce), Err(error::PROOF_NONCE_REJECTED.code_num));
+
+        issue(&nonce, None).unwrap();
+        let deadline = now() + 100;
+        issue(&nonce, Some(deadline)).unwrap();
+        assert_eq!(get(&nonce).unwrap().unwrap().expires_at, Some(deadline));
+
+        consume(&nonce).unwrap();
+        assert_eq!(consume(&nonce), Err(error::PROOF_NONCE_REJECTED.code_num));
+        assert_eq!(issue(&nonce, None), Err(error::PROOF_NONCE_REJECTED.code_num));
+    }
+
+    #[test]
+    fn test_expired_nonce_is_rejected() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let nonce = generate_nonce().unwrap();
+        issue(&nonce, Some(now() - 1)).unwrap();
+        assert_eq!(consume(&nonce), Err(error::PROOF_NONCE_REJECTED.code_num));
+        // the expired record may already be pruned by a concurrent test
+        assert_ne!(get(&nonce).unwrap().map(|record| record.status), Some(NonceStatus::Consumed));
+    }
+
+    #[test]
+    fn test_prune() {
+        settings::set_defaults();
+        settings::set_config_value(settings::CONFIG_ENABLE_TEST_MODE, "true");
+
+        let expired = generate_nonce().unwrap();
+        issue(&expired, Some(now() - 1)).unwrap();
+        let outstanding = generate_nonce().unwrap();
+        issue(&outstanding, None).unwrap();
+        let consumed = generate_nonce().unwrap();
+        issue(&consumed, None).unwrap();
+        consume(&consumed).unwrap();
+        let old_consumed = generate_nonce().unwrap();
+        issue(&old_consumed, None).unwrap();
+        consume(&old_consumed).unwrap();
+        let mut record = get(&old_consumed).unwrap().unwrap();
+        record.consumed_at = Some(now() - CONSUMED_RETENTION_SECS - 1);
+        update(&old_consumed, &record).unwrap();
+
+        assert!(prune().unwrap() >= 2);
+        assert_eq!(get(&expired), Ok(None));
+        assert_eq!(get(&old_consumed), Ok(None));
+        assert_eq!(get(&outstanding).unwrap().unwrap().status, NonceStatus::Outstanding);
+        assert_eq!(get(&consumed).unwrap().unwrap().status, NonceStatus::Consumed);
+        // a pruned nonce is rejected like an unknown one
+        assert_eq!(consume(&expired), Err(error::PROOF_NONCE_REJECTED.code_num));
+    }
+}
@@ -0,0 +1,129 @@
+extern crate serde;
+extern crate serde_json;
+
+use self::serde::Serialize;
+use self::serde::de::DeserializeOwned;
+use serde_json::Value;
+use utils::constants::DEFAULT_SERIALIZE_VERSION;
+use utils::error;
+
+/// Version assumed for blobs that carry "data" without a "version" (written before versioning existed)
+pub static UNVERSIONED: &str = "1.0";
+
+/// Upgrades the "data" of a serialized object from one version to the next
+pub struct Migration {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub migrate: fn(Value) -> Result<Value, u32>,
+}
+
+/// Objects persisted by the wrappers. Every change of the serialized shape bumps
+/// DEFAULT_SERIALIZE_VERSION and adds a migration from the previous version for each object.
+pub trait Versioned: Serialize + DeserializeOwned {
+    fn migrations() -> Vec<Migration>;
+}
+
+fn keep_data(data: Value) -> Result<Value, u32> { Ok(data) }
+
+/// Migration for objects whose shape did not change between two versions
+pub fn unchanged(from: &'static str, to: &'static str) -> Migration {
+    Migration { from, to, migrate: keep_data }
+}
+
+pub fn to_versioned_string<T: Versioned>(obj: &T) -> String {
+    json!({
+        "version": DEFAULT_SERIALIZE_VERSION,
+        "data": json!(obj),
+    }).to_string()
+}
+
+/// Splits a serialized object into its version and data
+pub fn parse_versioned(serialized: &str) -> Result<(String, Value), u32> {
+    let mut value: Value = serde_json::from_str(serialized)
+        .or(Err(error::INVALID_JSON.code_num))?;
+
+    let version = match value.get("version") {
+        Some(&Value::String(ref x)) => x.to_string(),
+        Some(_) => return Err(error::INVALID_JSON.code_num),
+        None => UNVERSIONED.to_string(),
+    };
+
+    if !value["data"].is_object() { return Err(error::INVALID_JSON.code_num); }
+
+    Ok((version, value["data"].take()))
+}
+
+/// Applies the migrations of T until the data reaches DEFAULT_SERIALIZE_VERSION
+pub fn migrate<T: Versioned>(version: &str, data: Value) -> Result<Value, u32> {
+    let migrations = T::migrations();
+    let mut version = version.to_string();
+    let mut data = data;
+
+    while version != DEFAULT_SERIALIZE_VERSION {
+        let migration = migrations.iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| {
+                warn!("no migration from serialization version {}", version);
+                error::UNSUPPORTED_SERIALIZATION_VERSION.code_num
+            })?;
+
+        debug!("migrating serialized object from version {} to {}", migration.from, migration.to);
+        data = (migration.migrate)(data)?;
+        version = migration.to.to_string();
+    }
+
+    Ok(data)
+}
+
+pub fn from_versioned_str<T: Versioned>(serialized: &str) -> Result<T, u32> {
+    let (version, data) = parse_versioned(serialized)?;
+
+    let data = migrate::<T>(&version, data)?;
+
+    serde_json::from_value(data).or(Err(error::INVALID_JSON.code_num))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sample {
+        name: String,
+        state: u32,
+    }
+
+    fn add_state(mut data: Value) -> Result<Value, u32> {
+        data["state"] = json!(1);
+        Ok(data)
+    }
+
+    impl Versioned for Sample {
+        fn migrations() -> Vec<Migration> {
+            vec![Migration { from: "1.0", to: "2.0", migrate: add_state },
+                 unchanged("2.0", "3.0")]
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let sample = Sample { name: "sample".to_string(), state: 4 };
+        let serialized = to_versioned_string(&sample);
+        assert_eq!(parse_versioned(&serialized).unwrap().0, DEFAULT_SERIALIZE_VERSION);
+        assert_eq!(from_versioned_str::<Sample>(&serialized).unwrap(), sample);
+    }
+
+    #[test]
+    fn test_migrates_older_versions() {
+        let expected = Sample { name: "sample".to_string(), state: 1 };
+        assert_eq!(from_versioned_str::<Sample>(r#"{"version":"1.0","data":{"name":"sample"}}"#).unwrap(), expected);
+        assert_eq!(from_versioned_str::<Sample>(r#"{"data":{"name":"sample"}}"#).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_rejects_unknown_versions() {
+        assert_eq!(from_versioned_str::<Sample>(r#"{"version":"0.1","data":{"name":"sample"}}"#).err(),
+                   Some(error::UNSUPPORTED_SERIALIZATION_VERSION.code_num));
+        assert_eq!(from_versioned_str::<Sample>(r#"{"version":"1.0"}"#).err(), Some(error::INVALID_JSON.code_num));
+    }
+}
@@ -90,7 +90,8 @@ export enum StateType {
     Accepted = 4,
     Unfulfilled = 5,
     Expired = 6,
-    Revoked = 7
+    Revoked = 7,
+    Queued = 8
 }
 
 export interface IInitVCXOptions {
@@ -10,6 +10,7 @@ class State(IntEnum):
     Unfulfilled = 5,
     Expired = 6,
     Revoked = 7,
+    Queued = 8,
 
 
 class ProofState(IntEnum):